@@ -1,32 +1,34 @@
-//! A crate for creating a 3d world in Bevy.
+//! A demo of the crate: a small map with a player, doors, and most of the map objects.
 //!
-//! The crate is composed of the following modules:
-//! - map: A collection of 3D tiles, obstacles, players, event spaces, and other objects.
-
-#![deny(missing_docs)]
-// #![forbid(missing_docs_in_private_items)]
-
-/// A module that integrates the adds some useful functions to the Rapier physics engine.
-pub mod rapier_mesh_bundles;
-
-/// A module that adds mouse/keyboard control to the camera.
-pub mod controller;
-
-use controller::{fps_controller::*, *};
-use rapier_mesh_bundles::*;
-
-use bevy::{core_pipeline::clear_color::*, pbr::*, prelude::*, render::camera::*, window::*};
+//! Run it with `cargo run --example demo --features full`.
+
+use map_builder_3d::{
+    controller::{
+        abilities::*, billboard::*, capsule::*, crosshair::*, fps_controller::*, health::*,
+        lock_on::*, look_target::*, sleep::*, split_screen::*, tuning::*, view_model::*, *,
+    },
+    debug::{collision_profile::*, overlay::*, rewind::*},
+    depenetration::*,
+    editor::history::*,
+    environment::*,
+    floating_origin::*,
+    freeze::*,
+    interpolation::*,
+    map::{
+        animated::*, bounce_pad::*, challenge::*, checkpoint::*, event_space::*, magnet::*,
+        minimap::*, pressure_plate::*, reset_volume::*, secret_area::*, signal::*, spawn::*,
+        wind::*, zip_line::*, *,
+    },
+    rapier_mesh_bundles::*,
+    state::*,
+    substeps::*,
+    surface::*,
+    teleport::*,
+};
+
+use bevy::{pbr::*, prelude::*, window::*};
 use bevy_rapier3d::prelude::*;
 
-#[derive(Component)]
-struct LeftCamera;
-
-#[derive(Component)]
-struct RightCamera;
-
-#[derive(Component)]
-struct Name(String);
-
 const PHYSICAL_SCALE: f32 = 1.0;
 
 fn main() {
@@ -36,6 +38,7 @@ fn main() {
             gravity: RapierConfiguration::default().gravity * PHYSICAL_SCALE,
             ..default()
         })
+        .insert_resource(WorldScale(PHYSICAL_SCALE))
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             window: WindowDescriptor {
                 title: "Map Builder 3D".to_string(),
@@ -49,13 +52,49 @@ fn main() {
             ..default()
         }))
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::default().with_physics_scale(PHYSICAL_SCALE))
-        // .add_plugin(RapierDebugRenderPlugin::default())
+        .add_plugin(MapDebugPlugin::new())
+        .add_plugin(CollisionProfilePlugin::new())
+        .add_plugin(RewindPlugin::new())
+        .add_plugin(SubstepPlugin::new())
+        .add_plugin(PhysicsInterpolationPlugin::new())
+        .add_plugin(DepenetrationPlugin::new())
+        .add_plugin(TeleportPlugin::new())
+        .add_plugin(FreezePlugin::new())
+        .add_plugin(MapBuilderStatePlugin::new())
         .add_plugin(LookTransformPlugin)
         .add_plugin(FpsCameraPlugin::new())
+        .add_plugin(LookTargetPlugin::new())
+        .add_plugin(LockOnPlugin::new())
+        .add_plugin(SplitScreenPlugin::new())
+        .add_plugin(AbilityPlugin::new())
+        .add_plugin(HealthPlugin::new())
+        .add_plugin(CapsuleResizePlugin::new())
+        .add_plugin(CharacterSleepPlugin::new())
+        .add_plugin(ViewModelPlugin::new())
+        .add_plugin(CrosshairPlugin::new())
+        .add_plugin(BillboardPlugin::new())
+        .add_plugin(FloatingOriginPlugin::new())
+        .add_plugin(DayNightCyclePlugin::new())
+        .add_plugin(MapPlugin::new())
+        .add_plugin(SpawnPointPlugin::new())
+        .add_plugin(EventSpacePlugin::new())
+        .add_plugin(MapSignalPlugin::new())
+        .add_plugin(CheckpointPlugin::new())
+        .add_plugin(AnimatedDoorPlugin::new())
+        .add_plugin(ZipLinePlugin::new())
+        .add_plugin(BouncePadPlugin::new())
+        .add_plugin(WindPlugin::new())
+        .add_plugin(ResetVolumePlugin::new())
+        .add_plugin(PressurePlatePlugin::new())
+        .add_plugin(MagnetPlugin::new())
+        .add_plugin(MinimapPlugin::new())
+        .add_plugin(TimedChallengePlugin::new())
+        .add_plugin(SecretAreaPlugin::new())
+        .add_plugin(SurfaceMaterialPlugin::new())
+        .add_plugin(EditorHistoryPlugin::new())
         .add_startup_system(setup_graphics)
         .add_startup_system(setup_physics)
         // .add_system(print_ball_altitude)
-        .add_system(set_camera_viewports)
         .run();
 }
 
@@ -64,14 +103,14 @@ fn setup_graphics(mut commands: Commands) {
     const CAM_DISTANCE: f32 = 30.;
     let initial_cam_pos = CAM_DISTANCE * Vec3::new(-3.0, 3.0, 10.0).normalize() * PHYSICAL_SCALE;
     commands
-        .spawn(LeftCamera)
+        .spawn(PlayerCamera::new(0))
         .insert(LookTransformCameraBundle {
             look_transform: LookTransform::from_pos_target(initial_cam_pos, Vec3::ZERO),
             ..default()
         });
 
-    // Light bulb
-    commands.spawn(DirectionalLightBundle {
+    // The sun, moved by the day/night cycle.
+    commands.spawn(Sun).insert(DirectionalLightBundle {
         directional_light: DirectionalLight {
             shadows_enabled: true,
             ..default()
@@ -94,6 +133,7 @@ fn setup_physics(
     commands.spawn(RapierColliderPbrBundle {
         shape: RapierShapeBundle::cuboid(Vec3::new(15.0, 5.0, 15.0) * PHYSICAL_SCALE, &mut meshes),
         material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
+        surface: SurfaceMaterial::GRASS,
         transform: Transform::from_translation(Vec3::new(0.0, -4.5, 0.0) * PHYSICAL_SCALE),
         ..default()
     });
@@ -106,13 +146,14 @@ fn setup_physics(
     commands.spawn(RapierColliderPbrBundle {
         shape: RapierShapeBundle::cuboid(Vec3::new(4.0, 2.5, 4.0) * PHYSICAL_SCALE, &mut meshes),
         material: materials.add(Color::rgb(0.2, 0.2, 0.4).into()),
+        surface: SurfaceMaterial::STONE,
         transform: Transform::from_translation(Vec3::new(0.0, -0.5, 0.0) * PHYSICAL_SCALE),
         ..default()
     });
 
     // Create the bouncing ball.
     commands
-        .spawn(Name("Kong Ball".into()))
+        .spawn(Name::new("Kong Ball"))
         .insert(RigidBody::Dynamic)
         .insert(Damping {
             linear_damping: 0.2,
@@ -128,26 +169,28 @@ fn setup_physics(
                 .spawn(RapierColliderPbrBundle {
                     shape: RapierShapeBundle::sphere(0.5 * PHYSICAL_SCALE, &mut meshes),
                     material: materials.add(Color::rgb(0.7, 0.3, 0.3).into()),
+                    surface: SurfaceMaterial::RUBBER,
                     transform: Transform::from_translation(
                         Vec3::new(0.0, -0.25, 0.0) * PHYSICAL_SCALE,
                     ),
                     ..default()
                 })
                 .insert(Restitution {
-                    coefficient: 0.7,
+                    coefficient: SurfaceMaterial::RUBBER.restitution,
                     combine_rule: CoefficientCombineRule::Max,
                 });
             children
                 .spawn(RapierColliderPbrBundle {
                     shape: RapierShapeBundle::sphere(0.5 * PHYSICAL_SCALE, &mut meshes),
                     material: materials.add(Color::rgb(0.7, 0.3, 0.3).into()),
+                    surface: SurfaceMaterial::RUBBER,
                     transform: Transform::from_translation(
                         Vec3::new(0.0, 0.25, 0.0) * PHYSICAL_SCALE,
                     ),
                     ..default()
                 })
                 .insert(Restitution {
-                    coefficient: 0.7,
+                    coefficient: SurfaceMaterial::RUBBER.restitution,
                     combine_rule: CoefficientCombineRule::Max,
                 });
         })
@@ -156,72 +199,22 @@ fn setup_physics(
         )))
         .insert(VisibilityBundle::default());
 
-    // Create the bouncing capsule.
-    let capsule_pos = Vec3::new(-1.0, 5.0, -1.0) * PHYSICAL_SCALE;
-    commands
-        .spawn(Name("Capsule".into()))
-        .insert(RapierColliderPbrBundle {
-            shape: RapierShapeBundle::capsule(
-                0.5 * PHYSICAL_SCALE,
-                0.5 * PHYSICAL_SCALE,
-                &mut meshes,
-            ),
-            material: materials.add(Color::rgb(0.3, 0.3, 0.7).into()),
-            transform: Transform::from_translation(capsule_pos),
-            ..default()
-        })
-        .insert(FpsControllerBodyBundle::new())
-        .with_children(|children| {
-            children
-                .spawn(RightCamera)
-                .insert(LookTransformCameraBundle {
-                    camera_bundle: Camera3dBundle {
-                        camera: Camera {
-                            // Renders the right camera after the left camera, which has a default priority of 0
-                            priority: 1,
-                            ..default()
-                        },
-                        camera_3d: Camera3d {
-                            // don't clear on the second camera because the first camera already cleared the window
-                            clear_color: ClearColorConfig::None,
-                            ..default()
-                        },
-                        ..default()
-                    },
-                    ..default()
-                });
-        });
+    // Create the player at its spawn point.
+    commands.spawn(SpawnPointBundle::new(
+        SpawnPoint::new("player"),
+        Transform::from_translation(Vec3::new(-1.0, 5.0, -1.0) * PHYSICAL_SCALE),
+    ));
+    let player = spawn_player_at(&mut commands, "player");
+    commands.entity(player.camera).insert(PlayerCamera::new(1));
+    commands.entity(player.body).insert((
+        Dash::default().scaled(WorldScale(PHYSICAL_SCALE)),
+        Glide::default().scaled(WorldScale(PHYSICAL_SCALE)),
+        Health::default(),
+    ));
 }
 
 // fn print_ball_altitude(positions: Query<(&Name, &Transform), With<RigidBody>>) {
 //     for (name, transform) in positions.iter() {
-//         println!("Altitude of {}: {}", name.0, transform.translation.y);
+//         println!("Altitude of {}: {}", name, transform.translation.y);
 //     }
 // }
-
-fn set_camera_viewports(
-    windows: Res<Windows>,
-    mut resize_events: EventReader<WindowResized>,
-    mut left_camera: Query<&mut Camera, (With<LeftCamera>, Without<RightCamera>)>,
-    mut right_camera: Query<&mut Camera, With<RightCamera>>,
-) {
-    // We need to dynamically resize the camera's viewports whenever the window size changes
-    // so then each camera always takes up half the screen.
-    // A resize_event is sent when the window is first created, allowing us to reuse this system for initial setup.
-    for resize_event in resize_events.iter() {
-        let window = windows.get(resize_event.id).unwrap();
-        let mut left_camera = left_camera.single_mut();
-        left_camera.viewport = Some(Viewport {
-            physical_position: UVec2::new(0, 0),
-            physical_size: UVec2::new(window.physical_width() / 2, window.physical_height()),
-            ..default()
-        });
-
-        let mut right_camera = right_camera.single_mut();
-        right_camera.viewport = Some(Viewport {
-            physical_position: UVec2::new(window.physical_width() / 2, 0),
-            physical_size: UVec2::new(window.physical_width() / 2, window.physical_height()),
-            ..default()
-        });
-    }
-}
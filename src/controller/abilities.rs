@@ -0,0 +1,316 @@
+//! A mod with special moves for the first-person controller.
+//!
+//! Abilities are used through [`FpsControlEvent::UseAbility`], which the controller turns into an
+//! [`AbilityEvent`] carrying the body and the direction the player is moving in, or looking in
+//! when standing still. Games listen to these events to implement their own abilities, such as
+//! [`Ability::Custom`] ones.
+//!
+//! The built-in [`Ability::Dash`] is enabled by giving a controller body a [`Dash`] component. It
+//! launches the body in a burst of speed, can be chained in the air a limited number of times,
+//! and can make the body [`Invulnerable`] while it lasts.
+//!
+//! Bodies with a [`Glide`] component glide instead of falling while their player holds jump in
+//! the air. A [`Gliding`] body falls no faster than the glide allows, which pairs well with the
+//! updrafts of [`WindVolume`](crate::map::wind::WindVolume)s for aerial traversal.
+
+use super::{fps_controller::*, split_screen::*, tuning::*, *};
+use crate::state::*;
+
+use serde::{Deserialize, Serialize};
+
+/// The abilities that a controller can use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Ability {
+    /// A short burst of speed, used by bodies with a [`Dash`].
+    Dash,
+    /// An ability implemented by the game, identified by a number of its choosing.
+    Custom(u32),
+}
+
+/// An event sent when a controller uses an ability.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AbilityEvent {
+    /// The controller body that used the ability.
+    pub body: Entity,
+    /// The ability that was used.
+    pub ability: Ability,
+    /// The direction the player was moving in, or looking in when standing still.
+    pub direction: Vec3,
+}
+
+/// A component that lets a controller body dash.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+#[serde(default)]
+pub struct Dash {
+    /// How fast the body moves during a dash, in units per second.
+    pub speed: f32,
+    /// How long a dash lasts, in seconds.
+    pub duration: f32,
+    /// How long after a dash starts until the next one can, in seconds.
+    pub cooldown: f32,
+    /// How many dashes can be used before landing again.
+    pub air_dashes: u32,
+    /// Whether the body is [`Invulnerable`] during a dash.
+    pub invulnerable: bool,
+    /// Whether dashes ignore the pitch of the camera and stay level.
+    pub horizontal_only: bool,
+}
+
+impl Default for Dash {
+    fn default() -> Self {
+        Self {
+            speed: 15.0,
+            duration: 0.2,
+            cooldown: 1.0,
+            air_dashes: 1,
+            invulnerable: false,
+            horizontal_only: true,
+        }
+    }
+}
+
+impl Dash {
+    /// Converts a dash measured in meters into world units.
+    pub fn scaled(self, scale: WorldScale) -> Self {
+        Self {
+            speed: self.speed * scale.0,
+            ..self
+        }
+    }
+}
+
+/// The progress of the current dash of a body with a [`Dash`].
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct DashState {
+    /// The velocity of the current dash.
+    pub velocity: Vec3,
+    /// How much longer the current dash lasts, in seconds.
+    pub remaining: f32,
+    /// How much longer until the next dash can start, in seconds.
+    pub cooldown: f32,
+    /// How many dashes were used since the body last touched the ground.
+    pub air_dashes_used: u32,
+}
+
+impl DashState {
+    /// Whether the body is dashing.
+    pub fn is_dashing(&self) -> bool {
+        self.remaining > 0.0
+    }
+}
+
+/// A component that lets a controller body glide while its player holds jump in the air.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+#[serde(default)]
+pub struct Glide {
+    /// The fastest the body falls while gliding, in units per second.
+    pub fall_speed: f32,
+}
+
+impl Default for Glide {
+    fn default() -> Self {
+        Self { fall_speed: 1.5 }
+    }
+}
+
+impl Glide {
+    /// Converts a glide measured in meters into world units.
+    pub fn scaled(self, scale: WorldScale) -> Self {
+        Self {
+            fall_speed: self.fall_speed * scale.0,
+        }
+    }
+}
+
+/// The glide of a body that is gliding right now.
+///
+/// This is managed by [`update_gliding`] and read by the controller when it applies gravity.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Gliding {
+    /// The fastest the body falls, in units per second.
+    pub fall_speed: f32,
+}
+
+/// A marker for bodies that should not take damage, such as during a dash.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Invulnerable;
+
+/// A plugin that adds the built-in abilities.
+#[derive(Default)]
+pub struct AbilityPlugin;
+
+impl AbilityPlugin {
+    /// Creates a new [`AbilityPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for AbilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Dash>()
+            .register_type::<Glide>()
+            .add_event::<AbilityEvent>()
+            .add_event::<FpsControlEvent>()
+            .add_event::<PlayerFpsControlEvent>()
+            .add_system(
+                start_dashes
+                    .with_run_criteria(is_playing)
+                    .after(fps_control_system),
+            )
+            .add_system(
+                apply_dashes
+                    .with_run_criteria(is_playing)
+                    .after(start_dashes),
+            )
+            .add_system(
+                update_gliding
+                    .with_run_criteria(is_playing)
+                    .after(fps_control_system),
+            );
+    }
+}
+
+/// Starts a dash for every [`Ability::Dash`] used by a body that is ready to dash.
+#[allow(clippy::type_complexity)]
+pub fn start_dashes(
+    mut commands: Commands,
+    mut events: EventReader<AbilityEvent>,
+    mut bodies: Query<(
+        &Dash,
+        Option<&mut DashState>,
+        Option<&KinematicCharacterControllerOutput>,
+    )>,
+) {
+    for event in events.iter() {
+        if event.ability != Ability::Dash {
+            continue;
+        }
+        let Ok((dash, state, output)) = bodies.get_mut(event.body) else {
+            continue;
+        };
+        let mut new_state = state.as_deref().copied().unwrap_or_default();
+        if new_state.is_dashing() || new_state.cooldown > 0.0 {
+            continue;
+        }
+
+        let grounded = output.is_some_and(|output| output.grounded);
+        if !grounded {
+            if new_state.air_dashes_used >= dash.air_dashes {
+                continue;
+            }
+            new_state.air_dashes_used += 1;
+        }
+
+        let mut direction = event.direction;
+        if dash.horizontal_only {
+            direction.y = 0.0;
+        }
+        let direction = direction.normalize_or_zero();
+        if direction == Vec3::ZERO {
+            continue;
+        }
+
+        new_state.velocity = dash.speed * direction;
+        new_state.remaining = dash.duration;
+        new_state.cooldown = dash.cooldown;
+        match state {
+            Some(mut state) => *state = new_state,
+            None => {
+                commands.entity(event.body).insert(new_state);
+            }
+        }
+        if dash.invulnerable {
+            commands.entity(event.body).insert(Invulnerable);
+        }
+    }
+}
+
+/// Moves dashing bodies and counts down the cooldowns.
+#[allow(clippy::type_complexity)]
+pub fn apply_dashes(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut bodies: Query<(
+        Entity,
+        &Dash,
+        &mut DashState,
+        &mut KinematicCharacterController,
+        Option<&mut CustomVelocity>,
+        Option<&KinematicCharacterControllerOutput>,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, dash, mut state, mut controller, velocity, output) in &mut bodies {
+        state.cooldown = (state.cooldown - dt).max(0.0);
+        if output.is_some_and(|output| output.grounded) && !state.is_dashing() {
+            state.air_dashes_used = 0;
+        }
+        if !state.is_dashing() {
+            continue;
+        }
+
+        // Dashes are not pulled down by gravity, so air dashes keep their height.
+        if let Some(mut velocity) = velocity {
+            if velocity.0.y < 0.0 {
+                velocity.0.y = 0.0;
+            }
+        }
+
+        let translation = dt.min(state.remaining) * state.velocity;
+        controller.translation = Some(
+            controller
+                .translation
+                .map(|t| t + translation)
+                .unwrap_or(translation),
+        );
+
+        state.remaining -= dt;
+        if !state.is_dashing() {
+            state.remaining = 0.0;
+            state.velocity = Vec3::ZERO;
+            if dash.invulnerable {
+                commands.entity(entity).remove::<Invulnerable>();
+            }
+        }
+    }
+}
+
+/// Starts and stops the glides of bodies with a [`Glide`].
+///
+/// A body glides while its player holds jump, it is in the air, and it is falling.
+#[allow(clippy::type_complexity)]
+pub fn update_gliding(
+    mut commands: Commands,
+    mut keyboard_events: EventReader<FpsControlEvent>,
+    mut player_events: EventReader<PlayerFpsControlEvent>,
+    cameras: Query<(&Parent, Option<&PlayerCamera>, Option<&PlayerInput>), With<LookTransform>>,
+    bodies: Query<(
+        Entity,
+        &Glide,
+        &CustomVelocity,
+        Option<&KinematicCharacterControllerOutput>,
+        Option<&Gliding>,
+    )>,
+) {
+    let jumping = jumping_bodies(keyboard_events.iter(), player_events.iter(), &cameras);
+
+    for (entity, glide, velocity, output, gliding) in &bodies {
+        let grounded = output.is_some_and(|output| output.grounded);
+        let glides = jumping.contains(&entity) && !grounded && velocity.0.y <= 0.0;
+        match (glides, gliding) {
+            (true, Some(gliding)) if gliding.fall_speed == glide.fall_speed => {}
+            (true, _) => {
+                commands.entity(entity).insert(Gliding {
+                    fall_speed: glide.fall_speed,
+                });
+            }
+            (false, Some(_)) => {
+                commands.entity(entity).remove::<Gliding>();
+            }
+            (false, None) => {}
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! A mod for text that floats over points in the world, such as damage numbers and names.
+//!
+//! A [`Billboard`] is placed on any entity with a [`GlobalTransform`], such as a character that
+//! shows its name or a short-lived entity that shows a damage number. Every frame, the point
+//! above the entity is projected through the camera of every [`Player`], and a UI text node is
+//! drawn there in the viewport of that camera, so every split-screen player sees the billboards
+//! in their own view. Text shrinks with the distance beyond its reference distance and fades out
+//! toward its maximum distance, and billboards with a lifetime fade out and despawn at the end of
+//! it.
+//!
+//! The [`BillboardPlugin`] also uses billboards itself. It pops up a damage number over every body
+//! that takes a [`DamageEvent`], and shows the prompt of the [`Interactable`] that a camera is
+//! focused on.
+
+use super::{abilities::*, health::*, interaction::*, split_screen::*, *};
+use crate::{dynamic_resolution::*, map::spawn::*, state::*};
+
+/// The width of the UI node of a billboard, which the text is centered in.
+const BILLBOARD_WIDTH: f32 = 400.0;
+
+/// Text drawn over a point in the world.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Billboard {
+    /// The text.
+    pub text: String,
+    /// The color of the text.
+    pub color: Color,
+    /// The size of the text at the reference distance and closer, in pixels.
+    pub font_size: f32,
+    /// Where the text is drawn relative to the entity, in world space.
+    pub offset: Vec3,
+    /// The distance beyond which the text shrinks.
+    pub reference_distance: f32,
+    /// The smallest the text shrinks to, as a share of its font size.
+    pub min_scale: f32,
+    /// The distance beyond which the text is hidden.
+    pub max_distance: f32,
+    /// How far before the maximum distance the text starts to fade out.
+    pub fade_distance: f32,
+    /// How many seconds are left before the billboard despawns its entity, if it does.
+    pub lifetime: Option<f32>,
+    /// How fast the entity drifts, in units per second, such as damage numbers rising.
+    pub velocity: Vec3,
+}
+
+impl Billboard {
+    /// Creates a new [`Billboard`] with some text.
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            color: Color::WHITE,
+            font_size: 24.0,
+            offset: Vec3::ZERO,
+            reference_distance: 5.0,
+            min_scale: 0.4,
+            max_distance: 30.0,
+            fade_distance: 5.0,
+            lifetime: None,
+            velocity: Vec3::ZERO,
+        }
+    }
+
+    /// Draws the text at an offset from the entity.
+    pub fn with_offset(mut self, offset: Vec3) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Draws the text in a color.
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Despawns the entity after some seconds, fading out over the last one.
+    pub fn with_lifetime(mut self, lifetime: f32) -> Self {
+        self.lifetime = Some(lifetime);
+        self
+    }
+
+    /// The share of the font size that the text is drawn at from a distance.
+    pub fn scale_at(&self, distance: f32) -> f32 {
+        if distance <= self.reference_distance {
+            1.0
+        } else {
+            (self.reference_distance / distance).max(self.min_scale)
+        }
+    }
+
+    /// How opaque the text is at a distance, from `0.0` to `1.0`.
+    pub fn alpha_at(&self, distance: f32) -> f32 {
+        let distance_alpha = if self.fade_distance > 0.0 {
+            ((self.max_distance - distance) / self.fade_distance).clamp(0.0, 1.0)
+        } else if distance <= self.max_distance {
+            1.0
+        } else {
+            0.0
+        };
+        let lifetime_alpha = self
+            .lifetime
+            .map_or(1.0, |lifetime| lifetime.clamp(0.0, 1.0));
+        distance_alpha * lifetime_alpha
+    }
+}
+
+/// The UI text node that draws a [`Billboard`] for a camera, managed by the [`BillboardPlugin`].
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BillboardText {
+    /// The entity with the billboard.
+    pub billboard: Entity,
+    /// The camera that the billboard is drawn for.
+    pub camera: Entity,
+}
+
+/// A marker for the billboards that show the prompt of a focused [`Interactable`].
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct InteractionPrompt;
+
+/// Settings for the [`BillboardPlugin`].
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct BillboardSettings {
+    /// The path of the font used for billboards, relative to the assets folder.
+    pub font: String,
+    /// Whether damage numbers pop up over bodies that take damage.
+    pub damage_numbers: bool,
+    /// The color of damage numbers.
+    pub damage_color: Color,
+    /// Whether the prompts of focused interactables are shown.
+    pub interaction_prompts: bool,
+    /// Where prompts are drawn relative to their interactable.
+    pub prompt_offset: Vec3,
+}
+
+impl Default for BillboardSettings {
+    fn default() -> Self {
+        Self {
+            font: "fonts/DejaVuSansMono.ttf".to_string(),
+            damage_numbers: true,
+            damage_color: Color::ORANGE,
+            interaction_prompts: true,
+            prompt_offset: 0.5 * Vec3::Y,
+        }
+    }
+}
+
+/// A plugin that draws [`Billboard`]s.
+#[derive(Default)]
+pub struct BillboardPlugin {
+    /// The settings used by the plugin.
+    pub settings: BillboardSettings,
+}
+
+impl BillboardPlugin {
+    /// Creates a new [`BillboardPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Plugin for BillboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone())
+            .add_event::<DamageEvent>()
+            .add_system(spawn_damage_numbers)
+            .add_system(show_interaction_prompts)
+            .add_system(age_billboards.with_run_criteria(is_playing))
+            .add_system_to_stage(CoreStage::PostUpdate, despawn_billboard_texts)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                draw_billboards.after(bevy::transform::TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Pops up a damage number over every body that takes damage.
+pub fn spawn_damage_numbers(
+    mut commands: Commands,
+    settings: Res<BillboardSettings>,
+    mut events: EventReader<DamageEvent>,
+    bodies: Query<&GlobalTransform, (With<Health>, Without<Invulnerable>)>,
+) {
+    for event in events.iter() {
+        if !settings.damage_numbers || event.amount <= 0.0 {
+            continue;
+        }
+        let Ok(transform) = bodies.get(event.entity) else {
+            continue;
+        };
+        let mut billboard = Billboard::new(format!("{:.0}", event.amount.ceil()))
+            .with_color(settings.damage_color)
+            .with_offset(Vec3::Y)
+            .with_lifetime(1.0);
+        billboard.velocity = Vec3::Y;
+        commands.spawn((
+            billboard,
+            TransformBundle::from(Transform::from_translation(transform.translation())),
+            Name::new("Damage Number"),
+        ));
+    }
+}
+
+/// Shows the prompt of every focused [`Interactable`] and hides the others.
+pub fn show_interaction_prompts(
+    mut commands: Commands,
+    settings: Res<BillboardSettings>,
+    cameras: Query<&InteractionFocus>,
+    interactables: Query<&Interactable>,
+    prompts: Query<(Entity, &Parent), With<InteractionPrompt>>,
+) {
+    let focused: Vec<Entity> = cameras
+        .iter()
+        .filter(|_| settings.interaction_prompts)
+        .filter_map(|focus| focus.0)
+        .collect();
+    for (prompt, parent) in &prompts {
+        if !focused.contains(&parent.get()) {
+            commands.entity(prompt).despawn_recursive();
+        }
+    }
+    for target in focused {
+        let has_prompt = prompts.iter().any(|(_, parent)| parent.get() == target);
+        let Ok(interactable) = interactables.get(target) else {
+            continue;
+        };
+        if has_prompt {
+            continue;
+        }
+        let prompt = commands
+            .spawn((
+                Billboard::new(interactable.prompt.clone()).with_offset(settings.prompt_offset),
+                InteractionPrompt,
+                TransformBundle::default(),
+            ))
+            .id();
+        commands.entity(target).add_child(prompt);
+    }
+}
+
+/// Counts down the lifetimes of billboards, despawns the ones that ran out, and moves the ones
+/// that drift.
+pub fn age_billboards(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut billboards: Query<(Entity, &mut Billboard, &mut Transform)>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut billboard, mut transform) in &mut billboards {
+        if billboard.velocity != Vec3::ZERO {
+            transform.translation += dt * billboard.velocity;
+        }
+        if let Some(lifetime) = billboard.lifetime.as_mut() {
+            *lifetime -= dt;
+            if *lifetime <= 0.0 {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Removes the text of billboards or cameras that are gone.
+pub fn despawn_billboard_texts(
+    mut commands: Commands,
+    texts: Query<(Entity, &BillboardText)>,
+    billboards: Query<(), With<Billboard>>,
+    cameras: Query<(), With<Camera>>,
+) {
+    for (entity, text) in &texts {
+        if !billboards.contains(text.billboard) || !cameras.contains(text.camera) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Projects every [`Billboard`] through the camera of every player, into that camera's viewport.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn draw_billboards(
+    mut commands: Commands,
+    settings: Res<BillboardSettings>,
+    asset_server: Option<Res<AssetServer>>,
+    windows: Res<Windows>,
+    render_scale: Option<Res<RenderScale>>,
+    cameras: Query<(
+        Entity,
+        &Camera,
+        &GlobalTransform,
+        &Parent,
+        Option<&DynamicResolution>,
+    )>,
+    players: Query<(), With<Player>>,
+    billboards: Query<(Entity, &Billboard, &GlobalTransform)>,
+    mut texts: Query<(&BillboardText, &mut Text, &mut Style, &mut Visibility)>,
+) {
+    let (Some(asset_server), Some(window)) = (asset_server, windows.get_primary()) else {
+        return;
+    };
+    let is_player_camera =
+        |camera: &Camera, parent: &Parent| camera.is_active && players.contains(parent.get());
+
+    let mut drawn: Vec<(Entity, Entity)> = Vec::new();
+    for (text, mut text_node, mut style, mut visibility) in &mut texts {
+        let Ok((_, billboard, transform)) = billboards.get(text.billboard) else {
+            continue;
+        };
+        drawn.push((text.camera, text.billboard));
+
+        let position = transform.translation() + billboard.offset;
+        let projected = cameras
+            .get(text.camera)
+            .ok()
+            .filter(|(_, camera, _, parent, _)| is_player_camera(camera, parent))
+            .and_then(|(_, camera, camera_transform, _, dynamic_resolution)| {
+                let distance = camera_transform.translation().distance(position);
+                let (scale, pixel_size) = match (&render_scale, dynamic_resolution) {
+                    (Some(render_scale), Some(_)) => (
+                        render_scale.0,
+                        render_scale.0 * window.scale_factor() as f32,
+                    ),
+                    (None, Some(_)) => (1.0, window.scale_factor() as f32),
+                    _ => (1.0, 1.0),
+                };
+                // The projection is relative to the viewport with its origin in the bottom left
+                // corner, and in pixels of the scaled image for dynamic resolution cameras.
+                let viewport = logical_viewport_rect(camera, window, scale);
+                let screen = camera.world_to_viewport(camera_transform, position)? / pixel_size;
+                let screen = Vec2::new(viewport.min.x + screen.x, viewport.max.y - screen.y);
+                viewport.contains(screen).then_some((screen, distance))
+            });
+        let shown = projected.filter(|(_, distance)| billboard.alpha_at(*distance) > 0.0);
+        let Some((screen, distance)) = shown else {
+            if visibility.is_visible {
+                visibility.is_visible = false;
+            }
+            continue;
+        };
+
+        if !visibility.is_visible {
+            visibility.is_visible = true;
+        }
+        let font_size = billboard.font_size * billboard.scale_at(distance);
+        let mut color = billboard.color;
+        color.set_a(color.a() * billboard.alpha_at(distance));
+        let section = &mut text_node.sections[0];
+        if section.value != billboard.text {
+            section.value.clone_from(&billboard.text);
+        }
+        section.style.font_size = font_size;
+        section.style.color = color;
+        // The text is aligned to its bottom, so it is placed from the bottom of the window.
+        style.position = UiRect {
+            left: Val::Px(screen.x - 0.5 * BILLBOARD_WIDTH),
+            bottom: Val::Px(window.height() - screen.y),
+            ..default()
+        };
+    }
+
+    let player_cameras = cameras
+        .iter()
+        .filter(|(_, camera, _, parent, _)| is_player_camera(camera, parent));
+    for (camera, ..) in player_cameras {
+        for (billboard, _, _) in &billboards {
+            if drawn.contains(&(camera, billboard)) {
+                continue;
+            }
+            commands.spawn((
+                TextBundle {
+                    visibility: Visibility { is_visible: false },
+                    ..TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load(settings.font.as_str()),
+                            font_size: 24.0,
+                            color: Color::NONE,
+                        },
+                    )
+                    .with_text_alignment(TextAlignment::BOTTOM_CENTER)
+                    .with_style(Style {
+                        position_type: PositionType::Absolute,
+                        size: Size::new(Val::Px(BILLBOARD_WIDTH), Val::Auto),
+                        ..default()
+                    })
+                },
+                BillboardText { billboard, camera },
+            ));
+        }
+    }
+}
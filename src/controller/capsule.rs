@@ -0,0 +1,192 @@
+//! A mod that resizes the capsule of a character at runtime.
+//!
+//! The [`CharacterCapsule`] of a body describes the capsule of its collider and how high its
+//! eyes are. Sending a [`ResizeCapsuleEvent`] smoothly changes the capsule to another size, for
+//! crouching, shapeshifting, or getting into a vehicle. The feet of the character stay where
+//! they are while the capsule changes, and the camera follows the eye height through the offset
+//! of its [`LookTransform`].
+//!
+//! A capsule only grows when there is room for it. While the new capsule would overlap the level,
+//! for example when standing up under a low ceiling, the resize waits and is marked as blocked
+//! until the character moves somewhere with enough headroom.
+
+use super::*;
+use crate::{rapier_mesh_bundles::*, state::*};
+
+/// The capsule of a character's collider.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct CharacterCapsule {
+    /// Half the length of the straight part of the capsule.
+    pub half_length: f32,
+    /// The radius of the capsule.
+    pub radius: f32,
+    /// The height of the camera above the center of the capsule.
+    pub eye_height: f32,
+}
+
+impl CharacterCapsule {
+    /// Creates a new [`CharacterCapsule`].
+    pub fn new(half_length: f32, radius: f32, eye_height: f32) -> Self {
+        Self {
+            half_length,
+            radius,
+            eye_height,
+        }
+    }
+
+    /// Half the height of the whole capsule, from its center to the top or the bottom.
+    pub fn half_height(&self) -> f32 {
+        self.half_length + self.radius
+    }
+
+    /// The upright collider of the capsule.
+    pub fn collider(&self) -> Collider {
+        Collider::capsule_y(self.half_length, self.radius)
+    }
+
+    /// The capsule part of the way to another one, where `t` goes from `0.0` to `1.0`.
+    pub fn lerp(&self, other: &CharacterCapsule, t: f32) -> Self {
+        let lerp = |from: f32, to: f32| from + (to - from) * t;
+        Self {
+            half_length: lerp(self.half_length, other.half_length),
+            radius: lerp(self.radius, other.radius),
+            eye_height: lerp(self.eye_height, other.eye_height),
+        }
+    }
+}
+
+/// A resize in progress, managed by the [`CapsuleResizePlugin`].
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct CapsuleResize {
+    /// The capsule when the resize started.
+    pub from: CharacterCapsule,
+    /// The capsule at the end of the resize.
+    pub to: CharacterCapsule,
+    /// How many seconds the whole resize takes.
+    pub duration: f32,
+    /// How far the resize has gone, from `0.0` to `1.0`.
+    pub progress: f32,
+    /// Whether the capsule is waiting for room to grow.
+    pub blocked: bool,
+}
+
+/// An event that smoothly resizes the capsule of a character.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResizeCapsuleEvent {
+    /// The body of the character.
+    pub body: Entity,
+    /// The new capsule.
+    pub capsule: CharacterCapsule,
+    /// How many seconds the resize takes. Zero resizes at once, if there is room.
+    pub duration: f32,
+}
+
+/// A plugin that resizes the capsules of characters.
+#[derive(Default)]
+pub struct CapsuleResizePlugin;
+
+impl CapsuleResizePlugin {
+    /// Creates a new [`CapsuleResizePlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for CapsuleResizePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ResizeCapsuleEvent>()
+            .add_system(start_capsule_resizes)
+            .add_system(
+                resize_capsules
+                    .with_run_criteria(is_playing)
+                    .after(start_capsule_resizes),
+            );
+    }
+}
+
+/// Starts a resize for every [`ResizeCapsuleEvent`], from wherever the capsule is now.
+pub fn start_capsule_resizes(
+    mut commands: Commands,
+    mut events: EventReader<ResizeCapsuleEvent>,
+    bodies: Query<&CharacterCapsule>,
+) {
+    for event in events.iter() {
+        let Ok(capsule) = bodies.get(event.body) else {
+            continue;
+        };
+        commands.entity(event.body).insert(CapsuleResize {
+            from: *capsule,
+            to: event.capsule,
+            duration: event.duration,
+            progress: 0.0,
+            blocked: false,
+        });
+    }
+}
+
+/// Moves every resize along, keeping the feet planted and checking for headroom.
+#[allow(clippy::type_complexity)]
+pub fn resize_capsules(
+    mut commands: Commands,
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut bodies: Query<(
+        Entity,
+        &mut CharacterCapsule,
+        &mut CapsuleResize,
+        &mut Transform,
+        &mut Collider,
+        Option<&Handle<Mesh>>,
+        Option<&Children>,
+    )>,
+    mut cameras: Query<&mut LookTransform>,
+) {
+    for (body, mut capsule, mut resize, mut transform, mut collider, mesh, children) in &mut bodies
+    {
+        let progress = if resize.duration > 0.0 {
+            (resize.progress + time.delta_seconds() / resize.duration).min(1.0)
+        } else {
+            1.0
+        };
+        let next = resize.from.lerp(&resize.to, progress);
+
+        // The center moves so that the bottom of the capsule stays where it is.
+        let rise = next.half_height() - capsule.half_height();
+        let center = transform.translation + transform.rotation * (rise * Vec3::Y);
+        let next_collider = next.collider();
+        let grows = next.half_height() > capsule.half_height() || next.radius > capsule.radius;
+        if grows {
+            let filter = QueryFilter::default()
+                .exclude_sensors()
+                .exclude_collider(body)
+                .exclude_rigid_body(body);
+            let blocked = rapier_context
+                .intersection_with_shape(center, transform.rotation, &next_collider, filter)
+                .is_some();
+            if resize.blocked != blocked {
+                resize.blocked = blocked;
+            }
+            if blocked {
+                continue;
+            }
+        }
+
+        resize.progress = progress;
+        transform.translation = center;
+        *collider = next_collider;
+        if let Some(mesh) = mesh.and_then(|mesh| meshes.get_mut(mesh)) {
+            *mesh = MeshQuality::default().capsule_mesh(next.half_length, next.radius);
+        }
+        for child in children.into_iter().flatten() {
+            if let Ok(mut look_transform) = cameras.get_mut(*child) {
+                look_transform.offset.y = next.eye_height;
+            }
+        }
+        *capsule = next;
+
+        if progress >= 1.0 {
+            commands.entity(body).remove::<CapsuleResize>();
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! A mod with a crosshair and hit markers for testing combat on maps.
+//!
+//! The [`CrosshairPlugin`] draws a crosshair in the middle of the viewport of every active camera
+//! that belongs to a [`Player`], in the style of the [`CrosshairSettings`], and rebuilds them
+//! whenever the settings change. Under split-screen, every player gets their own crosshair. Hit
+//! markers flash around the crosshair of the player named by each [`HitMarkerEvent`], in one color
+//! for hits and another for kills, and fade out.
+//!
+//! Damage dealt by a player, meaning a [`DamageEvent`] or [`DeathEvent`] whose source is a
+//! [`Player`], sends the hit markers by itself. Games with their own combat can send
+//! [`HitMarkerEvent`]s directly.
+
+use super::{health::*, split_screen::*};
+use crate::{dynamic_resolution::*, map::spawn::*};
+
+use bevy::prelude::*;
+
+/// The kinds of hit markers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HitKind {
+    /// Damage was dealt.
+    Hit,
+    /// The damage was fatal.
+    Kill,
+}
+
+/// An event that flashes a hit marker around a crosshair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HitMarkerEvent {
+    /// The kind of hit.
+    pub kind: HitKind,
+    /// The player whose crosshair flashes, or `None` to flash every crosshair.
+    pub player: Option<Entity>,
+}
+
+/// The look of the crosshair and the hit markers.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct CrosshairSettings {
+    /// Whether the crosshair is drawn.
+    pub visible: bool,
+    /// The color of the crosshair.
+    pub color: Color,
+    /// The length of each arm of the crosshair, in pixels.
+    pub arm_length: f32,
+    /// The thickness of the arms, in pixels.
+    pub thickness: f32,
+    /// The empty space between the center and the arms, in pixels.
+    pub gap: f32,
+    /// Whether a dot is drawn in the center.
+    pub dot: bool,
+    /// The color of the markers for hits.
+    pub hit_color: Color,
+    /// The color of the markers for kills.
+    pub kill_color: Color,
+    /// The size of each hit marker, in pixels.
+    pub hit_marker_size: f32,
+    /// How many seconds a hit marker takes to fade out.
+    pub hit_marker_duration: f32,
+}
+
+impl Default for CrosshairSettings {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            color: Color::WHITE,
+            arm_length: 8.0,
+            thickness: 2.0,
+            gap: 4.0,
+            dot: false,
+            hit_color: Color::WHITE,
+            kill_color: Color::RED,
+            hit_marker_size: 4.0,
+            hit_marker_duration: 0.3,
+        }
+    }
+}
+
+/// The UI node that holds the crosshair of a camera.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crosshair {
+    /// The camera whose viewport the crosshair is centered in.
+    pub camera: Entity,
+}
+
+/// A hit marker node, and how long it is still shown for.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct HitMarker {
+    /// The color of the latest hit.
+    pub color: Color,
+    /// How many seconds the marker is still shown for.
+    pub remaining: f32,
+}
+
+/// A plugin that draws a crosshair and hit markers.
+#[derive(Default)]
+pub struct CrosshairPlugin {
+    /// The settings used by the plugin.
+    pub settings: CrosshairSettings,
+}
+
+impl CrosshairPlugin {
+    /// Creates a new [`CrosshairPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Plugin for CrosshairPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings)
+            .add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_event::<HitMarkerEvent>()
+            .add_system(build_crosshairs)
+            .add_system(place_crosshairs.after(build_crosshairs))
+            .add_system(mark_player_hits)
+            .add_system(
+                show_hit_markers
+                    .after(build_crosshairs)
+                    .after(mark_player_hits),
+            );
+    }
+}
+
+/// Builds a crosshair for every active camera of a [`Player`] and removes those of cameras that
+/// are gone, rebuilding them all whenever the settings change.
+pub fn build_crosshairs(
+    mut commands: Commands,
+    settings: Res<CrosshairSettings>,
+    crosshairs: Query<(Entity, &Crosshair)>,
+    cameras: Query<(Entity, &Camera, &Parent)>,
+    players: Query<(), With<Player>>,
+) {
+    let is_player_camera = |camera: Entity| {
+        cameras
+            .get(camera)
+            .is_ok_and(|(_, camera, parent)| camera.is_active && players.contains(parent.get()))
+    };
+
+    let rebuild = settings.is_changed();
+    for (entity, crosshair) in &crosshairs {
+        if rebuild || !is_player_camera(crosshair.camera) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    if !settings.visible {
+        return;
+    }
+
+    for (camera, ..) in &cameras {
+        let has_crosshair = crosshairs
+            .iter()
+            .any(|(_, crosshair)| crosshair.camera == camera);
+        if is_player_camera(camera) && (rebuild || !has_crosshair) {
+            spawn_crosshair(&mut commands, &settings, camera);
+        }
+    }
+}
+
+/// Spawns the crosshair of a camera, which [`place_crosshairs`] then moves into its viewport.
+fn spawn_crosshair(commands: &mut Commands, settings: &CrosshairSettings, camera: Entity) {
+    // Every part is placed relative to the center of a zero-sized node in the middle.
+    let part = |center: Vec2, size: Vec2, color: Color| NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                left: Val::Px(center.x - 0.5 * size.x),
+                top: Val::Px(center.y - 0.5 * size.y),
+                ..default()
+            },
+            size: Size::new(Val::Px(size.x), Val::Px(size.y)),
+            ..default()
+        },
+        background_color: color.into(),
+        ..default()
+    };
+
+    let reach = settings.gap + 0.5 * settings.arm_length;
+    let horizontal = Vec2::new(settings.arm_length, settings.thickness);
+    let vertical = Vec2::new(settings.thickness, settings.arm_length);
+    let marker_reach = settings.gap + settings.arm_length;
+    let marker_size = Vec2::splat(settings.hit_marker_size);
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Percent(50.0),
+                        top: Val::Percent(50.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                ..default()
+            },
+            Crosshair { camera },
+            Name::new("Crosshair"),
+        ))
+        .with_children(|parent| {
+            for (center, size) in [
+                (Vec2::new(-reach, 0.0), horizontal),
+                (Vec2::new(reach, 0.0), horizontal),
+                (Vec2::new(0.0, -reach), vertical),
+                (Vec2::new(0.0, reach), vertical),
+            ] {
+                parent.spawn(part(center, size, settings.color));
+            }
+            if settings.dot {
+                parent.spawn(part(
+                    Vec2::ZERO,
+                    Vec2::splat(settings.thickness),
+                    settings.color,
+                ));
+            }
+            // The hit markers sit on the diagonals, hidden until something is hit.
+            for corner in [
+                Vec2::new(-1.0, -1.0),
+                Vec2::new(1.0, -1.0),
+                Vec2::new(-1.0, 1.0),
+                Vec2::new(1.0, 1.0),
+            ] {
+                parent.spawn((
+                    part(marker_reach * corner.normalize(), marker_size, Color::NONE),
+                    HitMarker::default(),
+                ));
+            }
+        });
+}
+
+/// Centers every crosshair in the viewport of its camera.
+pub fn place_crosshairs(
+    windows: Res<Windows>,
+    render_scale: Option<Res<RenderScale>>,
+    cameras: Query<(&Camera, Option<&DynamicResolution>)>,
+    mut crosshairs: Query<(&Crosshair, &mut Style)>,
+) {
+    let Some(window) = windows.get_primary() else {
+        return;
+    };
+    for (crosshair, mut style) in &mut crosshairs {
+        let Ok((camera, dynamic_resolution)) = cameras.get(crosshair.camera) else {
+            continue;
+        };
+        let scale = match (&render_scale, dynamic_resolution) {
+            (Some(render_scale), Some(_)) => render_scale.0,
+            _ => 1.0,
+        };
+        let center = logical_viewport_rect(camera, window, scale).center();
+        let position = UiRect {
+            left: Val::Px(center.x),
+            top: Val::Px(center.y),
+            ..default()
+        };
+        // Only touch the style when it moves, so the UI layout is not recomputed every frame.
+        if style.position != position {
+            style.position = position;
+        }
+    }
+}
+
+/// Sends a [`HitMarkerEvent`] for the damage dealt by players.
+pub fn mark_player_hits(
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventReader<DeathEvent>,
+    mut hit_events: EventWriter<HitMarkerEvent>,
+    players: Query<(), With<Player>>,
+) {
+    let by_player = |source: Option<Entity>| source.filter(|source| players.contains(*source));
+    let kills = death_events
+        .iter()
+        .filter_map(|event| by_player(event.source))
+        .map(|player| (HitKind::Kill, player));
+    let hits = damage_events
+        .iter()
+        .filter_map(|event| by_player(event.source))
+        .map(|player| (HitKind::Hit, player));
+    for (kind, player) in kills.chain(hits) {
+        hit_events.send(HitMarkerEvent {
+            kind,
+            player: Some(player),
+        });
+    }
+}
+
+/// Flashes the hit markers of the crosshairs named by every [`HitMarkerEvent`] and fades them
+/// out.
+pub fn show_hit_markers(
+    time: Res<Time>,
+    settings: Res<CrosshairSettings>,
+    mut events: EventReader<HitMarkerEvent>,
+    crosshairs: Query<&Crosshair>,
+    cameras: Query<&Parent, With<Camera>>,
+    mut markers: Query<(&mut HitMarker, &mut BackgroundColor, &Parent)>,
+) {
+    let events: Vec<_> = events.iter().copied().collect();
+    for (mut marker, mut background, crosshair) in &mut markers {
+        let player = crosshairs
+            .get(crosshair.get())
+            .and_then(|crosshair| cameras.get(crosshair.camera))
+            .map(|camera| camera.get())
+            .ok();
+        // Kills take precedence over hits in the same frame.
+        let flash = events
+            .iter()
+            .filter(|event| event.player.is_none() || event.player == player)
+            .map(|event| event.kind)
+            .max_by_key(|kind| *kind == HitKind::Kill);
+        match flash {
+            Some(HitKind::Kill) => {
+                marker.color = settings.kill_color;
+                marker.remaining = settings.hit_marker_duration;
+            }
+            Some(HitKind::Hit) => {
+                marker.color = settings.hit_color;
+                marker.remaining = settings.hit_marker_duration;
+            }
+            None if marker.remaining <= 0.0 => continue,
+            None => marker.remaining = (marker.remaining - time.delta_seconds()).max(0.0),
+        }
+        let alpha = if settings.hit_marker_duration > 0.0 {
+            marker.remaining / settings.hit_marker_duration
+        } else {
+            0.0
+        };
+        let mut color = marker.color;
+        color.set_a(color.a() * alpha);
+        background.0 = color;
+    }
+}
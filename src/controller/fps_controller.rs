@@ -32,7 +32,8 @@
 //                                                                                               //
 // ============================================================================================= //
 
-use super::*;
+use super::{abilities::*, lock_on::*, split_screen::*, tuning::*, *};
+use crate::state::*;
 
 use bevy::{
     app::prelude::*,
@@ -40,17 +41,26 @@ use bevy::{
     input::{mouse::*, prelude::*},
     math::prelude::*,
     prelude::*,
+    utils::HashSet,
 };
 use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
 
 /// Types of events that can be triggered for kinematic controllers.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum FpsControlEvent {
     /// Rotate the camera view.
     RotateCamera(Vec2),
-    /// Translate the character.
+    /// Translate the character, as a fraction of the move speed of its [`FpsControllerSettings`],
+    /// so a length of one moves at full speed.
     Translate(Vec3),
-    /// Have the character start a jump.
+    /// Have the character start a jump in a direction, as a fraction of the jump speed of its
+    /// [`FpsControllerSettings`], so [`Vec3::Y`] jumps at full speed.
     Jump(Vec3),
+    /// Use an ability in the direction the character is moving, or looking when standing still.
+    UseAbility(Ability),
+    /// Lock the camera onto the nearest target in view, or let go of its target.
+    ToggleLockOn,
 }
 
 /// A struct that contains the necessary body components to implement the [`FpsCameraPlugin`].
@@ -64,6 +74,8 @@ pub struct FpsControllerBodyBundle {
     ///
     /// Used to simulate gravity.
     additional_velocity: CustomVelocity,
+    /// The tuning of the character.
+    settings: FpsControllerSettings,
 }
 
 impl Default for FpsControllerBodyBundle {
@@ -73,9 +85,12 @@ impl Default for FpsControllerBodyBundle {
             character_controller: KinematicCharacterController {
                 translation: Some(Vec3::ZERO), // Allow gravity to be applied from the start
                 apply_impulse_to_dynamic_bodies: true,
+                // Event spaces are sensors that players walk into, not walls.
+                filter_flags: QueryFilterFlags::EXCLUDE_SENSORS,
                 ..default()
             },
             additional_velocity: CustomVelocity::default(),
+            settings: FpsControllerSettings::default(),
         }
     }
 }
@@ -85,9 +100,23 @@ impl FpsControllerBodyBundle {
     pub fn new() -> Self {
         FpsControllerBodyBundle::default()
     }
+
+    /// Creates a new [`FpsControllerBodyBundle`] tuned with a preset.
+    pub fn with_preset(preset: ControllerPreset, scale: WorldScale) -> Self {
+        Self::with_settings(preset.settings(scale))
+    }
+
+    /// Creates a new [`FpsControllerBodyBundle`] with the given tuning.
+    pub fn with_settings(settings: FpsControllerSettings) -> Self {
+        let mut bundle = Self::default();
+        settings.apply(&mut bundle.character_controller);
+        bundle.settings = settings;
+        bundle
+    }
 }
 
 /// A plugin that allows for custom character control in a first-person shooter style.
+#[derive(Default)]
 pub struct FpsCameraPlugin {}
 
 impl FpsCameraPlugin {
@@ -99,10 +128,20 @@ impl FpsCameraPlugin {
 
 impl Plugin for FpsCameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_to_stage(CoreStage::PreUpdate, apply_gravity)
-            .add_system(custom_input_map)
-            .add_system(fps_control_system)
-            .add_event::<FpsControlEvent>();
+        app.register_type::<CustomVelocity>()
+            .register_type::<FpsControllerSettings>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                apply_gravity.with_run_criteria(is_playing),
+            )
+            .add_system(custom_input_map.with_run_criteria(is_playing))
+            .add_system(fps_control_system.with_run_criteria(is_playing))
+            .add_system(apply_controller_settings.before(fps_control_system))
+            .add_event::<FpsControlEvent>()
+            .add_event::<PlayerFpsControlEvent>()
+            .add_event::<AbilityEvent>()
+            .add_event::<LockOnEvent>()
+            .add_event::<FallImpactEvent>();
     }
 }
 
@@ -112,9 +151,7 @@ pub fn custom_input_map(
     keyboard: Res<Input<KeyCode>>,
     mut mouse_motion_events: EventReader<MouseMotion>,
 ) {
-    let translate_velocity = 2.0;
     let mouse_rotate_sensitivity = Vec2::splat(0.1);
-    let jump_initial_velocity = 5.0 * Vec3::Y;
 
     let mut cursor_delta = Vec2::ZERO;
     for event in mouse_motion_events.iter() {
@@ -134,43 +171,76 @@ pub fn custom_input_map(
     .iter()
     .fold(None, |dir_acc, &(key, dir)| {
         if keyboard.pressed(key) {
-            return Some(dir_acc.map_or(dir, |acc| acc + dir));
+            Some(dir_acc.map_or(dir, |acc| acc + dir))
         } else {
-            return dir_acc;
+            dir_acc
         }
     });
 
     if let Some(translation_dir) = translation_dir_option {
-        events.send(FpsControlEvent::Translate(
-            translate_velocity * translation_dir.normalize(),
-        ));
+        events.send(FpsControlEvent::Translate(translation_dir.normalize()));
     }
 
     if keyboard.pressed(KeyCode::Space) {
-        events.send(FpsControlEvent::Jump(jump_initial_velocity));
+        events.send(FpsControlEvent::Jump(Vec3::Y));
+    }
+
+    if keyboard.just_pressed(KeyCode::LShift) {
+        events.send(FpsControlEvent::UseAbility(Ability::Dash));
+    }
+
+    if keyboard.just_pressed(KeyCode::Q) {
+        events.send(FpsControlEvent::ToggleLockOn);
     }
 }
 
 /// Implements the control system for [`FpsCameraPlugin`].
+///
+/// Plain [`FpsControlEvent`]s move every camera that reads the keyboard, and
+/// [`PlayerFpsControlEvent`]s only move the camera of their player.
+#[allow(clippy::type_complexity)]
 pub fn fps_control_system(
     time: Res<Time>,
-    rapier_context: Res<RapierContext>,
     mut events: EventReader<FpsControlEvent>,
-    mut cameras: Query<(&Parent, &mut LookTransform, &mut Transform)>,
+    mut player_events: EventReader<PlayerFpsControlEvent>,
+    mut ability_events: EventWriter<AbilityEvent>,
+    mut lock_on_events: EventWriter<LockOnEvent>,
+    mut cameras: Query<(
+        Entity,
+        &Parent,
+        &mut LookTransform,
+        &mut Transform,
+        Option<&PlayerCamera>,
+        Option<&PlayerInput>,
+    )>,
     mut controllers: Query<(
         &mut KinematicCharacterController,
         &mut CustomVelocity,
         &KinematicCharacterControllerOutput,
+        Option<&FpsControllerSettings>,
     )>,
 ) {
-    for (parent, mut look_transform, mut transform) in &mut cameras {
+    // Read the events once so that every camera sees them.
+    let keyboard_events: Vec<FpsControlEvent> = events.iter().copied().collect();
+    let player_events: Vec<PlayerFpsControlEvent> = player_events.iter().copied().collect();
+
+    for (camera, parent, mut look_transform, mut transform, player, input) in &mut cameras {
         let yaw_rot = Quat::from_axis_angle(Vec3::Y, look_transform.yaw);
         let rot_x = yaw_rot * Vec3::X;
         let rot_y = yaw_rot * Vec3::Y;
         let rot_z = yaw_rot * Vec3::Z;
 
+        let reads_keyboard = input.is_none_or(|input| *input == PlayerInput::Keyboard);
+        let camera_events = keyboard_events.iter().filter(|_| reads_keyboard).chain(
+            player_events
+                .iter()
+                .filter(|event| Some(event.player) == player.map(|player| player.index))
+                .map(|event| &event.event),
+        );
+
         let dt = time.delta_seconds();
-        for event in events.iter() {
+        let mut move_direction = Vec3::ZERO;
+        for event in camera_events {
             match event {
                 FpsControlEvent::RotateCamera(delta) => {
                     // Rotates with pitch and yaw.
@@ -180,10 +250,13 @@ pub fn fps_control_system(
                 }
                 FpsControlEvent::Translate(delta) => {
                     // Translates the parent up/down (Y) left/right (X) and forward/back (Z).
-                    if let Ok((mut parent_controller, _, _)) = controllers.get_mut(parent.get()) {
-                        let translation = dt
-                            * (delta.x * rot_x + delta.y * rot_y + delta.z * rot_z)
-                            * rapier_context.physics_scale();
+                    if let Ok((mut parent_controller, _, _, settings)) =
+                        controllers.get_mut(parent.get())
+                    {
+                        let move_speed = settings.copied().unwrap_or_default().move_speed;
+                        let direction = delta.x * rot_x + delta.y * rot_y + delta.z * rot_z;
+                        move_direction += direction;
+                        let translation = dt * move_speed * direction;
                         parent_controller.translation = Some(
                             parent_controller
                                 .translation
@@ -192,17 +265,72 @@ pub fn fps_control_system(
                         );
                     }
                 }
-                FpsControlEvent::Jump(jump_velocity) => {
+                FpsControlEvent::Jump(jump_direction) => {
                     // Start a jump
-                    if let Ok((_, mut velocity, parent_controller_output)) =
+                    if let Ok((_, mut velocity, parent_controller_output, settings)) =
                         controllers.get_mut(parent.get())
                     {
                         if parent_controller_output.grounded {
-                            velocity.0 = *jump_velocity * rapier_context.physics_scale();
+                            let jump_speed = settings.copied().unwrap_or_default().jump_speed;
+                            velocity.0 = *jump_direction * jump_speed;
                         }
                     }
                 }
+                FpsControlEvent::UseAbility(ability) => {
+                    let direction = if move_direction != Vec3::ZERO {
+                        move_direction.normalize()
+                    } else {
+                        transform.forward()
+                    };
+                    ability_events.send(AbilityEvent {
+                        body: parent.get(),
+                        ability: *ability,
+                        direction,
+                    });
+                }
+                FpsControlEvent::ToggleLockOn => {
+                    lock_on_events.send(LockOnEvent { camera });
+                }
             }
         }
     }
 }
+
+/// Finds the bodies whose players pressed jump, the same way [`fps_control_system`] does.
+///
+/// Systems that react to jumping read the control events themselves and pass them here along with
+/// the cameras, so that keyboard and gamepad players are told apart.
+pub fn jumping_bodies<'a>(
+    keyboard_events: impl IntoIterator<Item = &'a FpsControlEvent>,
+    player_events: impl IntoIterator<Item = &'a PlayerFpsControlEvent>,
+    cameras: impl IntoIterator<
+        Item = (
+            &'a Parent,
+            Option<&'a PlayerCamera>,
+            Option<&'a PlayerInput>,
+        ),
+    >,
+) -> HashSet<Entity> {
+    let is_jump = |event: &FpsControlEvent| matches!(event, FpsControlEvent::Jump(_));
+    // Read every event, so that none are left over for the next frame.
+    let keyboard_jump = keyboard_events
+        .into_iter()
+        .filter(|event| is_jump(event))
+        .count()
+        > 0;
+    let player_jumps: HashSet<usize> = player_events
+        .into_iter()
+        .filter(|event| is_jump(&event.event))
+        .map(|event| event.player)
+        .collect();
+
+    cameras
+        .into_iter()
+        .filter(|(_, player, input)| {
+            let reads_keyboard = input.is_none_or(|input| *input == PlayerInput::Keyboard);
+            (reads_keyboard && keyboard_jump)
+                || player.is_some_and(|player| player_jumps.contains(&player.index))
+        })
+        .map(|(body, ..)| body.get())
+        .collect()
+}
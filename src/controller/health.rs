@@ -0,0 +1,168 @@
+//! A mod for the health of characters and the damage they take.
+//!
+//! Anything that hurts a body, such as a crushing door, sends a [`DamageEvent`]. The
+//! [`HealthPlugin`] takes the damage off the [`Health`] of the body, unless the body is
+//! [`Invulnerable`], and sends a [`DeathEvent`] when its health runs out. What happens to a body
+//! that dies is left to the game, which can respawn it through a
+//! [`RespawnEvent`](crate::map::checkpoint::RespawnEvent) or end the round.
+//!
+//! Bodies with a [`FallDamage`] are hurt by the [`FallImpactEvent`]s of landing too fast, so that
+//! the height of a map has consequences.
+
+use super::{abilities::*, tuning::*, FallImpactEvent};
+use crate::state::*;
+
+use bevy::prelude::*;
+
+/// How much damage a body can take before it dies.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Health {
+    /// The health left.
+    pub current: f32,
+    /// The health of the body when it is unhurt.
+    pub max: f32,
+}
+
+impl Health {
+    /// Creates a new unhurt [`Health`].
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    /// Whether the health has run out.
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    /// Restores the health to its maximum, for example after respawning.
+    pub fn heal_fully(&mut self) {
+        self.current = self.max;
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new(100.0)
+    }
+}
+
+/// An event that damages a body.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DamageEvent {
+    /// The body that is damaged.
+    pub entity: Entity,
+    /// How much health the body loses.
+    pub amount: f32,
+    /// What caused the damage, if anything in particular.
+    pub source: Option<Entity>,
+}
+
+/// An event sent when the health of a body runs out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeathEvent {
+    /// The body that died.
+    pub entity: Entity,
+    /// What dealt the last damage, if anything in particular.
+    pub source: Option<Entity>,
+}
+
+/// A component that hurts a body that lands too fast.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct FallDamage {
+    /// The landing speed up to which the body is not hurt, in units per second.
+    pub min_speed: f32,
+    /// The damage for every unit per second of landing speed above the minimum.
+    pub damage_per_speed: f32,
+}
+
+impl Default for FallDamage {
+    fn default() -> Self {
+        Self {
+            min_speed: 12.0,
+            damage_per_speed: 8.0,
+        }
+    }
+}
+
+impl FallDamage {
+    /// Converts a fall damage measured in meters into world units.
+    pub fn scaled(self, scale: WorldScale) -> Self {
+        Self {
+            min_speed: self.min_speed * scale.0,
+            damage_per_speed: self.damage_per_speed / scale.0,
+        }
+    }
+
+    /// The damage for landing at a speed.
+    pub fn damage(&self, speed: f32) -> f32 {
+        (speed - self.min_speed).max(0.0) * self.damage_per_speed
+    }
+}
+
+/// A plugin that applies [`DamageEvent`]s to the [`Health`] of bodies.
+#[derive(Default)]
+pub struct HealthPlugin;
+
+impl HealthPlugin {
+    /// Creates a new [`HealthPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_event::<FallImpactEvent>()
+            .add_system(apply_fall_damage.with_run_criteria(is_playing))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                apply_damage.with_run_criteria(is_playing),
+            );
+    }
+}
+
+/// Takes damage off the health of bodies and sends a [`DeathEvent`] for those that die.
+pub fn apply_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+    mut bodies: Query<&mut Health, Without<Invulnerable>>,
+) {
+    for event in damage_events.iter() {
+        let Ok(mut health) = bodies.get_mut(event.entity) else {
+            continue;
+        };
+        if health.is_dead() {
+            continue;
+        }
+        health.current = (health.current - event.amount).max(0.0);
+        if health.is_dead() {
+            death_events.send(DeathEvent {
+                entity: event.entity,
+                source: event.source,
+            });
+        }
+    }
+}
+
+/// Sends a [`DamageEvent`] for every body with a [`FallDamage`] that landed too fast.
+pub fn apply_fall_damage(
+    mut impact_events: EventReader<FallImpactEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+    bodies: Query<&FallDamage>,
+) {
+    for event in impact_events.iter() {
+        let Ok(fall_damage) = bodies.get(event.entity) else {
+            continue;
+        };
+        let amount = fall_damage.damage(event.speed);
+        if amount > 0.0 {
+            damage_events.send(DamageEvent {
+                entity: event.entity,
+                amount,
+                source: None,
+            });
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! A mod that lets players use the objects they look at.
+//!
+//! Every frame, a ray is cast from each first-person camera. The closest [`Interactable`] it
+//! hits within the interactable's range becomes the camera's [`InteractionFocus`] and is
+//! highlighted. Pressing the use key then sends an [`InteractEvent`] for it, which is the hook
+//! that doors, buttons, and pickups listen to.
+//!
+//! The ray ignores sensors, such as event spaces, and the player's own body. An [`Interactable`]
+//! may be on the collider that was hit or on any of its ancestors, so objects built from several
+//! colliders only need one.
+
+use super::{split_screen::*, LookTransform};
+use crate::state::*;
+
+use bevy::{prelude::*, utils::HashSet};
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// An object that players can use.
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+pub struct Interactable {
+    /// The text shown to the player while the object is focused, such as `"Open door"`.
+    pub prompt: String,
+    /// How far from the camera the object can be used.
+    #[serde(default = "Interactable::default_range")]
+    pub range: f32,
+    /// The action that games run when the object is used, such as `"toggle_door"`.
+    #[serde(default)]
+    pub action: Option<String>,
+}
+
+impl Default for Interactable {
+    fn default() -> Self {
+        Self {
+            prompt: String::new(),
+            range: Self::default_range(),
+            action: None,
+        }
+    }
+}
+
+impl Interactable {
+    /// Creates a new [`Interactable`] with a prompt.
+    pub fn new(prompt: impl Into<String>) -> Self {
+        Self {
+            prompt: prompt.into(),
+            ..default()
+        }
+    }
+
+    /// Sets the action that games run when the object is used.
+    pub fn with_action(mut self, action: impl Into<String>) -> Self {
+        self.action = Some(action.into());
+        self
+    }
+
+    /// Sets how far from the camera the object can be used.
+    pub fn with_range(mut self, range: f32) -> Self {
+        self.range = range;
+        self
+    }
+
+    fn default_range() -> f32 {
+        2.0
+    }
+}
+
+/// An event sent when a player uses an [`Interactable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InteractEvent {
+    /// The [`Interactable`] that was used.
+    pub target: Entity,
+    /// The body of the player that used it.
+    pub user: Entity,
+}
+
+/// The [`Interactable`] a camera is looking at, if any.
+///
+/// This is inserted into first-person cameras by the [`InteractionPlugin`].
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InteractionFocus(pub Option<Entity>);
+
+/// The emissive color a highlighted [`Interactable`] had before it was focused.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct InteractionHighlight(pub Color);
+
+/// Settings for the [`InteractionPlugin`].
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct InteractionSettings {
+    /// The key that keyboard players use objects with.
+    pub key: KeyCode,
+    /// The button that gamepad players use objects with.
+    pub gamepad_button: GamepadButtonType,
+    /// The emissive color of focused objects.
+    pub highlight_color: Color,
+    /// How far a ray is cast at most, which limits the range of every [`Interactable`].
+    pub max_distance: f32,
+}
+
+impl Default for InteractionSettings {
+    fn default() -> Self {
+        Self {
+            key: KeyCode::E,
+            gamepad_button: GamepadButtonType::West,
+            highlight_color: Color::rgb(0.25, 0.25, 0.1),
+            max_distance: 10.0,
+        }
+    }
+}
+
+/// A plugin that focuses, highlights, and uses [`Interactable`]s.
+#[derive(Default)]
+pub struct InteractionPlugin {
+    /// The settings used by the plugin.
+    pub settings: InteractionSettings,
+}
+
+impl InteractionPlugin {
+    /// Creates a new [`InteractionPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Interactable>()
+            .register_type::<Option<String>>()
+            .insert_resource(self.settings)
+            .add_event::<InteractEvent>()
+            .add_system(update_interaction_focus.with_run_criteria(is_playing))
+            .add_system(highlight_interaction_focus.after(update_interaction_focus))
+            .add_system(
+                send_interact_events
+                    .with_run_criteria(is_playing)
+                    .after(update_interaction_focus),
+            );
+    }
+}
+
+/// Casts a ray from every first-person camera to find the [`Interactable`] it looks at.
+#[allow(clippy::type_complexity)]
+pub fn update_interaction_focus(
+    mut commands: Commands,
+    settings: Res<InteractionSettings>,
+    rapier_context: Res<RapierContext>,
+    mut cameras: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            &Parent,
+            Option<&mut InteractionFocus>,
+        ),
+        (With<Camera>, With<LookTransform>),
+    >,
+    interactables: Query<&Interactable>,
+    parents: Query<&Parent>,
+) {
+    for (camera, transform, body, focus) in &mut cameras {
+        let origin = transform.translation();
+        let direction = transform.forward();
+        let filter = QueryFilter::default()
+            .exclude_sensors()
+            .exclude_rigid_body(body.get())
+            .exclude_collider(body.get());
+
+        // Use the first interactable found walking up from the collider that was hit.
+        let focused = rapier_context
+            .cast_ray(origin, direction, settings.max_distance, true, filter)
+            .and_then(|(hit, distance)| {
+                std::iter::successors(Some(hit), |entity| {
+                    parents.get(*entity).ok().map(|parent| parent.get())
+                })
+                .find_map(|entity| interactables.get(entity).ok().map(|i| (entity, i)))
+                .filter(|(_, interactable)| distance <= interactable.range)
+                .map(|(entity, _)| entity)
+            });
+
+        match focus {
+            Some(mut focus) => {
+                if focus.0 != focused {
+                    focus.0 = focused;
+                }
+            }
+            None => {
+                commands.entity(camera).insert(InteractionFocus(focused));
+            }
+        }
+    }
+}
+
+/// Makes the focused [`Interactable`]s glow and restores the ones that lost focus.
+pub fn highlight_interaction_focus(
+    mut commands: Commands,
+    settings: Res<InteractionSettings>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    focuses: Query<&InteractionFocus>,
+    highlighted: Query<(Entity, &InteractionHighlight)>,
+    objects: Query<&Handle<StandardMaterial>>,
+) {
+    let focused: HashSet<Entity> = focuses.iter().filter_map(|focus| focus.0).collect();
+
+    for (entity, highlight) in &highlighted {
+        if focused.contains(&entity) {
+            continue;
+        }
+        if let Some(material) = objects.get(entity).ok().and_then(|m| materials.get_mut(m)) {
+            material.emissive = highlight.0;
+        }
+        commands.entity(entity).remove::<InteractionHighlight>();
+    }
+
+    for entity in focused {
+        if highlighted.contains(entity) {
+            continue;
+        }
+        let Some(material) = objects.get(entity).ok().and_then(|m| materials.get_mut(m)) else {
+            continue;
+        };
+        commands
+            .entity(entity)
+            .insert(InteractionHighlight(material.emissive));
+        material.emissive = settings.highlight_color;
+    }
+}
+
+/// Sends an [`InteractEvent`] for the focus of every player that presses the use key.
+pub fn send_interact_events(
+    settings: Res<InteractionSettings>,
+    keyboard: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    mut events: EventWriter<InteractEvent>,
+    cameras: Query<(&InteractionFocus, &Parent, Option<&PlayerInput>)>,
+) {
+    for (focus, body, input) in &cameras {
+        let Some(entity) = focus.0 else {
+            continue;
+        };
+        let pressed = match input.copied().unwrap_or_default() {
+            PlayerInput::Keyboard => keyboard.just_pressed(settings.key),
+            PlayerInput::Gamepad(gamepad) => {
+                buttons.just_pressed(GamepadButton::new(gamepad, settings.gamepad_button))
+            }
+        };
+        if pressed {
+            events.send(InteractEvent {
+                target: entity,
+                user: body.get(),
+            });
+        }
+    }
+}
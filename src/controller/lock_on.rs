@@ -0,0 +1,250 @@
+//! A mod for locking the camera of a controller onto a target.
+//!
+//! Entities with a [`LockOnTarget`] can be locked onto. [`FpsControlEvent::ToggleLockOn`] is
+//! turned into a [`LockOnEvent`] for the camera of the player, which locks onto the nearest
+//! target in front of the camera that is in range and not hidden behind the map, or lets go of the
+//! target when the camera is already locked on.
+//!
+//! A locked camera gets a [`LockedOn`] and a [`LookTarget`], so it keeps turning towards the
+//! target while the player moves around. Walking forward and back still moves the body towards
+//! and away from the target, but walking sideways circles around it at the same distance instead
+//! of drifting away. The lock is let go when the target is despawned or moves out of range.
+
+use super::{fps_controller::*, look_target::*, *};
+use crate::state::*;
+
+/// A marker for entities that cameras can lock onto.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct LockOnTarget {
+    /// The point to look at relative to the target, in world space.
+    pub offset: Vec3,
+}
+
+impl LockOnTarget {
+    /// Creates a new [`LockOnTarget`] that is looked at by its origin.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`LockOnTarget`] that is looked at by a point offset from its origin.
+    pub fn with_offset(offset: Vec3) -> Self {
+        Self { offset }
+    }
+}
+
+/// The target that a camera is locked onto.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockedOn {
+    /// The entity with the [`LockOnTarget`].
+    pub target: Entity,
+}
+
+/// An event that locks a camera onto the nearest target, or lets go of its target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockOnEvent {
+    /// The camera with the [`LookTransform`].
+    pub camera: Entity,
+}
+
+/// Settings for the [`LockOnPlugin`].
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct LockOnSettings {
+    /// The furthest a target can be to be locked onto, in units.
+    pub range: f32,
+    /// The furthest a locked target can move before the lock is let go, in units.
+    pub break_range: f32,
+    /// The largest angle from the middle of the view that a target can be locked onto at, in
+    /// radians.
+    pub max_angle: f32,
+    /// Whether targets hidden behind fixed colliders are skipped.
+    pub require_line_of_sight: bool,
+    /// How far the target can move from the middle of the view before the camera turns, in
+    /// radians.
+    pub deadzone: f32,
+    /// How quickly a locked camera turns, in radians per second.
+    pub max_angular_speed: f32,
+}
+
+impl Default for LockOnSettings {
+    fn default() -> Self {
+        Self {
+            range: 25.0,
+            break_range: 35.0,
+            max_angle: 0.6,
+            require_line_of_sight: true,
+            deadzone: 0.05,
+            max_angular_speed: 10.0,
+        }
+    }
+}
+
+/// A plugin that locks the cameras of controllers onto targets.
+///
+/// The [`LookTargetPlugin`] is added as well if it has not been added yet.
+#[derive(Default)]
+pub struct LockOnPlugin {
+    /// The settings used by the plugin.
+    pub settings: LockOnSettings,
+}
+
+impl LockOnPlugin {
+    /// Creates a new [`LockOnPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Plugin for LockOnPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<LookTargetPlugin>() {
+            app.add_plugin(LookTargetPlugin::new());
+        }
+
+        app.insert_resource(self.settings)
+            .add_event::<LockOnEvent>()
+            .add_system(
+                toggle_lock_on
+                    .with_run_criteria(is_playing)
+                    .after(fps_control_system),
+            )
+            .add_system(
+                release_lost_targets
+                    .with_run_criteria(is_playing)
+                    .after(toggle_lock_on),
+            )
+            .add_system(
+                orbit_locked_targets
+                    .with_run_criteria(is_playing)
+                    .after(fps_control_system),
+            );
+    }
+}
+
+/// Locks cameras onto the nearest target in view, or lets go of their targets.
+#[allow(clippy::type_complexity)]
+pub fn toggle_lock_on(
+    mut commands: Commands,
+    settings: Res<LockOnSettings>,
+    rapier_context: Option<Res<RapierContext>>,
+    mut events: EventReader<LockOnEvent>,
+    cameras: Query<(&GlobalTransform, Option<&Parent>, Option<&LockedOn>), With<LookTransform>>,
+    targets: Query<(Entity, &LockOnTarget, &GlobalTransform)>,
+) {
+    for event in events.iter() {
+        let Ok((camera_transform, parent, locked_on)) = cameras.get(event.camera) else {
+            continue;
+        };
+        if locked_on.is_some() {
+            commands
+                .entity(event.camera)
+                .remove::<(LockedOn, LookTarget)>();
+            continue;
+        }
+
+        let eye = camera_transform.translation();
+        let forward = camera_transform.forward();
+        let body = parent.map(|parent| parent.get());
+        let visible = |entity: Entity, point: Vec3, distance: f32| {
+            let Some(rapier_context) = &rapier_context else {
+                return true;
+            };
+            if !settings.require_line_of_sight || distance <= 0.0 {
+                return true;
+            }
+            let mut filter = QueryFilter::only_fixed().exclude_sensors();
+            if let Some(body) = body {
+                filter = filter.exclude_rigid_body(body);
+            }
+            rapier_context
+                .cast_ray(eye, (point - eye) / distance, distance, true, filter)
+                .is_none_or(|(hit, _)| hit == entity)
+        };
+
+        let nearest = targets
+            .iter()
+            .filter(|(entity, ..)| Some(*entity) != body && *entity != event.camera)
+            .filter_map(|(entity, target, transform)| {
+                let point = transform.translation() + target.offset;
+                let distance = eye.distance(point);
+                let angle = forward.angle_between(point - eye);
+                (distance <= settings.range
+                    && angle <= settings.max_angle
+                    && visible(entity, point, distance))
+                .then_some((entity, target, distance))
+            })
+            .min_by(|(_, _, a), (_, _, b)| a.total_cmp(b));
+
+        if let Some((entity, target, _)) = nearest {
+            commands.entity(event.camera).insert((
+                LockedOn { target: entity },
+                LookTarget::new(entity)
+                    .with_offset(target.offset)
+                    .with_deadzone(settings.deadzone)
+                    .with_max_angular_speed(settings.max_angular_speed),
+            ));
+        }
+    }
+}
+
+/// Lets go of targets that were despawned or moved out of range.
+pub fn release_lost_targets(
+    mut commands: Commands,
+    settings: Res<LockOnSettings>,
+    cameras: Query<(Entity, &LockedOn, &GlobalTransform)>,
+    targets: Query<&GlobalTransform, With<LockOnTarget>>,
+) {
+    for (camera, locked_on, camera_transform) in &cameras {
+        let in_range = targets.get(locked_on.target).is_ok_and(|target| {
+            target
+                .translation()
+                .distance(camera_transform.translation())
+                <= settings.break_range
+        });
+        if !in_range {
+            commands.entity(camera).remove::<(LockedOn, LookTarget)>();
+        }
+    }
+}
+
+/// Bends the sideways movement of bodies whose cameras are locked on into circles around the
+/// target.
+///
+/// The movement is split along the way the camera faces rather than the way to the target, so
+/// that strafing while the camera is still turning inside its deadzone does not spiral outward.
+pub fn orbit_locked_targets(
+    cameras: Query<(&Parent, &LockedOn, &LookTransform)>,
+    targets: Query<&GlobalTransform>,
+    mut bodies: Query<(&mut KinematicCharacterController, &GlobalTransform)>,
+) {
+    for (parent, locked_on, look_transform) in &cameras {
+        let Ok(target) = targets.get(locked_on.target) else {
+            continue;
+        };
+        let Ok((mut controller, body_transform)) = bodies.get_mut(parent.get()) else {
+            continue;
+        };
+        let Some(translation) = controller.translation else {
+            continue;
+        };
+
+        let to_body = body_transform.translation() - target.translation();
+        let offset = Vec2::new(to_body.x, to_body.z);
+        let radius = offset.length();
+        let step = Vec2::new(translation.x, translation.z);
+        if radius <= f32::EPSILON || step == Vec2::ZERO {
+            continue;
+        }
+
+        // The controller moves along the yaw of the camera, which faces the target.
+        let facing = Vec2::new(look_transform.yaw.sin(), look_transform.yaw.cos());
+        let radial = -step.dot(facing);
+        let tangential = step.dot((-facing).perp());
+
+        // Move towards the target by the forward part, and around it by the sideways part.
+        let outward = offset / radius;
+        let new_radius = (radius + radial).max(0.0);
+        let new_offset = Vec2::from_angle(tangential / radius).rotate(outward) * new_radius;
+        let orbit = new_offset - offset;
+        controller.translation = Some(Vec3::new(orbit.x, translation.y, orbit.y));
+    }
+}
@@ -0,0 +1,131 @@
+//! A mod for cameras that keep an entity in view.
+//!
+//! A camera with a [`LookTarget`] turns its [`LookTransform`] towards another entity every frame,
+//! after the player has had a chance to look around, so that a moving entity stays framed. This
+//! is what security cameras, boss introductions, and lock-on need.
+//!
+//! The target may wander inside a deadzone around the middle of the view before the camera
+//! follows it, and the camera can be limited to a top turning speed so that it swings around
+//! instead of snapping. Removing the [`LookTarget`] gives the camera back to the player.
+
+use super::{fps_controller::*, *};
+use crate::state::*;
+
+/// Keeps a camera with a [`LookTransform`] turned towards an entity.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct LookTarget {
+    /// The entity to keep in view.
+    pub target: Entity,
+    /// The point to look at relative to the target, in world space.
+    pub offset: Vec3,
+    /// How far the target may be from the middle of the view before the camera turns, in
+    /// radians.
+    pub deadzone: f32,
+    /// How quickly the camera may turn, in radians per second, or `None` to turn at once.
+    pub max_angular_speed: Option<f32>,
+}
+
+impl LookTarget {
+    /// Creates a new [`LookTarget`] that keeps an entity in the middle of the view.
+    pub fn new(target: Entity) -> Self {
+        Self {
+            target,
+            offset: Vec3::ZERO,
+            deadzone: 0.0,
+            max_angular_speed: None,
+        }
+    }
+
+    /// Looks at a point offset from the target, such as its head.
+    pub fn with_offset(self, offset: Vec3) -> Self {
+        Self { offset, ..self }
+    }
+
+    /// Lets the target move within an angle from the middle of the view before the camera turns.
+    pub fn with_deadzone(self, deadzone: f32) -> Self {
+        Self { deadzone, ..self }
+    }
+
+    /// Limits how quickly the camera turns, in radians per second.
+    pub fn with_max_angular_speed(self, max_angular_speed: f32) -> Self {
+        Self {
+            max_angular_speed: Some(max_angular_speed),
+            ..self
+        }
+    }
+}
+
+/// A plugin that turns cameras with a [`LookTarget`] towards their targets.
+#[derive(Default)]
+pub struct LookTargetPlugin;
+
+impl LookTargetPlugin {
+    /// Creates a new [`LookTargetPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for LookTargetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            track_look_targets
+                .with_run_criteria(is_playing)
+                .after(fps_control_system),
+        );
+    }
+}
+
+/// The signed difference between two angles, wrapped to `[-PI, PI]`.
+fn angle_difference(from: f32, to: f32) -> f32 {
+    use std::f32::consts::{PI, TAU};
+    (to - from + PI).rem_euclid(TAU) - PI
+}
+
+/// Turns every camera with a [`LookTarget`] towards its target.
+pub fn track_look_targets(
+    time: Res<Time>,
+    mut cameras: Query<(
+        &LookTarget,
+        &mut LookTransform,
+        &GlobalTransform,
+        Option<&Parent>,
+    )>,
+    transforms: Query<&GlobalTransform>,
+) {
+    for (look_target, mut look_transform, camera_transform, parent) in &mut cameras {
+        let Ok(target_transform) = transforms.get(look_target.target) else {
+            continue;
+        };
+
+        // Pitch and yaw are relative to the parent of the camera.
+        let to_target =
+            target_transform.translation() + look_target.offset - camera_transform.translation();
+        let parent_rotation = parent
+            .and_then(|parent| transforms.get(parent.get()).ok())
+            .map_or(Quat::IDENTITY, |parent| {
+                parent.to_scale_rotation_translation().1
+            });
+        let direction = parent_rotation.inverse() * to_target;
+        let Some((pitch, yaw)) = LookTransform::pitch_yaw_towards(direction) else {
+            continue;
+        };
+
+        let angle = look_transform.direction().angle_between(direction);
+        if !angle.is_finite() || angle <= look_target.deadzone {
+            continue;
+        }
+
+        // Turn until the target is back at the edge of the deadzone, no faster than allowed.
+        let mut turn = angle - look_target.deadzone;
+        if let Some(max_angular_speed) = look_target.max_angular_speed {
+            turn = turn.min(max_angular_speed * time.delta_seconds());
+        }
+        let fraction = (turn / angle).clamp(0.0, 1.0);
+
+        let pitch_delta = pitch - look_transform.pitch;
+        let yaw_delta = angle_difference(look_transform.yaw, yaw);
+        look_transform.pitch += fraction * pitch_delta;
+        look_transform.yaw += fraction * yaw_delta;
+    }
+}
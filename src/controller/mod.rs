@@ -32,14 +32,54 @@
 //                                                                                               //
 // ============================================================================================= //
 
+/// A mod with special moves for the first-person controller.
+pub mod abilities;
+
+/// A mod for text that floats over points in the world, such as damage numbers and names.
+pub mod billboard;
+
+/// A mod that resizes the capsule of a character at runtime.
+pub mod capsule;
+
+/// A mod with a crosshair and hit markers for testing combat on maps.
+pub mod crosshair;
+
 /// A mod that creates a controller that acts like a first-person shooter.
 pub mod fps_controller;
 
+/// A mod for the health of characters and the damage they take.
+pub mod health;
+
+/// A mod that lets players use the objects they look at.
+pub mod interaction;
+
+/// A mod for locking the camera of a controller onto a target.
+pub mod lock_on;
+
+/// A mod for cameras that keep an entity in view.
+pub mod look_target;
+
+/// A mod that puts characters that stand still to sleep.
+pub mod sleep;
+
+/// A mod that splits the window between up to four players.
+pub mod split_screen;
+
+/// A mod that steers non-player characters with weighted behaviors.
+pub mod steering;
+
+/// A mod with tuning presets for the first-person controller.
+pub mod tuning;
+
+/// A mod for first-person view models, such as the weapon held in front of the camera.
+pub mod view_model;
+
 use bevy::{ecs::prelude::*, math::prelude::*, prelude::*};
 use bevy_rapier3d::prelude::*;
 
 /// A struct used to generate simple transforms for cameras.
-#[derive(Component, Clone)]
+#[derive(Component, Clone, Reflect)]
+#[reflect(Component, Default)]
 pub struct LookTransform {
     /// The offset from the parent.
     pub offset: Vec3,
@@ -108,6 +148,20 @@ impl LookTransform {
         Mat3::from_axis_angle(pitch_axis, pitch) * ray
     }
 
+    /// The direction that the look transform faces, relative to its parent.
+    pub fn direction(&self) -> Vec3 {
+        Self::unit_vector_from_pitch_and_yaw(self.pitch, self.yaw)
+    }
+
+    /// The pitch and yaw that face a direction relative to the parent, or `None` for a zero
+    /// direction.
+    pub fn pitch_yaw_towards(direction: Vec3) -> Option<(f32, f32)> {
+        let direction = direction.try_normalize()?;
+        let pitch = direction.y.clamp(-1.0, 1.0).asin();
+        let yaw = direction.x.atan2(direction.z);
+        Some((pitch, yaw))
+    }
+
     /// Converts the look transform into a useful Bevy transform.
     pub fn to_transform(&self) -> Transform {
         let pitch_yaw_vector = Self::unit_vector_from_pitch_and_yaw(self.pitch, self.yaw);
@@ -124,20 +178,20 @@ impl LookTransform {
     }
 }
 
-impl Into<Transform> for LookTransform {
-    fn into(self) -> Transform {
-        self.to_transform()
+impl From<LookTransform> for Transform {
+    fn from(look_transform: LookTransform) -> Self {
+        look_transform.to_transform()
     }
 }
 
-impl Into<Transform> for &LookTransform {
-    fn into(self) -> Transform {
-        self.to_transform()
+impl From<&LookTransform> for Transform {
+    fn from(look_transform: &LookTransform) -> Self {
+        look_transform.to_transform()
     }
 }
 
 /// A struct that contains the necessary camera components for a camera with [`LookTransform`].
-#[derive(Bundle)]
+#[derive(Bundle, Default)]
 pub struct LookTransformCameraBundle {
     /// The camera transform bundle
     pub look_transform: LookTransform,
@@ -145,15 +199,6 @@ pub struct LookTransformCameraBundle {
     pub camera_bundle: Camera3dBundle,
 }
 
-impl Default for LookTransformCameraBundle {
-    fn default() -> Self {
-        Self {
-            camera_bundle: Camera3dBundle::default(),
-            look_transform: LookTransform::default(),
-        }
-    }
-}
-
 impl LookTransformCameraBundle {
     /// Creates a new [`LookTransformCameraBundle`]
     pub fn new() -> Self {
@@ -164,7 +209,8 @@ impl LookTransformCameraBundle {
 /// A custom velocity that is applied to kinematic controllers.
 ///
 /// This is also used to make emulate gravity since gravity acts as a contstant acceleration.
-#[derive(Debug, Clone, Component)]
+#[derive(Debug, Clone, Component, Reflect)]
+#[reflect(Component, Default)]
 pub struct CustomVelocity(pub Vec3);
 
 impl Default for CustomVelocity {
@@ -173,27 +219,74 @@ impl Default for CustomVelocity {
     }
 }
 
+/// A marker for kinematic controllers whose [`CustomVelocity`] was set by a launch, such as a
+/// bounce pad.
+///
+/// The sideways part of a launch stops when the controller lands, and the marker is removed.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct Launched;
+
+/// An event sent when a kinematic controller lands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FallImpactEvent {
+    /// The body that landed.
+    pub entity: Entity,
+    /// How fast the body was falling when it landed, in units per second.
+    pub speed: f32,
+}
+
+#[allow(clippy::type_complexity)]
 fn apply_gravity(
+    mut commands: Commands,
     time: Res<Time>,
     rapier_config: Res<RapierConfiguration>,
+    mut impact_events: EventWriter<FallImpactEvent>,
     mut query: Query<
         (
+            Entity,
             &mut CustomVelocity,
             &mut KinematicCharacterController,
             &KinematicCharacterControllerOutput,
+            Option<&GravityScale>,
+            Option<&abilities::Gliding>,
+            Option<&Launched>,
         ),
         With<KinematicCharacterController>,
     >,
 ) {
-    for (mut velocity, mut controller, controller_output) in &mut query {
+    for (
+        entity,
+        mut velocity,
+        mut controller,
+        controller_output,
+        gravity_scale,
+        gliding,
+        launched,
+    ) in &mut query
+    {
+        let gravity = rapier_config.gravity * gravity_scale.map_or(1.0, |scale| scale.0);
         if controller_output.grounded && (velocity.0.y < 0.0) {
+            // Standing still gathers one frame of gravity, which is not a fall.
+            let speed = -velocity.0.y;
+            if speed > 2.0 * time.delta_seconds() * gravity.length() {
+                impact_events.send(FallImpactEvent { entity, speed });
+            }
             // Stop vertical movement.
             velocity.0.y = 0.0;
+            // Launches carry the controller sideways only until it lands.
+            if launched.is_some() {
+                velocity.0.x = 0.0;
+                velocity.0.z = 0.0;
+                commands.entity(entity).remove::<Launched>();
+            }
         } else {
             // Accelerate due to gravity.
-            let new_velocity = velocity.0 + time.delta_seconds() * rapier_config.gravity;
+            let new_velocity = velocity.0 + time.delta_seconds() * gravity;
             velocity.0 = new_velocity;
         }
+        if let Some(gliding) = gliding {
+            velocity.0.y = velocity.0.y.max(-gliding.fall_speed);
+        }
 
         // Apply velocity.
         let translation = time.delta_seconds() * velocity.0;
@@ -207,6 +300,7 @@ fn apply_gravity(
 }
 
 /// A plugin that allows synchronization of [`LookTransform`] and camera transforms.
+#[derive(Default)]
 pub struct LookTransformPlugin;
 
 impl LookTransformPlugin {
@@ -218,7 +312,8 @@ impl LookTransformPlugin {
 
 impl Plugin for LookTransformPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_to_stage(CoreStage::PostUpdate, sync_camera_transforms);
+        app.register_type::<LookTransform>()
+            .add_system_to_stage(CoreStage::PostUpdate, sync_camera_transforms);
     }
 }
 
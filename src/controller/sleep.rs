@@ -0,0 +1,198 @@
+//! A mod that puts characters that stand still to sleep.
+//!
+//! Every frame, Rapier moves each [`KinematicCharacterController`] with a few shape casts, even
+//! when the character only presses into the ground it stands on. In maps with many idle
+//! characters, such as crowds of non-player characters, this is most of the work of the
+//! controllers.
+//!
+//! A character with [`CharacterSleep`] that is grounded and barely moves for a number of frames
+//! falls asleep. While it sleeps, its translation is dropped before Rapier sees it, so none of
+//! its shape casts run. It wakes up as soon as it asks to move again, or when a collider that
+//! moved, such as another character, a dynamic body, or a door, comes near its bounding box, or
+//! when a collider is removed from under it.
+
+use super::*;
+use crate::state::*;
+
+/// The stage that puts characters to sleep, right before Rapier moves them.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+pub struct CharacterSleepStage;
+
+/// Whether a character is asleep, and for how long it has stood still.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CharacterSleep {
+    /// The frames in a row that the character has stood still.
+    pub still_frames: u32,
+    /// Whether the character is asleep.
+    pub asleep: bool,
+}
+
+/// Settings for the [`CharacterSleepPlugin`].
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct CharacterSleepSettings {
+    /// The speed below which a grounded character is standing still, in units per second.
+    ///
+    /// Only sideways and upward movement counts, since a grounded character always presses down
+    /// into the ground under gravity.
+    pub linear_threshold: f32,
+    /// The frames that a character has to stand still before it falls asleep.
+    pub frames_to_sleep: u32,
+    /// How far outside the bounding box of a sleeping character a moving collider wakes it.
+    pub wake_margin: f32,
+    /// Whether every new character controller gets a [`CharacterSleep`].
+    pub auto_insert: bool,
+}
+
+impl Default for CharacterSleepSettings {
+    fn default() -> Self {
+        Self {
+            linear_threshold: 0.05,
+            frames_to_sleep: 30,
+            wake_margin: 0.25,
+            auto_insert: true,
+        }
+    }
+}
+
+/// A plugin that puts characters that stand still to sleep.
+///
+/// The characters are only put to sleep when the [`RapierPhysicsPlugin`] was added first with its
+/// default stages.
+#[derive(Default)]
+pub struct CharacterSleepPlugin {
+    /// The settings used by the plugin.
+    pub settings: CharacterSleepSettings,
+}
+
+impl CharacterSleepPlugin {
+    /// Creates a new [`CharacterSleepPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Plugin for CharacterSleepPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings)
+            .add_system(insert_character_sleep);
+
+        if app
+            .schedule
+            .get_stage::<SystemStage>(PhysicsStages::SyncBackend)
+            .is_some()
+        {
+            app.add_stage_before(
+                PhysicsStages::SyncBackend,
+                CharacterSleepStage,
+                SystemStage::single(update_character_sleep.with_run_criteria(is_playing)),
+            );
+        }
+    }
+}
+
+/// Gives every new character controller a [`CharacterSleep`].
+pub fn insert_character_sleep(
+    mut commands: Commands,
+    settings: Res<CharacterSleepSettings>,
+    characters: Query<Entity, (Added<KinematicCharacterController>, Without<CharacterSleep>)>,
+) {
+    if !settings.auto_insert {
+        return;
+    }
+    for entity in &characters {
+        commands.entity(entity).insert(CharacterSleep::default());
+    }
+}
+
+/// The corners of the world-space bounding box of a collider, grown by a margin.
+fn world_aabb(collider: &Collider, transform: &GlobalTransform, margin: f32) -> (Vec3, Vec3) {
+    let aabb = collider.raw.compute_local_aabb();
+    let local_center: Vec3 = aabb.center().coords.into();
+    let half_extents: Vec3 = aabb.half_extents().into();
+    let (_, rotation, translation) = transform.to_scale_rotation_translation();
+    let rotation = Mat3::from_quat(rotation);
+    let abs_rotation = Mat3::from_cols(
+        rotation.x_axis.abs(),
+        rotation.y_axis.abs(),
+        rotation.z_axis.abs(),
+    );
+    let center = translation + rotation * local_center;
+    let half_extents = abs_rotation * half_extents + Vec3::splat(margin);
+    (center - half_extents, center + half_extents)
+}
+
+/// Counts the frames that characters stand still, puts them to sleep, and wakes them up.
+#[allow(clippy::type_complexity)]
+pub fn update_character_sleep(
+    settings: Res<CharacterSleepSettings>,
+    time: Res<Time>,
+    removed: RemovedComponents<Collider>,
+    mut characters: Query<(
+        Entity,
+        &mut CharacterSleep,
+        &mut KinematicCharacterController,
+        Option<&KinematicCharacterControllerOutput>,
+        &Collider,
+        &GlobalTransform,
+    )>,
+    moved: Query<(Entity, &Collider, &GlobalTransform), Changed<GlobalTransform>>,
+) {
+    let dt = time.delta_seconds();
+    if dt <= 0.0 {
+        return;
+    }
+
+    // Characters that stand still are nudged by gravity without going anywhere, so they do not
+    // wake up the ones around them. Everything else that moved can.
+    let collider_removed = removed.iter().count() > 0;
+    let moving: Vec<(Entity, Vec3, Vec3)> = moved
+        .iter()
+        .filter(|(entity, ..)| {
+            characters
+                .get(*entity)
+                .map_or(true, |(_, sleep, ..)| *sleep == CharacterSleep::default())
+        })
+        .map(|(entity, collider, transform)| {
+            let (min, max) = world_aabb(collider, transform, 0.0);
+            (entity, min, max)
+        })
+        .collect();
+
+    for (entity, mut sleep, mut controller, output, collider, transform) in &mut characters {
+        let requested = controller.translation.unwrap_or(Vec3::ZERO);
+        let grounded = output.is_some_and(|output| output.grounded);
+        let sideways = Vec3::new(requested.x, 0.0, requested.z).length();
+        let still = grounded
+            && sideways <= settings.linear_threshold * dt
+            && requested.y <= settings.linear_threshold * dt;
+
+        if !still {
+            if *sleep != CharacterSleep::default() {
+                *sleep = CharacterSleep::default();
+            }
+            continue;
+        }
+
+        if sleep.asleep {
+            let (min, max) = world_aabb(collider, transform, settings.wake_margin);
+            let disturbed = collider_removed
+                || moving.iter().any(|(other, other_min, other_max)| {
+                    *other != entity && min.cmple(*other_max).all() && max.cmpge(*other_min).all()
+                });
+            if disturbed {
+                *sleep = CharacterSleep::default();
+                continue;
+            }
+        } else {
+            sleep.still_frames += 1;
+            if sleep.still_frames < settings.frames_to_sleep {
+                continue;
+            }
+            sleep.asleep = true;
+        }
+
+        if controller.translation.is_some() {
+            controller.translation = None;
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! A mod that splits the window between up to four players.
+//!
+//! Every camera with a [`PlayerCamera`] gets a slot of the window: one camera fills it, two share
+//! it side by side, and three or four share it in a grid. The viewports are recomputed when the
+//! window is resized or player cameras come and go.
+//!
+//! Each player camera reads the input named by its [`PlayerInput`]. Keyboard and mouse players
+//! take the plain [`FpsControlEvent`]s sent by
+//! [`custom_input_map`](super::fps_controller::custom_input_map), and gamepad players take the
+//! [`PlayerFpsControlEvent`]s sent for their index, so every player only moves their own body.
+
+use super::{abilities::*, fps_controller::*};
+use crate::dynamic_resolution::*;
+
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig, prelude::*, render::camera::Viewport,
+    window::WindowResized,
+};
+use serde::{Deserialize, Serialize};
+
+/// The largest number of players that can share the window.
+pub const MAX_SPLIT_SCREEN_PLAYERS: usize = 4;
+
+/// A camera that shows one player's slot of the window.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PlayerCamera {
+    /// The slot of the player, from `0` to [`MAX_SPLIT_SCREEN_PLAYERS`] - 1.
+    pub index: usize,
+}
+
+impl PlayerCamera {
+    /// Creates a new [`PlayerCamera`] for a slot.
+    pub fn new(index: usize) -> Self {
+        Self { index }
+    }
+}
+
+/// Where a player camera reads its input from.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlayerInput {
+    /// The keyboard and mouse, through the plain [`FpsControlEvent`]s.
+    #[default]
+    Keyboard,
+    /// A gamepad, through the [`PlayerFpsControlEvent`]s of the camera's [`PlayerCamera`] index.
+    Gamepad(Gamepad),
+}
+
+/// A control event for the player in a single slot.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlayerFpsControlEvent {
+    /// The slot of the player.
+    pub player: usize,
+    /// The control event.
+    pub event: FpsControlEvent,
+}
+
+/// A plugin that splits the window between [`PlayerCamera`]s and reads their gamepads.
+#[derive(Default)]
+pub struct SplitScreenPlugin {}
+
+impl SplitScreenPlugin {
+    /// Creates a new [`SplitScreenPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for SplitScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayerFpsControlEvent>()
+            .add_system(set_player_viewports)
+            .add_system(
+                gamepad_input_map
+                    .with_run_criteria(crate::state::is_playing)
+                    .before(fps_control_system),
+            );
+    }
+}
+
+/// Lays out the viewports of the [`PlayerCamera`]s in the window.
+pub fn set_player_viewports(
+    windows: Res<Windows>,
+    render_scale: Option<Res<RenderScale>>,
+    mut resize_events: EventReader<WindowResized>,
+    changed_cameras: Query<(), Changed<PlayerCamera>>,
+    removed_cameras: RemovedComponents<PlayerCamera>,
+    mut cameras: Query<(
+        &PlayerCamera,
+        &mut Camera,
+        &mut Camera3d,
+        Option<&DynamicResolution>,
+    )>,
+) {
+    // A resize event is sent when the window is first created, so this also sets up the viewports.
+    let resized = resize_events.iter().count() > 0;
+    let rescaled = render_scale
+        .as_ref()
+        .is_some_and(|render_scale| render_scale.is_changed());
+    if !resized
+        && !rescaled
+        && changed_cameras.is_empty()
+        && removed_cameras.iter().next().is_none()
+    {
+        return;
+    }
+    let Some(window) = windows.get_primary() else {
+        return;
+    };
+
+    let players = cameras
+        .iter()
+        .map(|(player, ..)| player.index + 1)
+        .max()
+        .unwrap_or(0)
+        .min(MAX_SPLIT_SCREEN_PLAYERS);
+    let (columns, rows) = if players <= 2 {
+        (players.max(1) as u32, 1)
+    } else {
+        (2, 2)
+    };
+    let window_size = UVec2::new(window.physical_width(), window.physical_height());
+    let slot_size = window_size / UVec2::new(columns, rows);
+
+    for (player, mut camera, mut camera_3d, dynamic_resolution) in &mut cameras {
+        if player.index >= MAX_SPLIT_SCREEN_PLAYERS {
+            warn!(
+                "Player camera {} is out of range, only {} players are supported",
+                player.index, MAX_SPLIT_SCREEN_PLAYERS
+            );
+            camera.is_active = false;
+            continue;
+        }
+
+        // Dynamic resolution cameras measure their viewports in pixels of the scaled image.
+        let scale = match (&render_scale, dynamic_resolution) {
+            (Some(render_scale), Some(_)) => render_scale.0,
+            _ => 1.0,
+        };
+        let index = player.index as u32;
+        let position = UVec2::new(index % columns, index / columns) * slot_size;
+        camera.viewport = Some(Viewport {
+            physical_position: (position.as_vec2() * scale).as_uvec2(),
+            physical_size: (slot_size.as_vec2() * scale).as_uvec2().max(UVec2::ONE),
+            ..default()
+        });
+
+        // Render the players in order and only let the first one clear the window.
+        camera.priority = player.index as isize;
+        if player.index > 0 {
+            camera_3d.clear_color = ClearColorConfig::None;
+        }
+    }
+}
+
+/// The part of the window that a camera draws to, in logical pixels from the top left corner.
+///
+/// Cameras without a viewport cover the whole window. Pass the [`RenderScale`] for
+/// [`DynamicResolution`] cameras, whose viewports are measured in pixels of the scaled image, and
+/// `1.0` for the others.
+pub fn logical_viewport_rect(camera: &Camera, window: &Window, render_scale: f32) -> Rect {
+    let Some(viewport) = &camera.viewport else {
+        return Rect::new(0.0, 0.0, window.width(), window.height());
+    };
+    let scale = window.scale_factor() as f32 * render_scale;
+    let min = viewport.physical_position.as_vec2() / scale;
+    Rect::from_corners(min, min + viewport.physical_size.as_vec2() / scale)
+}
+
+/// Sends the [`PlayerFpsControlEvent`]s of the players that use a gamepad.
+pub fn gamepad_input_map(
+    mut events: EventWriter<PlayerFpsControlEvent>,
+    axes: Res<Axis<GamepadAxis>>,
+    buttons: Res<Input<GamepadButton>>,
+    players: Query<(&PlayerCamera, &PlayerInput)>,
+) {
+    let stick_rotate_sensitivity = Vec2::splat(3.0);
+
+    for (player, input) in &players {
+        let PlayerInput::Gamepad(gamepad) = *input else {
+            continue;
+        };
+        let axis = |axis_type| {
+            axes.get(GamepadAxis::new(gamepad, axis_type))
+                .unwrap_or(0.0)
+        };
+        let mut send = |event| {
+            events.send(PlayerFpsControlEvent {
+                player: player.index,
+                event,
+            })
+        };
+
+        // Match the directions of the mouse and keyboard, where +X is left and +Z is forward.
+        let look = Vec2::new(
+            axis(GamepadAxisType::RightStickX),
+            -axis(GamepadAxisType::RightStickY),
+        );
+        if look != Vec2::ZERO {
+            send(FpsControlEvent::RotateCamera(
+                stick_rotate_sensitivity * look,
+            ));
+        }
+
+        let translation = Vec3::new(
+            -axis(GamepadAxisType::LeftStickX),
+            0.0,
+            axis(GamepadAxisType::LeftStickY),
+        );
+        if translation != Vec3::ZERO {
+            send(FpsControlEvent::Translate(
+                translation.clamp_length_max(1.0),
+            ));
+        }
+
+        if buttons.pressed(GamepadButton::new(gamepad, GamepadButtonType::South)) {
+            send(FpsControlEvent::Jump(Vec3::Y));
+        }
+
+        if buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::East)) {
+            send(FpsControlEvent::UseAbility(Ability::Dash));
+        }
+
+        if buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::RightThumb)) {
+            send(FpsControlEvent::ToggleLockOn);
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! Steering behaviors for non-player characters.
+//!
+//! A [`SteeringAgent`] combines several weighted [`SteeringBehavior`]s into a single planar
+//! velocity every frame. The velocity is written into the horizontal components of the agent's
+//! [`CustomVelocity`], which is then applied to its [`KinematicCharacterController`] alongside
+//! gravity. Agents are usually spawned with a [`FpsControllerBodyBundle`] so they collide with the
+//! map exactly like players do.
+//!
+//! [`FpsControllerBodyBundle`]: super::fps_controller::FpsControllerBodyBundle
+
+use super::*;
+use crate::{freeze::*, state::*};
+
+use bevy::{ecs::prelude::*, math::prelude::*, prelude::*};
+
+/// What a steering behavior is steering towards or away from.
+#[derive(Debug, Clone, Copy)]
+pub enum SteeringTarget {
+    /// A fixed position in the world.
+    Position(Vec3),
+    /// The current position of an entity.
+    Entity(Entity),
+}
+
+/// A list of waypoints followed by [`SteeringBehavior::FollowPath`].
+#[derive(Debug, Clone)]
+pub struct SteeringPath {
+    /// The points to visit in order.
+    pub waypoints: Vec<Vec3>,
+    /// How close the agent needs to get to a waypoint before moving on to the next one.
+    pub arrival_radius: f32,
+    /// Whether the agent returns to the first waypoint after reaching the last one.
+    pub looping: bool,
+    current: usize,
+}
+
+impl SteeringPath {
+    /// Creates a new [`SteeringPath`] that starts at the first waypoint.
+    pub fn new(waypoints: Vec<Vec3>, arrival_radius: f32, looping: bool) -> Self {
+        Self {
+            waypoints,
+            arrival_radius,
+            looping,
+            current: 0,
+        }
+    }
+
+    /// The waypoint the agent is currently heading towards.
+    pub fn current_waypoint(&self) -> Option<Vec3> {
+        self.waypoints.get(self.current).copied()
+    }
+}
+
+/// A single steering behavior.
+#[derive(Debug, Clone)]
+pub enum SteeringBehavior {
+    /// Move towards the target at full speed.
+    Seek(SteeringTarget),
+    /// Move away from the target while it is within the panic distance.
+    Flee {
+        /// What to flee from.
+        target: SteeringTarget,
+        /// The distance at which the agent starts to flee.
+        panic_distance: f32,
+    },
+    /// Wander around randomly with smooth changes of direction.
+    Wander {
+        /// How far in front of the agent the wander circle is.
+        distance: f32,
+        /// The radius of the wander circle.
+        radius: f32,
+        /// How fast, in radians per second, the wander direction may change.
+        jitter: f32,
+    },
+    /// Visit a list of waypoints in order.
+    FollowPath(SteeringPath),
+    /// Keep a distance from other steering agents.
+    Separation {
+        /// The distance below which other agents push this agent away.
+        radius: f32,
+    },
+}
+
+/// A component that steers a kinematic character using weighted behaviors.
+#[derive(Component, Debug, Clone)]
+pub struct SteeringAgent {
+    /// The maximum horizontal speed of the agent.
+    pub max_speed: f32,
+    /// The maximum horizontal acceleration of the agent.
+    pub max_force: f32,
+    /// The behaviors and their weights.
+    pub behaviors: Vec<(f32, SteeringBehavior)>,
+    wander_angle: f32,
+    rng_state: u32,
+}
+
+impl Default for SteeringAgent {
+    fn default() -> Self {
+        Self {
+            max_speed: 2.0,
+            max_force: 4.0,
+            behaviors: Vec::new(),
+            wander_angle: 0.0,
+            rng_state: 0x9E37_79B9,
+        }
+    }
+}
+
+impl SteeringAgent {
+    /// Creates a new [`SteeringAgent`] without any behaviors.
+    pub fn new(max_speed: f32, max_force: f32) -> Self {
+        Self {
+            max_speed,
+            max_force,
+            ..default()
+        }
+    }
+
+    /// Adds a weighted behavior to the agent.
+    pub fn with_behavior(mut self, weight: f32, behavior: SteeringBehavior) -> Self {
+        self.behaviors.push((weight, behavior));
+        self
+    }
+
+    /// Seeds the random number generator used by [`SteeringBehavior::Wander`].
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.rng_state = seed.max(1);
+        self
+    }
+
+    /// Returns a pseudo-random number in `[-1, 1]`.
+    fn next_random(&mut self) -> f32 {
+        // Xorshift is more than good enough for wandering around.
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 17;
+        self.rng_state ^= self.rng_state << 5;
+        (self.rng_state as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+}
+
+/// A plugin that runs the [`SteeringAgent`] behaviors.
+///
+/// The resulting velocities are applied by the gravity system of the
+/// [`FpsCameraPlugin`](super::fps_controller::FpsCameraPlugin), which must also be added.
+#[derive(Default)]
+pub struct SteeringPlugin;
+
+impl SteeringPlugin {
+    /// Creates a new [`SteeringPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for SteeringPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(steering_system.with_run_criteria(is_playing));
+    }
+}
+
+/// Combines the behaviors of every [`SteeringAgent`] that is not [`Frozen`] and writes the result
+/// to its [`CustomVelocity`].
+pub fn steering_system(
+    time: Res<Time>,
+    mut agents: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            &mut SteeringAgent,
+            &mut CustomVelocity,
+        ),
+        Without<Frozen>,
+    >,
+    targets: Query<&GlobalTransform>,
+) {
+    let dt = time.delta_seconds();
+    if dt <= 0.0 {
+        return;
+    }
+
+    let agent_positions: Vec<(Entity, Vec3)> = agents
+        .iter()
+        .map(|(entity, transform, _, _)| (entity, transform.translation()))
+        .collect();
+
+    for (entity, transform, mut agent, mut velocity) in &mut agents {
+        let position = flatten(transform.translation());
+        let current = flatten(velocity.0);
+        let max_speed = agent.max_speed;
+
+        let resolve = |target: &SteeringTarget| match target {
+            SteeringTarget::Position(position) => Some(flatten(*position)),
+            SteeringTarget::Entity(entity) => targets
+                .get(*entity)
+                .ok()
+                .map(|transform| flatten(transform.translation())),
+        };
+
+        let mut behaviors = std::mem::take(&mut agent.behaviors);
+        let mut force = Vec3::ZERO;
+        for (weight, behavior) in &mut behaviors {
+            let desired = match behavior {
+                SteeringBehavior::Seek(target) => {
+                    resolve(target).map(|target| seek(position, target, max_speed))
+                }
+                SteeringBehavior::Flee {
+                    target,
+                    panic_distance,
+                } => resolve(target)
+                    .filter(|target| position.distance(*target) < *panic_distance)
+                    .map(|target| -seek(position, target, max_speed)),
+                SteeringBehavior::Wander {
+                    distance,
+                    radius,
+                    jitter,
+                } => {
+                    agent.wander_angle += dt * *jitter * agent.next_random();
+                    let heading = current.try_normalize().unwrap_or(Vec3::Z);
+                    let offset = *radius
+                        * Vec3::new(agent.wander_angle.cos(), 0.0, agent.wander_angle.sin());
+                    Some(max_speed * (*distance * heading + offset).normalize_or_zero())
+                }
+                SteeringBehavior::FollowPath(path) => {
+                    while let Some(waypoint) = path.current_waypoint() {
+                        if position.distance(flatten(waypoint)) > path.arrival_radius {
+                            break;
+                        }
+                        path.current += 1;
+                        if path.looping && path.current >= path.waypoints.len() {
+                            path.current = 0;
+                            break;
+                        }
+                    }
+                    path.current_waypoint()
+                        .map(|waypoint| seek(position, flatten(waypoint), max_speed))
+                }
+                SteeringBehavior::Separation { radius } => {
+                    let push = agent_positions
+                        .iter()
+                        .filter(|(other, _)| *other != entity)
+                        .map(|(_, other)| position - flatten(*other))
+                        .filter(|away| away.length() < *radius)
+                        .fold(Vec3::ZERO, |acc, away| {
+                            acc + away.try_normalize().unwrap_or(Vec3::X) / away.length().max(0.01)
+                        });
+                    (push != Vec3::ZERO).then(|| max_speed * push.normalize())
+                }
+            };
+
+            if let Some(desired) = desired {
+                force += *weight * (desired - current);
+            }
+        }
+        agent.behaviors = behaviors;
+
+        let new_velocity = (current + dt * force.clamp_length_max(agent.max_force))
+            .clamp_length_max(agent.max_speed);
+        velocity.0.x = new_velocity.x;
+        velocity.0.z = new_velocity.z;
+    }
+}
+
+/// Projects a vector onto the XZ plane.
+fn flatten(v: Vec3) -> Vec3 {
+    Vec3::new(v.x, 0.0, v.z)
+}
+
+/// The velocity that moves straight towards the target at full speed.
+fn seek(position: Vec3, target: Vec3, max_speed: f32) -> Vec3 {
+    max_speed * (target - position).normalize_or_zero()
+}
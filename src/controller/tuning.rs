@@ -0,0 +1,144 @@
+//! A mod with tuning presets for the first-person controller.
+//!
+//! How a character feels depends on a handful of numbers: how fast it moves, how hard it jumps,
+//! how strong gravity is, and how tall a step it can climb. [`ControllerPreset`]s bundle values
+//! for these that work well together, measured in meters. Every length and speed is converted to
+//! world units with the [`WorldScale`], so a new map feels right without tuning raw constants.
+//!
+//! The resulting [`FpsControllerSettings`] live on each controller body, so every player can
+//! have a different feel. They can be edited at any time, and the
+//! [`KinematicCharacterController`] and [`GravityScale`] of the body follow them.
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How many world units make up one meter.
+///
+/// This should match the physics scale given to the Rapier plugin.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct WorldScale(pub f32);
+
+impl Default for WorldScale {
+    fn default() -> Self {
+        WorldScale(1.0)
+    }
+}
+
+/// Sets of controller tuning that work well together.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControllerPreset {
+    /// A person walking on Earth, sized for the default capsule that is two meters tall.
+    #[default]
+    RealisticHuman,
+    /// A quick character with a high jump and snappy gravity, like an arena shooter.
+    ArcadeFast,
+    /// A person in the weak gravity of the Moon, with slow, floaty jumps.
+    LowGravityMoon,
+}
+
+impl ControllerPreset {
+    /// Creates the settings of the preset in world units.
+    pub fn settings(self, scale: WorldScale) -> FpsControllerSettings {
+        let meters = match self {
+            ControllerPreset::RealisticHuman => FpsControllerSettings {
+                move_speed: 2.0,
+                jump_speed: 5.0,
+                gravity_scale: 1.0,
+                step_height: 0.5,
+                max_slope_angle: 45.0_f32.to_radians(),
+                snap_to_ground: 0.4,
+            },
+            ControllerPreset::ArcadeFast => FpsControllerSettings {
+                move_speed: 8.0,
+                jump_speed: 7.0,
+                gravity_scale: 1.5,
+                step_height: 0.5,
+                max_slope_angle: 55.0_f32.to_radians(),
+                snap_to_ground: 0.3,
+            },
+            ControllerPreset::LowGravityMoon => FpsControllerSettings {
+                move_speed: 1.5,
+                jump_speed: 2.5,
+                gravity_scale: 0.165,
+                step_height: 0.3,
+                max_slope_angle: 45.0_f32.to_radians(),
+                snap_to_ground: 0.05,
+            },
+        };
+        meters.scaled(scale)
+    }
+}
+
+/// The tuning of a controller body, in world units.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+pub struct FpsControllerSettings {
+    /// How fast the character moves at full input.
+    pub move_speed: f32,
+    /// The upward speed at the start of a jump.
+    pub jump_speed: f32,
+    /// How strongly gravity pulls the character, relative to the Rapier gravity.
+    pub gravity_scale: f32,
+    /// The tallest step the character climbs without jumping.
+    pub step_height: f32,
+    /// The steepest slope the character can walk up, in radians.
+    pub max_slope_angle: f32,
+    /// How far the character is pulled down to stay on the ground when walking down slopes.
+    pub snap_to_ground: f32,
+}
+
+impl Default for FpsControllerSettings {
+    fn default() -> Self {
+        ControllerPreset::default().settings(WorldScale::default())
+    }
+}
+
+impl FpsControllerSettings {
+    /// Creates the settings of a preset in world units.
+    pub fn from_preset(preset: ControllerPreset, scale: WorldScale) -> Self {
+        preset.settings(scale)
+    }
+
+    /// Converts settings measured in meters into world units.
+    pub fn scaled(self, scale: WorldScale) -> Self {
+        Self {
+            move_speed: self.move_speed * scale.0,
+            jump_speed: self.jump_speed * scale.0,
+            step_height: self.step_height * scale.0,
+            snap_to_ground: self.snap_to_ground * scale.0,
+            ..self
+        }
+    }
+
+    /// Copies the settings into a character controller.
+    pub fn apply(&self, controller: &mut KinematicCharacterController) {
+        controller.autostep = (self.step_height > 0.0).then(|| CharacterAutostep {
+            max_height: CharacterLength::Absolute(self.step_height),
+            ..default()
+        });
+        controller.max_slope_climb_angle = self.max_slope_angle;
+        controller.snap_to_ground =
+            (self.snap_to_ground > 0.0).then_some(CharacterLength::Absolute(self.snap_to_ground));
+    }
+}
+
+/// Applies changed [`FpsControllerSettings`] to the character controller and gravity scale.
+pub fn apply_controller_settings(
+    mut commands: Commands,
+    mut controllers: Query<
+        (
+            Entity,
+            &FpsControllerSettings,
+            &mut KinematicCharacterController,
+        ),
+        Changed<FpsControllerSettings>,
+    >,
+) {
+    for (entity, settings, mut controller) in &mut controllers {
+        settings.apply(&mut controller);
+        commands
+            .entity(entity)
+            .insert(GravityScale(settings.gravity_scale));
+    }
+}
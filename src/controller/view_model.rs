@@ -0,0 +1,258 @@
+//! A mod for first-person view models, such as the weapon held in front of the camera.
+//!
+//! A [`ViewModel`] is a child of a first-person camera that holds the model the player sees in
+//! their hands. It sways behind the camera as the player looks around, driven by the same
+//! [`FpsControlEvent::RotateCamera`] deltas that turn the camera, and settles back to its rest
+//! pose when the player stops.
+//!
+//! Sending a [`RecoilEvent`] kicks the aim of a camera up and to the side through the pitch and
+//! yaw of its [`LookTransform`], and knocks its view models back. A camera with a [`Recoil`]
+//! component then recovers most of the kick by itself, so that shooter prototypes feel right
+//! without fighting the player's own aim.
+
+use super::{fps_controller::*, split_screen::*, *};
+use crate::state::*;
+
+use bevy::utils::HashMap;
+
+/// The model held in front of a first-person camera.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct ViewModel {
+    /// The pose of the model relative to the camera when it is not swaying.
+    pub rest: Transform,
+    /// How far the model turns behind the camera, in radians per radian per second of turning.
+    pub sway_amount: f32,
+    /// The furthest the model turns behind the camera, in radians.
+    pub max_sway: f32,
+    /// How far the model moves behind the camera for every radian it turns.
+    pub sway_offset: f32,
+    /// How quickly the model catches up with the camera. Higher values feel stiffer.
+    pub stiffness: f32,
+    /// How far the model is knocked back for every radian of recoil.
+    pub kick_distance: f32,
+}
+
+impl ViewModel {
+    /// Creates a new [`ViewModel`] that rests at a pose relative to the camera.
+    pub fn new(rest: Transform) -> Self {
+        Self {
+            rest,
+            sway_amount: 0.02,
+            max_sway: 0.1,
+            sway_offset: 0.2,
+            stiffness: 10.0,
+            kick_distance: 0.5,
+        }
+    }
+}
+
+impl Default for ViewModel {
+    fn default() -> Self {
+        Self::new(Transform::from_xyz(0.25, -0.2, -0.5))
+    }
+}
+
+/// How far a [`ViewModel`] is from its rest pose, managed by the [`ViewModelPlugin`].
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct ViewModelState {
+    /// How far the model is turned behind the camera, around the yaw and pitch axes.
+    pub sway: Vec2,
+    /// How far the model is knocked back by recoil.
+    pub kick: f32,
+}
+
+/// The components of a [`ViewModel`], to be spawned as a child of a camera.
+#[derive(Bundle, Default)]
+pub struct ViewModelBundle {
+    /// The view model.
+    pub view_model: ViewModel,
+    /// The sway and kick of the view model.
+    pub state: ViewModelState,
+    /// The transform and visibility of the view model.
+    pub spatial: SpatialBundle,
+}
+
+impl ViewModelBundle {
+    /// Creates a new [`ViewModelBundle`] from a view model.
+    pub fn new(view_model: ViewModel) -> Self {
+        Self {
+            view_model,
+            state: ViewModelState::default(),
+            spatial: SpatialBundle::from_transform(view_model.rest),
+        }
+    }
+}
+
+/// Makes a camera recover from the kicks of [`RecoilEvent`]s.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Recoil {
+    /// How quickly the kick is recovered. Higher values recover faster.
+    pub recovery: f32,
+    /// The share of every kick that is recovered, from `0.0` to `1.0`.
+    pub recovered_share: f32,
+    /// The yaw and pitch still to be recovered, in radians.
+    pub remaining: Vec2,
+}
+
+impl Recoil {
+    /// Creates a new [`Recoil`] that recovers at a speed.
+    pub fn new(recovery: f32) -> Self {
+        Self {
+            recovery,
+            recovered_share: 0.8,
+            remaining: Vec2::ZERO,
+        }
+    }
+}
+
+impl Default for Recoil {
+    fn default() -> Self {
+        Self::new(8.0)
+    }
+}
+
+/// An event that kicks the aim of a camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecoilEvent {
+    /// The camera to kick.
+    pub camera: Entity,
+    /// The kick to the yaw and pitch, in radians. Positive pitch kicks the aim up.
+    pub kick: Vec2,
+}
+
+/// A plugin that sways view models and applies recoil.
+#[derive(Default)]
+pub struct ViewModelPlugin;
+
+impl ViewModelPlugin {
+    /// Creates a new [`ViewModelPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for ViewModelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FpsControlEvent>()
+            .add_event::<PlayerFpsControlEvent>()
+            .add_event::<RecoilEvent>()
+            .add_system(
+                apply_recoil
+                    .with_run_criteria(is_playing)
+                    .after(fps_control_system),
+            )
+            .add_system(
+                recover_recoil
+                    .with_run_criteria(is_playing)
+                    .after(apply_recoil),
+            )
+            .add_system(
+                sway_view_models
+                    .with_run_criteria(is_playing)
+                    .after(apply_recoil),
+            );
+    }
+}
+
+/// Kicks the aim of cameras and knocks back their view models for every [`RecoilEvent`].
+pub fn apply_recoil(
+    mut events: EventReader<RecoilEvent>,
+    mut cameras: Query<(&mut LookTransform, Option<&mut Recoil>, Option<&Children>)>,
+    mut view_models: Query<(&ViewModel, &mut ViewModelState)>,
+) {
+    for event in events.iter() {
+        let Ok((mut look_transform, recoil, children)) = cameras.get_mut(event.camera) else {
+            continue;
+        };
+        look_transform.yaw += event.kick.x;
+        look_transform.pitch += event.kick.y;
+        if let Some(mut recoil) = recoil {
+            let recovered = recoil.recovered_share * event.kick;
+            recoil.remaining += recovered;
+        }
+        for child in children.into_iter().flatten() {
+            if let Ok((view_model, mut state)) = view_models.get_mut(*child) {
+                state.kick += view_model.kick_distance * event.kick.length();
+            }
+        }
+    }
+}
+
+/// Eases the aim of cameras back from their recoil.
+pub fn recover_recoil(time: Res<Time>, mut cameras: Query<(&mut LookTransform, &mut Recoil)>) {
+    for (mut look_transform, mut recoil) in &mut cameras {
+        if recoil.remaining == Vec2::ZERO {
+            continue;
+        }
+        let step = recoil.remaining * (1.0 - (-recoil.recovery * time.delta_seconds()).exp());
+        look_transform.yaw -= step.x;
+        look_transform.pitch -= step.y;
+        recoil.remaining -= step;
+        if recoil.remaining.length_squared() < 1e-8 {
+            recoil.remaining = Vec2::ZERO;
+        }
+    }
+}
+
+/// Sways view models behind their cameras as they turn and settles their recoil.
+pub fn sway_view_models(
+    time: Res<Time>,
+    mut events: EventReader<FpsControlEvent>,
+    mut player_events: EventReader<PlayerFpsControlEvent>,
+    cameras: Query<(Option<&PlayerCamera>, Option<&PlayerInput>), With<LookTransform>>,
+    mut view_models: Query<(&Parent, &ViewModel, &mut ViewModelState, &mut Transform)>,
+) {
+    let rotation = |event: &FpsControlEvent| match event {
+        FpsControlEvent::RotateCamera(delta) => Some(*delta),
+        _ => None,
+    };
+    let keyboard_turn: Vec2 = events.iter().filter_map(rotation).sum();
+    let mut player_turns: HashMap<usize, Vec2> = HashMap::default();
+    for event in player_events.iter() {
+        if let Some(delta) = rotation(&event.event) {
+            *player_turns.entry(event.player).or_default() += delta;
+        }
+    }
+
+    let dt = time.delta_seconds();
+    for (parent, view_model, mut state, mut transform) in &mut view_models {
+        let Ok((player, input)) = cameras.get(parent.get()) else {
+            continue;
+        };
+        let reads_keyboard = input.is_none_or(|input| *input == PlayerInput::Keyboard);
+        let mut turn = player
+            .and_then(|player| player_turns.get(&player.index))
+            .copied()
+            .unwrap_or_default();
+        if reads_keyboard {
+            turn += keyboard_turn;
+        }
+
+        // The model lags behind the turn and eases back when the camera stops.
+        let target = (view_model.sway_amount * turn).clamp_length_max(view_model.max_sway);
+        let ease = 1.0 - (-view_model.stiffness * dt).exp();
+        let mut sway = state.sway.lerp(target, ease);
+        let mut kick = state.kick * (1.0 - ease);
+        // Settle completely so that resting models are left alone.
+        if target == Vec2::ZERO && sway.length_squared() < 1e-8 {
+            sway = Vec2::ZERO;
+        }
+        if kick < 1e-4 {
+            kick = 0.0;
+        }
+        if sway == state.sway && kick == state.kick && !state.is_added() {
+            continue;
+        }
+        state.sway = sway;
+        state.kick = kick;
+
+        let rest = view_model.rest;
+        *transform = Transform {
+            translation: rest.translation
+                + view_model.sway_offset * Vec3::new(-sway.x, sway.y, 0.0)
+                + kick * Vec3::Z,
+            rotation: Quat::from_rotation_y(sway.x) * Quat::from_rotation_x(sway.y) * rest.rotation,
+            ..rest
+        };
+    }
+}
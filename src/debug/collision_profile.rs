@@ -0,0 +1,245 @@
+//! A mod that profiles which shapes the collision system spends its time on.
+//!
+//! Every frame, the [`CollisionProfilePlugin`] fills the [`CollisionProfile`] resource with:
+//!
+//! - how long Rapier took to sync with the world, which includes moving the character
+//!   controllers and running their time-of-impact queries, and how long the physics step took,
+//!   which is where contacts are computed;
+//! - for every pair of shape types, such as a capsule against a triangle mesh, how many
+//!   time-of-impact hits the character controllers had, how many pairs the narrow phase tested,
+//!   how many of those touched, and how many contact points they produced.
+//!
+//! Rapier does not time pairs one by one, so the times are per stage and the split by shape type
+//! is in counts. Pairs with many contact points or many tested pairs are the ones that cost the
+//! most, which in practice means triangle meshes and heightfields near dynamic bodies.
+//!
+//! The [`MapDebugPlugin`](super::overlay::MapDebugPlugin) shows the busiest pairs in its text
+//! while the overlay is enabled.
+
+use bevy::{
+    prelude::*,
+    utils::{Duration, HashMap, Instant},
+};
+use bevy_rapier3d::{parry::shape::ShapeType, prelude::*};
+
+/// The stages that time the physics, around the stages of Rapier.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+pub enum CollisionProfileStage {
+    /// Runs before the character controllers move.
+    BeforeSyncBackend,
+    /// Runs after the character controllers moved, before the physics step.
+    BeforeStepSimulation,
+    /// Runs after the physics step.
+    AfterStepSimulation,
+}
+
+/// Two shape types, in a fixed order so that a pair is counted the same either way around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShapePair(pub ShapeType, pub ShapeType);
+
+impl ShapePair {
+    /// Creates a new [`ShapePair`] from two shape types in any order.
+    pub fn new(a: ShapeType, b: ShapeType) -> Self {
+        // `ShapeType` is a fieldless enum from parry, so its discriminant gives a cheap order.
+        if a as u8 <= b as u8 {
+            Self(a, b)
+        } else {
+            Self(b, a)
+        }
+    }
+}
+
+/// What the collision system did with one [`ShapePair`] in a frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShapePairStats {
+    /// The time-of-impact hits of character controllers.
+    pub toi_hits: usize,
+    /// The pairs whose bounding boxes overlap, which the narrow phase tested.
+    pub tested_pairs: usize,
+    /// The tested pairs that touched.
+    pub touching_pairs: usize,
+    /// The contact points of the touching pairs.
+    pub contact_points: usize,
+}
+
+impl ShapePairStats {
+    /// A rough measure of how much work the pair caused, used to sort pairs.
+    pub fn weight(&self) -> usize {
+        self.toi_hits + self.tested_pairs + self.contact_points
+    }
+}
+
+/// What the collision system did in the last frame.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct CollisionProfile {
+    /// How long Rapier took to sync with the world, moving the character controllers included.
+    pub character_time: Duration,
+    /// How long the physics step took, collision detection included.
+    pub step_time: Duration,
+    /// What the collision system did with every pair of shape types.
+    pub pairs: HashMap<ShapePair, ShapePairStats>,
+    /// When the current stage started.
+    stage_start: Option<Instant>,
+}
+
+impl CollisionProfile {
+    /// The pairs of shape types sorted from the most work to the least.
+    pub fn busiest_pairs(&self) -> Vec<(ShapePair, ShapePairStats)> {
+        let mut pairs: Vec<(ShapePair, ShapePairStats)> = self
+            .pairs
+            .iter()
+            .map(|(pair, stats)| (*pair, *stats))
+            .collect();
+        pairs.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.weight()));
+        pairs
+    }
+
+    /// Writes the stage times and the busiest pairs as lines of text.
+    pub fn report(&self, max_pairs: usize) -> String {
+        let mut report = format!(
+            "collision: characters {:.2} ms, step {:.2} ms\n",
+            1000.0 * self.character_time.as_secs_f64(),
+            1000.0 * self.step_time.as_secs_f64(),
+        );
+        for (ShapePair(a, b), stats) in self.busiest_pairs().into_iter().take(max_pairs) {
+            report += &format!(
+                "  {a:?} vs {b:?}: toi hits {}, tested {}, touching {}, points {}\n",
+                stats.toi_hits, stats.tested_pairs, stats.touching_pairs, stats.contact_points,
+            );
+        }
+        report
+    }
+}
+
+/// A plugin that fills the [`CollisionProfile`] every frame.
+///
+/// The physics is only timed when the [`RapierPhysicsPlugin`] was added first with its default
+/// stages. The pairs are counted either way.
+#[derive(Default)]
+pub struct CollisionProfilePlugin;
+
+impl CollisionProfilePlugin {
+    /// Creates a new [`CollisionProfilePlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for CollisionProfilePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CollisionProfile>()
+            .add_system_to_stage(CoreStage::PostUpdate, count_collision_pairs);
+
+        let has_stage = |app: &App, label: PhysicsStages| {
+            app.schedule.get_stage::<SystemStage>(label).is_some()
+        };
+        if has_stage(app, PhysicsStages::SyncBackend)
+            && has_stage(app, PhysicsStages::StepSimulation)
+        {
+            app.add_stage_before(
+                PhysicsStages::SyncBackend,
+                CollisionProfileStage::BeforeSyncBackend,
+                SystemStage::single(start_character_timer),
+            )
+            .add_stage_before(
+                PhysicsStages::StepSimulation,
+                CollisionProfileStage::BeforeStepSimulation,
+                SystemStage::single(start_step_timer),
+            )
+            .add_stage_after(
+                PhysicsStages::StepSimulation,
+                CollisionProfileStage::AfterStepSimulation,
+                SystemStage::single(stop_step_timer),
+            );
+        }
+    }
+}
+
+/// Starts timing the character controllers.
+pub fn start_character_timer(mut profile: ResMut<CollisionProfile>) {
+    profile.stage_start = Some(Instant::now());
+}
+
+/// Records how long the character controllers took and starts timing the physics step.
+pub fn start_step_timer(mut profile: ResMut<CollisionProfile>) {
+    let now = Instant::now();
+    if let Some(start) = profile.stage_start {
+        profile.character_time = now - start;
+    }
+    profile.stage_start = Some(now);
+}
+
+/// Records how long the physics step took.
+pub fn stop_step_timer(mut profile: ResMut<CollisionProfile>) {
+    if let Some(start) = profile.stage_start.take() {
+        profile.step_time = start.elapsed();
+    }
+}
+
+/// Counts the work of the last physics step for every pair of shape types.
+pub fn count_collision_pairs(
+    mut profile: ResMut<CollisionProfile>,
+    rapier_context: Res<RapierContext>,
+    characters: Query<(
+        &KinematicCharacterControllerOutput,
+        &KinematicCharacterController,
+        Option<&Collider>,
+    )>,
+    colliders: Query<&Collider>,
+) {
+    let mut pairs: HashMap<ShapePair, ShapePairStats> = HashMap::default();
+
+    for (output, controller, collider) in &characters {
+        let Some(shape) = controller
+            .custom_shape
+            .as_ref()
+            .map(|(shape, ..)| shape)
+            .or(collider)
+        else {
+            continue;
+        };
+        for collision in &output.collisions {
+            if let Ok(obstacle) = colliders.get(collision.entity) {
+                let pair = ShapePair::new(shape.raw.shape_type(), obstacle.raw.shape_type());
+                pairs.entry(pair).or_default().toi_hits += 1;
+            }
+        }
+    }
+
+    let shape_type = |handle| {
+        rapier_context
+            .colliders
+            .get(handle)
+            .map(|collider| collider.shape().shape_type())
+    };
+    for contact_pair in rapier_context.narrow_phase.contact_pairs() {
+        let (Some(a), Some(b)) = (
+            shape_type(contact_pair.collider1),
+            shape_type(contact_pair.collider2),
+        ) else {
+            continue;
+        };
+        let stats = pairs.entry(ShapePair::new(a, b)).or_default();
+        stats.tested_pairs += 1;
+        if contact_pair.has_any_active_contact {
+            stats.touching_pairs += 1;
+            stats.contact_points += contact_pair
+                .manifolds
+                .iter()
+                .map(|manifold| manifold.points.len())
+                .sum::<usize>();
+        }
+    }
+    for (collider1, collider2, intersecting) in rapier_context.narrow_phase.intersection_pairs() {
+        let (Some(a), Some(b)) = (shape_type(collider1), shape_type(collider2)) else {
+            continue;
+        };
+        let stats = pairs.entry(ShapePair::new(a, b)).or_default();
+        stats.tested_pairs += 1;
+        if intersecting {
+            stats.touching_pairs += 1;
+        }
+    }
+
+    profile.pairs = pairs;
+}
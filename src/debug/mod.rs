@@ -0,0 +1,19 @@
+//! A mod with tools for debugging maps and the controllers.
+
+/// A mod that profiles which shapes the collision system spends its time on.
+pub mod collision_profile;
+
+/// A mod that draws what the physics sees on top of a map.
+pub mod overlay;
+
+/// A mod with the colors shared by every debug visualization.
+pub mod palette;
+
+/// A mod that rewinds the walking objects and bodies to earlier physics steps.
+pub mod rewind;
+
+/// A mod that advances the physics one fixed step at a time.
+pub mod step;
+
+/// A mod that watches the physics for signs of an unstable map.
+pub mod watchdog;
@@ -0,0 +1,377 @@
+//! A mod that draws what the physics sees on top of a map.
+//!
+//! The [`MapDebugPlugin`] toggles three layers with a single key:
+//!
+//! - the wireframes of every collider, compound shapes and heightfields included, drawn by the
+//!   Rapier debug renderer, with the `debug-render` feature;
+//! - the time-of-impact hits of the character controllers from the last frame, as a marker at
+//!   the contact point and a line along the contact normal;
+//! - the state of every character controller, as a line along its [`CustomVelocity`] and as
+//!   on-screen text listing whether it is grounded and how fast it moves.
+//!
+//! When the [`CollisionProfilePlugin`](super::collision_profile::CollisionProfilePlugin) is
+//! added, the text also shows how long the collision system took and which pairs of shape types
+//! kept it busiest. When the [`RewindPlugin`](super::rewind::RewindPlugin) is added, the text
+//! shows which recorded step is shown while scrubbing, even when the overlay is disabled.
+//!
+//! Everything is drawn in the colors of the [`DebugPalette`]. The text uses the font at
+//! [`MapDebugSettings::font`], which must exist in the assets folder.
+
+use super::{collision_profile::*, palette::*, rewind::*};
+use crate::controller::*;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+/// Whether the debug overlay is shown.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MapDebug {
+    /// Whether the overlay is enabled.
+    pub enabled: bool,
+}
+
+/// Settings for the [`MapDebugPlugin`].
+#[derive(Resource, Debug, Clone)]
+pub struct MapDebugSettings {
+    /// A key that toggles the overlay, if any.
+    pub toggle_key: Option<KeyCode>,
+    /// Whether the wireframes of the colliders are drawn.
+    pub show_shapes: bool,
+    /// Whether the time-of-impact hits of the character controllers are drawn.
+    pub show_contacts: bool,
+    /// Whether the velocity and state of the character controllers are shown.
+    pub show_controllers: bool,
+    /// How many pairs of shape types from the [`CollisionProfile`] are listed, if any.
+    pub profile_pairs: usize,
+    /// The path of the font used for the text, relative to the assets folder.
+    pub font: String,
+}
+
+impl Default for MapDebugSettings {
+    fn default() -> Self {
+        Self {
+            toggle_key: Some(KeyCode::F3),
+            show_shapes: true,
+            show_contacts: true,
+            show_controllers: true,
+            profile_pairs: 5,
+            font: "fonts/DejaVuSansMono.ttf".to_string(),
+        }
+    }
+}
+
+/// A plugin that draws collider wireframes, controller contacts and controller state.
+///
+/// With the `debug-render` feature, the [`RapierDebugRenderPlugin`] is added as well if it has not
+/// been added yet.
+#[derive(Default)]
+pub struct MapDebugPlugin {
+    /// The settings used by the plugin.
+    pub settings: MapDebugSettings,
+}
+
+impl MapDebugPlugin {
+    /// Creates a new [`MapDebugPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Plugin for MapDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone())
+            .init_resource::<MapDebug>()
+            .init_resource::<DebugPalette>()
+            .add_system(toggle_map_debug_on_key)
+            .add_system_to_stage(CoreStage::PostUpdate, draw_controller_debug)
+            .add_system_to_stage(CoreStage::PostUpdate, update_debug_text);
+
+        #[cfg(feature = "debug-render")]
+        {
+            if !app.is_plugin_added::<RapierDebugRenderPlugin>() {
+                app.add_plugin(RapierDebugRenderPlugin::default().disabled());
+            }
+            app.add_system(sync_debug_render.after(toggle_map_debug_on_key));
+        }
+    }
+}
+
+/// A marker for the entities drawn by the overlay, which are replaced every frame.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct DebugMarker;
+
+/// A marker for the on-screen text of the overlay.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct DebugText;
+
+/// Toggles [`MapDebug`] when the toggle key is pressed.
+pub fn toggle_map_debug_on_key(
+    settings: Res<MapDebugSettings>,
+    keyboard: Res<Input<KeyCode>>,
+    mut map_debug: ResMut<MapDebug>,
+) {
+    if settings
+        .toggle_key
+        .is_some_and(|key| keyboard.just_pressed(key))
+    {
+        map_debug.enabled = !map_debug.enabled;
+    }
+}
+
+/// Turns the Rapier debug renderer on and off with the overlay, and colors it with the palette.
+#[cfg(feature = "debug-render")]
+pub fn sync_debug_render(
+    settings: Res<MapDebugSettings>,
+    map_debug: Res<MapDebug>,
+    palette: Res<DebugPalette>,
+    mut render_context: ResMut<DebugRenderContext>,
+) {
+    if map_debug.is_changed() || settings.is_changed() {
+        render_context.enabled = map_debug.enabled && settings.show_shapes;
+    }
+    if palette.is_changed() {
+        let colors = palette.colors();
+        let style = &mut render_context.pipeline.style;
+        style.collider_fixed_color = colors.fixed_collider.as_hsla_f32();
+        style.collider_dynamic_color = colors.dynamic_collider.as_hsla_f32();
+        style.collider_kinematic_color = colors.kinematic_collider.as_hsla_f32();
+        style.collider_parentless_color = colors.parentless_collider.as_hsla_f32();
+    }
+}
+
+/// The mesh and materials shared by the markers of the overlay.
+#[derive(Debug, Clone)]
+pub struct DebugMarkerAssets {
+    cube: Handle<Mesh>,
+    contact: Handle<StandardMaterial>,
+    velocity: Handle<StandardMaterial>,
+}
+
+/// Draws the contacts and velocities of the character controllers.
+///
+/// The markers are kept between frames: they are moved to where they are needed, more are spawned
+/// when there are not enough, and the rest are hidden.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn draw_controller_debug(
+    mut commands: Commands,
+    settings: Res<MapDebugSettings>,
+    map_debug: Res<MapDebug>,
+    palette: Res<DebugPalette>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut assets: Local<Option<DebugMarkerAssets>>,
+    mut markers: Query<
+        (
+            &mut Transform,
+            &mut Handle<StandardMaterial>,
+            &mut Visibility,
+        ),
+        With<DebugMarker>,
+    >,
+    controllers: Query<(
+        &GlobalTransform,
+        &KinematicCharacterControllerOutput,
+        Option<&CustomVelocity>,
+    )>,
+) {
+    if !map_debug.enabled {
+        for (_, _, mut visibility) in &mut markers {
+            if visibility.is_visible {
+                visibility.is_visible = false;
+            }
+        }
+        return;
+    }
+
+    let colors = palette.colors();
+    let unlit = |color: Color| StandardMaterial {
+        base_color: color,
+        unlit: true,
+        ..default()
+    };
+    let assets = match assets.as_mut() {
+        Some(assets) => {
+            if palette.is_changed() {
+                for (material, color) in [
+                    (&assets.contact, colors.contact),
+                    (&assets.velocity, colors.velocity),
+                ] {
+                    if let Some(material) = materials.get_mut(material) {
+                        material.base_color = color;
+                    }
+                }
+            }
+            assets
+        }
+        None => assets.insert(DebugMarkerAssets {
+            cube: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+            contact: materials.add(unlit(colors.contact)),
+            velocity: materials.add(unlit(colors.velocity)),
+        }),
+    };
+    let line_width = 0.02 * colors.line_scale;
+    let mut shapes = Vec::new();
+    let mut draw = |transform: Transform, material: &Handle<StandardMaterial>| {
+        shapes.push((transform, material.clone()));
+    };
+
+    for (transform, output, velocity) in &controllers {
+        if settings.show_contacts {
+            for collision in &output.collisions {
+                // The witness and normal of the character are relative to where it was hit.
+                let rotation = collision.character_rotation;
+                let point = collision.character_translation + rotation * collision.toi.witness1;
+                let normal = -(rotation * collision.toi.normal1);
+                draw(
+                    Transform::from_translation(point)
+                        .with_scale(Vec3::splat(0.08 * colors.line_scale)),
+                    &assets.contact,
+                );
+                draw(
+                    line_transform(point, point + 0.5 * normal, line_width),
+                    &assets.contact,
+                );
+            }
+        }
+        if settings.show_controllers {
+            if let Some(velocity) = velocity.filter(|velocity| velocity.0 != Vec3::ZERO) {
+                let start = transform.translation();
+                draw(
+                    line_transform(start, start + velocity.0, line_width),
+                    &assets.velocity,
+                );
+            }
+        }
+    }
+
+    let mut shapes = shapes.into_iter();
+    for (mut transform, mut material, mut visibility) in &mut markers {
+        match shapes.next() {
+            Some((shape_transform, shape_material)) => {
+                if *transform != shape_transform {
+                    *transform = shape_transform;
+                }
+                if *material != shape_material {
+                    *material = shape_material;
+                }
+                if !visibility.is_visible {
+                    visibility.is_visible = true;
+                }
+            }
+            None if visibility.is_visible => visibility.is_visible = false,
+            None => {}
+        }
+    }
+    for (transform, material) in shapes {
+        commands.spawn((
+            PbrBundle {
+                mesh: assets.cube.clone(),
+                material,
+                transform,
+                ..default()
+            },
+            DebugMarker,
+        ));
+    }
+}
+
+/// Creates the transform of a unit cube stretched into a thin line between two points.
+fn line_transform(start: Vec3, end: Vec3, width: f32) -> Transform {
+    let direction = end - start;
+    Transform {
+        translation: start + 0.5 * direction,
+        rotation: Quat::from_rotation_arc(Vec3::Y, direction.normalize_or_zero()),
+        scale: Vec3::new(width, direction.length(), width),
+    }
+}
+
+/// Writes the state of every character controller, the collision profile, and the rewound step
+/// into the on-screen text.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn update_debug_text(
+    mut commands: Commands,
+    settings: Res<MapDebugSettings>,
+    map_debug: Res<MapDebug>,
+    palette: Res<DebugPalette>,
+    profile: Option<Res<CollisionProfile>>,
+    rewind: Option<Res<RewindBuffer>>,
+    asset_server: Res<AssetServer>,
+    mut texts: Query<(Entity, &mut Text, &mut BackgroundColor), With<DebugText>>,
+    controllers: Query<(
+        Entity,
+        &KinematicCharacterControllerOutput,
+        Option<&CustomVelocity>,
+        Option<&Name>,
+    )>,
+) {
+    let show_profile = profile.is_some() && settings.profile_pairs > 0;
+    let rewind = rewind.filter(|rewind| rewind.is_scrubbing());
+    if !(map_debug.enabled && (settings.show_controllers || show_profile)) && rewind.is_none() {
+        for (entity, ..) in &texts {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+
+    let mut report: String = controllers
+        .iter()
+        .filter(|_| map_debug.enabled && settings.show_controllers)
+        .map(|(entity, output, velocity, name)| {
+            let velocity = velocity.map_or(Vec3::ZERO, |velocity| velocity.0);
+            format!(
+                "{} ({:?}): grounded {}, velocity [{:.2}, {:.2}, {:.2}], moved {:.3}, hits {}\n",
+                name.map_or("unnamed", |name| name.as_str()),
+                entity,
+                output.grounded,
+                velocity.x,
+                velocity.y,
+                velocity.z,
+                output.effective_translation.length(),
+                output.collisions.len(),
+            )
+        })
+        .collect();
+    if let Some(profile) = profile.filter(|_| map_debug.enabled && show_profile) {
+        report += &profile.report(settings.profile_pairs);
+    }
+    if let Some((cursor, len)) = rewind.and_then(|rewind| Some((rewind.cursor()?, rewind.len()))) {
+        report += &format!("rewind: step {} of {len}\n", cursor + 1);
+    }
+
+    let colors = palette.colors();
+    match texts.get_single_mut() {
+        Ok((_, mut text, mut background)) => {
+            if text.sections[0].value != report {
+                text.sections[0].value = report;
+            }
+            if palette.is_changed() {
+                text.sections[0].style.color = colors.text;
+                background.0 = colors.text_background;
+            }
+        }
+        Err(_) => {
+            commands.spawn((
+                TextBundle::from_section(
+                    report,
+                    TextStyle {
+                        font: asset_server.load(settings.font.as_str()),
+                        font_size: 16.0,
+                        color: colors.text,
+                    },
+                )
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        top: Val::Px(8.0),
+                        left: Val::Px(8.0),
+                        ..default()
+                    },
+                    ..default()
+                }),
+                // Text nodes draw a background too when they have a color for it.
+                BackgroundColor(colors.text_background),
+                DebugText,
+            ));
+        }
+    }
+}
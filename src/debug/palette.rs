@@ -0,0 +1,128 @@
+//! A mod with the colors shared by every debug visualization.
+//!
+//! The collider wireframes, contact markers, and velocity lines of the
+//! [`MapDebugPlugin`](super::overlay::MapDebugPlugin), the arcs of bounce pads, and the on-screen
+//! debug text all take their colors from the [`DebugPalette`] resource. Changing its preset
+//! recolors everything that is already drawn:
+//!
+//! - [`DebugPalettePreset::Standard`] uses the familiar colors of the Rapier debug renderer;
+//! - [`DebugPalettePreset::ColorblindSafe`] uses the Okabe-Ito palette, whose colors stay apart
+//!   for the common kinds of color blindness;
+//! - [`DebugPalettePreset::HighContrast`] uses saturated colors, thicker lines, and a dark
+//!   backing behind text;
+//! - [`DebugPalettePreset::Custom`] uses any set of [`DebugColors`].
+
+use bevy::prelude::*;
+
+/// The colors of the debug visualizations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DebugColors {
+    /// The color of colliders on fixed bodies.
+    pub fixed_collider: Color,
+    /// The color of colliders on dynamic bodies.
+    pub dynamic_collider: Color,
+    /// The color of colliders on kinematic bodies, such as doors and characters.
+    pub kinematic_collider: Color,
+    /// The color of colliders without a body, such as event spaces.
+    pub parentless_collider: Color,
+    /// The color of contact markers and normals.
+    pub contact: Color,
+    /// The color of velocity lines.
+    pub velocity: Color,
+    /// The color of predicted paths, such as the arcs of bounce pads.
+    pub trajectory: Color,
+    /// The color of debug text.
+    pub text: Color,
+    /// The color behind debug text.
+    pub text_background: Color,
+    /// How much thicker lines and markers are drawn than usual.
+    pub line_scale: f32,
+}
+
+impl DebugColors {
+    /// The colors of a preset.
+    pub fn from_preset(preset: DebugPalettePreset) -> Self {
+        match preset {
+            DebugPalettePreset::Standard => Self {
+                fixed_collider: Color::hsl(30.0, 1.0, 0.4),
+                dynamic_collider: Color::hsl(340.0, 1.0, 0.3),
+                kinematic_collider: Color::hsl(20.0, 1.0, 0.3),
+                parentless_collider: Color::hsl(30.0, 1.0, 0.4),
+                contact: Color::RED,
+                velocity: Color::CYAN,
+                trajectory: Color::YELLOW,
+                text: Color::WHITE,
+                text_background: Color::NONE,
+                line_scale: 1.0,
+            },
+            // The Okabe-Ito palette.
+            DebugPalettePreset::ColorblindSafe => Self {
+                fixed_collider: Color::rgb_u8(0, 114, 178),
+                dynamic_collider: Color::rgb_u8(230, 159, 0),
+                kinematic_collider: Color::rgb_u8(204, 121, 167),
+                parentless_collider: Color::rgb_u8(86, 180, 233),
+                contact: Color::rgb_u8(213, 94, 0),
+                velocity: Color::rgb_u8(0, 158, 115),
+                trajectory: Color::rgb_u8(240, 228, 66),
+                text: Color::WHITE,
+                text_background: Color::NONE,
+                line_scale: 1.0,
+            },
+            DebugPalettePreset::HighContrast => Self {
+                fixed_collider: Color::WHITE,
+                dynamic_collider: Color::YELLOW,
+                kinematic_collider: Color::CYAN,
+                parentless_collider: Color::WHITE,
+                contact: Color::FUCHSIA,
+                velocity: Color::LIME_GREEN,
+                trajectory: Color::YELLOW,
+                text: Color::WHITE,
+                text_background: Color::rgba(0.0, 0.0, 0.0, 0.8),
+                line_scale: 2.0,
+            },
+            DebugPalettePreset::Custom(colors) => colors,
+        }
+    }
+}
+
+impl Default for DebugColors {
+    fn default() -> Self {
+        Self::from_preset(DebugPalettePreset::default())
+    }
+}
+
+/// The sets of colors that the [`DebugPalette`] can use.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DebugPalettePreset {
+    /// The colors of the Rapier debug renderer.
+    #[default]
+    Standard,
+    /// Colors that can be told apart with the common kinds of color blindness.
+    ColorblindSafe,
+    /// Saturated colors and thick lines that stand out against any map.
+    HighContrast,
+    /// Colors chosen by the game.
+    Custom(DebugColors),
+}
+
+/// The colors used by every debug visualization.
+///
+/// Systems that draw debug shapes read their colors from here instead of from their own settings,
+/// and recolor what they have drawn when the palette changes.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq)]
+pub struct DebugPalette {
+    /// The preset in use.
+    pub preset: DebugPalettePreset,
+}
+
+impl DebugPalette {
+    /// Creates a new [`DebugPalette`] that uses a preset.
+    pub fn new(preset: DebugPalettePreset) -> Self {
+        Self { preset }
+    }
+
+    /// The colors of the palette.
+    pub fn colors(&self) -> DebugColors {
+        DebugColors::from_preset(self.preset)
+    }
+}
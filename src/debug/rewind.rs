@@ -0,0 +1,354 @@
+//! A mod that rewinds the walking objects and bodies to earlier physics steps.
+//!
+//! Every frame that the physics steps, the [`RewindPlugin`] records where the character
+//! controllers and the moving rigid bodies are and how fast they move into the [`RewindBuffer`],
+//! which keeps the last few hundred steps.
+//!
+//! While the simulation is single stepped with the [`SingleStepPlugin`], a [`RewindEvent`] (sent
+//! by the rewind keys) scrubs backwards and forwards through the recorded steps and puts every
+//! body back where it was. Advancing a frame from a rewound step simulates forward from there and
+//! drops the recorded steps after it, so an intermittent collision bug can be replayed as many
+//! times as it takes to see it.
+//!
+//! The [`MapDebugPlugin`](super::overlay::MapDebugPlugin) shows which step is shown while
+//! scrubbing.
+
+use super::step::*;
+use crate::{controller::*, state::*};
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use std::collections::VecDeque;
+
+/// The recorded state of a body in one step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RewindBody {
+    /// The entity of the body.
+    pub entity: Entity,
+    /// The transform of the body.
+    pub transform: Transform,
+    /// The Rapier velocity of the body, if it has one.
+    pub velocity: Option<Velocity>,
+    /// The velocity of a kinematic character, if the body is one.
+    pub custom_velocity: Option<Vec3>,
+}
+
+/// The recorded state of every body in one step.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RewindFrame {
+    /// The state of every body.
+    pub bodies: Vec<RewindBody>,
+}
+
+/// The last recorded steps, and which of them is shown while scrubbing.
+#[derive(Resource, Debug, Clone)]
+pub struct RewindBuffer {
+    frames: VecDeque<RewindFrame>,
+    capacity: usize,
+    cursor: Option<usize>,
+}
+
+impl Default for RewindBuffer {
+    fn default() -> Self {
+        Self::new(600)
+    }
+}
+
+impl RewindBuffer {
+    /// Creates a new [`RewindBuffer`] that keeps up to a number of steps.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+            cursor: None,
+        }
+    }
+
+    /// How many steps are recorded.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether no steps are recorded.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// A recorded step, from the oldest at `0` to the newest.
+    pub fn get(&self, index: usize) -> Option<&RewindFrame> {
+        self.frames.get(index)
+    }
+
+    /// The index of the step shown while scrubbing, or `None` when the newest state is shown.
+    pub fn cursor(&self) -> Option<usize> {
+        self.cursor
+    }
+
+    /// Whether an older step than the newest is shown.
+    pub fn is_scrubbing(&self) -> bool {
+        self.cursor.is_some()
+    }
+
+    /// Records a new step.
+    ///
+    /// When an older step is shown, the steps after it are dropped first, since the simulation
+    /// went on from there.
+    pub fn record(&mut self, frame: RewindFrame) {
+        if let Some(cursor) = self.cursor.take() {
+            self.frames.truncate(cursor + 1);
+        }
+        if self.capacity == 0 {
+            return;
+        }
+        while self.frames.len() >= self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    /// Moves the shown step back by a number of steps and returns it.
+    pub fn step_back(&mut self, steps: usize) -> Option<&RewindFrame> {
+        let newest = self.frames.len().checked_sub(1)?;
+        let index = self.cursor.unwrap_or(newest).saturating_sub(steps);
+        self.cursor = (index < newest).then_some(index);
+        self.frames.get(index)
+    }
+
+    /// Moves the shown step forward by a number of steps and returns it.
+    pub fn step_forward(&mut self, steps: usize) -> Option<&RewindFrame> {
+        let newest = self.frames.len().checked_sub(1)?;
+        let index = (self.cursor? + steps).min(newest);
+        self.cursor = (index < newest).then_some(index);
+        self.frames.get(index)
+    }
+
+    /// Forgets every recorded step.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.cursor = None;
+    }
+}
+
+/// An event that scrubs through the [`RewindBuffer`] while the simulation is single stepped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewindEvent {
+    /// Shows a number of steps further back.
+    Back(usize),
+    /// Shows a number of steps further forward, up to the newest.
+    Forward(usize),
+}
+
+/// Settings for the [`RewindPlugin`].
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct RewindSettings {
+    /// How many steps are kept.
+    pub capacity: usize,
+    /// A key that steps back, if any.
+    pub back_key: Option<KeyCode>,
+    /// A key that steps forward, if any.
+    pub forward_key: Option<KeyCode>,
+}
+
+impl Default for RewindSettings {
+    fn default() -> Self {
+        Self {
+            capacity: 600,
+            back_key: Some(KeyCode::LBracket),
+            forward_key: Some(KeyCode::RBracket),
+        }
+    }
+}
+
+/// A plugin that records the last physics steps and scrubs through them while single stepping.
+///
+/// The [`SingleStepPlugin`] is added as well if it has not been added yet.
+#[derive(Default)]
+pub struct RewindPlugin {
+    /// The settings used by the plugin.
+    pub settings: RewindSettings,
+}
+
+impl RewindPlugin {
+    /// Creates a new [`RewindPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Plugin for RewindPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<SingleStepPlugin>() {
+            app.add_plugin(SingleStepPlugin::new());
+        }
+
+        app.insert_resource(self.settings)
+            .insert_resource(RewindBuffer::new(self.settings.capacity))
+            .add_event::<RewindEvent>()
+            .add_system(rewind_on_key)
+            .add_system(apply_rewind_events.after(rewind_on_key))
+            .add_system_to_stage(CoreStage::PostUpdate, record_rewind_frames);
+    }
+}
+
+/// Sends a [`RewindEvent`] when a rewind key is pressed.
+pub fn rewind_on_key(
+    settings: Res<RewindSettings>,
+    keyboard: Res<Input<KeyCode>>,
+    mut events: EventWriter<RewindEvent>,
+) {
+    if settings
+        .back_key
+        .is_some_and(|key| keyboard.just_pressed(key))
+    {
+        events.send(RewindEvent::Back(1));
+    }
+    if settings
+        .forward_key
+        .is_some_and(|key| keyboard.just_pressed(key))
+    {
+        events.send(RewindEvent::Forward(1));
+    }
+}
+
+/// Records the state of every character controller and moving rigid body on frames that the
+/// physics stepped.
+#[allow(clippy::type_complexity)]
+pub fn record_rewind_frames(
+    time: Res<Time>,
+    rapier_config: Res<RapierConfiguration>,
+    state: Option<Res<State<MapBuilderState>>>,
+    mut buffer: ResMut<RewindBuffer>,
+    bodies: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&RigidBody>,
+            Option<&Velocity>,
+            Option<&CustomVelocity>,
+        ),
+        Or<(With<KinematicCharacterController>, With<RigidBody>)>,
+    >,
+) {
+    let playing = state.is_none_or(|state| *state.current() == MapBuilderState::Playing);
+    if !playing || !rapier_config.physics_pipeline_active || time.delta_seconds() <= 0.0 {
+        return;
+    }
+    let bodies = bodies
+        .iter()
+        .filter(|(_, _, body, ..)| *body != Some(&RigidBody::Fixed))
+        .map(
+            |(entity, transform, _, velocity, custom_velocity)| RewindBody {
+                entity,
+                transform: *transform,
+                velocity: velocity.copied(),
+                custom_velocity: custom_velocity.map(|velocity| velocity.0),
+            },
+        )
+        .collect();
+    buffer.record(RewindFrame { bodies });
+}
+
+/// Puts every body back where it was in the step that a [`RewindEvent`] scrubbed to.
+pub fn apply_rewind_events(
+    single_step: Res<SingleStep>,
+    mut buffer: ResMut<RewindBuffer>,
+    mut events: EventReader<RewindEvent>,
+    mut bodies: Query<(
+        &mut Transform,
+        Option<&mut Velocity>,
+        Option<&mut CustomVelocity>,
+    )>,
+) {
+    let mut shown = None;
+    for event in events.iter() {
+        if !single_step.enabled {
+            continue;
+        }
+        shown = match *event {
+            RewindEvent::Back(steps) => buffer.step_back(steps),
+            RewindEvent::Forward(steps) => buffer.step_forward(steps),
+        }
+        .cloned()
+        .or(shown);
+    }
+    let Some(frame) = shown else {
+        return;
+    };
+
+    for body in frame.bodies {
+        let Ok((mut transform, velocity, custom_velocity)) = bodies.get_mut(body.entity) else {
+            continue;
+        };
+        *transform = body.transform;
+        if let (Some(mut velocity), Some(recorded)) = (velocity, body.velocity) {
+            *velocity = recorded;
+        }
+        if let (Some(mut custom_velocity), Some(recorded)) = (custom_velocity, body.custom_velocity)
+        {
+            custom_velocity.0 = recorded;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(x: f32) -> RewindFrame {
+        RewindFrame {
+            bodies: vec![RewindBody {
+                entity: Entity::from_raw(0),
+                transform: Transform::from_xyz(x, 0.0, 0.0),
+                velocity: None,
+                custom_velocity: None,
+            }],
+        }
+    }
+
+    fn x(frame: Option<&RewindFrame>) -> Option<f32> {
+        frame.map(|frame| frame.bodies[0].transform.translation.x)
+    }
+
+    #[test]
+    fn rewind_buffer_forgets_the_oldest_steps() {
+        let mut buffer = RewindBuffer::new(3);
+        for i in 0..5 {
+            buffer.record(frame(i as f32));
+        }
+
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(x(buffer.get(0)), Some(2.0));
+        assert_eq!(x(buffer.get(2)), Some(4.0));
+    }
+
+    #[test]
+    fn rewind_buffer_scrubs_between_the_oldest_and_newest_steps() {
+        let mut buffer = RewindBuffer::new(10);
+        for i in 0..5 {
+            buffer.record(frame(i as f32));
+        }
+
+        assert_eq!(x(buffer.step_back(2)), Some(2.0));
+        assert_eq!(buffer.cursor(), Some(2));
+        assert_eq!(x(buffer.step_back(10)), Some(0.0));
+        assert_eq!(x(buffer.step_forward(1)), Some(1.0));
+        assert_eq!(x(buffer.step_forward(10)), Some(4.0));
+        assert!(!buffer.is_scrubbing());
+        assert_eq!(buffer.step_forward(1), None);
+    }
+
+    #[test]
+    fn rewind_buffer_drops_the_steps_after_a_rewound_one() {
+        let mut buffer = RewindBuffer::new(10);
+        for i in 0..5 {
+            buffer.record(frame(i as f32));
+        }
+
+        buffer.step_back(3);
+        buffer.record(frame(10.0));
+
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(x(buffer.get(2)), Some(10.0));
+        assert!(!buffer.is_scrubbing());
+    }
+}
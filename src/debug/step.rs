@@ -0,0 +1,235 @@
+//! A mod that advances the physics one fixed step at a time.
+//!
+//! While [`SingleStep`] is enabled, the [`TimeScale`] is zero and Rapier uses a fixed timestep
+//! equal to the frame advance delta. Every [`FrameAdvance`] then moves the controllers and the
+//! physics forward by exactly one step, which makes it possible to follow a collision or a
+//! time-of-impact bug in the walking system frame by frame.
+//!
+//! The player cameras freeze with the simulation, so a [`SingleStepCamera`] replaces them until
+//! the mode is disabled. It runs on real time and flies with the mouse, the arrow keys, and page
+//! up and page down, which leaves the player's own keys free to be replayed on the next step.
+
+use crate::{state::*, time_scale::*};
+
+use bevy::{input::mouse::MouseMotion, prelude::*};
+use bevy_rapier3d::prelude::*;
+
+/// Whether the simulation is being stepped one frame at a time.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SingleStep {
+    /// Whether single stepping is enabled.
+    pub enabled: bool,
+}
+
+/// A marker for the free camera used while single stepping.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct SingleStepCamera;
+
+/// Settings for the [`SingleStepPlugin`].
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct SingleStepSettings {
+    /// A key that toggles single stepping, if any.
+    pub toggle_key: Option<KeyCode>,
+    /// How fast the [`SingleStepCamera`] flies, in units per second.
+    pub camera_speed: f32,
+    /// How far the [`SingleStepCamera`] turns per pixel of mouse motion, in radians.
+    pub mouse_sensitivity: f32,
+}
+
+impl Default for SingleStepSettings {
+    fn default() -> Self {
+        Self {
+            toggle_key: Some(KeyCode::F9),
+            camera_speed: 10.0,
+            mouse_sensitivity: 0.003,
+        }
+    }
+}
+
+/// A plugin that steps the simulation one fixed step per [`FrameAdvance`] while it is enabled.
+///
+/// The [`TimeScalePlugin`] is added as well if it has not been added yet.
+#[derive(Default)]
+pub struct SingleStepPlugin {
+    /// The settings used by the plugin.
+    pub settings: SingleStepSettings,
+}
+
+impl SingleStepPlugin {
+    /// Creates a new [`SingleStepPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Plugin for SingleStepPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<TimeScalePlugin>() {
+            app.add_plugin(TimeScalePlugin::new());
+        }
+
+        app.insert_resource(self.settings)
+            .init_resource::<SingleStep>()
+            .add_system(toggle_single_step_on_key)
+            .add_system(update_single_step.after(toggle_single_step_on_key))
+            .add_system(
+                gate_physics_steps
+                    .after(update_single_step)
+                    .after(pause_physics_outside_play),
+            )
+            .add_system(move_single_step_camera.after(update_single_step));
+    }
+}
+
+/// Toggles [`SingleStep`] when the toggle key is pressed.
+pub fn toggle_single_step_on_key(
+    settings: Res<SingleStepSettings>,
+    keyboard: Res<Input<KeyCode>>,
+    mut single_step: ResMut<SingleStep>,
+) {
+    if settings
+        .toggle_key
+        .is_some_and(|key| keyboard.just_pressed(key))
+    {
+        single_step.enabled = !single_step.enabled;
+    }
+}
+
+/// What single stepping changed, so it can be put back when it is disabled.
+#[derive(Debug, Clone)]
+pub struct SavedSimulation {
+    time_scale: TimeScale,
+    timestep_mode: TimestepMode,
+    cameras: Vec<Entity>,
+    free_camera: Entity,
+}
+
+/// Freezes the simulation and swaps in the [`SingleStepCamera`] when [`SingleStep`] is enabled,
+/// and undoes it when it is disabled.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn update_single_step(
+    mut commands: Commands,
+    single_step: Res<SingleStep>,
+    frame_advance: Res<FrameAdvanceSettings>,
+    unscaled_timestep: Res<UnscaledTimestep>,
+    mut time_scale: ResMut<TimeScale>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut saved: Local<Option<SavedSimulation>>,
+    mut cameras: Query<
+        (Entity, &mut Camera, &GlobalTransform),
+        (With<Camera3d>, Without<SingleStepCamera>),
+    >,
+) {
+    match (single_step.enabled, saved.is_some()) {
+        (true, false) => {
+            // Start the free camera where the frontmost player camera is.
+            let (priority, transform) = cameras
+                .iter()
+                .filter(|(_, camera, _)| camera.is_active)
+                .max_by_key(|(_, camera, _)| camera.priority)
+                .map(|(_, camera, transform)| (camera.priority, transform.compute_transform()))
+                .unwrap_or_default();
+            let free_camera = commands
+                .spawn((
+                    Camera3dBundle {
+                        camera: Camera {
+                            priority,
+                            ..default()
+                        },
+                        transform,
+                        ..default()
+                    },
+                    SingleStepCamera,
+                    Name::new("Single Step Camera"),
+                ))
+                .id();
+
+            let mut deactivated = Vec::new();
+            for (entity, mut camera, _) in &mut cameras {
+                if camera.is_active {
+                    camera.is_active = false;
+                    deactivated.push(entity);
+                }
+            }
+
+            *saved = Some(SavedSimulation {
+                time_scale: *time_scale,
+                timestep_mode: unscaled_timestep.0,
+                cameras: deactivated,
+                free_camera,
+            });
+            *time_scale = TimeScale(0.0);
+            rapier_config.timestep_mode = TimestepMode::Fixed {
+                dt: frame_advance.delta,
+                substeps: 1,
+            };
+        }
+        (false, true) => {
+            let Some(saved) = saved.take() else {
+                return;
+            };
+            *time_scale = saved.time_scale;
+            rapier_config.timestep_mode = saved.timestep_mode;
+            // Outside of play the state plugin pauses the physics again on the next frame.
+            rapier_config.physics_pipeline_active = true;
+            commands.entity(saved.free_camera).despawn_recursive();
+            for entity in saved.cameras {
+                if let Ok((_, mut camera, _)) = cameras.get_mut(entity) {
+                    camera.is_active = true;
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Only lets Rapier step on frames that a [`FrameAdvance`] gave a delta time.
+pub fn gate_physics_steps(
+    time: Res<Time>,
+    single_step: Res<SingleStep>,
+    state: Option<Res<State<MapBuilderState>>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    if !single_step.enabled {
+        return;
+    }
+    let playing = state.is_none_or(|state| *state.current() == MapBuilderState::Playing);
+    let active = playing && time.delta_seconds() > 0.0;
+    if rapier_config.physics_pipeline_active != active {
+        rapier_config.physics_pipeline_active = active;
+    }
+}
+
+/// Flies the [`SingleStepCamera`] on real time while the simulation is frozen.
+pub fn move_single_step_camera(
+    time: Res<Time>,
+    settings: Res<SingleStepSettings>,
+    keyboard: Res<Input<KeyCode>>,
+    mut mouse_motion_events: EventReader<MouseMotion>,
+    mut cameras: Query<&mut Transform, With<SingleStepCamera>>,
+) {
+    let cursor_delta: Vec2 = mouse_motion_events.iter().map(|event| event.delta).sum();
+    let direction = [
+        (KeyCode::Up, -Vec3::Z),
+        (KeyCode::Down, Vec3::Z),
+        (KeyCode::Left, -Vec3::X),
+        (KeyCode::Right, Vec3::X),
+        (KeyCode::PageUp, Vec3::Y),
+        (KeyCode::PageDown, -Vec3::Y),
+    ]
+    .iter()
+    .filter(|(key, _)| keyboard.pressed(*key))
+    .map(|(_, direction)| *direction)
+    .sum::<Vec3>()
+    .normalize_or_zero();
+
+    for mut transform in &mut cameras {
+        // Yaw around the world's up axis and pitch around the camera's own right axis.
+        let yaw = Quat::from_rotation_y(-cursor_delta.x * settings.mouse_sensitivity);
+        let pitch = Quat::from_rotation_x(-cursor_delta.y * settings.mouse_sensitivity);
+        transform.rotation = yaw * transform.rotation * pitch;
+
+        let translation = transform.rotation * direction;
+        transform.translation += settings.camera_speed * time.raw_delta_seconds() * translation;
+    }
+}
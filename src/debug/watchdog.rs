@@ -0,0 +1,282 @@
+//! A mod that watches the physics for signs of an unstable map.
+//!
+//! Every frame, after Rapier has written its results back, the [`detect_collision_anomalies`]
+//! system looks at every non-fixed rigid body for three kinds of [`CollisionAnomaly`]:
+//!
+//! - transforms or velocities that are not finite, which poison everything they touch;
+//! - bodies that tunnel through fixed geometry, moving further in one frame than their own size
+//!   along a path that crosses a fixed collider;
+//! - bodies that move or spin faster than the limits in [`CollisionWatchdogSettings`], which
+//!   usually means colliders were spawned overlapping and are being pushed apart.
+//!
+//! Each anomaly is logged once with the name of the entity and sent as an event. When
+//! [`CollisionWatchdogSettings::freeze`] is set, the offending body is also turned into a fixed
+//! body so that it stops disturbing the rest of the map. The original body type is kept in
+//! [`FrozenByWatchdog`] so it can be restored.
+//!
+//! Moving the [`FloatingOrigin`](crate::floating_origin::FloatingOrigin), respawning players, and
+//! teleporting bodies are not mistaken for tunneling.
+
+use crate::{floating_origin::*, map::checkpoint::*, teleport::*};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_rapier3d::prelude::*;
+
+/// The kinds of problems found by the watchdog.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CollisionAnomalyKind {
+    /// The transform or velocity contains NaN or infinity.
+    NonFinite,
+    /// The body passed through a fixed collider between two frames.
+    Tunneling {
+        /// Where the body was in the previous frame.
+        from: Vec3,
+        /// Where the body is now.
+        to: Vec3,
+        /// The fixed collider that was crossed.
+        obstacle: Entity,
+    },
+    /// The body moves or spins faster than the limits.
+    Exploding {
+        /// The linear speed of the body.
+        speed: f32,
+        /// The angular speed of the body.
+        angular_speed: f32,
+    },
+}
+
+/// An event sent when the watchdog finds a problem with a body.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollisionAnomaly {
+    /// The rigid body with the problem.
+    pub entity: Entity,
+    /// What is wrong with it.
+    pub kind: CollisionAnomalyKind,
+}
+
+/// The body type of a body that the watchdog has frozen.
+///
+/// Insert the body type back and remove this component to release the body.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct FrozenByWatchdog(pub RigidBody);
+
+/// Settings for the [`CollisionWatchdogPlugin`].
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct CollisionWatchdogSettings {
+    /// Whether bodies with an anomaly are frozen in place.
+    pub freeze: bool,
+    /// The fastest a body may move, in units per second.
+    pub max_speed: f32,
+    /// The fastest a body may spin, in radians per second.
+    pub max_angular_speed: f32,
+    /// The size used for bodies without colliders of their own when checking for tunneling.
+    pub default_size: f32,
+}
+
+impl Default for CollisionWatchdogSettings {
+    fn default() -> Self {
+        Self {
+            freeze: false,
+            max_speed: 250.0,
+            max_angular_speed: 250.0,
+            default_size: 0.25,
+        }
+    }
+}
+
+/// A plugin that reports and optionally freezes bodies that misbehave.
+#[derive(Default)]
+pub struct CollisionWatchdogPlugin {
+    /// The settings used by the plugin.
+    pub settings: CollisionWatchdogSettings,
+}
+
+impl CollisionWatchdogPlugin {
+    /// Creates a new [`CollisionWatchdogPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`CollisionWatchdogPlugin`] that freezes the bodies it reports.
+    pub fn freezing() -> Self {
+        Self {
+            settings: CollisionWatchdogSettings {
+                freeze: true,
+                ..default()
+            },
+        }
+    }
+}
+
+impl Plugin for CollisionWatchdogPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings)
+            .add_event::<CollisionAnomaly>()
+            .add_event::<FloatingOriginShifted>()
+            .add_event::<RespawnEvent>()
+            .add_event::<TeleportEvent>()
+            .add_system_to_stage(CoreStage::PostUpdate, detect_collision_anomalies);
+    }
+}
+
+/// What the watchdog remembers about a body between frames.
+#[derive(Debug, Clone, Copy)]
+pub struct WatchedBody {
+    transform: Transform,
+    reported: bool,
+}
+
+/// Looks for [`CollisionAnomaly`]s and reports them.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn detect_collision_anomalies(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<CollisionWatchdogSettings>,
+    rapier_context: Res<RapierContext>,
+    mut shifts: EventReader<FloatingOriginShifted>,
+    mut respawns: EventReader<RespawnEvent>,
+    mut teleports: EventReader<TeleportEvent>,
+    mut anomalies: EventWriter<CollisionAnomaly>,
+    mut watched: Local<HashMap<Entity, WatchedBody>>,
+    mut bodies: Query<
+        (
+            Entity,
+            &RigidBody,
+            &mut Transform,
+            Option<&mut Velocity>,
+            Option<&Collider>,
+            Option<&Children>,
+            Option<&Name>,
+        ),
+        Without<FrozenByWatchdog>,
+    >,
+    colliders: Query<&Collider>,
+) {
+    // Teleports are not tunneling, so forget where the moved bodies were.
+    for shift in shifts.iter() {
+        for body in watched.values_mut() {
+            body.transform.translation -= shift.shift;
+        }
+    }
+    for respawn in respawns.iter() {
+        watched.remove(&respawn.entity);
+    }
+    for teleport in teleports.iter() {
+        watched.remove(&teleport.entity);
+    }
+
+    let dt = time.delta_seconds();
+    let mut seen = HashSet::new();
+    for (entity, rigid_body, mut transform, velocity, collider, children, name) in &mut bodies {
+        if *rigid_body == RigidBody::Fixed {
+            continue;
+        }
+        seen.insert(entity);
+        let previous = watched.get(&entity).copied();
+
+        let anomaly = if !is_finite(&transform, velocity.as_deref()) {
+            Some(CollisionAnomalyKind::NonFinite)
+        } else {
+            previous.and_then(|previous| {
+                let from = previous.transform.translation;
+                let to = transform.translation;
+                let distance = from.distance(to);
+
+                let (speed, angular_speed) = match velocity.as_deref() {
+                    Some(velocity) => (velocity.linvel.length(), velocity.angvel.length()),
+                    None if dt > 0.0 => (distance / dt, 0.0),
+                    None => (0.0, 0.0),
+                };
+                if speed > settings.max_speed || angular_speed > settings.max_angular_speed {
+                    return Some(CollisionAnomalyKind::Exploding {
+                        speed,
+                        angular_speed,
+                    });
+                }
+
+                // Moving less than its own size in a frame cannot skip over anything.
+                let size =
+                    body_size(collider, children, &colliders).unwrap_or(settings.default_size);
+                if distance <= size {
+                    return None;
+                }
+                let filter = QueryFilter::only_fixed().exclude_rigid_body(entity);
+                rapier_context
+                    .cast_ray(from, (to - from) / distance, distance, false, filter)
+                    .map(|(obstacle, _)| CollisionAnomalyKind::Tunneling { from, to, obstacle })
+            })
+        };
+
+        let reported = previous.is_some_and(|previous| previous.reported);
+        if let Some(kind) = anomaly {
+            if !reported {
+                warn!(
+                    "Collision anomaly on {:?} ({}): {:?}",
+                    entity,
+                    name.map_or("unnamed", |name| name.as_str()),
+                    kind
+                );
+                anomalies.send(CollisionAnomaly { entity, kind });
+            }
+
+            if settings.freeze {
+                // Put a body with a broken transform back where it was last seen healthy.
+                if kind == CollisionAnomalyKind::NonFinite {
+                    *transform =
+                        previous.map_or(Transform::IDENTITY, |previous| previous.transform);
+                }
+                if let Some(mut velocity) = velocity {
+                    *velocity = Velocity::zero();
+                }
+                commands
+                    .entity(entity)
+                    .insert((RigidBody::Fixed, FrozenByWatchdog(*rigid_body)));
+                watched.remove(&entity);
+                continue;
+            }
+        }
+
+        // Only remember healthy transforms, which are the ones a frozen body is put back to.
+        let transform = match (anomaly, previous) {
+            (Some(CollisionAnomalyKind::NonFinite), Some(previous)) => previous.transform,
+            _ => *transform,
+        };
+        watched.insert(
+            entity,
+            WatchedBody {
+                transform,
+                reported: anomaly.is_some(),
+            },
+        );
+    }
+
+    watched.retain(|entity, _| seen.contains(entity));
+}
+
+fn is_finite(transform: &Transform, velocity: Option<&Velocity>) -> bool {
+    transform.translation.is_finite()
+        && transform.rotation.is_finite()
+        && transform.scale.is_finite()
+        && velocity
+            .is_none_or(|velocity| velocity.linvel.is_finite() && velocity.angvel.is_finite())
+}
+
+/// The smallest half extent of the colliders of a body, including the ones on its children.
+fn body_size(
+    collider: Option<&Collider>,
+    children: Option<&Children>,
+    colliders: &Query<&Collider>,
+) -> Option<f32> {
+    let child_colliders = children
+        .into_iter()
+        .flatten()
+        .filter_map(|child| colliders.get(*child).ok());
+    collider
+        .into_iter()
+        .chain(child_colliders)
+        .map(|collider| collider.raw.compute_local_aabb().half_extents().min())
+        .reduce(f32::min)
+}
@@ -0,0 +1,323 @@
+//! A mod that pushes bodies out of the map geometry they were spawned inside.
+//!
+//! A character or prop that starts inside a wall or floor, because of a bad spawn point or a map
+//! that was edited around it, is never pushed out by a character controller, which falls through
+//! or jitters against the geometry forever. Bodies with a [`Depenetrate`] are moved out before
+//! their next physics step instead.
+//!
+//! [`resolve_overlap`] first pushes the shape out along the contact normals of everything fixed
+//! that it overlaps, a few times over for corners. If that does not free it, for example when it
+//! is buried deep inside a thick wall, it searches shells of growing radius around the start for
+//! the nearest free spot, trying spots above first.
+//!
+//! New character controllers and dynamic bodies get a [`Depenetrate`] when they are spawned.
+//! Insert one by hand to check a body again after moving it.
+
+use bevy::prelude::*;
+use bevy_rapier3d::{
+    prelude::*,
+    rapier::{
+        math::{Isometry, Real},
+        parry::{
+            query::{ContactManifold, DefaultQueryDispatcher, PersistentQueryDispatcher},
+            shape::Cuboid,
+        },
+    },
+};
+
+/// Settings for the [`DepenetrationPlugin`] and [`resolve_overlap`].
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct DepenetrationSettings {
+    /// How many times a shape is pushed out along its contact normals.
+    pub max_iterations: usize,
+    /// How far past the surface a shape is pushed, so that it does not touch it anymore.
+    pub skin: f32,
+    /// The furthest from the start that a free spot is searched for, in units.
+    pub search_radius: f32,
+    /// The distance between the shells that are searched, in units.
+    pub search_step: f32,
+    /// Whether new character controllers and dynamic bodies get a [`Depenetrate`].
+    pub auto_insert: bool,
+}
+
+impl Default for DepenetrationSettings {
+    fn default() -> Self {
+        Self {
+            max_iterations: 8,
+            skin: 0.01,
+            search_radius: 3.0,
+            search_step: 0.25,
+            auto_insert: true,
+        }
+    }
+}
+
+/// A marker for bodies that are pushed out of the fixed geometry they overlap before their next
+/// physics step.
+///
+/// The marker is removed once the body has been checked against everything in the physics world.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct Depenetrate;
+
+/// The stage that pushes bodies out of the geometry, right before Rapier syncs with the world.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+pub struct DepenetrationStage;
+
+/// A plugin that pushes bodies out of the map geometry they were spawned inside.
+///
+/// The bodies are only pushed out when the [`RapierPhysicsPlugin`] was added first with its
+/// default stages.
+#[derive(Default)]
+pub struct DepenetrationPlugin {
+    /// The settings used by the plugin.
+    pub settings: DepenetrationSettings,
+}
+
+impl DepenetrationPlugin {
+    /// Creates a new [`DepenetrationPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Plugin for DepenetrationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings)
+            .add_system(insert_depenetrate);
+
+        if app
+            .schedule
+            .get_stage::<SystemStage>(PhysicsStages::SyncBackend)
+            .is_some()
+        {
+            app.add_stage_before(
+                PhysicsStages::SyncBackend,
+                DepenetrationStage,
+                SystemStage::single(depenetrate_bodies),
+            );
+        }
+    }
+}
+
+/// The offset that pushes a box out of one collider, if they overlap.
+fn penetration(
+    rapier_context: &RapierContext,
+    entity: Entity,
+    half_extents: Vec3,
+    center: Vec3,
+    rotation: Quat,
+) -> Option<Vec3> {
+    let handle = rapier_context.entity2collider().get(&entity)?;
+    let other = rapier_context.colliders.get(*handle)?;
+    let scale = rapier_context.physics_scale();
+    let cuboid = Cuboid::new((half_extents / scale).into());
+    let position: Isometry<Real> = (center / scale, rotation).into();
+
+    let mut manifolds: Vec<ContactManifold<(), ()>> = Vec::new();
+    DefaultQueryDispatcher
+        .contact_manifolds(
+            &position.inv_mul(other.position()),
+            &cuboid,
+            other.shape(),
+            0.0,
+            &mut manifolds,
+            &mut None,
+        )
+        .ok()?;
+    let (normal, dist) = manifolds
+        .iter()
+        .flat_map(|manifold| {
+            manifold
+                .points
+                .iter()
+                .map(move |point| (manifold.local_n1, point.dist))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    let normal: Vec3 = (position.rotation * normal).into();
+    // The normal points out of the box towards the collider, so the box moves back along it.
+    (dist < 0.0).then_some(normal * dist * scale)
+}
+
+/// The box around a shape, which stands in for the shape in every test.
+///
+/// Box tests stay exact for deep overlaps, where the general tests for rounded shapes such as
+/// capsules can miss a shape buried in a large box.
+struct Bounds {
+    collider: Collider,
+    center: Vec3,
+    half_extents: Vec3,
+}
+
+impl Bounds {
+    fn new(shape: &Collider) -> Self {
+        let aabb = shape.raw.compute_local_aabb();
+        let half_extents: Vec3 = aabb.half_extents().into();
+        Self {
+            collider: Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+            center: aabb.center().coords.into(),
+            half_extents,
+        }
+    }
+
+    /// The offsets that push the box out of every collider that it overlaps and that the filter
+    /// lets through.
+    fn penetrations(
+        &self,
+        rapier_context: &RapierContext,
+        position: Vec3,
+        rotation: Quat,
+        filter: QueryFilter,
+    ) -> Vec<Vec3> {
+        let center = position + rotation * self.center;
+        let mut offsets = Vec::new();
+        rapier_context.intersections_with_shape(
+            center,
+            rotation,
+            &self.collider,
+            filter,
+            |entity| {
+                offsets.extend(penetration(
+                    rapier_context,
+                    entity,
+                    self.half_extents,
+                    center,
+                    rotation,
+                ));
+                true
+            },
+        );
+        offsets
+    }
+}
+
+/// The nearest position to a start where a shape does not overlap anything that the filter lets
+/// through, or `None` if there is none within the search radius.
+///
+/// The start itself is returned when the shape is free there. The shape is tested as its bounding
+/// box, so it can end up a little further out than it has to, most of all in corners.
+pub fn resolve_overlap(
+    rapier_context: &RapierContext,
+    settings: &DepenetrationSettings,
+    shape: &Collider,
+    start: Vec3,
+    rotation: Quat,
+    filter: QueryFilter,
+) -> Option<Vec3> {
+    let bounds = Bounds::new(shape);
+
+    // Push the shape out along the contact normals, which frees it from shallow overlaps.
+    let mut position = start;
+    for _ in 0..settings.max_iterations {
+        let offsets = bounds.penetrations(rapier_context, position, rotation, filter);
+        if offsets.is_empty() {
+            return Some(position);
+        }
+        let push: Vec3 = offsets.into_iter().sum();
+        if push == Vec3::ZERO {
+            break;
+        }
+        position += push + push.normalize() * settings.skin;
+    }
+
+    // Search shells around the start, preferring spots above it.
+    let mut directions: Vec<Vec3> = (-1..=1)
+        .flat_map(|x| {
+            (-1..=1)
+                .flat_map(move |y| (-1..=1).map(move |z| Vec3::new(x as f32, y as f32, z as f32)))
+        })
+        .filter(|direction| *direction != Vec3::ZERO)
+        .map(Vec3::normalize)
+        .collect();
+    directions.sort_by(|a, b| b.y.total_cmp(&a.y));
+
+    let step = settings.search_step.max(0.01);
+    let mut radius = step;
+    while radius <= settings.search_radius {
+        for direction in &directions {
+            let candidate = start + *direction * radius;
+            if bounds
+                .penetrations(rapier_context, candidate, rotation, filter)
+                .is_empty()
+            {
+                return Some(candidate);
+            }
+        }
+        radius += step;
+    }
+    None
+}
+
+/// Gives every new character controller and dynamic body a [`Depenetrate`].
+#[allow(clippy::type_complexity)]
+pub fn insert_depenetrate(
+    mut commands: Commands,
+    settings: Res<DepenetrationSettings>,
+    bodies: Query<
+        (
+            Entity,
+            Option<&RigidBody>,
+            Option<&KinematicCharacterController>,
+        ),
+        (Added<Collider>, Without<Depenetrate>),
+    >,
+) {
+    if !settings.auto_insert {
+        return;
+    }
+    for (entity, body, controller) in &bodies {
+        if controller.is_some() || body == Some(&RigidBody::Dynamic) {
+            commands.entity(entity).insert(Depenetrate);
+        }
+    }
+}
+
+/// Pushes every body with a [`Depenetrate`] out of the fixed geometry it overlaps.
+pub fn depenetrate_bodies(
+    mut commands: Commands,
+    settings: Res<DepenetrationSettings>,
+    rapier_context: Res<RapierContext>,
+    mut bodies: Query<(Entity, &Collider, &mut Transform, Option<&Parent>), With<Depenetrate>>,
+    parents: Query<&GlobalTransform>,
+) {
+    for (entity, collider, mut transform, parent) in &mut bodies {
+        let parent_transform = parent
+            .and_then(|parent| parents.get(parent.get()).ok())
+            .copied()
+            .unwrap_or_default();
+        let world = parent_transform.mul_transform(*transform);
+        let (_, rotation, start) = world.to_scale_rotation_translation();
+
+        let filter = QueryFilter::only_fixed()
+            .exclude_sensors()
+            .exclude_collider(entity)
+            .exclude_rigid_body(entity);
+        match resolve_overlap(
+            &rapier_context,
+            &settings,
+            collider,
+            start,
+            rotation,
+            filter,
+        ) {
+            Some(position) if position != start => {
+                let offset = parent_transform
+                    .affine()
+                    .inverse()
+                    .transform_vector3(position - start);
+                transform.translation += offset;
+                info!("pushed {entity:?} out of the map geometry by {offset}");
+            }
+            Some(_) => {}
+            None => warn!(
+                "{entity:?} is stuck inside the map geometry with no free spot within {} units",
+                settings.search_radius
+            ),
+        }
+
+        // Geometry spawned in the same frame is only in the physics world after the next sync,
+        // so keep checking until the body is there as well.
+        if rapier_context.entity2collider().contains_key(&entity) {
+            commands.entity(entity).remove::<Depenetrate>();
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! A mod that lowers the rendering resolution when frames take too long.
+//!
+//! Cameras with a [`DynamicResolution`] component render into an image instead of the window.
+//! The image is the size of the primary window multiplied by the [`RenderScale`], and it is
+//! stretched back over the window by a separate 2D camera. The [`DynamicResolutionPlugin`] watches
+//! the frame time and moves the scale between the bounds set by the map author to hold the target
+//! frame rate.
+//!
+//! The scale is only lowered or raised after the frame time has stayed outside of the target for
+//! a while, and there is a band around the target where it does not change at all. Otherwise the
+//! scale would oscillate, because changing it changes the frame time that it reacts to.
+//!
+//! The viewport of a [`DynamicResolution`] camera is measured in pixels of the scaled image, so
+//! split-screen cameras must multiply their viewports by the [`RenderScale`] as well.
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        view::RenderLayers,
+    },
+};
+
+/// The fraction of the window resolution that [`DynamicResolution`] cameras render at.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct RenderScale(pub f32);
+
+impl Default for RenderScale {
+    fn default() -> Self {
+        RenderScale(1.0)
+    }
+}
+
+/// A marker for cameras whose resolution follows the [`RenderScale`].
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct DynamicResolution;
+
+/// A marker for the camera and sprite that stretch the scaled image over the window.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct DynamicResolutionDisplay;
+
+/// Settings for the [`DynamicResolutionPlugin`].
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct DynamicResolutionSettings {
+    /// The frame rate to hold.
+    pub target_fps: f32,
+    /// The lowest allowed render scale.
+    pub min_scale: f32,
+    /// The highest allowed render scale.
+    pub max_scale: f32,
+    /// How much the scale changes at a time.
+    pub step: f32,
+    /// How far the frame time may stray from the target, as a fraction of it, before the scale
+    /// changes.
+    pub tolerance: f32,
+    /// How many seconds the frame time must stay outside of the tolerance before the scale
+    /// changes.
+    pub delay: f32,
+    /// The render layer used by the display camera and sprite, which no other camera should see.
+    pub display_layer: u8,
+}
+
+impl Default for DynamicResolutionSettings {
+    fn default() -> Self {
+        Self {
+            target_fps: 60.0,
+            min_scale: 0.5,
+            max_scale: 1.0,
+            step: 0.1,
+            tolerance: 0.1,
+            delay: 1.0,
+            display_layer: RenderLayers::TOTAL_LAYERS as u8 - 1,
+        }
+    }
+}
+
+/// A plugin that adjusts the [`RenderScale`] to hold a target frame rate.
+#[derive(Default)]
+pub struct DynamicResolutionPlugin {
+    /// The settings used by the plugin.
+    pub settings: DynamicResolutionSettings,
+}
+
+impl DynamicResolutionPlugin {
+    /// Creates a new [`DynamicResolutionPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`DynamicResolutionPlugin`] that holds the given frame rate by keeping the
+    /// render scale within `[min_scale, max_scale]`.
+    pub fn with_target(target_fps: f32, min_scale: f32, max_scale: f32) -> Self {
+        Self {
+            settings: DynamicResolutionSettings {
+                target_fps,
+                min_scale,
+                max_scale: max_scale.max(min_scale),
+                ..default()
+            },
+        }
+    }
+}
+
+impl Plugin for DynamicResolutionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings)
+            .insert_resource(RenderScale(self.settings.max_scale))
+            .add_system(adjust_render_scale)
+            .add_system_to_stage(CoreStage::PostUpdate, apply_render_scale);
+    }
+}
+
+/// Lowers the [`RenderScale`] when frames are too slow and raises it when they are fast again.
+pub fn adjust_render_scale(
+    time: Res<Time>,
+    settings: Res<DynamicResolutionSettings>,
+    mut render_scale: ResMut<RenderScale>,
+    mut average_frame_time: Local<Option<f32>>,
+    mut time_outside: Local<f32>,
+) {
+    // Measure the real frame time, which does not follow the time scale.
+    let dt = time.raw_delta_seconds();
+    if dt <= 0.0 || settings.target_fps <= 0.0 {
+        return;
+    }
+
+    // Smooth the frame time so a single hitch does not count.
+    let average = match *average_frame_time {
+        Some(average) => average + 0.1 * (dt - average),
+        None => dt,
+    };
+    *average_frame_time = Some(average);
+
+    let target = 1.0 / settings.target_fps;
+    let direction = if average > target * (1.0 + settings.tolerance) {
+        -1.0
+    } else if average < target * (1.0 - settings.tolerance) {
+        1.0
+    } else {
+        *time_outside = 0.0;
+        return;
+    };
+
+    *time_outside += dt;
+    if *time_outside < settings.delay {
+        return;
+    }
+    *time_outside = 0.0;
+
+    let scale =
+        (render_scale.0 + direction * settings.step).clamp(settings.min_scale, settings.max_scale);
+    if scale != render_scale.0 {
+        render_scale.0 = scale;
+    }
+}
+
+/// Resizes the scaled image and points the [`DynamicResolution`] cameras at it.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_render_scale(
+    mut commands: Commands,
+    settings: Res<DynamicResolutionSettings>,
+    render_scale: Res<RenderScale>,
+    windows: Res<Windows>,
+    mut images: ResMut<Assets<Image>>,
+    mut target: Local<Option<Handle<Image>>>,
+    mut cameras: Query<&mut Camera, With<DynamicResolution>>,
+    mut display_sprites: Query<&mut Sprite, With<DynamicResolutionDisplay>>,
+) {
+    let Some(window) = windows.get_primary() else {
+        return;
+    };
+    // The display covers the whole window, so it must not exist before a camera uses it.
+    if target.is_none() && cameras.is_empty() {
+        return;
+    }
+    let size = Extent3d {
+        width: ((window.physical_width() as f32 * render_scale.0) as u32).max(1),
+        height: ((window.physical_height() as f32 * render_scale.0) as u32).max(1),
+        depth_or_array_layers: 1,
+    };
+
+    let handle = target.get_or_insert_with(|| {
+        let handle = images.add(scaled_image(size));
+        spawn_display(&mut commands, &settings, handle.clone());
+        handle
+    });
+    if let Some(image) = images.get_mut(handle) {
+        if image.texture_descriptor.size != size {
+            image.resize(size);
+        }
+    }
+
+    for mut sprite in &mut display_sprites {
+        let window_size = Vec2::new(window.width(), window.height());
+        if sprite.custom_size != Some(window_size) {
+            sprite.custom_size = Some(window_size);
+        }
+    }
+
+    let image_target = RenderTarget::Image(handle.clone());
+    for mut camera in &mut cameras {
+        if camera.target != image_target {
+            camera.target = image_target.clone();
+        }
+    }
+}
+
+fn scaled_image(size: Extent3d) -> Image {
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("dynamic_resolution_target"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+        },
+        ..default()
+    };
+    image.resize(size);
+    image
+}
+
+fn spawn_display(
+    commands: &mut Commands,
+    settings: &DynamicResolutionSettings,
+    image: Handle<Image>,
+) {
+    let layer = RenderLayers::layer(settings.display_layer);
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                // Draw the scaled image after every other camera has rendered into it.
+                priority: isize::MAX,
+                ..default()
+            },
+            ..default()
+        },
+        layer,
+        DynamicResolutionDisplay,
+        Name::new("Dynamic Resolution Camera"),
+    ));
+    commands.spawn((
+        SpriteBundle {
+            texture: image,
+            ..default()
+        },
+        layer,
+        DynamicResolutionDisplay,
+        Name::new("Dynamic Resolution Image"),
+    ));
+}
@@ -0,0 +1,491 @@
+//! A mod that records editing commands so that they can be undone and redone.
+//!
+//! Every change an editor makes to the map is an [`EditorCommand`]. Commands are executed through
+//! [`execute`], or by adding an [`ExecuteEditorCommand`] to the [`Commands`] of a system, which
+//! applies them and pushes them onto the undo stack of the [`EditorHistory`]. [`undo`] reverts
+//! the last command and moves it to the redo stack, and [`redo`] applies it again. Executing a
+//! new command clears the redo stack.
+//!
+//! The built-in commands place, delete, move, and change [`MapObject`]s. Objects placed by the
+//! editor keep their description in an [`EditorObject`] and are identified by an [`EditorId`]
+//! rather than by their [`Entity`], since undoing a deletion respawns the object as a new entity.
+//!
+//! The [`EditorHistoryPlugin`] undoes with Ctrl+Z and redoes with Ctrl+Y or Ctrl+Shift+Z while
+//! the map is being edited.
+
+use crate::{
+    floating_origin::*,
+    map::{shape_cache::*, *},
+    state::*,
+};
+
+use bevy::{
+    ecs::system::{Command, SystemState},
+    prelude::*,
+};
+
+/// A reversible change to the world made by an editor.
+pub trait EditorCommand: Send + Sync + 'static {
+    /// A short description of the command, for menus like "Undo Move".
+    fn label(&self) -> String;
+
+    /// Makes the change. This is called again when the command is redone.
+    fn apply(&mut self, world: &mut World);
+
+    /// Undoes the change made by [`EditorCommand::apply`].
+    fn revert(&mut self, world: &mut World);
+}
+
+/// A stable identifier for an object placed by the editor, which survives respawning.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EditorId(pub u64);
+
+/// The description of an object placed by the editor, kept up to date by the editing commands.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct EditorObject(pub MapObject);
+
+/// The commands that can be undone and redone.
+#[derive(Resource)]
+pub struct EditorHistory {
+    undo: Vec<Box<dyn EditorCommand>>,
+    redo: Vec<Box<dyn EditorCommand>>,
+    next_id: u64,
+    /// The most commands kept on the undo stack. The oldest commands are forgotten first.
+    pub limit: usize,
+}
+
+impl EditorHistory {
+    /// Creates an empty [`EditorHistory`] that keeps at most `limit` commands.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            next_id: 0,
+            limit,
+        }
+    }
+
+    /// Whether there is a command to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Whether there is a command to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// The label of the command that would be undone.
+    pub fn undo_label(&self) -> Option<String> {
+        self.undo.last().map(|command| command.label())
+    }
+
+    /// The label of the command that would be redone.
+    pub fn redo_label(&self) -> Option<String> {
+        self.redo.last().map(|command| command.label())
+    }
+
+    /// Forgets every command, for example after loading another map.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Creates a new [`EditorId`].
+    pub fn next_id(&mut self) -> EditorId {
+        self.next_id += 1;
+        EditorId(self.next_id)
+    }
+}
+
+impl Default for EditorHistory {
+    fn default() -> Self {
+        Self::new(100)
+    }
+}
+
+/// Applies a command and pushes it onto the undo stack.
+pub fn execute(world: &mut World, mut command: Box<dyn EditorCommand>) {
+    world.init_resource::<EditorHistory>();
+    command.apply(world);
+    let mut history = world.resource_mut::<EditorHistory>();
+    history.undo.push(command);
+    history.redo.clear();
+    let overflow = history.undo.len().saturating_sub(history.limit);
+    history.undo.drain(..overflow);
+}
+
+/// Reverts the last command. Returns false if there was nothing to undo.
+pub fn undo(world: &mut World) -> bool {
+    let Some(mut command) = world
+        .get_resource_mut::<EditorHistory>()
+        .and_then(|mut history| history.undo.pop())
+    else {
+        return false;
+    };
+    command.revert(world);
+    world.resource_mut::<EditorHistory>().redo.push(command);
+    true
+}
+
+/// Applies the last undone command again. Returns false if there was nothing to redo.
+pub fn redo(world: &mut World) -> bool {
+    let Some(mut command) = world
+        .get_resource_mut::<EditorHistory>()
+        .and_then(|mut history| history.redo.pop())
+    else {
+        return false;
+    };
+    command.apply(world);
+    world.resource_mut::<EditorHistory>().undo.push(command);
+    true
+}
+
+/// A [`Command`] that executes an [`EditorCommand`] through the [`EditorHistory`].
+pub struct ExecuteEditorCommand(pub Box<dyn EditorCommand>);
+
+impl ExecuteEditorCommand {
+    /// Creates a new [`ExecuteEditorCommand`].
+    pub fn new(command: impl EditorCommand) -> Self {
+        Self(Box::new(command))
+    }
+}
+
+impl Command for ExecuteEditorCommand {
+    fn write(self, world: &mut World) {
+        execute(world, self.0);
+    }
+}
+
+/// Finds the entity of an object placed by the editor.
+pub fn find_editor_object(world: &mut World, id: EditorId) -> Option<Entity> {
+    world
+        .query::<(Entity, &EditorId)>()
+        .iter(world)
+        .find(|(_, editor_id)| **editor_id == id)
+        .map(|(entity, _)| entity)
+}
+
+/// Spawns an object for the editor relative to the floating origin.
+///
+/// Returns `None` if the shape of the object cannot be built, see [`MapObject::spawn`].
+#[allow(clippy::type_complexity)]
+pub fn spawn_editor_object(world: &mut World, object: &MapObject, id: EditorId) -> Option<Entity> {
+    let origin = world
+        .get_resource::<FloatingOrigin>()
+        .copied()
+        .unwrap_or_default();
+    world.init_resource::<ShapeCache>();
+    world.resource_scope(|world, mut shapes: Mut<ShapeCache>| {
+        let mut state: SystemState<(
+            Commands,
+            ResMut<Assets<Mesh>>,
+            ResMut<Assets<StandardMaterial>>,
+        )> = SystemState::new(world);
+        let (mut commands, mut meshes, mut materials) = state.get_mut(world);
+        let entity = object.spawn(
+            &mut commands,
+            &mut shapes,
+            &mut meshes,
+            &mut materials,
+            &origin,
+        )?;
+        commands
+            .entity(entity)
+            .insert((id, EditorObject(object.clone())));
+        state.apply(world);
+        Some(entity)
+    })
+}
+
+/// Despawns an object placed by the editor and returns its description.
+fn despawn_editor_object(world: &mut World, id: EditorId) -> Option<MapObject> {
+    let entity = find_editor_object(world, id)?;
+    let object = world
+        .get::<EditorObject>(entity)
+        .map(|object| object.0.clone());
+    world.entity_mut(entity).despawn_recursive();
+    object
+}
+
+/// Places a new object in the map.
+#[derive(Debug, Clone)]
+pub struct PlaceObject {
+    /// The object to place.
+    pub object: MapObject,
+    id: Option<EditorId>,
+}
+
+impl PlaceObject {
+    /// Creates a new [`PlaceObject`] command.
+    pub fn new(object: MapObject) -> Self {
+        Self { object, id: None }
+    }
+
+    /// Places the object with a known identifier, so that later commands can refer to it.
+    pub fn with_id(mut self, id: EditorId) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// The identifier of the placed object, once the command has been applied.
+    pub fn id(&self) -> Option<EditorId> {
+        self.id
+    }
+}
+
+impl EditorCommand for PlaceObject {
+    fn label(&self) -> String {
+        "Place".to_string()
+    }
+
+    fn apply(&mut self, world: &mut World) {
+        // Redoing places the object with the same identifier, so later commands still find it.
+        let id = *self
+            .id
+            .get_or_insert_with(|| world.resource_mut::<EditorHistory>().next_id());
+        spawn_editor_object(world, &self.object, id);
+    }
+
+    fn revert(&mut self, world: &mut World) {
+        if let Some(id) = self.id {
+            despawn_editor_object(world, id);
+        }
+    }
+}
+
+/// Deletes an object placed by the editor.
+#[derive(Debug, Clone)]
+pub struct DeleteObject {
+    /// The object to delete.
+    pub id: EditorId,
+    deleted: Option<MapObject>,
+}
+
+impl DeleteObject {
+    /// Creates a new [`DeleteObject`] command.
+    pub fn new(id: EditorId) -> Self {
+        Self { id, deleted: None }
+    }
+}
+
+impl EditorCommand for DeleteObject {
+    fn label(&self) -> String {
+        "Delete".to_string()
+    }
+
+    fn apply(&mut self, world: &mut World) {
+        self.deleted = despawn_editor_object(world, self.id);
+    }
+
+    fn revert(&mut self, world: &mut World) {
+        if let Some(object) = self.deleted.take() {
+            spawn_editor_object(world, &object, self.id);
+        }
+    }
+}
+
+/// Moves an object placed by the editor.
+#[derive(Debug, Clone)]
+pub struct MoveObject {
+    /// The object to move.
+    pub id: EditorId,
+    /// Where the object is moved to.
+    pub to: MapTransform,
+    from: Option<MapTransform>,
+}
+
+impl MoveObject {
+    /// Creates a new [`MoveObject`] command.
+    pub fn new(id: EditorId, to: MapTransform) -> Self {
+        Self { id, to, from: None }
+    }
+
+    fn move_to(world: &mut World, id: EditorId, to: MapTransform) -> Option<MapTransform> {
+        let origin = world
+            .get_resource::<FloatingOrigin>()
+            .copied()
+            .unwrap_or_default();
+        let entity = find_editor_object(world, id)?;
+        let mut entity = world.entity_mut(entity);
+        let mut object = entity.get_mut::<EditorObject>()?;
+        let from = std::mem::replace(&mut object.0.transform, to);
+        if let Some(mut transform) = entity.get_mut::<Transform>() {
+            *transform = to.to_transform(&origin);
+        }
+        Some(from)
+    }
+}
+
+impl EditorCommand for MoveObject {
+    fn label(&self) -> String {
+        "Move".to_string()
+    }
+
+    fn apply(&mut self, world: &mut World) {
+        self.from = Self::move_to(world, self.id, self.to);
+    }
+
+    fn revert(&mut self, world: &mut World) {
+        if let Some(from) = self.from {
+            Self::move_to(world, self.id, from);
+        }
+    }
+}
+
+/// Changes the properties of an object placed by the editor by respawning it.
+#[derive(Debug, Clone)]
+pub struct ChangeObject {
+    /// The object to change.
+    pub id: EditorId,
+    /// The new description of the object.
+    pub object: MapObject,
+    previous: Option<MapObject>,
+}
+
+impl ChangeObject {
+    /// Creates a new [`ChangeObject`] command.
+    pub fn new(id: EditorId, object: MapObject) -> Self {
+        Self {
+            id,
+            object,
+            previous: None,
+        }
+    }
+}
+
+impl EditorCommand for ChangeObject {
+    fn label(&self) -> String {
+        "Change".to_string()
+    }
+
+    fn apply(&mut self, world: &mut World) {
+        self.previous = despawn_editor_object(world, self.id);
+        if self.previous.is_some() {
+            spawn_editor_object(world, &self.object, self.id);
+        }
+    }
+
+    fn revert(&mut self, world: &mut World) {
+        if let Some(previous) = self.previous.take() {
+            despawn_editor_object(world, self.id);
+            spawn_editor_object(world, &previous, self.id);
+        }
+    }
+}
+
+/// A plugin that undoes and redoes editing commands with the keyboard.
+pub struct EditorHistoryPlugin {
+    /// The most commands kept on the undo stack.
+    pub limit: usize,
+}
+
+impl EditorHistoryPlugin {
+    /// Creates a new [`EditorHistoryPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for EditorHistoryPlugin {
+    fn default() -> Self {
+        Self {
+            limit: EditorHistory::default().limit,
+        }
+    }
+}
+
+impl Plugin for EditorHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EditorHistory::new(self.limit))
+            .add_system(undo_redo_on_keys.with_run_criteria(is_editing));
+    }
+}
+
+/// Undoes with Ctrl+Z and redoes with Ctrl+Y or Ctrl+Shift+Z.
+pub fn undo_redo_on_keys(world: &mut World) {
+    let Some(keys) = world.get_resource::<Input<KeyCode>>() else {
+        return;
+    };
+    if !keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
+        return;
+    }
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    if keys.just_pressed(KeyCode::Y) || (shift && keys.just_pressed(KeyCode::Z)) {
+        redo(world);
+    } else if keys.just_pressed(KeyCode::Z) {
+        undo(world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct Counter(i32);
+
+    struct Add(i32);
+
+    impl EditorCommand for Add {
+        fn label(&self) -> String {
+            format!("Add {}", self.0)
+        }
+
+        fn apply(&mut self, world: &mut World) {
+            world.resource_mut::<Counter>().0 += self.0;
+        }
+
+        fn revert(&mut self, world: &mut World) {
+            world.resource_mut::<Counter>().0 -= self.0;
+        }
+    }
+
+    #[test]
+    fn history_undoes_and_redoes_commands() {
+        let mut world = World::new();
+        world.init_resource::<Counter>();
+
+        execute(&mut world, Box::new(Add(1)));
+        execute(&mut world, Box::new(Add(2)));
+        assert_eq!(world.resource::<Counter>().0, 3);
+
+        assert!(undo(&mut world));
+        assert_eq!(world.resource::<Counter>().0, 1);
+        assert_eq!(
+            world.resource::<EditorHistory>().redo_label().as_deref(),
+            Some("Add 2")
+        );
+
+        assert!(redo(&mut world));
+        assert_eq!(world.resource::<Counter>().0, 3);
+        assert!(!redo(&mut world));
+    }
+
+    #[test]
+    fn history_clears_the_redo_stack_on_execute() {
+        let mut world = World::new();
+        world.init_resource::<Counter>();
+
+        execute(&mut world, Box::new(Add(1)));
+        undo(&mut world);
+        execute(&mut world, Box::new(Add(5)));
+
+        assert!(!world.resource::<EditorHistory>().can_redo());
+        assert_eq!(world.resource::<Counter>().0, 5);
+    }
+
+    #[test]
+    fn history_forgets_commands_past_its_limit() {
+        let mut world = World::new();
+        world.init_resource::<Counter>();
+        world.insert_resource(EditorHistory::new(2));
+
+        for _ in 0..3 {
+            execute(&mut world, Box::new(Add(1)));
+        }
+        while undo(&mut world) {}
+
+        assert_eq!(world.resource::<Counter>().0, 1);
+    }
+}
@@ -0,0 +1,7 @@
+//! A mod with the building blocks of a runtime map editor.
+//!
+//! Editing happens in [`MapBuilderState::Editing`](crate::state::MapBuilderState::Editing).
+//! Every change made to the map goes through the [`history`] so that it can be undone and redone.
+
+/// A mod that records editing commands so that they can be undone and redone.
+pub mod history;
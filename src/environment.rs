@@ -0,0 +1,378 @@
+//! A mod that animates the lighting and sky over the course of a day.
+//!
+//! The [`DayNightCyclePlugin`] advances the [`TimeOfDay`] resource and uses it to move every
+//! [`Sun`] across the sky. The color and brightness of the sun, the ambient light, and the sky
+//! are interpolated between the [`DayNightKey`]s of the [`DayNightSettings`]. The sky is drawn on
+//! a procedural dome around the camera, or only as the clear color when the dome is disabled.
+//!
+//! The time of day is a resource so maps and games can set the starting conditions, or pause the
+//! cycle to keep a fixed time.
+
+use crate::{map::minimap::*, state::*};
+
+use bevy::{
+    prelude::*,
+    render::{camera::CameraRenderGraph, mesh::VertexAttributeValues},
+};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// The current time of day.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeOfDay {
+    /// The hour of the day in `[0, 24)`, where 12 is noon.
+    pub hour: f32,
+    /// Whether the time stays still.
+    pub paused: bool,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self {
+            hour: 9.0,
+            paused: false,
+        }
+    }
+}
+
+impl TimeOfDay {
+    /// Creates a running time of day at the given hour.
+    pub fn new(hour: f32) -> Self {
+        Self {
+            hour: hour.rem_euclid(24.0),
+            paused: false,
+        }
+    }
+
+    /// Creates a paused time of day at the given hour.
+    pub fn fixed(hour: f32) -> Self {
+        Self {
+            paused: true,
+            ..Self::new(hour)
+        }
+    }
+
+    /// Whether the sun is above the horizon.
+    pub fn is_day(&self) -> bool {
+        (6.0..18.0).contains(&self.hour)
+    }
+}
+
+/// A marker for the directional lights moved and colored by the day/night cycle.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct Sun;
+
+/// A marker for the procedural sky dome.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct SkyDome;
+
+/// The lighting at a given hour of the day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DayNightKey {
+    /// The hour of the day of the key.
+    pub hour: f32,
+    /// The color of the sun.
+    pub sun_color: Color,
+    /// The illuminance of the sun in lux.
+    pub sun_illuminance: f32,
+    /// The color of the ambient light.
+    pub ambient_color: Color,
+    /// The brightness of the ambient light.
+    pub ambient_brightness: f32,
+    /// The color of the sky at the horizon.
+    pub horizon_color: Color,
+    /// The color of the sky straight up.
+    pub zenith_color: Color,
+}
+
+impl DayNightKey {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            hour: self.hour + t * (other.hour - self.hour),
+            sun_color: lerp_color(self.sun_color, other.sun_color, t),
+            sun_illuminance: self.sun_illuminance
+                + t * (other.sun_illuminance - self.sun_illuminance),
+            ambient_color: lerp_color(self.ambient_color, other.ambient_color, t),
+            ambient_brightness: self.ambient_brightness
+                + t * (other.ambient_brightness - self.ambient_brightness),
+            horizon_color: lerp_color(self.horizon_color, other.horizon_color, t),
+            zenith_color: lerp_color(self.zenith_color, other.zenith_color, t),
+        }
+    }
+}
+
+fn lerp_color(a: Color, b: Color, t: f32) -> Color {
+    let a = Vec4::from(a.as_linear_rgba_f32());
+    let b = Vec4::from(b.as_linear_rgba_f32());
+    let [r, g, b, alpha] = a.lerp(b, t).to_array();
+    Color::rgba_linear(r, g, b, alpha)
+}
+
+/// Settings for the [`DayNightCyclePlugin`].
+#[derive(Resource, Debug, Clone)]
+pub struct DayNightSettings {
+    /// How many seconds a whole day lasts.
+    pub day_length: f32,
+    /// How far the path of the sun is tilted away from straight overhead, in radians.
+    pub sun_tilt: f32,
+    /// The lighting over the day, sorted by hour. The lighting wraps around from the last key
+    /// back to the first.
+    pub keys: Vec<DayNightKey>,
+    /// Whether to draw the procedural sky dome.
+    pub sky_dome: bool,
+    /// The radius of the sky dome. It must be smaller than the far plane of the cameras.
+    pub sky_dome_radius: f32,
+}
+
+impl Default for DayNightSettings {
+    fn default() -> Self {
+        let night = DayNightKey {
+            hour: 0.0,
+            sun_color: Color::rgb(0.3, 0.35, 0.6),
+            sun_illuminance: 0.0,
+            ambient_color: Color::rgb(0.3, 0.35, 0.6),
+            ambient_brightness: 0.05,
+            horizon_color: Color::rgb(0.04, 0.05, 0.1),
+            zenith_color: Color::rgb(0.0, 0.0, 0.03),
+        };
+        let sunrise = DayNightKey {
+            hour: 6.0,
+            sun_color: Color::rgb(1.0, 0.6, 0.3),
+            sun_illuminance: 10000.0,
+            ambient_color: Color::rgb(0.9, 0.7, 0.6),
+            ambient_brightness: 0.15,
+            horizon_color: Color::rgb(0.95, 0.55, 0.3),
+            zenith_color: Color::rgb(0.2, 0.3, 0.6),
+        };
+        let noon = DayNightKey {
+            hour: 12.0,
+            sun_color: Color::rgb(1.0, 0.98, 0.95),
+            sun_illuminance: 100000.0,
+            ambient_color: Color::WHITE,
+            ambient_brightness: 0.3,
+            horizon_color: Color::rgb(0.7, 0.85, 1.0),
+            zenith_color: Color::rgb(0.25, 0.5, 0.9),
+        };
+
+        Self {
+            day_length: 600.0,
+            sun_tilt: 0.4,
+            keys: vec![
+                night,
+                sunrise,
+                noon,
+                DayNightKey {
+                    hour: 18.0,
+                    ..sunrise
+                },
+            ],
+            sky_dome: true,
+            sky_dome_radius: 900.0,
+        }
+    }
+}
+
+impl DayNightSettings {
+    /// Interpolates the lighting at an hour of the day.
+    pub fn sample(&self, hour: f32) -> Option<DayNightKey> {
+        let first = self.keys.first()?;
+        let last = self.keys.last()?;
+        let hour = hour.rem_euclid(24.0);
+
+        let next = self.keys.iter().position(|key| key.hour > hour);
+        let (from, to) = match next {
+            Some(0) | None => {
+                // Wrap around midnight.
+                let to = DayNightKey {
+                    hour: first.hour + 24.0,
+                    ..*first
+                };
+                (*last, to)
+            }
+            Some(i) => (self.keys[i - 1], self.keys[i]),
+        };
+
+        let hour = if hour < from.hour { hour + 24.0 } else { hour };
+        let span = to.hour - from.hour;
+        let t = if span > 0.0 {
+            (hour - from.hour) / span
+        } else {
+            0.0
+        };
+        Some(from.lerp(&to, t.clamp(0.0, 1.0)))
+    }
+
+    /// The direction from the ground towards the sun at an hour of the day.
+    ///
+    /// The sun rises in the east (+X) at 6, is highest at 12, and sets in the west at 18.
+    pub fn sun_direction(&self, hour: f32) -> Vec3 {
+        let angle = (hour - 6.0) / 12.0 * PI;
+        Quat::from_rotation_x(-self.sun_tilt) * Vec3::new(angle.cos(), angle.sin(), 0.0)
+    }
+}
+
+/// A plugin that animates the sun, ambient light, and sky over the course of a day.
+#[derive(Default)]
+pub struct DayNightCyclePlugin {
+    /// The settings used by the plugin.
+    pub settings: DayNightSettings,
+    /// The time of day when the app starts.
+    pub start: TimeOfDay,
+}
+
+impl DayNightCyclePlugin {
+    /// Creates a new [`DayNightCyclePlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how many seconds a whole day lasts.
+    pub fn with_day_length(mut self, day_length: f32) -> Self {
+        self.settings.day_length = day_length;
+        self
+    }
+
+    /// Sets the time of day when the app starts.
+    pub fn with_time_of_day(mut self, start: TimeOfDay) -> Self {
+        self.start = start;
+        self
+    }
+}
+
+impl Plugin for DayNightCyclePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone())
+            .insert_resource(self.start)
+            .add_startup_system(spawn_sky_dome)
+            .add_system(advance_time_of_day.with_run_criteria(is_playing))
+            .add_system(update_day_night_lighting.after(advance_time_of_day))
+            .add_system(update_sky.after(advance_time_of_day));
+    }
+}
+
+/// Advances the time of day unless it is paused.
+pub fn advance_time_of_day(
+    time: Res<Time>,
+    settings: Res<DayNightSettings>,
+    mut time_of_day: ResMut<TimeOfDay>,
+) {
+    if time_of_day.paused || settings.day_length <= 0.0 {
+        return;
+    }
+    let hours = 24.0 * time.delta_seconds() / settings.day_length;
+    time_of_day.hour = (time_of_day.hour + hours).rem_euclid(24.0);
+}
+
+/// Moves and colors every [`Sun`] and the ambient light for the time of day.
+pub fn update_day_night_lighting(
+    settings: Res<DayNightSettings>,
+    time_of_day: Res<TimeOfDay>,
+    mut ambient_light: ResMut<AmbientLight>,
+    mut suns: Query<(&mut DirectionalLight, &mut Transform), With<Sun>>,
+) {
+    let Some(key) = settings.sample(time_of_day.hour) else {
+        return;
+    };
+
+    let direction = settings.sun_direction(time_of_day.hour);
+    for (mut light, mut transform) in &mut suns {
+        // Directional lights shine along their forward (-Z) axis.
+        transform.rotation = Quat::from_rotation_arc(Vec3::NEG_Z, -direction);
+        light.color = key.sun_color;
+        // The sun cannot light anything once it is below the horizon.
+        light.illuminance = if direction.y > 0.0 {
+            key.sun_illuminance
+        } else {
+            0.0
+        };
+    }
+
+    ambient_light.color = key.ambient_color;
+    ambient_light.brightness = key.ambient_brightness;
+}
+
+/// Spawns the procedural sky dome if it is enabled.
+pub fn spawn_sky_dome(
+    mut commands: Commands,
+    settings: Res<DayNightSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !settings.sky_dome {
+        return;
+    }
+
+    let mut mesh = Mesh::from(shape::UVSphere {
+        radius: settings.sky_dome_radius,
+        sectors: 32,
+        stacks: 16,
+    });
+    let vertex_count = mesh.count_vertices();
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, vec![[1.0; 4]; vertex_count]);
+
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(mesh),
+            material: materials.add(StandardMaterial {
+                unlit: true,
+                // The dome is seen from the inside.
+                cull_mode: None,
+                ..default()
+            }),
+            ..default()
+        },
+        bevy::pbr::NotShadowCaster,
+        SkyDome,
+        Name::new("Sky Dome"),
+    ));
+}
+
+/// Colors the sky for the time of day and keeps the sky dome centered on the camera.
+#[allow(clippy::type_complexity)]
+pub fn update_sky(
+    settings: Res<DayNightSettings>,
+    time_of_day: Res<TimeOfDay>,
+    mut clear_color: ResMut<ClearColor>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    cameras: Query<(&Camera, &GlobalTransform), (With<CameraRenderGraph>, Without<MinimapCamera>)>,
+    mut domes: Query<(&Handle<Mesh>, &mut Transform), With<SkyDome>>,
+) {
+    let Some(key) = settings.sample(time_of_day.hour) else {
+        return;
+    };
+    clear_color.0 = key.horizon_color;
+
+    let camera_position = cameras
+        .iter()
+        .filter(|(camera, _)| camera.is_active)
+        .min_by_key(|(camera, _)| camera.priority)
+        .map(|(_, transform)| transform.translation());
+
+    for (handle, mut transform) in &mut domes {
+        if let Some(camera_position) = camera_position {
+            transform.translation = camera_position;
+        }
+
+        if !time_of_day.is_changed() {
+            continue;
+        }
+        let Some(mesh) = meshes.get_mut(handle) else {
+            continue;
+        };
+        let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+        else {
+            continue;
+        };
+        let colors: Vec<[f32; 4]> = normals
+            .iter()
+            .map(|normal| {
+                // Blend quickly away from the horizon, which looks closer to a real sky.
+                let t = normal[1].max(0.0).sqrt();
+                lerp_color(key.horizon_color, key.zenith_color, t).as_linear_rgba_f32()
+            })
+            .collect();
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    }
+}
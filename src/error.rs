@@ -0,0 +1,106 @@
+//! A mod with the errors of the crate.
+//!
+//! Every fallible public API, from reading maps and prefabs to importing meshes, exporting
+//! colliders, and saving snapshots and replays, returns a [`MapBuilderError`]. Systems that
+//! fail while handling a request, such as a request to load a snapshot, send a
+//! [`MapBuilderErrorEvent`] so the app can show the failure to the player.
+
+use bevy::{prelude::*, render::render_resource::TextureFormat};
+use std::io;
+use thiserror::Error;
+
+/// An error of the crate.
+#[derive(Debug, Error)]
+pub enum MapBuilderError {
+    /// A file could not be read or written.
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    /// RON could not be read.
+    #[cfg(feature = "ron")]
+    #[error("invalid RON: {0}")]
+    RonRead(#[from] ron::error::SpannedError),
+    /// RON could not be written.
+    #[cfg(feature = "ron")]
+    #[error("could not write RON: {0}")]
+    RonWrite(#[from] ron::Error),
+    /// JSON could not be read or written.
+    #[cfg(feature = "export")]
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    /// An imported file could not be parsed.
+    #[error("invalid {format}: {message}")]
+    Parse {
+        /// The format of the file, such as `"OBJ"`.
+        format: &'static str,
+        /// What was wrong with the file.
+        message: String,
+    },
+    /// A file was written with a version of its format that cannot be read.
+    #[error("{kind} version {found} is not supported (expected {expected})")]
+    UnsupportedVersion {
+        /// What the file holds, such as `"snapshot"`.
+        kind: &'static str,
+        /// The version of the file.
+        found: u32,
+        /// The version that can be read.
+        expected: u32,
+    },
+    /// A heightmap image has a format that heights cannot be read from.
+    #[error("heightmaps cannot be read from {0:?} images")]
+    UnsupportedImageFormat(TextureFormat),
+    /// A shape cannot be built from its parameters.
+    #[error("invalid {shape}: {message}")]
+    InvalidShape {
+        /// The kind of shape, such as `"heightfield"`.
+        shape: &'static str,
+        /// What was wrong with the parameters.
+        message: String,
+    },
+    /// No collider could be created for a mesh.
+    #[error("could not create a collider for the mesh")]
+    NoCollider,
+    /// A recording was stopped while nothing was being recorded.
+    #[error("not recording")]
+    NotRecording,
+}
+
+impl MapBuilderError {
+    /// Creates a new [`MapBuilderError::Parse`].
+    pub fn parse(format: &'static str, message: impl Into<String>) -> Self {
+        Self::Parse {
+            format,
+            message: message.into(),
+        }
+    }
+}
+
+/// A result with a [`MapBuilderError`].
+pub type Result<T, E = MapBuilderError> = std::result::Result<T, E>;
+
+/// An event sent when a system could not do what it was asked to.
+#[derive(Debug)]
+pub struct MapBuilderErrorEvent {
+    /// What the system was doing, such as `"saving snapshot saves/1.ron"`.
+    pub operation: String,
+    /// What went wrong.
+    pub error: MapBuilderError,
+}
+
+impl MapBuilderErrorEvent {
+    /// Creates a new [`MapBuilderErrorEvent`].
+    pub fn new(operation: impl Into<String>, error: MapBuilderError) -> Self {
+        Self {
+            operation: operation.into(),
+            error,
+        }
+    }
+}
+
+/// Logs an error and sends it as a [`MapBuilderErrorEvent`], if the event has been added.
+pub fn report_error(world: &mut World, operation: impl Into<String>, error: MapBuilderError) {
+    let event = MapBuilderErrorEvent::new(operation, error);
+    error!("Could not finish {}: {}", event.operation, event.error);
+    if let Some(mut events) = world.get_resource_mut::<Events<MapBuilderErrorEvent>>() {
+        events.send(event);
+    }
+}
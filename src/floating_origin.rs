@@ -0,0 +1,128 @@
+//! A mod that keeps very large worlds precise by periodically moving the origin.
+//!
+//! Bevy and Rapier both store positions as `f32`, which loses precision the farther an entity is
+//! from the origin. The [`FloatingOriginPlugin`] watches a [`FloatingOriginAnchor`] (usually the
+//! active camera or player) and, whenever it strays beyond a threshold from the origin, shifts
+//! every root entity and every Rapier body back towards the origin. The accumulated shift is kept
+//! in double precision in the [`FloatingOrigin`] resource so world positions can still be
+//! recovered.
+
+use bevy::{math::DVec3, prelude::*, transform::TransformSystem};
+use bevy_rapier3d::{na::Vector3, prelude::*, rapier::prelude::RigidBodyType};
+
+/// The position of the local (rendered) origin in world space.
+#[derive(Resource, Debug, Clone, Copy, Default)]
+pub struct FloatingOrigin {
+    /// The world position of the local origin.
+    pub offset: DVec3,
+}
+
+impl FloatingOrigin {
+    /// Converts a world position into a position relative to the local origin.
+    pub fn world_to_local(&self, world: DVec3) -> Vec3 {
+        (world - self.offset).as_vec3()
+    }
+
+    /// Converts a position relative to the local origin into a world position.
+    pub fn local_to_world(&self, local: Vec3) -> DVec3 {
+        self.offset + local.as_dvec3()
+    }
+}
+
+/// The entity that the origin follows.
+///
+/// Only one anchor should exist at a time. If there are several, the first one found is used.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct FloatingOriginAnchor;
+
+/// An event sent every time the origin is moved.
+#[derive(Debug, Clone, Copy)]
+pub struct FloatingOriginShifted {
+    /// The translation that has been subtracted from every root entity.
+    pub shift: Vec3,
+}
+
+/// Settings for the [`FloatingOriginPlugin`].
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct FloatingOriginSettings {
+    /// How far the anchor may stray from the origin before the origin is moved.
+    pub threshold: f32,
+}
+
+impl Default for FloatingOriginSettings {
+    fn default() -> Self {
+        Self { threshold: 1000.0 }
+    }
+}
+
+/// A plugin that moves the origin to follow the [`FloatingOriginAnchor`].
+#[derive(Default)]
+pub struct FloatingOriginPlugin {
+    /// The settings used by the plugin.
+    pub settings: FloatingOriginSettings,
+}
+
+impl FloatingOriginPlugin {
+    /// Creates a new [`FloatingOriginPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`FloatingOriginPlugin`] that moves the origin once the anchor is further
+    /// than `threshold` from it.
+    pub fn with_threshold(threshold: f32) -> Self {
+        Self {
+            settings: FloatingOriginSettings { threshold },
+        }
+    }
+}
+
+impl Plugin for FloatingOriginPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings)
+            .init_resource::<FloatingOrigin>()
+            .add_event::<FloatingOriginShifted>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                rebase_floating_origin.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Moves every root entity and Rapier body so that the anchor is back near the origin.
+pub fn rebase_floating_origin(
+    settings: Res<FloatingOriginSettings>,
+    mut origin: ResMut<FloatingOrigin>,
+    mut rapier_context: ResMut<RapierContext>,
+    mut events: EventWriter<FloatingOriginShifted>,
+    anchors: Query<&GlobalTransform, With<FloatingOriginAnchor>>,
+    mut roots: Query<&mut Transform, Without<Parent>>,
+) {
+    let Some(anchor) = anchors.iter().next() else {
+        return;
+    };
+
+    let shift = anchor.translation();
+    if shift.length() < settings.threshold {
+        return;
+    }
+
+    for mut transform in &mut roots {
+        transform.translation -= shift;
+    }
+
+    // Move the Rapier bodies at the same time as their transforms. Otherwise kinematic bodies
+    // would be given a huge velocity to reach their new position during the next step.
+    let physics_shift = Vector3::from(shift / rapier_context.physics_scale());
+    for (_, body) in rapier_context.bodies.iter_mut() {
+        let mut position = *body.position();
+        position.translation.vector -= physics_shift;
+        body.set_position(position, true);
+        if body.body_type() == RigidBodyType::KinematicPositionBased {
+            body.set_next_kinematic_position(position);
+        }
+    }
+
+    origin.offset += shift.as_dvec3();
+    events.send(FloatingOriginShifted { shift });
+}
@@ -0,0 +1,197 @@
+//! A mod for freezing entities in place and letting them go again exactly as they were.
+//!
+//! Pause menus, cutscenes, and photo mode stop some entities while the rest of the world keeps
+//! going. [`FreezeExt::freeze_entities`] gives each entity a [`Frozen`] that remembers its
+//! [`Velocity`], [`CustomVelocity`], and [`Sleeping`] state, stops it, and puts dynamic bodies to
+//! sleep. [`FreezeExt::unfreeze_entities`] puts all of that back.
+//!
+//! While an entity is frozen, the movement queued on its [`KinematicCharacterController`] is
+//! dropped before Rapier sees it, its velocities are kept at zero, and a dynamic body that is
+//! woken by something bumping into it is put back to sleep. Controller and AI systems that should
+//! not even run for frozen entities, such as steering, skip entities with a [`Frozen`].
+
+use crate::controller::*;
+
+use bevy::{ecs::system::Command, prelude::*};
+use bevy_rapier3d::prelude::*;
+
+/// The state of a frozen entity from before it was frozen.
+///
+/// Removing this component by hand lets the entity move again without restoring its state.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Frozen {
+    /// The [`Velocity`] of the entity, if it had one.
+    pub velocity: Option<Velocity>,
+    /// The [`CustomVelocity`] of the entity, if it had one.
+    pub custom_velocity: Option<Vec3>,
+    /// The [`Sleeping`] of the entity, if it had one.
+    pub sleeping: Option<Sleeping>,
+}
+
+/// The stage that holds frozen entities still, right before Rapier syncs with the world.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+pub struct FreezeStage;
+
+/// A plugin that holds [`Frozen`] entities still.
+///
+/// The entities are only held still when the [`RapierPhysicsPlugin`] was added first with its
+/// default stages.
+#[derive(Default)]
+pub struct FreezePlugin;
+
+impl FreezePlugin {
+    /// Creates a new [`FreezePlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for FreezePlugin {
+    fn build(&self, app: &mut App) {
+        if app
+            .schedule
+            .get_stage::<SystemStage>(PhysicsStages::SyncBackend)
+            .is_some()
+        {
+            app.add_stage_before(
+                PhysicsStages::SyncBackend,
+                FreezeStage,
+                SystemStage::single(hold_frozen_entities),
+            );
+        }
+    }
+}
+
+/// A command that freezes entities.
+///
+/// Entities that are already frozen keep the state they were first frozen with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FreezeEntities(pub Vec<Entity>);
+
+impl Command for FreezeEntities {
+    fn write(self, world: &mut World) {
+        for entity in self.0 {
+            let Some(mut entity) = world.get_entity_mut(entity) else {
+                continue;
+            };
+            if entity.contains::<Frozen>() {
+                continue;
+            }
+
+            let frozen = Frozen {
+                velocity: entity.get::<Velocity>().copied(),
+                custom_velocity: entity.get::<CustomVelocity>().map(|velocity| velocity.0),
+                sleeping: entity.get::<Sleeping>().copied(),
+            };
+            if let Some(mut velocity) = entity.get_mut::<Velocity>() {
+                *velocity = Velocity::zero();
+            }
+            if let Some(mut velocity) = entity.get_mut::<CustomVelocity>() {
+                velocity.0 = Vec3::ZERO;
+            }
+            if let Some(mut controller) = entity.get_mut::<KinematicCharacterController>() {
+                controller.translation = None;
+            }
+            if entity.get::<RigidBody>() == Some(&RigidBody::Dynamic) {
+                entity.insert(Sleeping {
+                    sleeping: true,
+                    ..frozen.sleeping.unwrap_or_default()
+                });
+            }
+            entity.insert(frozen);
+        }
+    }
+}
+
+/// A command that lets frozen entities go with the state they had before they were frozen.
+///
+/// Entities that are not frozen are left alone.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnfreezeEntities(pub Vec<Entity>);
+
+impl Command for UnfreezeEntities {
+    fn write(self, world: &mut World) {
+        for entity in self.0 {
+            let Some(mut entity) = world.get_entity_mut(entity) else {
+                continue;
+            };
+            let Some(frozen) = entity.remove::<Frozen>() else {
+                continue;
+            };
+
+            if let (Some(mut velocity), Some(previous)) =
+                (entity.get_mut::<Velocity>(), frozen.velocity)
+            {
+                *velocity = previous;
+            }
+            if let (Some(mut velocity), Some(previous)) =
+                (entity.get_mut::<CustomVelocity>(), frozen.custom_velocity)
+            {
+                velocity.0 = previous;
+            }
+            // Rapier's own default is the same as the default of the component, so a body that had
+            // none is woken up with the thresholds it had.
+            if entity.contains::<Sleeping>() {
+                entity.insert(frozen.sleeping.unwrap_or_default());
+            }
+        }
+    }
+}
+
+/// An extension to [`Commands`] for freezing entities.
+pub trait FreezeExt {
+    /// Stops entities and holds them still until they are unfrozen.
+    fn freeze_entities(&mut self, entities: impl IntoIterator<Item = Entity>);
+
+    /// Lets frozen entities go with the state they had before they were frozen.
+    fn unfreeze_entities(&mut self, entities: impl IntoIterator<Item = Entity>);
+}
+
+impl<'w, 's> FreezeExt for Commands<'w, 's> {
+    fn freeze_entities(&mut self, entities: impl IntoIterator<Item = Entity>) {
+        self.add(FreezeEntities(entities.into_iter().collect()));
+    }
+
+    fn unfreeze_entities(&mut self, entities: impl IntoIterator<Item = Entity>) {
+        self.add(UnfreezeEntities(entities.into_iter().collect()));
+    }
+}
+
+/// Keeps every [`Frozen`] entity from moving, whatever other systems asked of it this frame.
+#[allow(clippy::type_complexity)]
+pub fn hold_frozen_entities(
+    mut entities: Query<
+        (
+            Option<&RigidBody>,
+            Option<&mut Velocity>,
+            Option<&mut CustomVelocity>,
+            Option<&mut KinematicCharacterController>,
+            Option<&mut Sleeping>,
+        ),
+        With<Frozen>,
+    >,
+) {
+    for (rigid_body, velocity, custom_velocity, controller, sleeping) in &mut entities {
+        // Only write what changed, so that Rapier is not told about the same state every frame.
+        if let Some(mut velocity) = velocity {
+            if *velocity != Velocity::zero() {
+                *velocity = Velocity::zero();
+            }
+        }
+        if let Some(mut velocity) = custom_velocity {
+            if velocity.0 != Vec3::ZERO {
+                velocity.0 = Vec3::ZERO;
+            }
+        }
+        if let Some(mut controller) = controller {
+            if controller.translation.is_some() {
+                controller.translation = None;
+            }
+        }
+        if let (Some(RigidBody::Dynamic), Some(mut sleeping)) = (rigid_body, sleeping) {
+            if !sleeping.sleeping {
+                sleeping.sleeping = true;
+            }
+        }
+    }
+}
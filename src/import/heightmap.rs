@@ -0,0 +1,115 @@
+//! A mod that creates terrain from heightmap images.
+//!
+//! The first channel of every pixel is used as the height, where black is the bottom of the
+//! terrain and white is the top. Rows of the image run along the Z axis and columns along the X
+//! axis.
+
+use super::*;
+use crate::{error::*, rapier_mesh_bundles::*};
+
+use bevy::render::render_resource::TextureFormat;
+
+/// Reads the heights of a heightmap image in the column-major order used by heightfields.
+///
+/// Returns the heights normalized to `[0, 1]` along with the number of rows and columns, or an
+/// error if the image format is not supported.
+pub fn heights_from_image(image: &Image) -> Result<(Vec<f32>, usize, usize)> {
+    let size = image.texture_descriptor.size;
+    let (num_cols, num_rows) = (size.width as usize, size.height as usize);
+
+    let read_u16 = |offset: usize| {
+        u16::from_ne_bytes([image.data[offset], image.data[offset + 1]]) as f32 / u16::MAX as f32
+    };
+    let pixel: Box<dyn Fn(usize) -> f32> = match image.texture_descriptor.format {
+        TextureFormat::R8Unorm => Box::new(|i| image.data[i] as f32 / 255.0),
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => {
+            Box::new(|i| image.data[4 * i] as f32 / 255.0)
+        }
+        TextureFormat::R16Uint | TextureFormat::R16Unorm => Box::new(move |i| read_u16(2 * i)),
+        TextureFormat::Rg16Uint => Box::new(move |i| read_u16(4 * i)),
+        TextureFormat::Rgba16Uint => Box::new(move |i| read_u16(8 * i)),
+        format => return Err(MapBuilderError::UnsupportedImageFormat(format)),
+    };
+
+    let mut heights = Vec::with_capacity(num_rows * num_cols);
+    for col in 0..num_cols {
+        for row in 0..num_rows {
+            heights.push(pixel(row * num_cols + col));
+        }
+    }
+
+    Ok((heights, num_rows, num_cols))
+}
+
+/// Creates a terrain collider and mesh from a heightmap image.
+///
+/// `size` is the size of the terrain along each axis in the units of the source convention, so
+/// `size.y` is the height of a white pixel. Left-handed conventions mirror the terrain along Z.
+/// Returns an error if the image format is not supported or the image is smaller than 2x2 pixels.
+pub fn heightfield_from_image(
+    image: &Image,
+    size: Vec3,
+    convention: &AxisConvention,
+    meshes: &mut ResMut<Assets<Mesh>>,
+) -> Result<RapierShapeBundle> {
+    let (mut heights, num_rows, num_cols) = heights_from_image(image)?;
+    if num_rows < 2 || num_cols < 2 {
+        return Err(MapBuilderError::InvalidShape {
+            shape: "heightfield",
+            message: format!("{num_cols}x{num_rows} image, at least 2x2 pixels are needed"),
+        });
+    }
+
+    if convention.flips_winding() {
+        for column in heights.chunks_exact_mut(num_rows) {
+            column.reverse();
+        }
+    }
+
+    Ok(RapierShapeBundle::heightfield(
+        heights,
+        num_rows,
+        num_cols,
+        convention.unit_scale * size,
+        meshes,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::{
+        ecs::system::SystemState,
+        render::render_resource::{Extent3d, TextureDimension},
+    };
+
+    fn image(width: u32, height: u32) -> Image {
+        Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            vec![0; (width * height) as usize],
+            TextureFormat::R8Unorm,
+        )
+    }
+
+    #[test]
+    fn heightmap_thinner_than_two_pixels_is_rejected() {
+        let mut app = App::new();
+        app.add_plugin(AssetPlugin::default()).add_asset::<Mesh>();
+        let mut meshes: SystemState<ResMut<Assets<Mesh>>> = SystemState::new(&mut app.world);
+        let mut meshes = meshes.get_mut(&mut app.world);
+        let convention = AxisConvention::default();
+
+        for image in [image(1, 4), image(4, 1)] {
+            assert!(matches!(
+                heightfield_from_image(&image, Vec3::ONE, &convention, &mut meshes),
+                Err(MapBuilderError::InvalidShape { .. })
+            ));
+        }
+        assert!(heightfield_from_image(&image(2, 3), Vec3::ONE, &convention, &mut meshes).is_ok());
+    }
+}
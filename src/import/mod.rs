@@ -0,0 +1,357 @@
+//! A mod for bringing content authored in other tools into a map.
+//!
+//! Every DCC tool has its own idea of which axis points up, whether the coordinate system is
+//! left- or right-handed, and what a unit is. An [`AxisConvention`] describes the conventions of
+//! the source content so it can be converted into Bevy's right-handed, Y-up, meter-based space.
+//! Meshes can be converted directly with [`AxisConvention::convert_mesh`], while whole glTF scenes
+//! are converted by adding an [`ImportConvention`] to their root entity.
+//!
+//! For props that do not need a full glTF pipeline, OBJ and STL files can be turned straight into
+//! a [`RapierShapeBundle`] with [`import_obj`] and [`import_stl`].
+
+/// A mod that creates terrain from heightmap images.
+pub mod heightmap;
+
+/// A mod that reads Wavefront OBJ meshes.
+pub mod obj;
+
+/// A mod that reads STL meshes.
+pub mod stl;
+
+use crate::{error::*, rapier_mesh_bundles::*};
+
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, VertexAttributeValues},
+    scene::{SceneInstance, SceneSpawner},
+    utils::HashSet,
+};
+use bevy_rapier3d::prelude::*;
+use std::path::Path;
+
+/// The axis that points up in the source content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UpAxis {
+    /// The Y axis points up, like in Bevy.
+    #[default]
+    Y,
+    /// The Z axis points up.
+    Z,
+}
+
+/// The handedness of the source content's coordinate system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Handedness {
+    /// A right-handed coordinate system, like in Bevy.
+    #[default]
+    Right,
+    /// A left-handed coordinate system.
+    Left,
+}
+
+/// The axis conventions used by the source of imported content.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisConvention {
+    /// The axis that points up.
+    pub up: UpAxis,
+    /// The handedness of the coordinate system.
+    pub handedness: Handedness,
+    /// The size of one source unit in meters.
+    pub unit_scale: f32,
+}
+
+impl Default for AxisConvention {
+    fn default() -> Self {
+        Self::BEVY
+    }
+}
+
+impl AxisConvention {
+    /// Bevy's own conventions: right-handed, Y-up, and in meters.
+    pub const BEVY: Self = Self {
+        up: UpAxis::Y,
+        handedness: Handedness::Right,
+        unit_scale: 1.0,
+    };
+
+    /// The conventions used by Blender: right-handed, Z-up, and in meters.
+    pub const BLENDER: Self = Self {
+        up: UpAxis::Z,
+        handedness: Handedness::Right,
+        unit_scale: 1.0,
+    };
+
+    /// The conventions used by Unity: left-handed, Y-up, and in meters.
+    pub const UNITY: Self = Self {
+        up: UpAxis::Y,
+        handedness: Handedness::Left,
+        unit_scale: 1.0,
+    };
+
+    /// The conventions used by Unreal Engine: left-handed, Z-up, and in centimeters.
+    pub const UNREAL: Self = Self {
+        up: UpAxis::Z,
+        handedness: Handedness::Left,
+        unit_scale: 0.01,
+    };
+
+    /// Creates a new [`AxisConvention`].
+    pub fn new(up: UpAxis, handedness: Handedness, unit_scale: f32) -> Self {
+        Self {
+            up,
+            handedness,
+            unit_scale,
+        }
+    }
+
+    /// The mirroring applied to convert a left-handed system into a right-handed one.
+    fn mirror(&self) -> Vec3 {
+        match (self.handedness, self.up) {
+            (Handedness::Right, _) => Vec3::ONE,
+            (Handedness::Left, UpAxis::Y) => Vec3::new(1.0, 1.0, -1.0),
+            (Handedness::Left, UpAxis::Z) => Vec3::new(1.0, -1.0, 1.0),
+        }
+    }
+
+    /// The rotation that turns the source up axis into Bevy's up axis.
+    fn rotation(&self) -> Quat {
+        match self.up {
+            UpAxis::Y => Quat::IDENTITY,
+            UpAxis::Z => Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2),
+        }
+    }
+
+    /// Whether the conversion mirrors the content, which reverses the winding of triangles.
+    pub fn flips_winding(&self) -> bool {
+        self.handedness == Handedness::Left
+    }
+
+    /// Converts a point from the source conventions into Bevy's.
+    pub fn convert_point(&self, point: Vec3) -> Vec3 {
+        self.rotation() * (self.unit_scale * self.mirror() * point)
+    }
+
+    /// Converts a direction, such as a normal, from the source conventions into Bevy's.
+    pub fn convert_direction(&self, direction: Vec3) -> Vec3 {
+        self.rotation() * (self.mirror() * direction)
+    }
+
+    /// The transform that converts content from the source conventions into Bevy's.
+    ///
+    /// This is useful to convert a whole hierarchy at once by using it as the root transform.
+    /// When the conversion mirrors the content, the triangle winding of the meshes must also be
+    /// reversed (see [`ImportConvention`]).
+    pub fn to_transform(&self) -> Transform {
+        Transform {
+            rotation: self.rotation(),
+            scale: self.unit_scale * self.mirror(),
+            ..default()
+        }
+    }
+
+    /// Converts the vertices, normals, and triangle winding of a mesh in place.
+    pub fn convert_mesh(&self, mesh: &mut Mesh) {
+        if let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+        {
+            for position in positions.iter_mut() {
+                *position = self.convert_point(Vec3::from(*position)).into();
+            }
+        }
+        if let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL)
+        {
+            for normal in normals.iter_mut() {
+                *normal = self.convert_direction(Vec3::from(*normal)).into();
+            }
+        }
+        if self.flips_winding() {
+            flip_winding(mesh);
+        }
+    }
+}
+
+/// Reverses the winding of every triangle in a mesh.
+pub fn flip_winding(mesh: &mut Mesh) {
+    match mesh.indices_mut() {
+        Some(Indices::U16(indices)) => indices.chunks_exact_mut(3).for_each(|t| t.swap(1, 2)),
+        Some(Indices::U32(indices)) => indices.chunks_exact_mut(3).for_each(|t| t.swap(1, 2)),
+        None => {
+            // Without indices, every three consecutive vertices form a triangle.
+            let Some(count) = mesh
+                .attribute(Mesh::ATTRIBUTE_POSITION)
+                .map(|positions| positions.len() as u32)
+            else {
+                return;
+            };
+            let indices = (0..count / 3)
+                .flat_map(|t| [3 * t, 3 * t + 2, 3 * t + 1])
+                .collect();
+            mesh.set_indices(Some(Indices::U32(indices)));
+        }
+    }
+}
+
+/// The type of collider generated for an imported mesh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColliderType {
+    /// An axis-aligned box around the mesh. The cheapest option.
+    Aabb,
+    /// The convex hull of the mesh.
+    #[default]
+    ConvexHull,
+    /// The exact triangles of the mesh. The most precise but most expensive option, and it is
+    /// hollow so it should only be used for static objects.
+    Trimesh,
+}
+
+impl ColliderType {
+    /// Creates a collider of this type for a mesh.
+    ///
+    /// Returns `None` if the mesh has no positions or the hull cannot be computed.
+    pub fn collider_for_mesh(&self, mesh: &Mesh) -> Option<Collider> {
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            return None;
+        };
+        let points: Vec<Vec3> = positions.iter().copied().map(Vec3::from).collect();
+        if points.is_empty() {
+            return None;
+        }
+
+        match self {
+            ColliderType::Aabb => {
+                let min = points
+                    .iter()
+                    .copied()
+                    .fold(Vec3::splat(f32::MAX), Vec3::min);
+                let max = points
+                    .iter()
+                    .copied()
+                    .fold(Vec3::splat(f32::MIN), Vec3::max);
+                let half_size = 0.5 * (max - min);
+                Some(Collider::compound(vec![(
+                    0.5 * (max + min),
+                    Quat::IDENTITY,
+                    Collider::cuboid(half_size.x, half_size.y, half_size.z),
+                )]))
+            }
+            ColliderType::ConvexHull => Collider::convex_hull(&points),
+            ColliderType::Trimesh => {
+                let indices: Vec<u32> = match mesh.indices() {
+                    Some(indices) => indices.iter().map(|i| i as u32).collect(),
+                    None => (0..points.len() as u32).collect(),
+                };
+                let triangles = indices
+                    .chunks_exact(3)
+                    .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                    .collect();
+                Some(Collider::trimesh(points, triangles))
+            }
+        }
+    }
+}
+
+/// Converts an imported mesh and creates its collider.
+pub fn shape_bundle_from_mesh(
+    mut mesh: Mesh,
+    convention: &AxisConvention,
+    collider_type: ColliderType,
+    meshes: &mut ResMut<Assets<Mesh>>,
+) -> Result<RapierShapeBundle> {
+    convention.convert_mesh(&mut mesh);
+    let collider = collider_type
+        .collider_for_mesh(&mesh)
+        .ok_or(MapBuilderError::NoCollider)?;
+
+    Ok(RapierShapeBundle {
+        collider,
+        mesh: meshes.add(mesh),
+    })
+}
+
+/// Reads an OBJ file into a mesh and collider.
+pub fn import_obj(
+    path: impl AsRef<Path>,
+    convention: &AxisConvention,
+    collider_type: ColliderType,
+    meshes: &mut ResMut<Assets<Mesh>>,
+) -> Result<RapierShapeBundle> {
+    shape_bundle_from_mesh(obj::load_obj(path)?, convention, collider_type, meshes)
+}
+
+/// Reads an STL file into a mesh and collider.
+pub fn import_stl(
+    path: impl AsRef<Path>,
+    convention: &AxisConvention,
+    collider_type: ColliderType,
+    meshes: &mut ResMut<Assets<Mesh>>,
+) -> Result<RapierShapeBundle> {
+    shape_bundle_from_mesh(stl::load_stl(path)?, convention, collider_type, meshes)
+}
+
+/// A component for scene roots, such as glTF scenes, that were authored with other conventions.
+///
+/// The root transform is multiplied by the conversion once, and if the conversion mirrors the
+/// scene, the winding of every mesh in the scene is reversed once the scene has been spawned.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct ImportConvention(pub AxisConvention);
+
+/// A marker for scene roots whose [`ImportConvention`] has been applied.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct ImportConventionApplied;
+
+/// A plugin that applies [`ImportConvention`]s to scenes.
+#[derive(Default)]
+pub struct ImportPlugin;
+
+impl ImportPlugin {
+    /// Creates a new [`ImportPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for ImportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(apply_import_conventions);
+    }
+}
+
+/// Converts the scenes that have an [`ImportConvention`] once they have been spawned.
+pub fn apply_import_conventions(
+    mut commands: Commands,
+    scene_spawner: Res<SceneSpawner>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut flipped_meshes: Local<HashSet<Handle<Mesh>>>,
+    mut roots: Query<
+        (Entity, &ImportConvention, &SceneInstance, &mut Transform),
+        Without<ImportConventionApplied>,
+    >,
+    mesh_handles: Query<&Handle<Mesh>>,
+) {
+    for (entity, convention, instance, mut transform) in &mut roots {
+        if !scene_spawner.instance_is_ready(**instance) {
+            continue;
+        }
+
+        *transform = *transform * convention.0.to_transform();
+
+        if convention.0.flips_winding() {
+            for handle in scene_spawner
+                .iter_instance_entities(**instance)
+                .filter_map(|entity| mesh_handles.get(entity).ok())
+            {
+                // Meshes may be shared by several nodes, so they must only be flipped once.
+                if flipped_meshes.insert(handle.clone_weak()) {
+                    if let Some(mesh) = meshes.get_mut(handle) {
+                        flip_winding(mesh);
+                    }
+                }
+            }
+        }
+
+        commands.entity(entity).insert(ImportConventionApplied);
+    }
+}
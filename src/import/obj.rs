@@ -0,0 +1,150 @@
+//! A mod that reads Wavefront OBJ meshes.
+//!
+//! Only the geometry is read: positions, texture coordinates, and normals. Polygons are
+//! triangulated as fans, and materials, groups, and curves are ignored. Meshes without normals
+//! are given flat normals.
+
+use super::*;
+
+use bevy::{render::render_resource::PrimitiveTopology, utils::HashMap};
+use std::path::Path;
+
+/// Parses the contents of an OBJ file into a mesh.
+pub fn parse_obj(source: &str) -> Result<Mesh> {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+
+    let mut vertices: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::default();
+    let mut mesh_positions = Vec::new();
+    let mut mesh_uvs = Vec::new();
+    let mut mesh_normals = Vec::new();
+    let mut has_normals = true;
+    let mut indices = Vec::new();
+
+    for (line_number, line) in source.lines().enumerate() {
+        let invalid = |message: &str| {
+            MapBuilderError::parse("OBJ", format!("line {}: {}", line_number + 1, message))
+        };
+
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                positions.push(parse_floats(&mut tokens).ok_or_else(|| invalid("bad vertex"))?)
+            }
+            Some("vt") => {
+                let [u, v] =
+                    parse_floats(&mut tokens).ok_or_else(|| invalid("bad texture coordinate"))?;
+                uvs.push([u, 1.0 - v]);
+            }
+            Some("vn") => {
+                normals.push(parse_floats(&mut tokens).ok_or_else(|| invalid("bad normal"))?)
+            }
+            Some("f") => {
+                let mut face = Vec::new();
+                for token in tokens {
+                    let mut parts = token.split('/');
+                    let mut index = |count: usize| -> Result<Option<usize>> {
+                        match parts.next() {
+                            None | Some("") => Ok(None),
+                            Some(part) => resolve_index(part, count)
+                                .map(Some)
+                                .ok_or_else(|| invalid("bad face index")),
+                        }
+                    };
+                    let position =
+                        index(positions.len())?.ok_or_else(|| invalid("missing vertex index"))?;
+                    let uv = index(uvs.len())?;
+                    let normal = index(normals.len())?;
+                    has_normals &= normal.is_some();
+
+                    let vertex = *vertices.entry((position, uv, normal)).or_insert_with(|| {
+                        mesh_positions.push(positions[position]);
+                        mesh_uvs.push(uv.map_or([0.0, 0.0], |uv| uvs[uv]));
+                        mesh_normals.push(normal.map_or([0.0, 1.0, 0.0], |normal| normals[normal]));
+                        (mesh_positions.len() - 1) as u32
+                    });
+                    face.push(vertex);
+                }
+
+                if face.len() < 3 {
+                    return Err(invalid("faces need at least three vertices"));
+                }
+                for i in 1..face.len() - 1 {
+                    indices.extend([face[0], face[i], face[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, mesh_positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, mesh_normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, mesh_uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+
+    if !has_normals {
+        mesh.duplicate_vertices();
+        mesh.compute_flat_normals();
+    }
+
+    Ok(mesh)
+}
+
+/// Reads an OBJ file into a mesh.
+pub fn load_obj(path: impl AsRef<Path>) -> Result<Mesh> {
+    parse_obj(&std::fs::read_to_string(path)?)
+}
+
+fn parse_floats<const N: usize>(tokens: &mut std::str::SplitWhitespace) -> Option<[f32; N]> {
+    let mut values = [0.0; N];
+    for value in values.iter_mut() {
+        *value = tokens.next()?.parse().ok()?;
+    }
+    Some(values)
+}
+
+/// Resolves a one-based (or negative, relative) OBJ index into a zero-based index.
+fn resolve_index(part: &str, count: usize) -> Option<usize> {
+    let index: isize = part.parse().ok()?;
+    let resolved = if index < 0 {
+        count as isize + index
+    } else {
+        index - 1
+    };
+    (0..count as isize)
+        .contains(&resolved)
+        .then_some(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn obj_quads_are_triangulated() {
+        let mesh =
+            parse_obj("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1 4//1\n")
+                .unwrap();
+
+        assert_eq!(mesh.count_vertices(), 4);
+        assert_eq!(mesh.indices().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn obj_negative_indices_are_relative() {
+        let mesh = parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n").unwrap();
+
+        assert_eq!(mesh.count_vertices(), 3);
+    }
+
+    #[test]
+    fn obj_bad_indices_are_rejected() {
+        assert!(matches!(
+            parse_obj("v 0 0 0\nv 1 0 0\nf 1 2 3\n"),
+            Err(MapBuilderError::Parse { .. })
+        ));
+        assert!(parse_obj("v 0 0 0\nv 1 0 0\nf 1 2\n").is_err());
+    }
+}
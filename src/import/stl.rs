@@ -0,0 +1,164 @@
+//! A mod that reads STL meshes in both the binary and the ASCII formats.
+//!
+//! STL files only contain triangles with a normal per face, so the resulting meshes are flat
+//! shaded.
+
+use super::*;
+
+use bevy::render::render_resource::PrimitiveTopology;
+use std::path::Path;
+
+/// Parses the contents of an STL file into a mesh.
+pub fn parse_stl(bytes: &[u8]) -> Result<Mesh> {
+    let triangles = if is_binary(bytes) {
+        parse_binary(bytes)
+    } else {
+        let source = std::str::from_utf8(bytes)
+            .map_err(|error| MapBuilderError::parse("STL", error.to_string()))?;
+        parse_ascii(source)?
+    };
+
+    let mut positions = Vec::with_capacity(3 * triangles.len());
+    let mut normals = Vec::with_capacity(3 * triangles.len());
+    for (normal, vertices) in triangles {
+        // Recompute the normal when the file does not provide one.
+        let [a, b, c] = vertices.map(Vec3::from);
+        let normal = Vec3::from(normal)
+            .try_normalize()
+            .or_else(|| (b - a).cross(c - a).try_normalize())
+            .unwrap_or(Vec3::Y);
+        positions.extend(vertices);
+        normals.extend([<[f32; 3]>::from(normal); 3]);
+    }
+
+    let vertex_count = positions.len();
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; vertex_count]);
+    mesh.set_indices(Some(Indices::U32((0..vertex_count as u32).collect())));
+    Ok(mesh)
+}
+
+/// Reads an STL file into a mesh.
+pub fn load_stl(path: impl AsRef<Path>) -> Result<Mesh> {
+    parse_stl(&std::fs::read(path)?)
+}
+
+type StlTriangle = ([f32; 3], [[f32; 3]; 3]);
+
+/// Binary files may also start with "solid", so the size of the file is checked instead.
+fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.len() < 84 {
+        return false;
+    }
+    let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    bytes.len() == 84 + 50 * count
+}
+
+fn parse_binary(bytes: &[u8]) -> Vec<StlTriangle> {
+    let read_vec3 = |bytes: &[u8]| {
+        let mut vec = [0.0; 3];
+        for (value, chunk) in vec.iter_mut().zip(bytes.chunks_exact(4)) {
+            *value = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        vec
+    };
+
+    bytes[84..]
+        .chunks_exact(50)
+        .map(|facet| {
+            (
+                read_vec3(&facet[0..12]),
+                [
+                    read_vec3(&facet[12..24]),
+                    read_vec3(&facet[24..36]),
+                    read_vec3(&facet[36..48]),
+                ],
+            )
+        })
+        .collect()
+}
+
+fn parse_ascii(source: &str) -> Result<Vec<StlTriangle>> {
+    let invalid = |message: &str| MapBuilderError::parse("STL", message);
+    let parse_vec3 = |tokens: &mut std::str::SplitWhitespace| -> Result<[f32; 3]> {
+        let mut vec = [0.0; 3];
+        for value in vec.iter_mut() {
+            *value = tokens
+                .next()
+                .and_then(|token| token.parse().ok())
+                .ok_or_else(|| invalid("bad coordinate"))?;
+        }
+        Ok(vec)
+    };
+
+    let mut triangles = Vec::new();
+    let mut normal = [0.0; 3];
+    let mut vertices = Vec::with_capacity(3);
+    for line in source.lines() {
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("facet") => {
+                tokens.next(); // "normal"
+                normal = parse_vec3(&mut tokens)?;
+                vertices.clear();
+            }
+            Some("vertex") => vertices.push(parse_vec3(&mut tokens)?),
+            Some("endfacet") => {
+                let triangle: [[f32; 3]; 3] = vertices
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| invalid("facets need exactly three vertices"))?;
+                triangles.push((normal, triangle));
+            }
+            _ => {}
+        }
+    }
+    Ok(triangles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+
+    #[test]
+    fn stl_ascii_is_read() {
+        let source = "solid test
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid test
+";
+        let mesh = parse_stl(source.as_bytes()).unwrap();
+
+        assert_eq!(mesh.count_vertices(), 3);
+    }
+
+    #[test]
+    fn stl_binary_is_read_with_missing_normals_recomputed() {
+        let mut bytes = vec![0; 80];
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend([0.0f32; 3].iter().flat_map(|value| value.to_le_bytes()));
+        for vertex in TRIANGLE {
+            bytes.extend(vertex.iter().flat_map(|value| value.to_le_bytes()));
+        }
+        bytes.extend([0; 2]);
+
+        let mesh = parse_stl(&bytes).unwrap();
+
+        assert_eq!(mesh.count_vertices(), 3);
+        let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+        else {
+            panic!("the mesh has no normals");
+        };
+        assert_eq!(normals[0], [0.0, 0.0, 1.0]);
+    }
+}
@@ -0,0 +1,198 @@
+//! A mod that smooths the rendered motion of bodies between fixed physics steps.
+//!
+//! With an interpolated [`TimestepMode`], Rapier steps at a fixed rate that can be lower than the
+//! frame rate, and a body only moves on the frames that run a step. Rapier can draw such a body
+//! between its last two positions instead, but only when the body has a
+//! [`TransformInterpolation`]. The [`PhysicsInterpolationPlugin`] gives one to every body that
+//! physics moves, and can switch Rapier to an interpolated timestep itself.
+//!
+//! Bodies whose transforms are set by the game every frame, such as character controllers and
+//! position-based kinematic bodies like doors, already move smoothly and are left alone.
+//! Interpolating them would draw them a step behind where the game put them.
+//!
+//! Rapier resets the interpolation of a body whenever its transform changes, to respect
+//! teleports, and this includes the interpolated transforms that Rapier writes itself. The plugin
+//! keeps the interpolation across the frames where the only change was Rapier's own, so that
+//! bodies move between steps instead of jumping from one to the next.
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+/// Settings for the [`PhysicsInterpolationPlugin`].
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct PhysicsInterpolationSettings {
+    /// The fixed length of a physics step that Rapier is switched to, if any.
+    pub timestep: Option<f32>,
+    /// Whether bodies moved by physics get a [`TransformInterpolation`].
+    pub auto_insert: bool,
+}
+
+impl Default for PhysicsInterpolationSettings {
+    fn default() -> Self {
+        Self {
+            timestep: None,
+            auto_insert: true,
+        }
+    }
+}
+
+/// The stages that keep interpolations across Rapier's sync with the world.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+pub enum PhysicsInterpolationStage {
+    /// Runs before Rapier syncs with the world, and saves the interpolations.
+    BeforeSyncBackend,
+    /// Runs after Rapier synced with the world, and restores the interpolations.
+    AfterSyncBackend,
+}
+
+/// Keeps the [`TransformInterpolation`] of a body when Rapier resets it for a transform that
+/// Rapier wrote itself.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct InterpolationGuard {
+    /// The transform that Rapier wrote in the last frame.
+    written: Option<Transform>,
+    /// The interpolation to restore after the sync, if the transform was not changed since.
+    saved: Option<TransformInterpolation>,
+}
+
+/// A plugin that smooths the rendered motion of bodies between fixed physics steps.
+#[derive(Default)]
+pub struct PhysicsInterpolationPlugin {
+    /// The settings used by the plugin.
+    pub settings: PhysicsInterpolationSettings,
+}
+
+impl PhysicsInterpolationPlugin {
+    /// Creates a new [`PhysicsInterpolationPlugin`] that keeps the timestep mode of Rapier.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`PhysicsInterpolationPlugin`] that switches Rapier to steps of a fixed
+    /// length, drawn interpolated.
+    pub fn with_timestep(dt: f32) -> Self {
+        Self {
+            settings: PhysicsInterpolationSettings {
+                timestep: Some(dt),
+                ..default()
+            },
+        }
+    }
+}
+
+impl Plugin for PhysicsInterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings)
+            .add_startup_system(set_interpolated_timestep)
+            .add_system_to_stage(CoreStage::PostUpdate, insert_transform_interpolation)
+            .add_system_to_stage(CoreStage::PostUpdate, record_written_transforms);
+
+        if app
+            .schedule
+            .get_stage::<SystemStage>(PhysicsStages::SyncBackend)
+            .is_some()
+        {
+            app.add_stage_before(
+                PhysicsStages::SyncBackend,
+                PhysicsInterpolationStage::BeforeSyncBackend,
+                SystemStage::single(save_interpolations),
+            )
+            .add_stage_after(
+                PhysicsStages::SyncBackend,
+                PhysicsInterpolationStage::AfterSyncBackend,
+                SystemStage::single(restore_interpolations),
+            );
+        }
+    }
+}
+
+/// Switches Rapier to the interpolated timestep of the settings, if they have one.
+pub fn set_interpolated_timestep(
+    settings: Res<PhysicsInterpolationSettings>,
+    rapier_config: Option<ResMut<RapierConfiguration>>,
+) {
+    let (Some(dt), Some(mut rapier_config)) = (settings.timestep, rapier_config) else {
+        return;
+    };
+    let substeps = match rapier_config.timestep_mode {
+        TimestepMode::Fixed { substeps, .. }
+        | TimestepMode::Variable { substeps, .. }
+        | TimestepMode::Interpolated { substeps, .. } => substeps,
+    };
+    rapier_config.timestep_mode = TimestepMode::Interpolated {
+        dt,
+        time_scale: 1.0,
+        substeps,
+    };
+}
+
+/// Gives every body that physics moves a [`TransformInterpolation`] while the timestep mode of
+/// Rapier is interpolated.
+#[allow(clippy::type_complexity)]
+pub fn insert_transform_interpolation(
+    mut commands: Commands,
+    settings: Res<PhysicsInterpolationSettings>,
+    rapier_config: Res<RapierConfiguration>,
+    bodies: Query<
+        (Entity, &RigidBody),
+        (
+            Without<TransformInterpolation>,
+            Without<KinematicCharacterController>,
+        ),
+    >,
+    unguarded: Query<Entity, (With<TransformInterpolation>, Without<InterpolationGuard>)>,
+) {
+    for entity in &unguarded {
+        commands
+            .entity(entity)
+            .insert(InterpolationGuard::default());
+    }
+
+    if !settings.auto_insert
+        || !matches!(
+            rapier_config.timestep_mode,
+            TimestepMode::Interpolated { .. }
+        )
+    {
+        return;
+    }
+    for (entity, body) in &bodies {
+        if matches!(body, RigidBody::Dynamic | RigidBody::KinematicVelocityBased) {
+            commands.entity(entity).insert((
+                TransformInterpolation::default(),
+                InterpolationGuard::default(),
+            ));
+        }
+    }
+}
+
+/// Records the transforms that Rapier wrote to interpolated bodies.
+pub fn record_written_transforms(
+    mut bodies: Query<(&Transform, &mut InterpolationGuard), With<TransformInterpolation>>,
+) {
+    for (transform, mut guard) in &mut bodies {
+        guard.written = Some(*transform);
+    }
+}
+
+/// Saves the interpolations of bodies whose transforms were only changed by Rapier.
+pub fn save_interpolations(
+    mut bodies: Query<(&Transform, &TransformInterpolation, &mut InterpolationGuard)>,
+) {
+    for (transform, interpolation, mut guard) in &mut bodies {
+        guard.saved = (guard.written == Some(*transform)).then_some(*interpolation);
+    }
+}
+
+/// Restores the interpolations that Rapier reset for its own transform changes.
+pub fn restore_interpolations(
+    mut bodies: Query<(&mut TransformInterpolation, &mut InterpolationGuard)>,
+) {
+    for (mut interpolation, mut guard) in &mut bodies {
+        if let Some(saved) = guard.saved.take() {
+            if *interpolation != saved {
+                *interpolation = saved;
+            }
+        }
+    }
+}
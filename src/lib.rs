@@ -2,6 +2,17 @@
 //!
 //! The crate is composed of the following modules:
 //! - map: A collection of 3D tiles, obstacles, players, event spaces, and other objects.
+//!
+//! Optional subsystems are behind cargo features, none of which are enabled by default:
+//! - `editor`: the building blocks of a runtime map editor;
+//! - `import`: the importers for meshes, heightmaps and glTF scenes;
+//! - `persistence`: snapshots and replays, which also enables `ron`;
+//! - `ron`: reading and writing maps and prefabs as RON files;
+//! - `export`: the export of map colliders to JSON;
+//! - `debug-render`: collider wireframes in the debug overlay;
+//! - `audio`: the sounds played by map objects.
+//!
+//! The `full` feature enables all of them along with a window, and is needed by the demo example.
 
 #![deny(missing_docs)]
 // #![forbid(missing_docs_in_private_items)]
@@ -11,3 +22,61 @@ pub mod rapier_mesh_bundles;
 
 /// A module that adds mouse/keyboard control to the camera.
 pub mod controller;
+
+/// A module with tools for debugging maps and the controllers.
+pub mod debug;
+
+/// A module that pushes bodies out of the map geometry they were spawned inside.
+pub mod depenetration;
+
+/// A module that lowers the rendering resolution when frames take too long.
+pub mod dynamic_resolution;
+
+/// A module with the building blocks of a runtime map editor.
+#[cfg(feature = "editor")]
+pub mod editor;
+
+/// A module with the errors of the crate.
+pub mod error;
+
+/// A module that animates the lighting and sky over the course of a day.
+pub mod environment;
+
+/// A module that moves the origin to keep large worlds precise.
+pub mod floating_origin;
+
+/// A module for freezing entities in place and letting them go again exactly as they were.
+pub mod freeze;
+
+/// A module that imports content authored in other tools.
+#[cfg(feature = "import")]
+pub mod import;
+
+/// A module that smooths the rendered motion of bodies between fixed physics steps.
+pub mod interpolation;
+
+/// A module that describes maps and spawns them into the world.
+pub mod map;
+
+/// A module that saves and restores the dynamic state of a running map.
+#[cfg(feature = "persistence")]
+pub mod persistence;
+
+/// A module that records controller input and plays it back.
+#[cfg(feature = "persistence")]
+pub mod replay;
+
+/// A module that integrates the crate with a stack of game states.
+pub mod state;
+
+/// A module that splits the physics step into substeps when bodies move fast.
+pub mod substeps;
+
+/// A module with physics materials for surfaces and the footsteps heard on them.
+pub mod surface;
+
+/// A module for moving bodies somewhere else without leaving them stuck or still moving.
+pub mod teleport;
+
+/// A module that slows down, speeds up and steps the simulation clock.
+pub mod time_scale;
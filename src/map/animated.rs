@@ -0,0 +1,484 @@
+//! A mod for doors and other moving parts of a map.
+//!
+//! A [`SlidingDoor`] moves along an offset and a [`RotatingDoor`] swings around a hinge. Both
+//! toggle when a player uses them through an [`InteractEvent`], and close again by themselves
+//! after a delay if they have one. A door also listens for [`MapSignal`]s on the channel named by
+//! its trigger: a pulse opens it, such as a player entering an [`EventSpace`] of that name, and
+//! an on or off signal opens or closes it, such as a [`PressurePlate`] of that name being pressed
+//! and released.
+//!
+//! Doors are turned into kinematic bodies when they are spawned. Moving the [`Transform`] of a
+//! kinematic body moves its Rapier collider along with it, so doors push dynamic bodies out of the
+//! way. Rapier does not move character controllers out of kinematic bodies, so before every step
+//! the door casts its collider along the motion and moves a character in the way along with it.
+//!
+//! A door that would pin a character against the level decides what to do through its
+//! [`CrushBehavior`]. A character in the way is crushed if casting the character along the push
+//! hits something solid. The door can then push anyway, stop, turn back, or stop and hurt the
+//! character through a [`DamageEvent`].
+
+use super::{signal::*, *};
+use crate::{controller::health::*, state::*};
+
+/// How much room a character needs behind it, on top of its width, to not be crushed.
+const CRUSH_SKIN: f32 = 0.05;
+
+/// What a door does when it would crush a character against the level.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, Reflect, FromReflect)]
+pub enum CrushBehavior {
+    /// Keeps moving and pushes the character, even into the level.
+    #[default]
+    Push,
+    /// Stops until the character moves away.
+    Stop,
+    /// Turns back the way it came.
+    Reverse,
+    /// Stops and damages the character until it moves away.
+    Damage {
+        /// The damage dealt every second.
+        per_second: f32,
+    },
+}
+
+/// A door that slides open along an offset.
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+pub struct SlidingDoor {
+    /// How far the door moves when it opens, in its own space.
+    pub open_offset: Vec3,
+    /// How fast the door moves, in units per second.
+    #[serde(default = "SlidingDoor::default_speed")]
+    pub speed: f32,
+    /// How many seconds the door stays open before closing by itself, if it does.
+    #[serde(default)]
+    pub auto_close: Option<f32>,
+    /// The channel of the [`MapSignal`]s that open and close the door, such as the name of an
+    /// [`EventSpace`] or a [`PressurePlate`].
+    #[serde(default)]
+    pub trigger: Option<String>,
+    /// What the door does when it would crush a character.
+    #[serde(default)]
+    pub crush: CrushBehavior,
+}
+
+impl Default for SlidingDoor {
+    fn default() -> Self {
+        Self::new(Vec3::ZERO)
+    }
+}
+
+impl SlidingDoor {
+    /// Creates a new [`SlidingDoor`] that slides by an offset.
+    pub fn new(open_offset: Vec3) -> Self {
+        Self {
+            open_offset,
+            speed: Self::default_speed(),
+            auto_close: None,
+            trigger: None,
+            crush: CrushBehavior::default(),
+        }
+    }
+
+    fn default_speed() -> f32 {
+        1.0
+    }
+}
+
+/// A door that swings open around a hinge.
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+pub struct RotatingDoor {
+    /// The axis the door swings around, in its own space.
+    #[serde(default = "RotatingDoor::default_axis")]
+    pub axis: Vec3,
+    /// Where the hinge is, in the door's own space.
+    #[serde(default)]
+    pub pivot: Vec3,
+    /// How far the door swings when it opens, in radians.
+    pub open_angle: f32,
+    /// How fast the door swings, in radians per second.
+    #[serde(default = "RotatingDoor::default_speed")]
+    pub speed: f32,
+    /// How many seconds the door stays open before closing by itself, if it does.
+    #[serde(default)]
+    pub auto_close: Option<f32>,
+    /// The channel of the [`MapSignal`]s that open and close the door, such as the name of an
+    /// [`EventSpace`] or a [`PressurePlate`].
+    #[serde(default)]
+    pub trigger: Option<String>,
+    /// What the door does when it would crush a character.
+    #[serde(default)]
+    pub crush: CrushBehavior,
+}
+
+impl Default for RotatingDoor {
+    fn default() -> Self {
+        Self::new(Vec3::ZERO, 0.0)
+    }
+}
+
+impl RotatingDoor {
+    /// Creates a new [`RotatingDoor`] that swings around a vertical hinge.
+    pub fn new(pivot: Vec3, open_angle: f32) -> Self {
+        Self {
+            axis: Self::default_axis(),
+            pivot,
+            open_angle,
+            speed: Self::default_speed(),
+            auto_close: None,
+            trigger: None,
+            crush: CrushBehavior::default(),
+        }
+    }
+
+    fn default_axis() -> Vec3 {
+        Vec3::Y
+    }
+
+    fn default_speed() -> f32 {
+        std::f32::consts::FRAC_PI_2
+    }
+}
+
+/// A door placed in a [`Map`] or [`Prefab`](super::prefab::Prefab).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MapDoor {
+    /// A door that slides open.
+    Sliding(SlidingDoor),
+    /// A door that swings open.
+    Rotating(RotatingDoor),
+}
+
+impl MapDoor {
+    /// Inserts the door component into an entity.
+    pub fn insert(&self, entity: &mut EntityCommands) {
+        match self {
+            MapDoor::Sliding(door) => {
+                entity.insert(door.clone());
+            }
+            MapDoor::Rotating(door) => {
+                entity.insert(door.clone());
+            }
+        }
+    }
+}
+
+/// The animation state of a door.
+///
+/// Set `open` to open or close the door from game code.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct DoorState {
+    /// Whether the door is opening or open.
+    pub open: bool,
+    /// How far the door has opened, from `0.0` when closed to `1.0` when open.
+    pub progress: f32,
+    /// How long the door has been fully open, in seconds.
+    pub open_time: f32,
+    /// The transform of the door when it is closed.
+    pub closed_transform: Transform,
+}
+
+/// A plugin that opens, closes, and animates doors.
+#[derive(Default)]
+pub struct AnimatedDoorPlugin;
+
+impl AnimatedDoorPlugin {
+    /// Creates a new [`AnimatedDoorPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for AnimatedDoorPlugin {
+    fn build(&self, app: &mut App) {
+        register_map_types(app);
+
+        if !app.is_plugin_added::<MapSignalPlugin>() {
+            app.add_plugin(MapSignalPlugin::new());
+        }
+
+        app.register_type::<SlidingDoor>()
+            .register_type::<RotatingDoor>()
+            .register_type::<CrushBehavior>()
+            .add_event::<InteractEvent>()
+            .add_event::<FloatingOriginShifted>()
+            .add_event::<DamageEvent>()
+            .add_system(init_doors)
+            .add_system(trigger_doors.after(init_doors).after(send_map_signals))
+            .add_system(
+                animate_doors
+                    .with_run_criteria(is_playing)
+                    .after(trigger_doors),
+            );
+    }
+}
+
+/// Makes new doors kinematic and remembers their closed pose.
+#[allow(clippy::type_complexity)]
+pub fn init_doors(
+    mut commands: Commands,
+    doors: Query<
+        (Entity, &Transform),
+        (
+            Or<(Added<SlidingDoor>, Added<RotatingDoor>)>,
+            Without<DoorState>,
+        ),
+    >,
+) {
+    for (entity, transform) in &doors {
+        commands.entity(entity).insert((
+            RigidBody::KinematicPositionBased,
+            DoorState {
+                open: false,
+                progress: 0.0,
+                open_time: 0.0,
+                closed_transform: *transform,
+            },
+        ));
+    }
+}
+
+/// Toggles doors that are used, and opens or closes doors that a signal is sent to.
+pub fn trigger_doors(
+    mut interact_events: EventReader<InteractEvent>,
+    mut signals: EventReader<MapSignal>,
+    mut doors: Query<(&mut DoorState, Option<&SlidingDoor>, Option<&RotatingDoor>)>,
+) {
+    for event in interact_events.iter() {
+        if let Ok((mut state, ..)) = doors.get_mut(event.target) {
+            state.open = !state.open;
+            state.open_time = 0.0;
+        }
+    }
+
+    for signal in signals.iter() {
+        let Some(open) = signal.payload.as_bool() else {
+            continue;
+        };
+        for (mut state, sliding, rotating) in &mut doors {
+            if door_trigger(sliding, rotating) == Some(&signal.channel) {
+                state.open = open;
+                state.open_time = 0.0;
+            }
+        }
+    }
+}
+
+/// The trigger of a door, whichever kind it is.
+fn door_trigger<'a>(
+    sliding: Option<&'a SlidingDoor>,
+    rotating: Option<&'a RotatingDoor>,
+) -> Option<&'a String> {
+    sliding
+        .and_then(|door| door.trigger.as_ref())
+        .or_else(|| rotating.and_then(|door| door.trigger.as_ref()))
+}
+
+/// Moves doors toward their open or closed pose and closes doors that stayed open too long.
+///
+/// Characters in the way are pushed along, unless the door would crush them and its
+/// [`CrushBehavior`] says otherwise.
+#[allow(clippy::type_complexity)]
+pub fn animate_doors(
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    mut shifts: EventReader<FloatingOriginShifted>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut doors: Query<(
+        Entity,
+        &mut DoorState,
+        &mut Transform,
+        Option<&SlidingDoor>,
+        Option<&RotatingDoor>,
+        Option<&Parent>,
+        Option<(&Collider, &GlobalTransform)>,
+    )>,
+    characters: Query<(&Collider, &GlobalTransform), With<KinematicCharacterController>>,
+    mut controllers: Query<&mut KinematicCharacterController>,
+) {
+    let shift: Vec3 = shifts.iter().map(|shifted| shifted.shift).sum();
+    let dt = time.delta_seconds();
+
+    for (door, mut state, mut transform, sliding, rotating, parent, collider) in &mut doors {
+        // The origin moves root entities, so the closed pose must move with them.
+        if parent.is_none() {
+            state.closed_transform.translation -= shift;
+        }
+
+        // Seconds for a full swing, so that both kinds of doors share the same progress.
+        let duration = match (sliding, rotating) {
+            (Some(door), _) => door.open_offset.length() / door.speed,
+            (None, Some(door)) => door.open_angle.abs() / door.speed,
+            (None, None) => continue,
+        };
+        let auto_close = sliding
+            .and_then(|door| door.auto_close)
+            .or_else(|| rotating.and_then(|door| door.auto_close));
+
+        let target = if state.open { 1.0 } else { 0.0 };
+        let step = if duration > 0.0 && duration.is_finite() {
+            dt / duration
+        } else {
+            1.0
+        };
+        let progress = if state.progress < target {
+            (state.progress + step).min(target)
+        } else {
+            (state.progress - step).max(target)
+        };
+
+        if state.open && progress >= 1.0 {
+            state.open_time += dt;
+            if auto_close.is_some_and(|delay| state.open_time >= delay) {
+                state.open = false;
+                state.open_time = 0.0;
+            }
+        }
+        if progress == state.progress && !state.is_changed() && shift == Vec3::ZERO {
+            continue;
+        }
+
+        let closed = state.closed_transform;
+        let pose = match (sliding, rotating) {
+            (Some(door), _) => Transform {
+                translation: closed.translation
+                    + closed.rotation * (closed.scale * door.open_offset * progress),
+                ..closed
+            },
+            (None, Some(door)) => {
+                let swing = Quat::from_axis_angle(
+                    door.axis.normalize_or_zero(),
+                    door.open_angle * progress,
+                );
+                let pivot = closed.scale * door.pivot;
+                Transform {
+                    translation: closed.translation + closed.rotation * (pivot - swing * pivot),
+                    rotation: closed.rotation * swing,
+                    ..closed
+                }
+            }
+            (None, None) => continue,
+        };
+
+        let crush = sliding
+            .map(|door| door.crush)
+            .or_else(|| rotating.map(|door| door.crush))
+            .unwrap_or_default();
+        let in_path = match collider {
+            Some((collider, global_transform)) if progress != state.progress => character_in_path(
+                &rapier_context,
+                door,
+                collider,
+                global_transform,
+                &transform,
+                &pose,
+                &characters,
+            ),
+            _ => None,
+        };
+        if let Some((character, push)) = in_path {
+            let crushed = || is_crushed(&rapier_context, door, character, push, &characters);
+            match crush {
+                CrushBehavior::Stop if crushed() => continue,
+                CrushBehavior::Reverse if crushed() => {
+                    state.open = !state.open;
+                    state.open_time = 0.0;
+                    continue;
+                }
+                CrushBehavior::Damage { per_second } if crushed() => {
+                    damage_events.send(DamageEvent {
+                        entity: character,
+                        amount: per_second * dt,
+                        source: Some(door),
+                    });
+                    continue;
+                }
+                _ => {}
+            }
+            if let Ok(mut controller) = controllers.get_mut(character) {
+                controller.translation =
+                    Some(controller.translation.map(|t| t + push).unwrap_or(push));
+            }
+        }
+
+        state.progress = progress;
+        if *transform != pose {
+            *transform = pose;
+        }
+    }
+}
+
+/// The character in the way of a door moving from one pose to another, and how far the door
+/// pushes it.
+fn character_in_path(
+    rapier_context: &RapierContext,
+    door: Entity,
+    collider: &Collider,
+    global_transform: &GlobalTransform,
+    from: &Transform,
+    to: &Transform,
+    characters: &Query<(&Collider, &GlobalTransform), With<KinematicCharacterController>>,
+) -> Option<(Entity, Vec3)> {
+    // The poses are relative to the parent of the door, so the motion is moved to world space.
+    let parent = global_transform.affine() * from.compute_affine().inverse();
+    let world_to = parent * to.compute_affine();
+    let motion = world_to * global_transform.affine().inverse();
+    let start = global_transform.translation();
+    let (_, rotation, end) = world_to.to_scale_rotation_translation();
+
+    let is_character = |entity| characters.contains(entity);
+    let ahead = QueryFilter::default()
+        .exclude_sensors()
+        .exclude_collider(door)
+        .exclude_rigid_body(door)
+        .predicate(&is_character);
+    let (character, toi) =
+        rapier_context.cast_shape(start, rotation, end - start, collider, 1.0, ahead)?;
+    let (_, character_transform) = characters.get(character).ok()?;
+
+    // The door only pushes the character for the part of the motion after it reaches it.
+    let position = character_transform.translation();
+    let push = (motion.transform_point3(position) - position) * (1.0 - toi.toi);
+    (push != Vec3::ZERO).then_some((character, push))
+}
+
+/// Whether pushing a character would pin it against the level.
+fn is_crushed(
+    rapier_context: &RapierContext,
+    door: Entity,
+    character: Entity,
+    push: Vec3,
+    characters: &Query<(&Collider, &GlobalTransform), With<KinematicCharacterController>>,
+) -> bool {
+    let Ok((character_collider, character_transform)) = characters.get(character) else {
+        return false;
+    };
+
+    // Pushing a character clear of a door can take up to its whole width, so the character is
+    // crushed if something solid is closer than that behind it along the push.
+    let position = character_transform.translation();
+    let direction = push.normalize_or_zero();
+    let (_, character_rotation, _) = character_transform.to_scale_rotation_translation();
+    let half_extents: Vec3 = character_collider
+        .raw
+        .compute_local_aabb()
+        .half_extents()
+        .into();
+    let width = 2.0 * half_extents.dot((character_rotation.inverse() * direction).abs());
+    let is_not_door = |entity| entity != door;
+    let behind = QueryFilter::exclude_dynamic()
+        .exclude_sensors()
+        .exclude_collider(character)
+        .exclude_rigid_body(character)
+        .predicate(&is_not_door);
+    rapier_context
+        .cast_shape(
+            position,
+            character_rotation,
+            direction,
+            character_collider,
+            width + CRUSH_SKIN,
+            behind,
+        )
+        .is_some()
+}
@@ -0,0 +1,354 @@
+//! A mod for bounce pads that launch whatever touches them.
+//!
+//! A [`BouncePad`] can be a solid surface or an [`EventSpace`]. Characters that touch a solid
+//! pad, dynamic bodies resting on it, and anything that enters a pad volume have their velocity
+//! set to the launch vector of the pad. The launch vector is given in the space of the pad, so
+//! rotating the pad aims it.
+//!
+//! To help aim pads at their landing zones, the [`BouncePadPlugin`] can draw the arc that a
+//! launched body follows, up to where it hits the map. The arcs are drawn while
+//! [`BouncePadSettings::preview_arcs`] is set or while the [`MapDebug`] overlay is enabled, in
+//! the trajectory color of the [`DebugPalette`]. They follow the Rapier gravity scaled by
+//! [`BouncePadSettings::gravity_scale`], so set it to the gravity scale of the player's preset.
+
+use super::{event_space::*, *};
+use crate::{
+    controller::*,
+    debug::{overlay::*, palette::*},
+    state::*,
+};
+
+use bevy::utils::HashMap;
+
+/// A surface or volume that launches bodies.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+pub struct BouncePad {
+    /// The velocity given to launched bodies, in the space of the pad.
+    pub launch: Vec3,
+    /// How long a body has to wait before the same pad launches it again, in seconds.
+    #[serde(default = "BouncePad::default_cooldown")]
+    pub cooldown: f32,
+}
+
+impl Default for BouncePad {
+    fn default() -> Self {
+        Self::new(Vec3::ZERO)
+    }
+}
+
+impl BouncePad {
+    /// Creates a new [`BouncePad`] with a launch velocity in the space of the pad.
+    pub fn new(launch: Vec3) -> Self {
+        Self {
+            launch,
+            cooldown: Self::default_cooldown(),
+        }
+    }
+
+    fn default_cooldown() -> f32 {
+        0.25
+    }
+
+    /// The launch velocity of the pad in world space.
+    pub fn world_launch(&self, transform: &GlobalTransform) -> Vec3 {
+        let (_, rotation, _) = transform.to_scale_rotation_translation();
+        rotation * self.launch
+    }
+}
+
+/// Computes the points along the arc of a body launched from `start`, one every `step` seconds.
+///
+/// The arc stops after `duration` seconds.
+pub fn launch_arc(
+    start: Vec3,
+    velocity: Vec3,
+    gravity: Vec3,
+    step: f32,
+    duration: f32,
+) -> impl Iterator<Item = Vec3> {
+    let steps = if step > 0.0 {
+        (duration / step).ceil() as usize
+    } else {
+        0
+    };
+    (0..=steps).map(move |i| {
+        let t = i as f32 * step;
+        start + velocity * t + 0.5 * gravity * t * t
+    })
+}
+
+/// Settings for the [`BouncePadPlugin`].
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct BouncePadSettings {
+    /// Whether the arcs of the pads are drawn.
+    pub preview_arcs: bool,
+    /// The time between two points of an arc, in seconds.
+    pub arc_step: f32,
+    /// The longest an arc is followed, in seconds.
+    pub arc_duration: f32,
+    /// The [`GravityScale`] of the bodies the arcs are drawn for, such as the
+    /// [`FpsControllerSettings::gravity_scale`] of the player.
+    pub gravity_scale: f32,
+}
+
+impl Default for BouncePadSettings {
+    fn default() -> Self {
+        Self {
+            preview_arcs: false,
+            arc_step: 0.05,
+            arc_duration: 5.0,
+            gravity_scale: 1.0,
+        }
+    }
+}
+
+/// A plugin that launches bodies off [`BouncePad`]s and previews their arcs.
+#[derive(Default)]
+pub struct BouncePadPlugin {
+    /// The settings used by the plugin.
+    pub settings: BouncePadSettings,
+}
+
+impl BouncePadPlugin {
+    /// Creates a new [`BouncePadPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Plugin for BouncePadPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<BouncePad>()
+            .insert_resource(self.settings)
+            .init_resource::<DebugPalette>()
+            .add_event::<EventSpaceEvent>()
+            .add_system(launch_from_bounce_pads.with_run_criteria(is_playing))
+            .add_system_to_stage(CoreStage::PostUpdate, draw_bounce_pad_arcs);
+    }
+}
+
+/// Launches the bodies that touch or enter a [`BouncePad`].
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn launch_from_bounce_pads(
+    mut commands: Commands,
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    mut event_space_events: EventReader<EventSpaceEvent>,
+    mut last_launches: Local<HashMap<(Entity, Entity), f32>>,
+    pads: Query<(Entity, &BouncePad, &GlobalTransform)>,
+    characters: Query<(Entity, &KinematicCharacterControllerOutput)>,
+    mut bodies: Query<(
+        &RigidBody,
+        Option<&mut Velocity>,
+        Option<&mut CustomVelocity>,
+    )>,
+) {
+    let now = time.elapsed_seconds();
+    let mut touches = Vec::new();
+
+    // Characters that bumped into a solid pad.
+    for (character, output) in &characters {
+        for collision in &output.collisions {
+            if pads.contains(collision.entity) {
+                touches.push((collision.entity, character));
+            }
+        }
+    }
+
+    // Dynamic bodies in contact with a solid pad.
+    for (pad, ..) in &pads {
+        for pair in rapier_context.contacts_with(pad) {
+            if !pair.has_any_active_contacts() {
+                continue;
+            }
+            let other = if pair.collider1() == pad {
+                pair.collider2()
+            } else {
+                pair.collider1()
+            };
+            touches.push((pad, rapier_context.collider_parent(other).unwrap_or(other)));
+        }
+    }
+
+    // Anything that entered a pad volume.
+    for event in event_space_events.iter() {
+        if let EventSpaceEvent::Entered { space, entity } = *event {
+            if pads.contains(space) {
+                let body = rapier_context.collider_parent(entity).unwrap_or(entity);
+                touches.push((space, body));
+            }
+        }
+    }
+
+    for (pad, body) in touches {
+        let Ok((_, bounce_pad, transform)) = pads.get(pad) else {
+            continue;
+        };
+        if last_launches
+            .get(&(pad, body))
+            .is_some_and(|last| now - last < bounce_pad.cooldown)
+        {
+            continue;
+        }
+        let Ok((rigid_body, velocity, custom_velocity)) = bodies.get_mut(body) else {
+            continue;
+        };
+
+        let launch = bounce_pad.world_launch(transform);
+        match (rigid_body, custom_velocity, velocity) {
+            (_, Some(mut custom_velocity), _) => {
+                custom_velocity.0 = launch;
+                commands.entity(body).insert(Launched);
+            }
+            (RigidBody::Dynamic, None, Some(mut velocity)) => velocity.linvel = launch,
+            (RigidBody::Dynamic, None, None) => {
+                commands.entity(body).insert(Velocity::linear(launch));
+            }
+            _ => continue,
+        }
+        last_launches.insert((pad, body), now);
+    }
+
+    last_launches.retain(|(pad, _), last| {
+        pads.get(*pad)
+            .is_ok_and(|(_, bounce_pad, _)| now - *last < bounce_pad.cooldown)
+    });
+}
+
+/// A marker for the points of the arcs drawn by [`draw_bounce_pad_arcs`].
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct BouncePadArcMarker;
+
+/// Draws the arcs of every [`BouncePad`] while the preview is enabled.
+///
+/// Each pad keeps its own markers. They are only moved when the pad, its launch, the settings,
+/// the gravity or the colliders of the map change, and are hidden while the preview is off.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn draw_bounce_pad_arcs(
+    mut commands: Commands,
+    settings: Res<BouncePadSettings>,
+    map_debug: Option<Res<MapDebug>>,
+    palette: Res<DebugPalette>,
+    rapier_config: Res<RapierConfiguration>,
+    rapier_context: Res<RapierContext>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut assets: Local<Option<(Handle<Mesh>, Handle<StandardMaterial>)>>,
+    mut arcs: Local<HashMap<Entity, Vec<Entity>>>,
+    mut shown: Local<bool>,
+    mut drawn_gravity: Local<Vec3>,
+    mut markers: Query<(&mut Transform, &mut Visibility), With<BouncePadArcMarker>>,
+    pads: Query<(Entity, &BouncePad, &GlobalTransform)>,
+    changed_pads: Query<(), Or<(Changed<BouncePad>, Changed<GlobalTransform>)>>,
+    added_colliders: Query<(), Added<Collider>>,
+) {
+    arcs.retain(|pad, points| {
+        let keep = pads.contains(*pad);
+        if !keep {
+            for &point in points.iter() {
+                commands.entity(point).despawn();
+            }
+        }
+        keep
+    });
+
+    if !settings.preview_arcs && !map_debug.is_some_and(|map_debug| map_debug.enabled) {
+        if *shown {
+            for (_, mut visibility) in &mut markers {
+                visibility.is_visible = false;
+            }
+            *shown = false;
+        }
+        return;
+    }
+    let gravity = rapier_config.gravity * settings.gravity_scale;
+    let redraw_all = !*shown
+        || settings.is_changed()
+        || palette.is_changed()
+        || *drawn_gravity != gravity
+        || !added_colliders.is_empty();
+    *shown = true;
+    *drawn_gravity = gravity;
+
+    let colors = palette.colors();
+    let (mesh, material) = assets.get_or_insert_with(|| {
+        (
+            meshes.add(Mesh::from(shape::Cube { size: 0.08 })),
+            materials.add(StandardMaterial {
+                base_color: colors.trajectory,
+                unlit: true,
+                ..default()
+            }),
+        )
+    });
+    if palette.is_changed() {
+        if let Some(material) = materials.get_mut(material) {
+            material.base_color = colors.trajectory;
+        }
+    }
+
+    for (pad, bounce_pad, transform) in &pads {
+        if !redraw_all && !changed_pads.contains(pad) && arcs.contains_key(&pad) {
+            continue;
+        }
+
+        let filter = QueryFilter::default()
+            .exclude_sensors()
+            .exclude_collider(pad)
+            .exclude_rigid_body(pad);
+        let mut previous = transform.translation();
+        let mut points = Vec::new();
+        for point in launch_arc(
+            previous,
+            bounce_pad.world_launch(transform),
+            gravity,
+            settings.arc_step,
+            settings.arc_duration,
+        )
+        .skip(1)
+        {
+            // Stop where the arc meets the map, which is where a launched body lands.
+            let segment = point - previous;
+            let length = segment.length();
+            let hit = (length > 0.0)
+                .then(|| rapier_context.cast_ray(previous, segment / length, length, true, filter))
+                .flatten();
+            points.push(hit.map_or(point, |(_, toi)| previous + segment / length * toi));
+            if hit.is_some() {
+                break;
+            }
+            previous = point;
+        }
+
+        let pool = arcs.entry(pad).or_default();
+        for (i, point) in points.iter().enumerate() {
+            let marker_transform =
+                Transform::from_translation(*point).with_scale(Vec3::splat(colors.line_scale));
+            match pool.get(i).and_then(|&marker| markers.get_mut(marker).ok()) {
+                Some((mut transform, mut visibility)) => {
+                    *transform = marker_transform;
+                    visibility.is_visible = true;
+                }
+                None => pool.push(
+                    commands
+                        .spawn((
+                            PbrBundle {
+                                mesh: mesh.clone(),
+                                material: material.clone(),
+                                transform: marker_transform,
+                                ..default()
+                            },
+                            BouncePadArcMarker,
+                        ))
+                        .id(),
+                ),
+            }
+        }
+        for &marker in pool.iter().skip(points.len()) {
+            if let Ok((_, mut visibility)) = markers.get_mut(marker) {
+                visibility.is_visible = false;
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! A mod for building maps in code.
+//!
+//! A [`MapBuilder`] puts together the same [`Map`] that is read from a map file, so maps built
+//! by hand in code, generated procedurally, or written in tests are spawned exactly like the
+//! ones that are loaded:
+//!
+//! ```ignore
+//! let map = MapBuilder::new()
+//!     .tile(
+//!         MapShape::Cuboid { half_size: Vec3::new(10.0, 0.5, 10.0) },
+//!         MapTransform::default(),
+//!     )
+//!     .named("Floor")
+//!     .with_surface(SurfaceMaterial::GRASS)
+//!     .obstacle(
+//!         MapShape::Sphere { radius: 0.5 },
+//!         MapTransform::from_translation(DVec3::new(0.0, 3.0, 0.0)),
+//!     )
+//!     .checkpoint("Start", start_shape, start_transform)
+//!     .spawn_point(SpawnPoint::new("player"), spawn_transform)
+//!     .time_of_day(TimeOfDay::fixed(18.0))
+//!     .build()?;
+//! ```
+//!
+//! Like maps read from a file, built maps are checked for shapes that cannot be built.
+//!
+//! Maps have no lights of their own. The sun and the ambient light follow the time of day, which
+//! is set with [`MapBuilder::time_of_day`].
+//!
+//! Methods that change an object, such as [`MapBuilder::named`], apply to the object added
+//! last.
+
+use super::*;
+
+/// A fluent builder for a [`Map`].
+#[derive(Debug, Clone, Default)]
+pub struct MapBuilder {
+    map: Map,
+}
+
+impl MapBuilder {
+    /// Creates a new builder for an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds any object.
+    pub fn object(mut self, object: MapObject) -> Self {
+        self.map.objects.push(object);
+        self
+    }
+
+    /// Adds a fixed object, such as a piece of floor or a wall.
+    pub fn tile(self, shape: MapShape, transform: MapTransform) -> Self {
+        self.object(MapObject::new(shape, transform))
+    }
+
+    /// Adds an object that the physics moves, such as a crate or a ball.
+    pub fn obstacle(self, shape: MapShape, transform: MapTransform) -> Self {
+        self.object(MapObject {
+            body: MapBody::Dynamic,
+            ..MapObject::new(shape, transform)
+        })
+    }
+
+    /// Adds an event space that only sends events.
+    pub fn event_space(
+        self,
+        name: impl Into<String>,
+        shape: MapShape,
+        transform: MapTransform,
+    ) -> Self {
+        self.object(MapObject::event_space(name, shape, transform))
+    }
+
+    /// Adds a checkpoint.
+    pub fn checkpoint(
+        self,
+        name: impl Into<String>,
+        shape: MapShape,
+        transform: MapTransform,
+    ) -> Self {
+        self.object(MapObject::checkpoint(name, shape, transform))
+    }
+
+    /// Adds a kill volume.
+    pub fn kill_volume(
+        self,
+        name: impl Into<String>,
+        shape: MapShape,
+        transform: MapTransform,
+    ) -> Self {
+        self.object(MapObject::kill_volume(name, shape, transform))
+    }
+
+    /// Changes the object added last, if there is one.
+    pub fn with(mut self, change: impl FnOnce(&mut MapObject)) -> Self {
+        if let Some(object) = self.map.objects.last_mut() {
+            change(object);
+        }
+        self
+    }
+
+    /// Names the object added last.
+    pub fn named(self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        self.with(|object| object.name = Some(name))
+    }
+
+    /// Colors the object added last.
+    pub fn with_color(self, color: Color) -> Self {
+        self.with(|object| object.color = color)
+    }
+
+    /// Sets the surface material of the object added last.
+    pub fn with_surface(self, surface: SurfaceMaterial) -> Self {
+        self.with(|object| object.surface = surface)
+    }
+
+    /// Adds a place where players enter the map.
+    pub fn spawn_point(mut self, spawn_point: SpawnPoint, transform: MapTransform) -> Self {
+        self.map.spawn_points.push(MapSpawnPoint {
+            spawn_point,
+            transform,
+        });
+        self
+    }
+
+    /// Adds a timed challenge between two event spaces.
+    pub fn challenge(mut self, challenge: TimedChallenge) -> Self {
+        self.map.challenges.push(challenge);
+        self
+    }
+
+    /// Sets the time of day when the map is spawned, which places the sun and sets the light.
+    pub fn time_of_day(mut self, time_of_day: TimeOfDay) -> Self {
+        self.map.time_of_day = Some(time_of_day);
+        self
+    }
+
+    /// Finishes the map, rejecting shapes that cannot be built like [`Map::from_ron`] does.
+    pub fn build(self) -> Result<Map> {
+        self.map.validate()?;
+        Ok(self.map)
+    }
+}
+
+impl TryFrom<MapBuilder> for Map {
+    type Error = MapBuilderError;
+
+    fn try_from(builder: MapBuilder) -> Result<Self> {
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_rejects_degenerate_heightfield() {
+        let heightfield = |heights: Vec<f32>, num_rows, num_cols| MapShape::Heightfield {
+            heights,
+            num_rows,
+            num_cols,
+            size: Vec3::ONE,
+            holes: Vec::new(),
+            skirt_depth: 0.0,
+        };
+
+        for shape in [
+            heightfield(vec![0.0; 3], 1, 3),
+            heightfield(vec![0.0; 5], 2, 3),
+        ] {
+            assert!(matches!(
+                MapBuilder::new()
+                    .tile(MapShape::Sphere { radius: 1.0 }, MapTransform::default())
+                    .tile(shape, MapTransform::default())
+                    .build(),
+                Err(MapBuilderError::InvalidShape { .. })
+            ));
+        }
+
+        let map = MapBuilder::new()
+            .tile(heightfield(vec![0.0; 6], 2, 3), MapTransform::default())
+            .build()
+            .unwrap();
+        assert_eq!(map.objects.len(), 1);
+    }
+}
@@ -0,0 +1,240 @@
+//! A mod for timed challenges that run between two event spaces of a map.
+//!
+//! A [`TimedChallenge`] starts the clock for a player who enters its start [`EventSpace`] and
+//! stops it when the same player enters its finish event space. The time is compared to the par
+//! times of the challenge to award a [`Medal`], and a [`ChallengeEvent`] reports every start and
+//! finish so that game modes and UI can show the results.
+//!
+//! The clock only runs while the game is playing, so pausing does not count against the player.
+//! Entering the start again restarts the run, and respawning abandons it.
+
+use super::{checkpoint::*, event_space::*, *};
+use crate::state::*;
+
+/// The medals that can be awarded for finishing a [`TimedChallenge`], from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Medal {
+    /// Finished within the gold par time.
+    Gold,
+    /// Finished within the silver par time.
+    Silver,
+    /// Finished within the bronze par time.
+    Bronze,
+}
+
+/// The times to beat for every [`Medal`] of a [`TimedChallenge`], in seconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, Reflect, FromReflect)]
+pub struct ParTimes {
+    /// The time to beat for a gold medal.
+    pub gold: f32,
+    /// The time to beat for a silver medal.
+    pub silver: f32,
+    /// The time to beat for a bronze medal.
+    pub bronze: f32,
+}
+
+impl ParTimes {
+    /// The best medal earned by a time, if any.
+    pub fn medal(&self, time: f32) -> Option<Medal> {
+        [
+            (Medal::Gold, self.gold),
+            (Medal::Silver, self.silver),
+            (Medal::Bronze, self.bronze),
+        ]
+        .into_iter()
+        .find(|(_, par)| time <= *par)
+        .map(|(medal, _)| medal)
+    }
+}
+
+/// A timed run from one event space to another.
+#[derive(Component, Debug, Clone, Default, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+pub struct TimedChallenge {
+    /// The name used to identify the challenge in results.
+    pub name: String,
+    /// The name of the [`EventSpace`] that starts the clock.
+    pub start: String,
+    /// The name of the [`EventSpace`] that stops the clock.
+    pub finish: String,
+    /// The times to beat for each medal.
+    pub par_times: ParTimes,
+}
+
+impl TimedChallenge {
+    /// Creates a new [`TimedChallenge`] between two event spaces.
+    pub fn new(
+        name: impl Into<String>,
+        start: impl Into<String>,
+        finish: impl Into<String>,
+        par_times: ParTimes,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            start: start.into(),
+            finish: finish.into(),
+            par_times,
+        }
+    }
+
+    /// Spawns the challenge so that it starts timing players.
+    pub fn spawn(&self, commands: &mut Commands) -> Entity {
+        commands
+            .spawn((
+                self.clone(),
+                ChallengeRecord::default(),
+                Name::new(self.name.clone()),
+            ))
+            .id()
+    }
+}
+
+/// The best result of a [`TimedChallenge`] so far.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct ChallengeRecord {
+    /// The best time, in seconds.
+    pub best_time: Option<f32>,
+    /// The best medal.
+    pub best_medal: Option<Medal>,
+}
+
+/// A player in the middle of a [`TimedChallenge`].
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct ChallengeRun {
+    /// The challenge being run.
+    pub challenge: Entity,
+    /// How long the run has lasted, in seconds.
+    pub elapsed: f32,
+}
+
+/// Events sent as players take on [`TimedChallenge`]s.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChallengeEvent {
+    /// A player entered the start of a challenge.
+    Started {
+        /// The challenge entity.
+        challenge: Entity,
+        /// The player.
+        player: Entity,
+    },
+    /// A player reached the finish of a challenge.
+    Finished {
+        /// The challenge entity.
+        challenge: Entity,
+        /// The player.
+        player: Entity,
+        /// How long the run took, in seconds.
+        time: f32,
+        /// The medal earned, if any.
+        medal: Option<Medal>,
+        /// Whether the time is the best one so far.
+        new_record: bool,
+    },
+    /// A player abandoned a challenge by respawning.
+    Abandoned {
+        /// The challenge entity.
+        challenge: Entity,
+        /// The player.
+        player: Entity,
+    },
+}
+
+/// A plugin that times players through [`TimedChallenge`]s.
+#[derive(Default)]
+pub struct TimedChallengePlugin;
+
+impl TimedChallengePlugin {
+    /// Creates a new [`TimedChallengePlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for TimedChallengePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<TimedChallenge>()
+            .register_type::<ParTimes>()
+            .add_event::<EventSpaceEvent>()
+            .add_event::<RespawnEvent>()
+            .add_event::<ChallengeEvent>()
+            .add_system(tick_challenge_runs.with_run_criteria(is_playing))
+            .add_system(
+                update_challenges
+                    .with_run_criteria(is_playing)
+                    .after(tick_challenge_runs),
+            );
+    }
+}
+
+/// Advances the clock of every run.
+pub fn tick_challenge_runs(time: Res<Time>, mut runs: Query<&mut ChallengeRun>) {
+    for mut run in &mut runs {
+        run.elapsed += time.delta_seconds();
+    }
+}
+
+/// Starts, finishes, and abandons runs as players enter the gates of challenges or respawn.
+#[allow(clippy::too_many_arguments)]
+pub fn update_challenges(
+    mut commands: Commands,
+    mut event_space_events: EventReader<EventSpaceEvent>,
+    mut respawn_events: EventReader<RespawnEvent>,
+    mut challenge_events: EventWriter<ChallengeEvent>,
+    event_spaces: Query<&EventSpace>,
+    mut challenges: Query<(Entity, &TimedChallenge, &mut ChallengeRecord)>,
+    players: Query<Option<&ChallengeRun>, With<Player>>,
+) {
+    for event in respawn_events.iter() {
+        if let Ok(Some(run)) = players.get(event.entity) {
+            commands.entity(event.entity).remove::<ChallengeRun>();
+            challenge_events.send(ChallengeEvent::Abandoned {
+                challenge: run.challenge,
+                player: event.entity,
+            });
+        }
+    }
+
+    for event in event_space_events.iter() {
+        let EventSpaceEvent::Entered { space, entity } = *event else {
+            continue;
+        };
+        let (Ok(event_space), Ok(run)) = (event_spaces.get(space), players.get(entity)) else {
+            continue;
+        };
+
+        for (challenge, timed_challenge, mut record) in &mut challenges {
+            if event_space.name == timed_challenge.start {
+                commands.entity(entity).insert(ChallengeRun {
+                    challenge,
+                    elapsed: 0.0,
+                });
+                challenge_events.send(ChallengeEvent::Started {
+                    challenge,
+                    player: entity,
+                });
+            } else if event_space.name == timed_challenge.finish {
+                let Some(run) = run.filter(|run| run.challenge == challenge) else {
+                    continue;
+                };
+                let time = run.elapsed;
+                let medal = timed_challenge.par_times.medal(time);
+                let new_record = record.best_time.is_none_or(|best| time < best);
+                if new_record {
+                    record.best_time = Some(time);
+                }
+                record.best_medal = match (record.best_medal, medal) {
+                    (Some(best), Some(medal)) => Some(best.min(medal)),
+                    (best, medal) => best.or(medal),
+                };
+                commands.entity(entity).remove::<ChallengeRun>();
+                challenge_events.send(ChallengeEvent::Finished {
+                    challenge,
+                    player: entity,
+                    time,
+                    medal,
+                    new_record,
+                });
+            }
+        }
+    }
+}
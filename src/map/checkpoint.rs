@@ -0,0 +1,174 @@
+//! A mod for checkpoints and respawning players.
+//!
+//! Entering an [`EventSpace`] marked as a [`Checkpoint`] records where the player is in the
+//! [`RespawnState`] resource. Falling below the kill height or entering an event space marked as
+//! a [`KillVolume`] teleports the player back to the last checkpoint, or to where the player was
+//! first seen if no checkpoint has been reached yet.
+//!
+//! Players are the entities with a [`Player`] marker, such as the ones created by
+//! [`spawn_player_at`](super::spawn::spawn_player_at). Other characters, such as steering agents,
+//! neither record checkpoints nor respawn. Positions are recorded in world space, so they stay
+//! valid when the [`FloatingOrigin`] moves.
+
+use super::{event_space::*, *};
+use crate::{controller::*, state::*, teleport::*};
+
+/// A marker for [`EventSpace`]s that record the respawn position of players entering them.
+#[derive(Component, Debug, Clone, Copy, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct Checkpoint;
+
+/// A marker for [`EventSpace`]s that respawn players entering them.
+#[derive(Component, Debug, Clone, Copy, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct KillVolume;
+
+/// Where players are sent back to when they die.
+#[derive(Resource, Debug, Clone, Copy, Default)]
+pub struct RespawnState {
+    /// The world transform to respawn at, or `None` before any player has been seen.
+    pub transform: Option<MapTransform>,
+    /// The velocity the player had when the respawn transform was recorded.
+    pub velocity: Vec3,
+    /// The last checkpoint that was reached, if any.
+    pub checkpoint: Option<Entity>,
+}
+
+/// An event sent when a player is sent back to the respawn position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RespawnEvent {
+    /// The player that respawned.
+    pub entity: Entity,
+}
+
+/// Settings for the [`CheckpointPlugin`].
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct CheckpointSettings {
+    /// Players whose world height is below this respawn.
+    pub kill_y: f64,
+}
+
+impl Default for CheckpointSettings {
+    fn default() -> Self {
+        Self { kill_y: -100.0 }
+    }
+}
+
+/// A plugin that records checkpoints and respawns players.
+#[derive(Default)]
+pub struct CheckpointPlugin {
+    /// The settings used by the plugin.
+    pub settings: CheckpointSettings,
+}
+
+impl CheckpointPlugin {
+    /// Creates a new [`CheckpointPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`CheckpointPlugin`] that respawns players below the given world height.
+    pub fn with_kill_y(kill_y: f64) -> Self {
+        Self {
+            settings: CheckpointSettings { kill_y },
+        }
+    }
+}
+
+impl Plugin for CheckpointPlugin {
+    fn build(&self, app: &mut App) {
+        register_map_types(app);
+
+        app.register_type::<Checkpoint>()
+            .register_type::<KillVolume>()
+            .insert_resource(self.settings)
+            .init_resource::<RespawnState>()
+            .add_event::<EventSpaceEvent>()
+            .add_event::<RespawnEvent>()
+            .add_event::<TeleportEvent>()
+            .add_system(record_checkpoints)
+            .add_system(
+                respawn_players
+                    .with_run_criteria(is_playing)
+                    .after(record_checkpoints),
+            );
+    }
+}
+
+/// Records the respawn position when a player first appears or enters a [`Checkpoint`].
+pub fn record_checkpoints(
+    origin: Option<Res<FloatingOrigin>>,
+    mut respawn_state: ResMut<RespawnState>,
+    mut events: EventReader<EventSpaceEvent>,
+    checkpoints: Query<(), With<Checkpoint>>,
+    players: Query<(&Transform, &CustomVelocity), With<Player>>,
+    new_players: Query<Entity, Added<Player>>,
+) {
+    let origin = origin.map(|origin| *origin).unwrap_or_default();
+    let record = |respawn_state: &mut RespawnState, player: Entity| {
+        if let Ok((transform, velocity)) = players.get(player) {
+            respawn_state.transform = Some(MapTransform::from_transform(transform, &origin));
+            respawn_state.velocity = velocity.0;
+        }
+    };
+
+    if respawn_state.transform.is_none() {
+        if let Some(player) = new_players.iter().next() {
+            record(&mut respawn_state, player);
+        }
+    }
+
+    for event in events.iter() {
+        let EventSpaceEvent::Entered { space, entity } = *event else {
+            continue;
+        };
+        if checkpoints.contains(space) && players.contains(entity) {
+            record(&mut respawn_state, entity);
+            respawn_state.checkpoint = Some(space);
+        }
+    }
+}
+
+/// Sends players back to the respawn position when they fall too low or enter a [`KillVolume`].
+///
+/// Players are teleported, so they land on the nearest free spot if the respawn position has been
+/// blocked since it was recorded.
+#[allow(clippy::too_many_arguments)]
+pub fn respawn_players(
+    mut commands: Commands,
+    settings: Res<CheckpointSettings>,
+    origin: Option<Res<FloatingOrigin>>,
+    respawn_state: Res<RespawnState>,
+    mut event_space_events: EventReader<EventSpaceEvent>,
+    mut respawn_events: EventWriter<RespawnEvent>,
+    kill_volumes: Query<(), With<KillVolume>>,
+    players: Query<(Entity, &Transform), With<Player>>,
+) {
+    let Some(respawn_transform) = respawn_state.transform else {
+        return;
+    };
+    let origin = origin.map(|origin| *origin).unwrap_or_default();
+
+    let killed: Vec<Entity> = event_space_events
+        .iter()
+        .filter_map(|event| match *event {
+            EventSpaceEvent::Entered { space, entity } if kill_volumes.contains(space) => {
+                Some(entity)
+            }
+            _ => None,
+        })
+        .collect();
+
+    for (entity, transform) in &players {
+        let height = origin.local_to_world(transform.translation).y;
+        if height >= settings.kill_y && !killed.contains(&entity) {
+            continue;
+        }
+
+        commands.add(
+            TeleportEntity::new(entity, respawn_transform.to_transform(&origin))
+                .with_velocity(respawn_state.velocity),
+        );
+        respawn_events.send(RespawnEvent { entity });
+    }
+}
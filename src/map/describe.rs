@@ -0,0 +1,536 @@
+//! A mod that describes the structure of a map in words.
+//!
+//! [`Map::describe`] summarizes an authored map as a [`MapDescription`]: its rooms, the
+//! connections between them, the objectives a player can reach, and the hazards to avoid. The
+//! description can be serialized for tools or printed with [`Display`](std::fmt::Display) as
+//! plain sentences, without tables or drawings, so that it reads well in a screen reader and in
+//! the documentation of a map.
+//!
+//! Maps have no separate room graph, so the graph is derived from the objects. Every trigger
+//! [`EventSpace`] that is not a secret area is a room. Objects belong to the smallest room that
+//! contains their origin, rooms whose bounds touch are connected by an opening, doors connect the
+//! rooms they touch, and zip lines connect the rooms of their two ends. Bounds are the axis-aligned
+//! boxes around the shapes, so rotated or round rooms are treated a little larger than they are.
+
+use super::*;
+
+use std::fmt;
+
+/// How far apart two boxes may be and still count as touching.
+const TOUCH_DISTANCE: f64 = 0.01;
+
+/// A structured summary of a [`Map`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MapDescription {
+    /// The rooms of the map.
+    pub rooms: Vec<RoomDescription>,
+    /// The ways between rooms.
+    pub connections: Vec<ConnectionDescription>,
+    /// The places and things a player is meant to reach.
+    pub objectives: Vec<ObjectiveDescription>,
+    /// The things that hurt or move a player.
+    pub hazards: Vec<HazardDescription>,
+}
+
+/// A room of a map.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomDescription {
+    /// The name of the event space of the room.
+    pub name: String,
+    /// The center of the room.
+    pub center: DVec3,
+    /// The width, height, and depth of the room.
+    pub size: DVec3,
+    /// The names of the named objects in the room.
+    pub contents: Vec<String>,
+}
+
+/// How two rooms are connected.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConnectionKind {
+    /// The rooms touch, so players can walk between them.
+    Opening,
+    /// A door stands between the rooms.
+    Door {
+        /// The event space or pressure plate that opens the door, if the door has one.
+        trigger: Option<String>,
+    },
+    /// A zip line carries players from one room to the other.
+    ZipLine,
+}
+
+/// A way between rooms.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionDescription {
+    /// How the rooms are connected.
+    pub kind: ConnectionKind,
+    /// The name of the object that connects the rooms, if it has one.
+    pub name: Option<String>,
+    /// The rooms that are connected, where `None` is outside of every room.
+    pub rooms: Vec<Option<String>>,
+}
+
+/// The kinds of objectives.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ObjectiveKind {
+    /// A place where players enter the map.
+    SpawnPoint {
+        /// The team allowed to spawn there, if it is limited to one.
+        team: Option<String>,
+    },
+    /// A checkpoint that players respawn at.
+    Checkpoint,
+    /// A timed run from one event space to another.
+    Challenge {
+        /// The event space that starts the clock.
+        start: String,
+        /// The event space that stops the clock.
+        finish: String,
+    },
+    /// A secret area to discover.
+    SecretArea,
+    /// An object that players can use.
+    Interactable {
+        /// The prompt shown to players.
+        prompt: String,
+    },
+}
+
+/// Something a player is meant to reach.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectiveDescription {
+    /// What the objective is.
+    pub kind: ObjectiveKind,
+    /// The name of the objective, if it has one.
+    pub name: Option<String>,
+    /// The room of the objective, if it is in one.
+    pub room: Option<String>,
+}
+
+/// The kinds of hazards.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum HazardKind {
+    /// A volume that kills players.
+    KillVolume,
+    /// A door that damages the characters it would crush.
+    CrushingDoor {
+        /// The damage dealt every second.
+        per_second: f32,
+    },
+    /// A volume that pushes bodies with wind.
+    Wind {
+        /// How strongly the wind pushes, in units per second squared.
+        strength: f32,
+    },
+    /// A volume that pulls or pushes bodies.
+    Magnet {
+        /// The force at its center. Negative strengths push bodies away.
+        strength: f32,
+    },
+}
+
+/// Something that hurts or moves a player.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HazardDescription {
+    /// What the hazard is.
+    pub kind: HazardKind,
+    /// The name of the hazard, if it has one.
+    pub name: Option<String>,
+    /// The room of the hazard, if it is in one.
+    pub room: Option<String>,
+}
+
+/// The axis-aligned box around a shape placed with a map transform.
+fn bounds(shape: &MapShape, transform: &MapTransform) -> (DVec3, DVec3) {
+    let half_size = match shape {
+        MapShape::Plane { half_size } => Vec3::new(half_size.x, 0.0, half_size.y),
+        MapShape::Cuboid { half_size } => *half_size,
+        MapShape::Sphere { radius } => Vec3::splat(*radius),
+        MapShape::Capsule {
+            half_length,
+            radius,
+        } => Vec3::new(*radius, half_length + radius, *radius),
+        MapShape::Heightfield { size, .. } => 0.5 * *size,
+    } * transform.scale;
+    let rotation = Mat3::from_quat(transform.rotation);
+    let extents = Vec3::new(
+        rotation.row(0).abs().dot(half_size),
+        rotation.row(1).abs().dot(half_size),
+        rotation.row(2).abs().dot(half_size),
+    )
+    .as_dvec3();
+    let center = transform.translation;
+    (center - extents, center + extents)
+}
+
+/// Whether two boxes overlap or touch.
+fn touches(a: (DVec3, DVec3), b: (DVec3, DVec3)) -> bool {
+    (a.0 - TOUCH_DISTANCE).cmple(b.1).all() && (b.0 - TOUCH_DISTANCE).cmple(a.1).all()
+}
+
+/// Whether a box is entirely inside another.
+fn contains(outer: (DVec3, DVec3), inner: (DVec3, DVec3)) -> bool {
+    outer.0.cmple(inner.0).all() && inner.1.cmple(outer.1).all()
+}
+
+/// The volume of a box.
+fn volume(bounds: (DVec3, DVec3)) -> f64 {
+    let size = bounds.1 - bounds.0;
+    size.x * size.y * size.z
+}
+
+impl Map {
+    /// Summarizes the rooms, connections, objectives, and hazards of the map.
+    pub fn describe(&self) -> MapDescription {
+        let is_room = |object: &MapObject| {
+            object.event_space.is_some()
+                && object.role == EventSpaceRole::Trigger
+                && object.secret_area.is_none()
+        };
+        let rooms: Vec<(&String, (DVec3, DVec3))> = self
+            .objects
+            .iter()
+            .filter(|object| is_room(object))
+            .filter_map(|object| {
+                let name = object.event_space.as_ref()?;
+                Some((name, bounds(&object.shape, &object.transform)))
+            })
+            .collect();
+        // The smallest room around a point, so that rooms inside rooms win.
+        let room_at = |point: DVec3| {
+            rooms
+                .iter()
+                .filter(|(_, (min, max))| min.cmple(point).all() && point.cmple(*max).all())
+                .min_by(|(_, a), (_, b)| volume(*a).total_cmp(&volume(*b)))
+                .map(|(name, _)| (*name).clone())
+        };
+
+        let mut description = MapDescription {
+            rooms: rooms
+                .iter()
+                .map(|(name, (min, max))| RoomDescription {
+                    name: (*name).clone(),
+                    center: 0.5 * (*min + *max),
+                    size: *max - *min,
+                    contents: Vec::new(),
+                })
+                .collect(),
+            ..default()
+        };
+
+        for (i, (name, a)) in rooms.iter().enumerate() {
+            for (other, b) in &rooms[i + 1..] {
+                if touches(*a, *b) && !contains(*a, *b) && !contains(*b, *a) {
+                    description.connections.push(ConnectionDescription {
+                        kind: ConnectionKind::Opening,
+                        name: None,
+                        rooms: vec![Some((*name).clone()), Some((*other).clone())],
+                    });
+                }
+            }
+        }
+
+        for spawn_point in &self.spawn_points {
+            description.objectives.push(ObjectiveDescription {
+                kind: ObjectiveKind::SpawnPoint {
+                    team: spawn_point.spawn_point.team.clone(),
+                },
+                name: Some(spawn_point.spawn_point.tag.clone()),
+                room: room_at(spawn_point.transform.translation),
+            });
+        }
+
+        for object in &self.objects {
+            let position = object.transform.translation;
+            let room = room_at(position);
+            let name = object.name.clone().or_else(|| object.event_space.clone());
+
+            if !is_room(object) {
+                if let (Some(room), Some(name)) = (&room, &object.name) {
+                    if let Some(room) = description.rooms.iter_mut().find(|r| &r.name == room) {
+                        room.contents.push(name.clone());
+                    }
+                }
+            }
+
+            if let Some(door) = &object.door {
+                let (trigger, crush) = match door {
+                    MapDoor::Sliding(door) => (&door.trigger, door.crush),
+                    MapDoor::Rotating(door) => (&door.trigger, door.crush),
+                };
+                let door_bounds = bounds(&object.shape, &object.transform);
+                let mut door_rooms: Vec<Option<String>> = rooms
+                    .iter()
+                    .filter(|(_, room)| touches(*room, door_bounds))
+                    .map(|(name, _)| Some((*name).clone()))
+                    .collect();
+                // A door on the edge of a single room leads outside.
+                if door_rooms.len() < 2 {
+                    door_rooms.push(None);
+                }
+                description.connections.push(ConnectionDescription {
+                    kind: ConnectionKind::Door {
+                        trigger: trigger.clone(),
+                    },
+                    name: name.clone(),
+                    rooms: door_rooms,
+                });
+                if let CrushBehavior::Damage { per_second } = crush {
+                    description.hazards.push(HazardDescription {
+                        kind: HazardKind::CrushingDoor { per_second },
+                        name: name.clone(),
+                        room: room.clone(),
+                    });
+                }
+            }
+            if let Some(zip_line) = &object.zip_line {
+                let end = position
+                    + (object.transform.rotation * (object.transform.scale * zip_line.end))
+                        .as_dvec3();
+                description.connections.push(ConnectionDescription {
+                    kind: ConnectionKind::ZipLine,
+                    name: name.clone(),
+                    rooms: vec![room.clone(), room_at(end)],
+                });
+            }
+
+            if object.event_space.is_some() {
+                match object.role {
+                    EventSpaceRole::Checkpoint => {
+                        description.objectives.push(ObjectiveDescription {
+                            kind: ObjectiveKind::Checkpoint,
+                            name: name.clone(),
+                            room: room.clone(),
+                        });
+                    }
+                    EventSpaceRole::Kill => {
+                        description.hazards.push(HazardDescription {
+                            kind: HazardKind::KillVolume,
+                            name: name.clone(),
+                            room: room.clone(),
+                        });
+                    }
+                    EventSpaceRole::Trigger => {}
+                }
+            }
+            if object.secret_area.is_some() {
+                description.objectives.push(ObjectiveDescription {
+                    kind: ObjectiveKind::SecretArea,
+                    name: name.clone(),
+                    room: room.clone(),
+                });
+            }
+            if let Some(interactable) = &object.interactable {
+                description.objectives.push(ObjectiveDescription {
+                    kind: ObjectiveKind::Interactable {
+                        prompt: interactable.prompt.clone(),
+                    },
+                    name: name.clone(),
+                    room: room.clone(),
+                });
+            }
+            if let Some(wind) = &object.wind {
+                description.hazards.push(HazardDescription {
+                    kind: HazardKind::Wind {
+                        strength: wind.acceleration.length(),
+                    },
+                    name: name.clone(),
+                    room: room.clone(),
+                });
+            }
+            if let Some(magnet) = &object.magnet {
+                description.hazards.push(HazardDescription {
+                    kind: HazardKind::Magnet {
+                        strength: magnet.strength,
+                    },
+                    name: name.clone(),
+                    room,
+                });
+            }
+        }
+
+        for challenge in &self.challenges {
+            let start = self
+                .objects
+                .iter()
+                .find(|object| object.event_space.as_ref() == Some(&challenge.start));
+            description.objectives.push(ObjectiveDescription {
+                kind: ObjectiveKind::Challenge {
+                    start: challenge.start.clone(),
+                    finish: challenge.finish.clone(),
+                },
+                name: Some(challenge.name.clone()),
+                room: start.and_then(|start| room_at(start.transform.translation)),
+            });
+        }
+
+        description
+    }
+}
+
+/// Writes a name in quotes, or a placeholder for unnamed things.
+fn quoted(name: &Option<String>) -> String {
+    match name {
+        Some(name) => format!("\"{name}\""),
+        None => "unnamed".to_string(),
+    }
+}
+
+/// Writes a room name, or a phrase for the space outside of every room.
+fn room_name(room: &Option<String>) -> String {
+    match room {
+        Some(room) => format!("\"{room}\""),
+        None => "the outside".to_string(),
+    }
+}
+
+/// Writes where something is, in a room or outside of every room.
+fn location(room: &Option<String>) -> String {
+    match room {
+        Some(room) => format!("in \"{room}\""),
+        None => "outside".to_string(),
+    }
+}
+
+/// Writes a list of words as a sentence fragment, such as `a, b, and c`.
+fn list(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [item] => item.clone(),
+        [first, second] => format!("{first} and {second}"),
+        [rest @ .., last] => format!("{}, and {last}", rest.join(", ")),
+    }
+}
+
+/// Writes a count with the singular or plural of a word.
+fn count(n: usize, singular: &str, plural: &str) -> String {
+    format!("{n} {}", if n == 1 { singular } else { plural })
+}
+
+impl fmt::Display for MapDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "The map has {}.",
+            list(&[
+                count(self.rooms.len(), "room", "rooms"),
+                count(self.connections.len(), "connection", "connections"),
+                count(self.objectives.len(), "objective", "objectives"),
+                count(self.hazards.len(), "hazard", "hazards"),
+            ])
+        )?;
+
+        if !self.rooms.is_empty() {
+            writeln!(f, "\nRooms:")?;
+        }
+        for room in &self.rooms {
+            write!(
+                f,
+                "- Room \"{}\", {:.0} wide, {:.0} high, and {:.0} deep, centered at {:.0}, {:.0}, {:.0}.",
+                room.name,
+                room.size.x,
+                room.size.y,
+                room.size.z,
+                room.center.x,
+                room.center.y,
+                room.center.z,
+            )?;
+            if room.contents.is_empty() {
+                writeln!(f)?;
+            } else {
+                let contents: Vec<String> = room
+                    .contents
+                    .iter()
+                    .map(|name| format!("\"{name}\""))
+                    .collect();
+                writeln!(f, " It contains {}.", list(&contents))?;
+            }
+        }
+
+        if !self.connections.is_empty() {
+            writeln!(f, "\nConnections:")?;
+        }
+        for connection in &self.connections {
+            let rooms: Vec<String> = connection.rooms.iter().map(room_name).collect();
+            match &connection.kind {
+                ConnectionKind::Opening => {
+                    writeln!(f, "- An opening connects {}.", list(&rooms))?;
+                }
+                ConnectionKind::Door { trigger } => {
+                    write!(
+                        f,
+                        "- Door {} connects {}.",
+                        quoted(&connection.name),
+                        list(&rooms)
+                    )?;
+                    match trigger {
+                        Some(trigger) => writeln!(f, " It is opened by \"{trigger}\".")?,
+                        None => writeln!(f)?,
+                    }
+                }
+                ConnectionKind::ZipLine => {
+                    writeln!(
+                        f,
+                        "- Zip line {} runs from {}.",
+                        quoted(&connection.name),
+                        rooms.join(" to ")
+                    )?;
+                }
+            }
+        }
+
+        if !self.objectives.is_empty() {
+            writeln!(f, "\nObjectives:")?;
+        }
+        for objective in &self.objectives {
+            let name = quoted(&objective.name);
+            let room = location(&objective.room);
+            match &objective.kind {
+                ObjectiveKind::SpawnPoint { team } => {
+                    write!(f, "- Spawn point {name} {room}")?;
+                    match team {
+                        Some(team) => writeln!(f, ", for team \"{team}\".")?,
+                        None => writeln!(f, ".")?,
+                    }
+                }
+                ObjectiveKind::Checkpoint => writeln!(f, "- Checkpoint {name} {room}.")?,
+                ObjectiveKind::Challenge { start, finish } => writeln!(
+                    f,
+                    "- Timed challenge {name}, from \"{start}\" to \"{finish}\"."
+                )?,
+                ObjectiveKind::SecretArea => writeln!(f, "- Secret area {name} {room}.")?,
+                ObjectiveKind::Interactable { prompt } => {
+                    writeln!(f, "- Usable object {name} {room}: \"{prompt}\".")?
+                }
+            }
+        }
+
+        if !self.hazards.is_empty() {
+            writeln!(f, "\nHazards:")?;
+        }
+        for hazard in &self.hazards {
+            let name = quoted(&hazard.name);
+            let room = location(&hazard.room);
+            match &hazard.kind {
+                HazardKind::KillVolume => writeln!(f, "- Kill volume {name} {room}.")?,
+                HazardKind::CrushingDoor { per_second } => writeln!(
+                    f,
+                    "- Door {name} {room} deals {per_second:.0} damage per second to anyone it crushes."
+                )?,
+                HazardKind::Wind { strength } => {
+                    writeln!(f, "- Wind {name} {room}, pushing with a strength of {strength:.0}.")?
+                }
+                HazardKind::Magnet { strength } if *strength < 0.0 => writeln!(
+                    f,
+                    "- Magnet {name} {room}, pushing away with a strength of {:.0}.",
+                    -strength
+                )?,
+                HazardKind::Magnet { strength } => {
+                    writeln!(f, "- Magnet {name} {room}, pulling with a strength of {strength:.0}.")?
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+//! A mod for invisible volumes that report when something enters or leaves them.
+//!
+//! Event spaces are Rapier sensors. They are the building block for map logic such as
+//! checkpoints, triggers, and kill volumes.
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+/// A volume that sends [`EventSpaceEvent`]s when colliders enter or leave it.
+#[derive(Component, Debug, Clone, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct EventSpace {
+    /// The name used to identify the event space in map logic.
+    pub name: String,
+    /// Whether anything has ever entered the event space.
+    pub triggered: bool,
+}
+
+impl EventSpace {
+    /// Creates a new [`EventSpace`].
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            triggered: false,
+        }
+    }
+}
+
+/// The components necessary to create an event space.
+#[derive(Bundle, Clone)]
+pub struct EventSpaceBundle {
+    /// The event space itself.
+    pub event_space: EventSpace,
+    /// The volume of the event space.
+    pub collider: Collider,
+    /// Marks the collider as a sensor so nothing collides with it.
+    pub sensor: Sensor,
+    /// Enables the collision events used to detect entries and exits.
+    pub active_events: ActiveEvents,
+    /// Allows kinematic characters to be detected by the fixed sensor.
+    pub active_collision_types: ActiveCollisionTypes,
+    /// The transform of the event space.
+    pub transform: Transform,
+    /// The global transform of the event space.
+    pub global_transform: GlobalTransform,
+}
+
+impl Default for EventSpaceBundle {
+    fn default() -> Self {
+        Self {
+            event_space: EventSpace::default(),
+            collider: Collider::ball(0.0),
+            sensor: Sensor,
+            active_events: ActiveEvents::COLLISION_EVENTS,
+            active_collision_types: ActiveCollisionTypes::default()
+                | ActiveCollisionTypes::KINEMATIC_STATIC,
+            transform: Transform::default(),
+            global_transform: GlobalTransform::default(),
+        }
+    }
+}
+
+impl EventSpaceBundle {
+    /// Creates a new [`EventSpaceBundle`].
+    pub fn new(name: impl Into<String>, collider: Collider, transform: Transform) -> Self {
+        Self {
+            event_space: EventSpace::new(name),
+            collider,
+            transform,
+            ..default()
+        }
+    }
+}
+
+/// Events sent when an entity enters or leaves an [`EventSpace`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventSpaceEvent {
+    /// An entity entered an event space.
+    Entered {
+        /// The event space entity.
+        space: Entity,
+        /// The entity that entered.
+        entity: Entity,
+    },
+    /// An entity left an event space.
+    Exited {
+        /// The event space entity.
+        space: Entity,
+        /// The entity that left.
+        entity: Entity,
+    },
+}
+
+/// A plugin that turns Rapier collision events into [`EventSpaceEvent`]s.
+#[derive(Default)]
+pub struct EventSpacePlugin;
+
+impl EventSpacePlugin {
+    /// Creates a new [`EventSpacePlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for EventSpacePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<EventSpace>()
+            .add_event::<EventSpaceEvent>()
+            .add_system_to_stage(CoreStage::PreUpdate, detect_event_spaces);
+    }
+}
+
+/// Sends [`EventSpaceEvent`]s for every collision event involving an [`EventSpace`].
+pub fn detect_event_spaces(
+    mut collision_events: EventReader<CollisionEvent>,
+    mut event_spaces: Query<&mut EventSpace>,
+    mut events: EventWriter<EventSpaceEvent>,
+) {
+    for collision_event in collision_events.iter() {
+        let (a, b, entered) = match *collision_event {
+            CollisionEvent::Started(a, b, _) => (a, b, true),
+            CollisionEvent::Stopped(a, b, _) => (a, b, false),
+        };
+
+        for (space, entity) in [(a, b), (b, a)] {
+            let Ok(mut event_space) = event_spaces.get_mut(space) else {
+                continue;
+            };
+
+            if entered {
+                event_space.triggered = true;
+                events.send(EventSpaceEvent::Entered { space, entity });
+            } else {
+                events.send(EventSpaceEvent::Exited { space, entity });
+            }
+        }
+    }
+}
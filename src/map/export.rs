@@ -0,0 +1,247 @@
+//! A mod that exports the collision geometry of a map to JSON.
+//!
+//! The exported format does not depend on Bevy or Rapier, so servers or other engines can rebuild
+//! the same physics world from it. Every collider is written with its world transform, using
+//! `f64` translations like [`MapTransform`], and one of a small set of shapes: primitives,
+//! convex hulls, triangle meshes, and compounds of those. Heightfields are written as triangle
+//! meshes, without the cells removed for holes.
+//!
+//! Vectors are written as `[x, y, z]` arrays and rotations as `[x, y, z, w]` quaternions.
+
+use super::*;
+
+use bevy_rapier3d::parry::{math::Point, shape::TypedShape};
+
+/// A collider shape in the exported format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ExportedShape {
+    /// A sphere centered on the origin.
+    Ball {
+        /// The radius of the sphere.
+        radius: f32,
+    },
+    /// A box centered on the origin.
+    Cuboid {
+        /// Half the size of the box along each axis.
+        half_extents: Vec3,
+    },
+    /// A capsule around the segment between two points.
+    Capsule {
+        /// The first end of the segment.
+        a: Vec3,
+        /// The second end of the segment.
+        b: Vec3,
+        /// The radius of the capsule.
+        radius: f32,
+    },
+    /// A cylinder along the Y axis, centered on the origin.
+    Cylinder {
+        /// Half the height of the cylinder.
+        half_height: f32,
+        /// The radius of the cylinder.
+        radius: f32,
+    },
+    /// A cone along the Y axis, centered on the origin, with its tip pointing up.
+    Cone {
+        /// Half the height of the cone.
+        half_height: f32,
+        /// The radius of the base of the cone.
+        radius: f32,
+    },
+    /// Everything behind a plane going through the origin.
+    HalfSpace {
+        /// The normal of the plane, pointing out of the solid side.
+        normal: Vec3,
+    },
+    /// The convex hull of a set of points.
+    ConvexHull {
+        /// The vertices of the hull.
+        points: Vec<Vec3>,
+    },
+    /// A hollow triangle mesh.
+    Trimesh {
+        /// The vertices of the mesh.
+        vertices: Vec<Vec3>,
+        /// The vertex indices of every triangle.
+        indices: Vec<[u32; 3]>,
+    },
+    /// A group of shapes, each with its own position relative to the collider.
+    Compound {
+        /// The shapes of the group.
+        shapes: Vec<ExportedChildShape>,
+    },
+}
+
+/// A shape of an [`ExportedShape::Compound`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedChildShape {
+    /// The position of the shape relative to the collider.
+    pub translation: Vec3,
+    /// The rotation of the shape relative to the collider.
+    pub rotation: Quat,
+    /// The shape itself.
+    pub shape: ExportedShape,
+}
+
+impl ExportedShape {
+    /// Converts the shape of a collider into the exported format.
+    ///
+    /// Returns `None` for shapes that have no equivalent, such as rounded shapes.
+    pub fn from_collider(collider: &Collider) -> Option<Self> {
+        Self::from_typed_shape(collider.raw.as_typed_shape())
+    }
+
+    fn from_typed_shape(shape: TypedShape) -> Option<Self> {
+        let vec3 = |point: &Point<f32>| Vec3::new(point.x, point.y, point.z);
+
+        Some(match shape {
+            TypedShape::Ball(ball) => ExportedShape::Ball {
+                radius: ball.radius,
+            },
+            TypedShape::Cuboid(cuboid) => ExportedShape::Cuboid {
+                half_extents: cuboid.half_extents.into(),
+            },
+            TypedShape::Capsule(capsule) => ExportedShape::Capsule {
+                a: vec3(&capsule.segment.a),
+                b: vec3(&capsule.segment.b),
+                radius: capsule.radius,
+            },
+            TypedShape::Cylinder(cylinder) => ExportedShape::Cylinder {
+                half_height: cylinder.half_height,
+                radius: cylinder.radius,
+            },
+            TypedShape::Cone(cone) => ExportedShape::Cone {
+                half_height: cone.half_height,
+                radius: cone.radius,
+            },
+            TypedShape::HalfSpace(half_space) => ExportedShape::HalfSpace {
+                normal: half_space.normal.into_inner().into(),
+            },
+            TypedShape::ConvexPolyhedron(polyhedron) => ExportedShape::ConvexHull {
+                points: polyhedron.points().iter().map(vec3).collect(),
+            },
+            TypedShape::TriMesh(trimesh) => ExportedShape::Trimesh {
+                vertices: trimesh.vertices().iter().map(vec3).collect(),
+                indices: trimesh.indices().to_vec(),
+            },
+            TypedShape::HeightField(heightfield) => {
+                let triangles: Vec<_> = heightfield.triangles().collect();
+                ExportedShape::Trimesh {
+                    vertices: triangles
+                        .iter()
+                        .flat_map(|triangle| [triangle.a, triangle.b, triangle.c])
+                        .map(|point| vec3(&point))
+                        .collect(),
+                    indices: (0..triangles.len() as u32)
+                        .map(|i| [3 * i, 3 * i + 1, 3 * i + 2])
+                        .collect(),
+                }
+            }
+            TypedShape::Compound(compound) => ExportedShape::Compound {
+                shapes: compound
+                    .shapes()
+                    .iter()
+                    .map(|(isometry, shape)| {
+                        Some(ExportedChildShape {
+                            translation: isometry.translation.vector.into(),
+                            rotation: isometry.rotation.into(),
+                            shape: Self::from_typed_shape(shape.as_typed_shape())?,
+                        })
+                    })
+                    .collect::<Option<_>>()?,
+            },
+            _ => return None,
+        })
+    }
+}
+
+/// A collider in the exported format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedCollider {
+    /// The name of the object the collider belongs to, if any.
+    pub name: Option<String>,
+    /// Where the collider is placed in the world.
+    pub transform: MapTransform,
+    /// How the collider takes part in the physics simulation.
+    pub body: MapBody,
+    /// Whether the collider only detects intersections instead of blocking movement.
+    pub sensor: bool,
+    /// The shape of the collider.
+    pub shape: ExportedShape,
+}
+
+/// The collision geometry of a map in a format that does not depend on Bevy or Rapier.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ColliderExport {
+    /// Every exported collider.
+    pub colliders: Vec<ExportedCollider>,
+}
+
+impl ColliderExport {
+    /// Creates an empty export.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Exports the colliders of every object in a map.
+    pub fn from_map(map: &Map) -> Self {
+        let mut export = Self::new();
+        for object in &map.objects {
+            export.push(
+                object.name.clone(),
+                object.transform,
+                object.body,
+                object.event_space.is_some(),
+                &object.shape.to_collider(),
+            );
+        }
+        export
+    }
+
+    /// Adds a collider to the export.
+    ///
+    /// Returns `false` if the shape of the collider cannot be exported.
+    pub fn push(
+        &mut self,
+        name: Option<String>,
+        transform: MapTransform,
+        body: MapBody,
+        sensor: bool,
+        collider: &Collider,
+    ) -> bool {
+        let Some(shape) = ExportedShape::from_collider(collider) else {
+            return false;
+        };
+        self.colliders.push(ExportedCollider {
+            name,
+            transform,
+            body,
+            sensor,
+            shape,
+        });
+        true
+    }
+
+    /// Reads an export from JSON.
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Writes the export to JSON.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Writes the export to a file.
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        Ok(std::fs::write(path, self.to_json()?)?)
+    }
+}
+
+impl Map {
+    /// Exports the collision geometry of the map.
+    pub fn export_colliders(&self) -> ColliderExport {
+        ColliderExport::from_map(self)
+    }
+}
@@ -0,0 +1,226 @@
+//! A mod for magnets that pull or push the bodies inside a volume.
+//!
+//! A [`Magnet`] is placed on an [`EventSpace`] and applies a force to the dynamic bodies inside
+//! it. A point magnet pulls bodies toward the center of the volume, or pushes them away with a
+//! negative strength, while a directional magnet pulls everything along one direction like a
+//! tractor beam. The force fades with the distance from the center of the volume according to
+//! the [`MagnetFalloff`].
+//!
+//! Heavier bodies are moved less by the same force. Magnets can also pull characters, which are
+//! given the character mass of the [`MagnetSettings`]. Characters are pulled through their
+//! [`CustomVelocity`] like any other push, so they are held in place again when they land.
+
+use super::{event_space::*, *};
+use crate::{controller::*, state::*};
+
+/// How the force of a [`Magnet`] pulls bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Reflect, FromReflect)]
+pub enum MagnetMode {
+    /// Toward the center of the volume.
+    Point,
+    /// Along a direction in the space of the volume.
+    Directional(Vec3),
+}
+
+/// How the force of a [`Magnet`] fades with the distance from the center of its volume.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Reflect, FromReflect,
+)]
+pub enum MagnetFalloff {
+    /// The force is the same everywhere.
+    Constant,
+    /// The force fades linearly to nothing at the range of the magnet.
+    #[default]
+    Linear,
+    /// The force falls with the square of the distance, like a real magnet, and is cut off at
+    /// the range of the magnet.
+    InverseSquare,
+}
+
+impl MagnetFalloff {
+    /// The fraction of the full force felt at a distance from the center.
+    pub fn factor(self, distance: f32, range: f32) -> f32 {
+        if distance > range {
+            return 0.0;
+        }
+        match self {
+            MagnetFalloff::Constant => 1.0,
+            MagnetFalloff::Linear => 1.0 - distance / range,
+            MagnetFalloff::InverseSquare => 1.0 / distance.max(1.0).powi(2),
+        }
+    }
+}
+
+/// A volume that pulls or pushes the bodies inside it.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+pub struct Magnet {
+    /// The force at the center of the volume. Negative strengths push bodies away.
+    pub strength: f32,
+    /// How the force pulls bodies.
+    #[serde(default = "Magnet::default_mode")]
+    pub mode: MagnetMode,
+    /// How the force fades with the distance from the center.
+    #[serde(default)]
+    pub falloff: MagnetFalloff,
+    /// The distance from the center where the force is gone.
+    #[serde(default = "Magnet::default_range")]
+    pub range: f32,
+    /// Whether characters are pulled as well.
+    #[serde(default)]
+    pub affect_characters: bool,
+}
+
+impl Default for Magnet {
+    fn default() -> Self {
+        Self::new(0.0, Self::default_range())
+    }
+}
+
+impl Magnet {
+    /// Creates a new point [`Magnet`] that pulls bodies toward its center.
+    pub fn new(strength: f32, range: f32) -> Self {
+        Self {
+            strength,
+            mode: Self::default_mode(),
+            falloff: MagnetFalloff::default(),
+            range,
+            affect_characters: false,
+        }
+    }
+
+    /// Creates a new directional [`Magnet`] that pulls bodies along a direction in its space.
+    pub fn directional(strength: f32, direction: Vec3, range: f32) -> Self {
+        Self {
+            mode: MagnetMode::Directional(direction),
+            ..Self::new(strength, range)
+        }
+    }
+
+    fn default_mode() -> MagnetMode {
+        MagnetMode::Point
+    }
+
+    fn default_range() -> f32 {
+        10.0
+    }
+
+    /// The force felt by a body at a point, in world space.
+    pub fn force_at(&self, transform: &GlobalTransform, point: Vec3) -> Vec3 {
+        let center = transform.translation();
+        let factor = self.falloff.factor(point.distance(center), self.range);
+        let direction = match self.mode {
+            MagnetMode::Point => (center - point).normalize_or_zero(),
+            MagnetMode::Directional(direction) => {
+                let (_, rotation, _) = transform.to_scale_rotation_translation();
+                (rotation * direction).normalize_or_zero()
+            }
+        };
+        self.strength * factor * direction
+    }
+}
+
+/// Settings for the [`MagnetPlugin`].
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct MagnetSettings {
+    /// The mass of a character pulled by a magnet.
+    pub character_mass: f32,
+}
+
+impl Default for MagnetSettings {
+    fn default() -> Self {
+        Self {
+            character_mass: 80.0,
+        }
+    }
+}
+
+/// A plugin that pulls bodies toward [`Magnet`]s.
+#[derive(Default)]
+pub struct MagnetPlugin {
+    /// The settings used by the plugin.
+    pub settings: MagnetSettings,
+}
+
+impl MagnetPlugin {
+    /// Creates a new [`MagnetPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Plugin for MagnetPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Magnet>()
+            .register_type::<MagnetMode>()
+            .register_type::<MagnetFalloff>()
+            .insert_resource(self.settings)
+            .add_system(apply_magnets.with_run_criteria(is_playing));
+    }
+}
+
+/// Applies the force of every [`Magnet`] to the bodies inside it.
+#[allow(clippy::type_complexity)]
+pub fn apply_magnets(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<MagnetSettings>,
+    rapier_context: Res<RapierContext>,
+    magnets: Query<(Entity, &Magnet, &GlobalTransform), With<EventSpace>>,
+    mut bodies: Query<(
+        &RigidBody,
+        &GlobalTransform,
+        Option<&mut Velocity>,
+        Option<&mut CustomVelocity>,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    for (volume, magnet, magnet_transform) in &magnets {
+        let mut pulled: Vec<Entity> = Vec::new();
+        for (collider1, collider2, intersecting) in rapier_context.intersections_with(volume) {
+            if !intersecting {
+                continue;
+            }
+            let other = if collider1 == volume {
+                collider2
+            } else {
+                collider1
+            };
+            let body = rapier_context.collider_parent(other).unwrap_or(other);
+            // Bodies with several colliders are only pulled once.
+            if pulled.contains(&body) {
+                continue;
+            }
+            pulled.push(body);
+            let Ok((rigid_body, transform, velocity, custom_velocity)) = bodies.get_mut(body)
+            else {
+                continue;
+            };
+
+            let force = magnet.force_at(magnet_transform, transform.translation());
+            match (rigid_body, custom_velocity, velocity) {
+                (_, Some(mut custom_velocity), _) if magnet.affect_characters => {
+                    custom_velocity.0 += dt * force / settings.character_mass;
+                }
+                (RigidBody::Dynamic, None, velocity) => {
+                    let mass = rapier_context
+                        .entity2body()
+                        .get(&body)
+                        .and_then(|handle| rapier_context.bodies.get(*handle))
+                        .map_or(0.0, |body| body.mass());
+                    if mass <= 0.0 {
+                        continue;
+                    }
+                    let change = dt * force / mass;
+                    match velocity {
+                        Some(mut velocity) => velocity.linvel += change,
+                        None => {
+                            commands.entity(body).insert(Velocity::linear(change));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,273 @@
+//! A mod that shows the map from above in a corner of the screen.
+//!
+//! The [`MinimapPlugin`] renders the map with an orthographic camera that looks straight down on
+//! the player into an image, and shows that image in a UI node in a corner of the window. North,
+//! the negative Z direction, is always at the top.
+//!
+//! The camera floats a little above the player and does not see anything above itself, so the
+//! ceilings and roofs over the player are left out and the rooms below them stay visible.
+//!
+//! The minimap follows the [`Player`] whose camera has the lowest [`PlayerCamera`] index, and every
+//! player is drawn as a marker. Any other entity can be shown by giving it a [`MinimapMarker`].
+
+use super::spawn::*;
+use crate::controller::split_screen::*;
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{RenderTarget, ScalingMode},
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+    },
+};
+
+/// A marker for the minimap camera.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct MinimapCamera;
+
+/// A marker for the UI node that shows the minimap.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct MinimapDisplay;
+
+/// A marker for the UI nodes of the markers drawn over the minimap, which are replaced every
+/// frame.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct MinimapMarkerNode;
+
+/// Shows an entity on the minimap.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct MinimapMarker {
+    /// The color of the marker.
+    pub color: Color,
+    /// The size of the marker, in pixels.
+    pub size: f32,
+}
+
+impl MinimapMarker {
+    /// Creates a new [`MinimapMarker`] with a color.
+    pub fn new(color: Color) -> Self {
+        Self { color, size: 6.0 }
+    }
+}
+
+impl Default for MinimapMarker {
+    fn default() -> Self {
+        Self::new(Color::YELLOW)
+    }
+}
+
+/// Settings for the [`MinimapPlugin`].
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct MinimapSettings {
+    /// The width and height of the minimap on the screen and of its image, in pixels.
+    pub size: u32,
+    /// Half the width of the area shown by the minimap, in world units.
+    pub extent: f32,
+    /// How far above the player the camera is. Anything higher is not drawn.
+    pub ceiling: f32,
+    /// How far below the camera the map is still drawn.
+    pub depth: f32,
+    /// The distance between the minimap and the top right corner of the window, in pixels.
+    pub margin: f32,
+    /// The marker drawn for every [`Player`].
+    pub player_marker: MinimapMarker,
+}
+
+impl Default for MinimapSettings {
+    fn default() -> Self {
+        Self {
+            size: 256,
+            extent: 25.0,
+            ceiling: 2.0,
+            depth: 200.0,
+            margin: 16.0,
+            player_marker: MinimapMarker {
+                color: Color::WHITE,
+                size: 8.0,
+            },
+        }
+    }
+}
+
+/// A plugin that shows a top-down minimap of the map.
+#[derive(Default)]
+pub struct MinimapPlugin {
+    /// The settings used by the plugin.
+    pub settings: MinimapSettings,
+}
+
+impl MinimapPlugin {
+    /// Creates a new [`MinimapPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings)
+            .add_startup_system(setup_minimap)
+            .add_system_to_stage(CoreStage::PostUpdate, follow_player_on_minimap)
+            .add_system_to_stage(CoreStage::PostUpdate, draw_minimap_markers);
+    }
+}
+
+/// Creates the minimap camera, its image, and the UI node that shows it.
+pub fn setup_minimap(
+    mut commands: Commands,
+    settings: Res<MinimapSettings>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let size = Extent3d {
+        width: settings.size,
+        height: settings.size,
+        ..default()
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("minimap_target"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+        },
+        ..default()
+    };
+    image.resize(size);
+    let image = images.add(image);
+
+    commands.spawn((
+        Camera3dBundle {
+            camera: Camera {
+                // Render the minimap before the players, which do not depend on it.
+                priority: -1,
+                target: RenderTarget::Image(image.clone()),
+                ..default()
+            },
+            projection: OrthographicProjection {
+                near: 0.0,
+                far: settings.ceiling + settings.depth,
+                scaling_mode: ScalingMode::FixedVertical(2.0 * settings.extent),
+                ..default()
+            }
+            .into(),
+            ..default()
+        },
+        // The minimap must not draw the UI, or it would draw itself.
+        UiCameraConfig { show_ui: false },
+        MinimapCamera,
+        Name::new("Minimap Camera"),
+    ));
+
+    let size = Val::Px(settings.size as f32);
+    commands.spawn((
+        ImageBundle {
+            image: image.into(),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(settings.margin),
+                    right: Val::Px(settings.margin),
+                    ..default()
+                },
+                size: Size::new(size, size),
+                ..default()
+            },
+            ..default()
+        },
+        MinimapDisplay,
+        Name::new("Minimap"),
+    ));
+}
+
+/// Keeps the minimap camera above the first player, looking down with north at the top.
+pub fn follow_player_on_minimap(
+    settings: Res<MinimapSettings>,
+    players: Query<(Entity, &GlobalTransform), With<Player>>,
+    player_cameras: Query<(&PlayerCamera, &Parent)>,
+    mut cameras: Query<&mut Transform, With<MinimapCamera>>,
+) {
+    // Players without a camera of their own come last.
+    let camera_index = |player: Entity| {
+        player_cameras
+            .iter()
+            .filter(|(_, parent)| parent.get() == player)
+            .map(|(camera, _)| camera.index)
+            .min()
+            .unwrap_or(usize::MAX)
+    };
+    let Some((_, player)) = players
+        .iter()
+        .min_by_key(|(entity, _)| (camera_index(*entity), *entity))
+    else {
+        return;
+    };
+    let position = player.translation() + settings.ceiling * Vec3::Y;
+    for mut transform in &mut cameras {
+        *transform =
+            Transform::from_translation(position).looking_at(position - Vec3::Y, Vec3::NEG_Z);
+    }
+}
+
+/// Redraws the markers of the players and of every [`MinimapMarker`] over the minimap.
+#[allow(clippy::type_complexity)]
+pub fn draw_minimap_markers(
+    mut commands: Commands,
+    settings: Res<MinimapSettings>,
+    displays: Query<Entity, With<MinimapDisplay>>,
+    nodes: Query<Entity, With<MinimapMarkerNode>>,
+    cameras: Query<&GlobalTransform, With<MinimapCamera>>,
+    players: Query<&GlobalTransform, (With<Player>, Without<MinimapMarker>)>,
+    markers: Query<(&GlobalTransform, &MinimapMarker)>,
+) {
+    for node in &nodes {
+        commands.entity(node).despawn_recursive();
+    }
+    let (Ok(display), Ok(camera)) = (displays.get_single(), cameras.get_single()) else {
+        return;
+    };
+    let center = camera.translation();
+
+    let markers = players
+        .iter()
+        .map(|transform| (transform, &settings.player_marker))
+        .chain(markers.iter());
+    let mut nodes = Vec::new();
+    for (transform, marker) in markers {
+        // The right of the minimap is +X and the bottom is +Z.
+        let offset = (transform.translation() - center) / settings.extent;
+        if offset.x.abs() > 1.0 || offset.z.abs() > 1.0 {
+            continue;
+        }
+        let pixels = 0.5 * settings.size as f32 * (Vec2::new(offset.x, offset.z) + 1.0);
+        let half_size = 0.5 * marker.size;
+        nodes.push(
+            commands
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            position: UiRect {
+                                left: Val::Px(pixels.x - half_size),
+                                top: Val::Px(pixels.y - half_size),
+                                ..default()
+                            },
+                            size: Size::new(Val::Px(marker.size), Val::Px(marker.size)),
+                            ..default()
+                        },
+                        background_color: marker.color.into(),
+                        ..default()
+                    },
+                    MinimapMarkerNode,
+                ))
+                .id(),
+        );
+    }
+    commands.entity(display).push_children(&nodes);
+}
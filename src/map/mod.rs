@@ -0,0 +1,702 @@
+//! A mod that describes maps in a serializable format.
+//!
+//! A [`Map`] is a list of [`MapObject`]s that can be saved to and loaded from RON. Authored
+//! positions are stored as `f64` in [`MapTransform`] and are only converted to `f32` relative to
+//! the current [`FloatingOrigin`] when the map is spawned. This means that a huge world can be
+//! saved and loaded any number of times without accumulating precision loss, no matter where
+//! the origin happened to be at the time.
+
+/// A mod for doors and other moving parts of a map.
+pub mod animated;
+
+/// A mod that exports the collision geometry of a map to JSON.
+#[cfg(feature = "export")]
+pub mod export;
+
+/// A mod for bounce pads that launch whatever touches them.
+pub mod bounce_pad;
+
+/// A mod with a fluent builder for maps written in code.
+pub mod builder;
+
+/// A mod for timed challenges that run between two event spaces of a map.
+pub mod challenge;
+
+/// A mod for checkpoints and respawning players.
+pub mod checkpoint;
+
+/// A mod that describes the structure of a map in words.
+pub mod describe;
+
+/// A mod for invisible volumes that report when something enters or leaves them.
+pub mod event_space;
+
+/// A mod for reusable hierarchies of map objects.
+pub mod prefab;
+
+/// A mod for magnets that pull or push the bodies inside a volume.
+pub mod magnet;
+
+/// A mod that shows the map from above in a corner of the screen.
+pub mod minimap;
+
+/// A mod for pressure plates that only press down under enough weight.
+pub mod pressure_plate;
+
+/// A mod for volumes that put physics puzzles back the way they started.
+pub mod reset_volume;
+
+/// A mod for secret areas that players discover by entering them.
+pub mod secret_area;
+
+/// A mod that shares the colliders and meshes of identical map shapes.
+pub mod shape_cache;
+
+/// A mod for the signals that map logic sends along named channels.
+pub mod signal;
+
+/// A mod for the places where players enter a map.
+pub mod spawn;
+
+/// A mod that streams chunks of a map in and out around the player.
+pub mod streaming;
+
+/// A mod for wind volumes that push characters and props.
+pub mod wind;
+
+/// A mod for zip lines that carry players between two points.
+pub mod zip_line;
+
+use crate::{
+    controller::interaction::*, environment::*, error::*, floating_origin::*,
+    rapier_mesh_bundles::*, surface::*,
+};
+use animated::*;
+use bounce_pad::*;
+use challenge::*;
+use checkpoint::*;
+use event_space::*;
+use magnet::*;
+use pressure_plate::*;
+use reset_volume::*;
+use secret_area::*;
+use shape_cache::*;
+use spawn::*;
+use wind::*;
+use zip_line::*;
+
+use bevy::{ecs::system::EntityCommands, math::DVec3, prelude::*};
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A transform with a double-precision translation used to author maps.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapTransform {
+    /// The position of the object in world space.
+    pub translation: DVec3,
+    /// The rotation of the object.
+    pub rotation: Quat,
+    /// The scale of the object.
+    pub scale: Vec3,
+}
+
+impl Default for MapTransform {
+    fn default() -> Self {
+        Self {
+            translation: DVec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+impl MapTransform {
+    /// Creates a map transform from a world position.
+    pub fn from_translation(translation: DVec3) -> Self {
+        Self {
+            translation,
+            ..default()
+        }
+    }
+
+    /// Converts the map transform into a Bevy transform relative to the floating origin.
+    pub fn to_transform(&self, origin: &FloatingOrigin) -> Transform {
+        Transform {
+            translation: origin.world_to_local(self.translation),
+            rotation: self.rotation,
+            scale: self.scale,
+        }
+    }
+
+    /// Converts a Bevy transform relative to the floating origin into a map transform.
+    pub fn from_transform(transform: &Transform, origin: &FloatingOrigin) -> Self {
+        Self {
+            translation: origin.local_to_world(transform.translation),
+            rotation: transform.rotation,
+            scale: transform.scale,
+        }
+    }
+}
+
+/// The shape of a [`MapObject`], used for both its collider and its mesh.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MapShape {
+    /// A plane in the XZ plane.
+    Plane {
+        /// Half the size of the plane along X and Z.
+        half_size: Vec2,
+    },
+    /// A box.
+    Cuboid {
+        /// Half the size of the box along each axis.
+        half_size: Vec3,
+    },
+    /// A sphere.
+    Sphere {
+        /// The radius of the sphere.
+        radius: f32,
+    },
+    /// A capsule standing tall in the Y direction.
+    Capsule {
+        /// Half the length between the two hemispheres.
+        half_length: f32,
+        /// The radius of the capsule.
+        radius: f32,
+    },
+    /// A heightfield terrain, optionally with holes.
+    Heightfield {
+        /// The heights in column-major order.
+        heights: Vec<f32>,
+        /// The number of rows (along Z).
+        num_rows: usize,
+        /// The number of columns (along X).
+        num_cols: usize,
+        /// The size of the terrain along each axis.
+        size: Vec3,
+        /// The `(row, column)` cells that are cut out of the terrain.
+        #[serde(default)]
+        holes: Vec<(usize, usize)>,
+        /// How far the walls around the holes extend below the surface.
+        #[serde(default)]
+        skirt_depth: f32,
+    },
+}
+
+impl MapShape {
+    /// Checks that a collider and a mesh can be built from the parameters of the shape.
+    pub fn validate(&self) -> Result<()> {
+        let MapShape::Heightfield {
+            heights,
+            num_rows,
+            num_cols,
+            ..
+        } = self
+        else {
+            return Ok(());
+        };
+        let invalid = |message: String| MapBuilderError::InvalidShape {
+            shape: "heightfield",
+            message,
+        };
+        if *num_rows < 2 || *num_cols < 2 {
+            return Err(invalid(format!(
+                "{num_rows}x{num_cols} heights, at least 2x2 are needed"
+            )));
+        }
+        if num_rows.checked_mul(*num_cols) != Some(heights.len()) {
+            return Err(invalid(format!(
+                "{} heights given for {num_rows}x{num_cols}",
+                heights.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Creates the collider for the shape without a mesh.
+    ///
+    /// Panics if the shape is invalid, see [`MapShape::validate`].
+    pub fn to_collider(&self) -> Collider {
+        match self {
+            MapShape::Plane { half_size } => Collider::heightfield(
+                vec![0., 0., 0., 0.],
+                2,
+                2,
+                Vec3::new(2. * half_size.x, 1., 2. * half_size.y),
+            ),
+            MapShape::Cuboid { half_size } => {
+                Collider::cuboid(half_size.x, half_size.y, half_size.z)
+            }
+            MapShape::Sphere { radius } => Collider::ball(*radius),
+            MapShape::Capsule {
+                half_length,
+                radius,
+            } => Collider::capsule_y(*half_length, *radius),
+            MapShape::Heightfield {
+                heights,
+                num_rows,
+                num_cols,
+                size,
+                holes,
+                ..
+            } => heightfield_collider(heights.clone(), *num_rows, *num_cols, *size, holes),
+        }
+    }
+
+    /// Creates the collider and mesh for the shape.
+    ///
+    /// Panics if the shape is invalid, see [`MapShape::validate`].
+    pub fn to_shape_bundle(&self, meshes: &mut ResMut<Assets<Mesh>>) -> RapierShapeBundle {
+        match self {
+            MapShape::Plane { half_size } => RapierShapeBundle::plane(*half_size, meshes),
+            MapShape::Cuboid { half_size } => RapierShapeBundle::cuboid(*half_size, meshes),
+            MapShape::Sphere { radius } => RapierShapeBundle::sphere(*radius, meshes),
+            MapShape::Capsule {
+                half_length,
+                radius,
+            } => RapierShapeBundle::capsule(*half_length, *radius, meshes),
+            MapShape::Heightfield {
+                heights,
+                num_rows,
+                num_cols,
+                size,
+                holes,
+                skirt_depth,
+            } => RapierShapeBundle::heightfield_with_holes(
+                heights.clone(),
+                *num_rows,
+                *num_cols,
+                *size,
+                holes,
+                *skirt_depth,
+                meshes,
+            ),
+        }
+    }
+}
+
+/// How a [`MapObject`] takes part in the physics simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MapBody {
+    /// The object never moves.
+    #[default]
+    Fixed,
+    /// The object is moved by the physics simulation.
+    Dynamic,
+}
+
+/// What an [`EventSpace`] does besides sending [`EventSpaceEvent`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EventSpaceRole {
+    /// The event space only sends events for map logic.
+    #[default]
+    Trigger,
+    /// The event space is a [`Checkpoint`].
+    Checkpoint,
+    /// The event space is a [`KillVolume`].
+    Kill,
+}
+
+impl EventSpaceRole {
+    /// Inserts the components of the role into an event space entity.
+    pub fn insert(&self, entity: &mut EntityCommands) {
+        match self {
+            EventSpaceRole::Trigger => {}
+            EventSpaceRole::Checkpoint => {
+                entity.insert(Checkpoint);
+            }
+            EventSpaceRole::Kill => {
+                entity.insert(KillVolume);
+            }
+        }
+    }
+}
+
+/// A single object placed in a [`Map`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapObject {
+    /// An optional name used to find the object once spawned.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Where the object is placed in the world.
+    #[serde(default)]
+    pub transform: MapTransform,
+    /// The shape of the object.
+    pub shape: MapShape,
+    /// The color of the object.
+    #[serde(default = "MapObject::default_color")]
+    pub color: Color,
+    /// How the object takes part in the physics simulation.
+    #[serde(default)]
+    pub body: MapBody,
+    /// When set, the object is an invisible [`EventSpace`] with this name instead of a solid
+    /// object.
+    #[serde(default)]
+    pub event_space: Option<String>,
+    /// What the event space does, if the object is one.
+    #[serde(default)]
+    pub role: EventSpaceRole,
+    /// Makes the object usable by players, such as a door or a button.
+    #[serde(default)]
+    pub interactable: Option<Interactable>,
+    /// Makes the object a door that opens and closes.
+    #[serde(default)]
+    pub door: Option<MapDoor>,
+    /// The physical material of the object's surface.
+    #[serde(default)]
+    pub surface: SurfaceMaterial,
+    /// Makes the object the start handle of a zip line.
+    #[serde(default)]
+    pub zip_line: Option<ZipLine>,
+    /// Makes the object, or its event space, launch whatever touches it.
+    #[serde(default)]
+    pub bounce_pad: Option<BouncePad>,
+    /// Makes the object's event space blow what is inside it along a wind.
+    #[serde(default)]
+    pub wind: Option<WindVolume>,
+    /// Makes the object's event space record the props inside it so they can be reset.
+    #[serde(default)]
+    pub reset_volume: Option<ResetVolume>,
+    /// Makes the object a plate that is pressed by enough mass resting on it.
+    #[serde(default)]
+    pub pressure_plate: Option<PressurePlate>,
+    /// Makes the object's event space pull or push the bodies inside it.
+    #[serde(default)]
+    pub magnet: Option<Magnet>,
+    /// Makes the object's event space a secret area that players discover by entering it.
+    #[serde(default)]
+    pub secret_area: Option<SecretArea>,
+}
+
+impl MapObject {
+    /// Creates a new fixed map object.
+    pub fn new(shape: MapShape, transform: MapTransform) -> Self {
+        Self {
+            name: None,
+            transform,
+            shape,
+            color: Self::default_color(),
+            body: MapBody::default(),
+            event_space: None,
+            role: EventSpaceRole::default(),
+            interactable: None,
+            door: None,
+            surface: SurfaceMaterial::default(),
+            zip_line: None,
+            bounce_pad: None,
+            wind: None,
+            reset_volume: None,
+            pressure_plate: None,
+            magnet: None,
+            secret_area: None,
+        }
+    }
+
+    /// Creates a new event space map object.
+    pub fn event_space(name: impl Into<String>, shape: MapShape, transform: MapTransform) -> Self {
+        Self {
+            event_space: Some(name.into()),
+            ..Self::new(shape, transform)
+        }
+    }
+
+    /// Creates a new checkpoint map object.
+    pub fn checkpoint(name: impl Into<String>, shape: MapShape, transform: MapTransform) -> Self {
+        Self {
+            role: EventSpaceRole::Checkpoint,
+            ..Self::event_space(name, shape, transform)
+        }
+    }
+
+    /// Creates a new kill volume map object.
+    pub fn kill_volume(name: impl Into<String>, shape: MapShape, transform: MapTransform) -> Self {
+        Self {
+            role: EventSpaceRole::Kill,
+            ..Self::event_space(name, shape, transform)
+        }
+    }
+
+    fn default_color() -> Color {
+        Color::GRAY
+    }
+
+    /// Spawns the object relative to the floating origin.
+    ///
+    /// The collider and mesh are shared with identical shapes through the [`ShapeCache`]. Objects
+    /// whose shape cannot be built are skipped with a warning, in which case `None` is returned.
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        shapes: &mut ShapeCache,
+        meshes: &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<StandardMaterial>>,
+        origin: &FloatingOrigin,
+    ) -> Option<Entity> {
+        if let Err(error) = self.shape.validate() {
+            warn!("Skipping the map object {:?}: {error}", self.name);
+            return None;
+        }
+
+        let transform = self.transform.to_transform(origin);
+        let mut entity = match &self.event_space {
+            Some(name) => commands.spawn(EventSpaceBundle::new(
+                name.clone(),
+                shapes.collider(&self.shape),
+                transform,
+            )),
+            None => commands.spawn(RapierColliderPbrBundle {
+                shape: shapes.shape_bundle(&self.shape, meshes),
+                material: materials.add(self.color.into()),
+                surface: self.surface,
+                transform,
+                ..default()
+            }),
+        };
+        insert_object_components(&mut entity, self);
+
+        Some(entity.id())
+    }
+}
+
+/// Inserts the components of a spawned [`MapObject`] besides its shape, such as its name, the role
+/// of its event space, and the optional parts like doors and magnets.
+///
+/// Used for both map objects and the nodes of prefabs, so that they get the same components.
+pub(crate) fn insert_object_components(entity: &mut EntityCommands, object: &MapObject) {
+    if object.event_space.is_some() {
+        // Event spaces are invisible, but their children, such as the lights of a prefab, are not.
+        entity.insert(VisibilityBundle::default());
+        object.role.insert(entity);
+    }
+    if let Some(name) = &object.name {
+        entity.insert(Name::new(name.clone()));
+    }
+    if object.body == MapBody::Dynamic {
+        entity.insert(RigidBody::Dynamic);
+    }
+    if let Some(interactable) = &object.interactable {
+        entity.insert(interactable.clone());
+    }
+    if let Some(door) = &object.door {
+        door.insert(entity);
+    }
+    if let Some(zip_line) = object.zip_line {
+        entity.insert(zip_line);
+    }
+    if let Some(bounce_pad) = object.bounce_pad {
+        entity.insert(bounce_pad);
+    }
+    if let Some(wind) = object.wind {
+        entity.insert(wind);
+    }
+    if let Some(reset_volume) = &object.reset_volume {
+        entity.insert(reset_volume.clone());
+    }
+    if let Some(pressure_plate) = object.pressure_plate {
+        entity.insert(pressure_plate);
+    }
+    if let Some(magnet) = object.magnet {
+        entity.insert(magnet);
+    }
+    if let Some(secret_area) = &object.secret_area {
+        entity.insert(secret_area.clone());
+    }
+}
+
+/// A collection of objects that make up a world.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Map {
+    /// Every object in the map.
+    pub objects: Vec<MapObject>,
+    /// The places where players enter the map.
+    #[serde(default)]
+    pub spawn_points: Vec<MapSpawnPoint>,
+    /// The time of day when the map is spawned, if the map sets one.
+    #[serde(default)]
+    pub time_of_day: Option<TimeOfDay>,
+    /// The timed challenges of the map.
+    #[serde(default)]
+    pub challenges: Vec<TimedChallenge>,
+}
+
+impl Map {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a map from RON, rejecting shapes that cannot be built.
+    #[cfg(feature = "ron")]
+    pub fn from_ron(ron: &str) -> Result<Self> {
+        let map: Self = ron::from_str(ron)?;
+        map.validate()?;
+        Ok(map)
+    }
+
+    /// Checks that every shape of the map can be built.
+    ///
+    /// Maps read from RON or built with a [`MapBuilder`](builder::MapBuilder) are checked already.
+    pub fn validate(&self) -> Result<()> {
+        self.objects
+            .iter()
+            .try_for_each(|object| object.shape.validate())
+    }
+
+    /// Writes the map to RON.
+    #[cfg(feature = "ron")]
+    pub fn to_ron(&self) -> Result<String> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    /// Reads a map file.
+    #[cfg(feature = "ron")]
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        Self::from_ron(&std::fs::read_to_string(path)?)
+    }
+
+    /// Writes the map to a file.
+    #[cfg(feature = "ron")]
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        Ok(std::fs::write(path, self.to_ron()?)?)
+    }
+
+    /// Spawns every object, spawn point, and challenge of the map relative to the floating
+    /// origin, and sets the time of day if the map has one.
+    ///
+    /// Objects whose shape cannot be built are skipped, see [`MapObject::spawn`].
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        shapes: &mut ShapeCache,
+        meshes: &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<StandardMaterial>>,
+        origin: &FloatingOrigin,
+    ) -> Vec<Entity> {
+        if let Some(time_of_day) = self.time_of_day {
+            commands.insert_resource(time_of_day);
+        }
+        let mut entities: Vec<Entity> = self
+            .objects
+            .iter()
+            .filter_map(|object| object.spawn(commands, shapes, meshes, materials, origin))
+            .collect();
+        entities.extend(
+            self.spawn_points
+                .iter()
+                .map(|spawn_point| spawn_point.spawn(commands, origin)),
+        );
+        entities.extend(
+            self.challenges
+                .iter()
+                .map(|challenge| challenge.spawn(commands)),
+        );
+        entities
+    }
+}
+
+/// A plugin for what the plugins of map objects share, such as the reflection of the optional
+/// fields of their components.
+///
+/// The plugins of map objects register what they share themselves, so adding it is optional.
+#[derive(Default)]
+pub struct MapPlugin;
+
+impl MapPlugin {
+    /// Creates a new [`MapPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        register_map_types(app);
+    }
+}
+
+/// Registers the types shared by the components of map objects.
+///
+/// Unlike adding [`MapPlugin`], this can be done any number of times.
+pub(crate) fn register_map_types(app: &mut App) {
+    app.register_type::<Option<f32>>()
+        .register_type::<Option<String>>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heightfield_map(heights: Vec<f32>, num_rows: usize, num_cols: usize) -> Map {
+        Map {
+            objects: vec![MapObject::new(
+                MapShape::Heightfield {
+                    heights,
+                    num_rows,
+                    num_cols,
+                    size: Vec3::ONE,
+                    holes: Vec::new(),
+                    skirt_depth: 0.0,
+                },
+                MapTransform::default(),
+            )],
+            ..default()
+        }
+    }
+
+    #[cfg(feature = "ron")]
+    #[test]
+    fn map_with_valid_heightfield_is_read() {
+        let map = heightfield_map(vec![0.0; 6], 2, 3);
+
+        assert_eq!(Map::from_ron(&map.to_ron().unwrap()).unwrap(), map);
+    }
+
+    #[cfg(feature = "ron")]
+    #[test]
+    fn map_with_invalid_heightfield_is_rejected() {
+        for map in [
+            heightfield_map(vec![0.0; 5], 2, 3),
+            heightfield_map(vec![0.0; 3], 1, 3),
+        ] {
+            assert!(matches!(
+                Map::from_ron(&map.to_ron().unwrap()),
+                Err(MapBuilderError::InvalidShape { .. })
+            ));
+        }
+    }
+
+    #[test]
+    #[allow(clippy::type_complexity)]
+    fn invalid_objects_are_skipped_when_spawned() {
+        use bevy::ecs::system::SystemState;
+
+        let mut app = App::new();
+        app.add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>();
+        let mut state: SystemState<(
+            Commands,
+            ResMut<Assets<Mesh>>,
+            ResMut<Assets<StandardMaterial>>,
+        )> = SystemState::new(&mut app.world);
+        let (mut commands, mut meshes, mut materials) = state.get_mut(&mut app.world);
+
+        let mut map = heightfield_map(vec![0.0; 3], 1, 3);
+        map.objects.push(MapObject::new(
+            MapShape::Sphere { radius: 1.0 },
+            MapTransform::default(),
+        ));
+        let entities = map.spawn(
+            &mut commands,
+            &mut ShapeCache::new(),
+            &mut meshes,
+            &mut materials,
+            &FloatingOrigin::default(),
+        );
+
+        assert_eq!(entities.len(), 1);
+    }
+}
@@ -0,0 +1,349 @@
+//! A mod for reusable hierarchies of map objects.
+//!
+//! A [`Prefab`] describes a tree of shapes, lights, and event spaces that can be stamped into a
+//! map any number of times with [`SpawnPrefabExt::spawn_prefab`]. Prefabs are Bevy assets and can
+//! either be created in code or loaded from `.prefab.ron` files with the `ron` feature.
+
+use super::{event_space::*, *};
+
+use bevy::reflect::TypeUuid;
+#[cfg(feature = "ron")]
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    utils::BoxedFuture,
+};
+
+/// A light attached to a [`PrefabNode`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PrefabLight {
+    /// A light that shines in every direction, like a torch.
+    Point {
+        /// The color of the light.
+        color: Color,
+        /// The luminous intensity of the light.
+        intensity: f32,
+        /// How far the light reaches.
+        range: f32,
+        /// Whether the light casts shadows.
+        #[serde(default)]
+        shadows: bool,
+    },
+    /// A light that shines in a cone along the node's forward direction.
+    Spot {
+        /// The color of the light.
+        color: Color,
+        /// The luminous intensity of the light.
+        intensity: f32,
+        /// How far the light reaches.
+        range: f32,
+        /// The angle of the cone in radians.
+        angle: f32,
+        /// Whether the light casts shadows.
+        #[serde(default)]
+        shadows: bool,
+    },
+}
+
+/// A single node in a [`Prefab`] hierarchy.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrefabNode {
+    /// An optional name for the spawned entity.
+    pub name: Option<String>,
+    /// The transform relative to the parent node.
+    pub transform: Transform,
+    /// The shape of the node. Nodes without a shape only group their children.
+    pub shape: Option<MapShape>,
+    /// The color of the shape.
+    pub color: Option<Color>,
+    /// How the shape takes part in the physics simulation.
+    pub body: MapBody,
+    /// The physical material of the shape's surface.
+    pub surface: SurfaceMaterial,
+    /// When set, the shape becomes an invisible [`EventSpace`] with this name.
+    pub event_space: Option<String>,
+    /// What the event space does, if the node is one.
+    pub role: EventSpaceRole,
+    /// Makes the node usable by players, such as a door or a button.
+    pub interactable: Option<Interactable>,
+    /// Makes the node a door that opens and closes.
+    pub door: Option<MapDoor>,
+    /// Makes the node the start handle of a zip line.
+    pub zip_line: Option<ZipLine>,
+    /// Makes the node, or its event space, launch whatever touches it.
+    pub bounce_pad: Option<BouncePad>,
+    /// Makes the node's event space blow what is inside it along a wind.
+    pub wind: Option<WindVolume>,
+    /// Makes the node's event space record the props inside it so they can be reset.
+    pub reset_volume: Option<ResetVolume>,
+    /// Makes the node a plate that is pressed by enough mass resting on it.
+    pub pressure_plate: Option<PressurePlate>,
+    /// Makes the node's event space pull or push the bodies inside it.
+    pub magnet: Option<Magnet>,
+    /// Makes the node's event space a secret area that players discover by entering it.
+    pub secret_area: Option<SecretArea>,
+    /// A light attached to the node.
+    pub light: Option<PrefabLight>,
+    /// The child nodes.
+    pub children: Vec<PrefabNode>,
+}
+
+impl PrefabNode {
+    /// Returns the map object that a node with the given shape is spawned as.
+    fn to_object(&self, shape: MapShape) -> MapObject {
+        MapObject {
+            name: self.name.clone(),
+            transform: MapTransform {
+                translation: self.transform.translation.as_dvec3(),
+                rotation: self.transform.rotation,
+                scale: self.transform.scale,
+            },
+            shape,
+            color: self.color.unwrap_or_else(MapObject::default_color),
+            body: self.body,
+            event_space: self.event_space.clone(),
+            role: self.role,
+            interactable: self.interactable.clone(),
+            door: self.door.clone(),
+            surface: self.surface,
+            zip_line: self.zip_line,
+            bounce_pad: self.bounce_pad,
+            wind: self.wind,
+            reset_volume: self.reset_volume.clone(),
+            pressure_plate: self.pressure_plate,
+            magnet: self.magnet,
+            secret_area: self.secret_area.clone(),
+        }
+    }
+
+    /// Checks that the shapes of the node and all of its children can be built.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(shape) = &self.shape {
+            shape.validate()?;
+        }
+        self.children.iter().try_for_each(PrefabNode::validate)
+    }
+}
+
+/// A reusable hierarchy of map objects.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "0b0d64f2-7d3e-4c55-9a4e-2f5d1f6c7b31"]
+pub struct Prefab {
+    /// The root of the hierarchy.
+    pub root: PrefabNode,
+}
+
+#[cfg(feature = "ron")]
+impl Prefab {
+    /// Reads a prefab from RON, rejecting shapes that cannot be built.
+    pub fn from_ron(ron: &str) -> Result<Self> {
+        let prefab: Self = ron::from_str(ron)?;
+        prefab.root.validate()?;
+        Ok(prefab)
+    }
+
+    /// Writes the prefab to RON.
+    pub fn to_ron(&self) -> Result<String> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+}
+
+/// Loads [`Prefab`]s from `.prefab.ron` files.
+#[cfg(feature = "ron")]
+#[derive(Default)]
+pub struct PrefabLoader;
+
+#[cfg(feature = "ron")]
+impl AssetLoader for PrefabLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let prefab: Prefab = ron::de::from_bytes(bytes)?;
+            prefab.root.validate()?;
+            load_context.set_default_asset(LoadedAsset::new(prefab));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["prefab.ron"]
+    }
+}
+
+/// A component for entities that should be filled with the contents of a [`Prefab`].
+///
+/// The hierarchy is spawned as children of the entity as soon as the prefab has been loaded.
+#[derive(Component, Debug, Clone, Default)]
+pub struct PrefabInstance(pub Handle<Prefab>);
+
+/// A marker for [`PrefabInstance`]s whose hierarchy has been spawned.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct PrefabSpawned;
+
+/// An extension to [`Commands`] for spawning prefabs.
+pub trait SpawnPrefabExt {
+    /// Spawns an instance of the prefab at the given transform and returns the root entity.
+    fn spawn_prefab(&mut self, prefab: Handle<Prefab>, transform: Transform) -> Entity;
+}
+
+impl<'w, 's> SpawnPrefabExt for Commands<'w, 's> {
+    fn spawn_prefab(&mut self, prefab: Handle<Prefab>, transform: Transform) -> Entity {
+        self.spawn(SpatialBundle::from_transform(transform))
+            .insert(PrefabInstance(prefab))
+            .id()
+    }
+}
+
+/// A plugin that loads and spawns [`Prefab`]s.
+#[derive(Default)]
+pub struct PrefabPlugin;
+
+impl PrefabPlugin {
+    /// Creates a new [`PrefabPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for PrefabPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Prefab>()
+            .init_resource::<ShapeCache>()
+            .add_system(spawn_prefab_instances);
+        #[cfg(feature = "ron")]
+        app.init_asset_loader::<PrefabLoader>();
+    }
+}
+
+/// Spawns the hierarchy of every [`PrefabInstance`] whose prefab has finished loading.
+pub fn spawn_prefab_instances(
+    mut commands: Commands,
+    prefabs: Res<Assets<Prefab>>,
+    mut shapes: ResMut<ShapeCache>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    instances: Query<(Entity, &PrefabInstance), Without<PrefabSpawned>>,
+) {
+    for (entity, instance) in &instances {
+        let Some(prefab) = prefabs.get(&instance.0) else {
+            continue;
+        };
+
+        commands
+            .entity(entity)
+            .insert(PrefabSpawned)
+            .with_children(|parent| {
+                spawn_node(
+                    parent,
+                    &prefab.root,
+                    &mut shapes,
+                    &mut meshes,
+                    &mut materials,
+                );
+            });
+    }
+}
+
+fn spawn_node(
+    parent: &mut ChildBuilder,
+    node: &PrefabNode,
+    shapes: &mut ShapeCache,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+) {
+    // Nodes whose shape cannot be built still group their children.
+    let shape = node.shape.as_ref().filter(|shape| match shape.validate() {
+        Ok(()) => true,
+        Err(error) => {
+            warn!(
+                "Skipping the shape of the prefab node {:?}: {error}",
+                node.name
+            );
+            false
+        }
+    });
+    let mut entity = match shape {
+        Some(shape) => {
+            let object = node.to_object(shape.clone());
+            let mut entity = match &object.event_space {
+                Some(name) => parent.spawn(EventSpaceBundle::new(
+                    name.clone(),
+                    shapes.collider(shape),
+                    node.transform,
+                )),
+                None => parent.spawn(RapierColliderPbrBundle {
+                    shape: shapes.shape_bundle(shape, meshes),
+                    material: materials.add(object.color.into()),
+                    surface: object.surface,
+                    transform: node.transform,
+                    ..default()
+                }),
+            };
+            insert_object_components(&mut entity, &object);
+            entity
+        }
+        None => {
+            let mut entity = parent.spawn(SpatialBundle::from_transform(node.transform));
+            if let Some(name) = &node.name {
+                entity.insert(Name::new(name.clone()));
+            }
+            if let Some(interactable) = &node.interactable {
+                entity.insert(interactable.clone());
+            }
+            entity
+        }
+    };
+
+    entity.with_children(|children| {
+        match node.light {
+            Some(PrefabLight::Point {
+                color,
+                intensity,
+                range,
+                shadows,
+            }) => {
+                children.spawn(PointLightBundle {
+                    point_light: PointLight {
+                        color,
+                        intensity,
+                        range,
+                        shadows_enabled: shadows,
+                        ..default()
+                    },
+                    ..default()
+                });
+            }
+            Some(PrefabLight::Spot {
+                color,
+                intensity,
+                range,
+                angle,
+                shadows,
+            }) => {
+                children.spawn(SpotLightBundle {
+                    spot_light: SpotLight {
+                        color,
+                        intensity,
+                        range,
+                        outer_angle: angle,
+                        inner_angle: angle * 0.8,
+                        shadows_enabled: shadows,
+                        ..default()
+                    },
+                    ..default()
+                });
+            }
+            None => {}
+        }
+
+        for child in &node.children {
+            spawn_node(children, child, shapes, meshes, materials);
+        }
+    });
+}
@@ -0,0 +1,186 @@
+//! A mod for pressure plates that only press down under enough weight.
+//!
+//! A [`PressurePlate`] adds up the mass of everything standing on it every frame. Dynamic bodies
+//! are found through their contacts with the plate and weigh their Rapier mass. Character
+//! controllers are found by looking straight down from them and weigh the character mass of the
+//! [`PressurePlateSettings`], since kinematic bodies have no meaningful mass of their own.
+//!
+//! A plate is pressed while the total reaches its minimum mass, which makes puzzles like "place
+//! the heavy crate here" possible. Plates send [`PressurePlateEvent`]s when they are pressed and
+//! released, and doors whose trigger is the [`Name`] of a plate stay open while it is pressed.
+
+use super::*;
+use crate::state::*;
+
+/// A plate that is pressed while enough mass rests on it.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+pub struct PressurePlate {
+    /// The total mass that has to rest on the plate for it to be pressed.
+    pub min_mass: f32,
+}
+
+impl PressurePlate {
+    /// Creates a new [`PressurePlate`] that needs a minimum mass to be pressed.
+    pub fn new(min_mass: f32) -> Self {
+        Self { min_mass }
+    }
+}
+
+impl Default for PressurePlate {
+    fn default() -> Self {
+        Self { min_mass: 1.0 }
+    }
+}
+
+/// What rests on a [`PressurePlate`].
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct PressurePlateState {
+    /// The total mass on the plate in the last frame.
+    pub mass: f32,
+    /// Whether the plate is pressed.
+    pub pressed: bool,
+}
+
+/// Events sent when a [`PressurePlate`] is pressed or released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PressurePlateEvent {
+    /// Enough mass came to rest on a plate.
+    Pressed {
+        /// The plate entity.
+        plate: Entity,
+    },
+    /// The mass on a plate dropped below its minimum.
+    Released {
+        /// The plate entity.
+        plate: Entity,
+    },
+}
+
+/// Settings for the [`PressurePlatePlugin`].
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct PressurePlateSettings {
+    /// The mass of a character controller standing on a plate.
+    pub character_mass: f32,
+    /// How far below the bottom of a character a plate is still stood on.
+    pub ground_distance: f32,
+}
+
+impl Default for PressurePlateSettings {
+    fn default() -> Self {
+        Self {
+            character_mass: 80.0,
+            ground_distance: 0.1,
+        }
+    }
+}
+
+/// A plugin that weighs what rests on [`PressurePlate`]s.
+#[derive(Default)]
+pub struct PressurePlatePlugin {
+    /// The settings used by the plugin.
+    pub settings: PressurePlateSettings,
+}
+
+impl PressurePlatePlugin {
+    /// Creates a new [`PressurePlatePlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Plugin for PressurePlatePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<PressurePlate>()
+            .insert_resource(self.settings)
+            .add_event::<PressurePlateEvent>()
+            .add_system(weigh_pressure_plates.with_run_criteria(is_playing));
+    }
+}
+
+/// Adds up the mass on every [`PressurePlate`] and presses or releases it.
+#[allow(clippy::type_complexity)]
+pub fn weigh_pressure_plates(
+    mut commands: Commands,
+    settings: Res<PressurePlateSettings>,
+    rapier_context: Res<RapierContext>,
+    mut events: EventWriter<PressurePlateEvent>,
+    mut plates: Query<(Entity, &PressurePlate, Option<&mut PressurePlateState>)>,
+    characters: Query<
+        (Entity, &GlobalTransform, Option<&Collider>),
+        With<KinematicCharacterController>,
+    >,
+) {
+    let body_mass = |body: Entity| {
+        rapier_context
+            .entity2body()
+            .get(&body)
+            .and_then(|handle| rapier_context.bodies.get(*handle))
+            .filter(|body| body.is_dynamic())
+            .map_or(0.0, |body| body.mass())
+    };
+
+    // The plate below every character.
+    let stood_on: Vec<Entity> = characters
+        .iter()
+        .filter_map(|(character, transform, collider)| {
+            let half_height = collider.map_or(0.0, |collider| {
+                collider.raw.compute_local_aabb().half_extents().y
+            });
+            let filter = QueryFilter::default()
+                .exclude_sensors()
+                .exclude_rigid_body(character)
+                .exclude_collider(character);
+            rapier_context
+                .cast_ray(
+                    transform.translation(),
+                    -Vec3::Y,
+                    half_height + settings.ground_distance,
+                    true,
+                    filter,
+                )
+                .map(|(hit, _)| hit)
+        })
+        .collect();
+
+    for (plate, pressure_plate, state) in &mut plates {
+        let mut bodies: Vec<Entity> = Vec::new();
+        for pair in rapier_context.contacts_with(plate) {
+            if !pair.has_any_active_contacts() {
+                continue;
+            }
+            let other = if pair.collider1() == plate {
+                pair.collider2()
+            } else {
+                pair.collider1()
+            };
+            let body = rapier_context.collider_parent(other).unwrap_or(other);
+            if !bodies.contains(&body) {
+                bodies.push(body);
+            }
+        }
+        let characters_on_plate = stood_on.iter().filter(|hit| **hit == plate).count();
+        let mass = bodies.into_iter().map(body_mass).sum::<f32>()
+            + characters_on_plate as f32 * settings.character_mass;
+        let pressed = mass >= pressure_plate.min_mass;
+
+        let was_pressed = state.as_ref().is_some_and(|state| state.pressed);
+        if pressed && !was_pressed {
+            events.send(PressurePlateEvent::Pressed { plate });
+        } else if !pressed && was_pressed {
+            events.send(PressurePlateEvent::Released { plate });
+        }
+
+        let new_state = PressurePlateState { mass, pressed };
+        match state {
+            Some(mut state) => {
+                if *state != new_state {
+                    *state = new_state;
+                }
+            }
+            None => {
+                commands.entity(plate).insert(new_state);
+            }
+        }
+    }
+}
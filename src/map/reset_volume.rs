@@ -0,0 +1,206 @@
+//! A mod for volumes that put physics puzzles back the way they started.
+//!
+//! A [`ResetVolume`] is placed on an [`EventSpace`] that surrounds the dynamic props of a puzzle.
+//! Once the physics has run, the volume records where every dynamic body inside it is. Resetting
+//! the volume moves those bodies back and stops them, so a puzzle can be retried without reloading
+//! the map.
+//!
+//! A volume resets when a [`MapSignal`] that turns something on is sent on the channel named by
+//! its trigger, such as a player entering the event space or using the button of that name, when
+//! its interval runs out, and when a [`ResetVolumeEvent`] is sent for it. Props are recorded in
+//! world space, so they stay valid when the [`FloatingOrigin`] moves.
+
+use super::{signal::*, *};
+use crate::state::*;
+
+/// A volume that records the dynamic props inside it and can put them back.
+#[derive(Component, Debug, Clone, Default, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+#[serde(default)]
+pub struct ResetVolume {
+    /// The channel of the [`MapSignal`]s that reset the volume, such as the name of an
+    /// [`EventSpace`] that players enter or of an object that players use.
+    pub trigger: Option<String>,
+    /// How many seconds pass between automatic resets, if the volume resets by itself.
+    pub interval: Option<f32>,
+}
+
+impl ResetVolume {
+    /// Creates a new [`ResetVolume`] that only resets through [`ResetVolumeEvent`]s.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`ResetVolume`] that resets when its trigger is entered or used.
+    pub fn with_trigger(trigger: impl Into<String>) -> Self {
+        Self {
+            trigger: Some(trigger.into()),
+            ..default()
+        }
+    }
+}
+
+/// The props recorded by a [`ResetVolume`].
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct ResetVolumeState {
+    /// Every recorded body along with its world transform.
+    pub props: Vec<(Entity, MapTransform)>,
+    /// How many seconds have passed since the last reset.
+    pub elapsed: f32,
+}
+
+/// An event that resets a [`ResetVolume`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResetVolumeEvent {
+    /// The volume to reset.
+    pub volume: Entity,
+}
+
+/// A plugin that records and resets [`ResetVolume`]s.
+#[derive(Default)]
+pub struct ResetVolumePlugin;
+
+impl ResetVolumePlugin {
+    /// Creates a new [`ResetVolumePlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for ResetVolumePlugin {
+    fn build(&self, app: &mut App) {
+        register_map_types(app);
+
+        if !app.is_plugin_added::<MapSignalPlugin>() {
+            app.add_plugin(MapSignalPlugin::new());
+        }
+
+        app.register_type::<ResetVolume>()
+            .add_event::<ResetVolumeEvent>()
+            .add_system(record_reset_volumes)
+            .add_system(
+                trigger_reset_volumes
+                    .with_run_criteria(is_playing)
+                    .after(record_reset_volumes)
+                    .after(send_map_signals),
+            )
+            .add_system(
+                reset_volumes
+                    .with_run_criteria(is_playing)
+                    .after(trigger_reset_volumes),
+            );
+    }
+}
+
+/// Records the dynamic bodies inside new [`ResetVolume`]s.
+///
+/// Volumes are recorded once their collider has been through a physics step, since Rapier only
+/// knows what intersects them from then on. Maps spawned while the physics is paused, such as
+/// while loading or editing, are recorded after the first step once the game plays.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn record_reset_volumes(
+    mut commands: Commands,
+    mut stepped: Local<bool>,
+    time: Res<Time>,
+    state: Option<Res<State<MapBuilderState>>>,
+    origin: Option<Res<FloatingOrigin>>,
+    rapier_config: Res<RapierConfiguration>,
+    rapier_context: Res<RapierContext>,
+    volumes: Query<
+        Entity,
+        (
+            With<ResetVolume>,
+            With<RapierColliderHandle>,
+            Without<ResetVolumeState>,
+        ),
+    >,
+    bodies: Query<(&RigidBody, &Transform)>,
+) {
+    // The physics steps at the end of the frames in which it is active, after this system ran.
+    let playing = state.is_none_or(|state| *state.current() == MapBuilderState::Playing);
+    let stepping = playing && rapier_config.physics_pipeline_active && time.delta_seconds() > 0.0;
+    if !std::mem::replace(&mut *stepped, stepping) {
+        return;
+    }
+
+    let origin = origin.map(|origin| *origin).unwrap_or_default();
+    for volume in &volumes {
+        let mut props: Vec<(Entity, MapTransform)> = Vec::new();
+        for (collider1, collider2, intersecting) in rapier_context.intersections_with(volume) {
+            if !intersecting {
+                continue;
+            }
+            let other = if collider1 == volume {
+                collider2
+            } else {
+                collider1
+            };
+            let body = rapier_context.collider_parent(other).unwrap_or(other);
+            let Ok((rigid_body, transform)) = bodies.get(body) else {
+                continue;
+            };
+            if *rigid_body == RigidBody::Dynamic && props.iter().all(|(prop, _)| *prop != body) {
+                props.push((body, MapTransform::from_transform(transform, &origin)));
+            }
+        }
+        commands.entity(volume).insert(ResetVolumeState {
+            props,
+            elapsed: 0.0,
+        });
+    }
+}
+
+/// Sends a [`ResetVolumeEvent`] for volumes whose trigger was signalled, or whose interval ran
+/// out.
+pub fn trigger_reset_volumes(
+    time: Res<Time>,
+    mut signals: EventReader<MapSignal>,
+    mut reset_events: EventWriter<ResetVolumeEvent>,
+    mut volumes: Query<(Entity, &ResetVolume, &mut ResetVolumeState)>,
+) {
+    let triggers: Vec<&str> = signals
+        .iter()
+        .filter(|signal| signal.payload.as_bool() == Some(true))
+        .map(|signal| signal.channel.as_str())
+        .collect();
+
+    for (volume, reset_volume, mut state) in &mut volumes {
+        state.elapsed += time.delta_seconds();
+        let triggered = reset_volume
+            .trigger
+            .as_ref()
+            .is_some_and(|trigger| triggers.contains(&trigger.as_str()));
+        let timed_out = reset_volume
+            .interval
+            .is_some_and(|interval| state.elapsed >= interval);
+        if triggered || timed_out {
+            reset_events.send(ResetVolumeEvent { volume });
+        }
+    }
+}
+
+/// Moves the props of every reset volume back to where they were recorded and stops them.
+pub fn reset_volumes(
+    origin: Option<Res<FloatingOrigin>>,
+    mut events: EventReader<ResetVolumeEvent>,
+    mut volumes: Query<&mut ResetVolumeState>,
+    mut props: Query<(&mut Transform, Option<&mut Velocity>)>,
+) {
+    let origin = origin.map(|origin| *origin).unwrap_or_default();
+    for event in events.iter() {
+        let Ok(mut state) = volumes.get_mut(event.volume) else {
+            continue;
+        };
+        state.elapsed = 0.0;
+        for (prop, recorded) in &state.props {
+            // Props that were destroyed since are skipped.
+            let Ok((mut transform, velocity)) = props.get_mut(*prop) else {
+                continue;
+            };
+            *transform = recorded.to_transform(&origin);
+            if let Some(mut velocity) = velocity {
+                *velocity = Velocity::zero();
+            }
+        }
+    }
+}
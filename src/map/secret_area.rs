@@ -0,0 +1,295 @@
+//! A mod for secret areas that players discover by entering them.
+//!
+//! A [`SecretArea`] is placed on an [`EventSpace`]. The first time a player enters it, the area
+//! is discovered: a [`SecretAreaEvent`] is sent, the sound of the [`SecretAreaSettings`] is
+//! played, and a message is shown in the middle of the screen for a few seconds. The
+//! [`SecretStats`] resource counts the secrets of the map and how many have been found, for end
+//! of level screens.
+//!
+//! Areas can hide their contents until they are discovered. Once the physics has run, the area
+//! records every mesh whose origin is inside its volume and makes it invisible. The contents are
+//! only hidden from view, so they still collide.
+
+use super::{event_space::*, *};
+use crate::state::*;
+
+/// A volume that players discover by entering it.
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+#[serde(default)]
+pub struct SecretArea {
+    /// The message shown when the area is discovered, instead of the one in the settings.
+    pub message: Option<String>,
+    /// Whether the meshes inside the area are invisible until it is discovered.
+    pub hide_contents: bool,
+}
+
+impl SecretArea {
+    /// Creates a new [`SecretArea`] that hides its contents.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for SecretArea {
+    fn default() -> Self {
+        Self {
+            message: None,
+            hide_contents: true,
+        }
+    }
+}
+
+/// Whether a [`SecretArea`] has been discovered, and what it hides until then.
+#[derive(Component, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SecretAreaState {
+    /// Whether a player has entered the area.
+    pub discovered: bool,
+    /// The meshes hidden by the area.
+    pub contents: Vec<Entity>,
+}
+
+/// An event sent when a player discovers a [`SecretArea`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SecretAreaEvent {
+    /// The secret area.
+    pub area: Entity,
+    /// The player who discovered it.
+    pub player: Entity,
+}
+
+/// How many [`SecretArea`]s the map has and how many have been found.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SecretStats {
+    /// The number of secret areas that have been discovered.
+    pub found: usize,
+    /// The number of secret areas in the map.
+    pub total: usize,
+}
+
+/// The message shown when a [`SecretArea`] is discovered.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct SecretRevealText {
+    /// How many seconds the message stays on the screen.
+    pub remaining: f32,
+}
+
+/// Settings for the [`SecretAreaPlugin`].
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct SecretAreaSettings {
+    /// The path of the sound played when an area is discovered, relative to the assets folder.
+    ///
+    /// Sounds are only played with the `audio` feature.
+    pub sound: Option<String>,
+    /// The message shown when an area is discovered.
+    pub message: String,
+    /// How many seconds the message stays on the screen.
+    pub message_duration: f32,
+    /// The path of the font used for the message, relative to the assets folder.
+    pub font: String,
+}
+
+impl Default for SecretAreaSettings {
+    fn default() -> Self {
+        Self {
+            sound: None,
+            message: "You found a secret area!".to_string(),
+            message_duration: 3.0,
+            font: "fonts/DejaVuSansMono.ttf".to_string(),
+        }
+    }
+}
+
+/// A plugin that lets players discover [`SecretArea`]s.
+#[derive(Default)]
+pub struct SecretAreaPlugin {
+    /// The settings used by the plugin.
+    pub settings: SecretAreaSettings,
+}
+
+impl SecretAreaPlugin {
+    /// Creates a new [`SecretAreaPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Plugin for SecretAreaPlugin {
+    fn build(&self, app: &mut App) {
+        register_map_types(app);
+
+        app.register_type::<SecretArea>()
+            .insert_resource(self.settings.clone())
+            .init_resource::<SecretStats>()
+            .add_event::<EventSpaceEvent>()
+            .add_event::<SecretAreaEvent>()
+            .add_system(record_secret_areas)
+            .add_system(
+                discover_secret_areas
+                    .with_run_criteria(is_playing)
+                    .after(record_secret_areas),
+            )
+            .add_system(update_secret_stats.after(discover_secret_areas))
+            .add_system(reveal_secret_areas.after(discover_secret_areas))
+            .add_system(fade_secret_reveal_text);
+    }
+}
+
+/// Records and hides the contents of new [`SecretArea`]s.
+///
+/// Areas are recorded once their collider has been through a physics step, so that the
+/// transforms of their contents are up to date.
+#[allow(clippy::type_complexity)]
+pub fn record_secret_areas(
+    mut commands: Commands,
+    areas: Query<
+        (Entity, &SecretArea, &Collider, &GlobalTransform),
+        (With<RapierColliderHandle>, Without<SecretAreaState>),
+    >,
+    mut meshes: Query<(Entity, &GlobalTransform, &mut Visibility), With<Handle<Mesh>>>,
+) {
+    for (area, secret_area, collider, transform) in &areas {
+        let mut contents = Vec::new();
+        if secret_area.hide_contents {
+            let (_, rotation, translation) = transform.to_scale_rotation_translation();
+            for (entity, mesh_transform, mut visibility) in &mut meshes {
+                if entity != area
+                    && collider.contains_point(translation, rotation, mesh_transform.translation())
+                {
+                    visibility.is_visible = false;
+                    contents.push(entity);
+                }
+            }
+        }
+        commands.entity(area).insert(SecretAreaState {
+            discovered: false,
+            contents,
+        });
+    }
+}
+
+/// Discovers the [`SecretArea`]s that players enter for the first time.
+pub fn discover_secret_areas(
+    mut event_space_events: EventReader<EventSpaceEvent>,
+    mut secret_events: EventWriter<SecretAreaEvent>,
+    players: Query<(), With<Player>>,
+    mut areas: Query<&mut SecretAreaState>,
+) {
+    for event in event_space_events.iter() {
+        let EventSpaceEvent::Entered { space, entity } = *event else {
+            continue;
+        };
+        if !players.contains(entity) {
+            continue;
+        }
+        let Ok(mut state) = areas.get_mut(space) else {
+            continue;
+        };
+        if !state.discovered {
+            state.discovered = true;
+            secret_events.send(SecretAreaEvent {
+                area: space,
+                player: entity,
+            });
+        }
+    }
+}
+
+/// Counts the secret areas of the map and how many have been found.
+pub fn update_secret_stats(
+    mut stats: ResMut<SecretStats>,
+    areas: Query<Option<&SecretAreaState>, With<SecretArea>>,
+) {
+    let new_stats = SecretStats {
+        found: areas
+            .iter()
+            .flatten()
+            .filter(|state| state.discovered)
+            .count(),
+        total: areas.iter().count(),
+    };
+    if *stats != new_stats {
+        *stats = new_stats;
+    }
+}
+
+/// Shows the contents of discovered areas, plays the discovery sound, and shows the message.
+#[allow(clippy::too_many_arguments)]
+pub fn reveal_secret_areas(
+    mut commands: Commands,
+    settings: Res<SecretAreaSettings>,
+    asset_server: Option<Res<AssetServer>>,
+    #[cfg(feature = "audio")] audio: Option<Res<Audio>>,
+    mut events: EventReader<SecretAreaEvent>,
+    areas: Query<(&SecretArea, &SecretAreaState)>,
+    texts: Query<Entity, With<SecretRevealText>>,
+    mut visibilities: Query<&mut Visibility>,
+) {
+    for event in events.iter() {
+        let Ok((secret_area, state)) = areas.get(event.area) else {
+            continue;
+        };
+        for entity in &state.contents {
+            if let Ok(mut visibility) = visibilities.get_mut(*entity) {
+                visibility.is_visible = true;
+            }
+        }
+
+        let Some(asset_server) = &asset_server else {
+            continue;
+        };
+        #[cfg(feature = "audio")]
+        if let (Some(audio), Some(sound)) = (&audio, &settings.sound) {
+            audio.play(asset_server.load(sound.as_str()));
+        }
+        // Only the latest message is shown.
+        for text in &texts {
+            commands.entity(text).despawn_recursive();
+        }
+        let message = secret_area.message.as_ref().unwrap_or(&settings.message);
+        commands.spawn((
+            TextBundle::from_section(
+                message.clone(),
+                TextStyle {
+                    font: asset_server.load(settings.font.as_str()),
+                    font_size: 32.0,
+                    color: Color::GOLD,
+                },
+            )
+            .with_text_alignment(TextAlignment::TOP_CENTER)
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Percent(25.0),
+                    left: Val::Px(0.0),
+                    ..default()
+                },
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                ..default()
+            }),
+            SecretRevealText {
+                remaining: settings.message_duration,
+            },
+        ));
+    }
+}
+
+/// Fades out the discovery message and removes it when its time is up.
+pub fn fade_secret_reveal_text(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut texts: Query<(Entity, &mut SecretRevealText, &mut Text)>,
+) {
+    for (entity, mut reveal, mut text) in &mut texts {
+        reveal.remaining -= time.delta_seconds();
+        if reveal.remaining <= 0.0 {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        // Fade over the last second.
+        let alpha = reveal.remaining.min(1.0);
+        for section in &mut text.sections {
+            section.style.color.set_a(alpha);
+        }
+    }
+}
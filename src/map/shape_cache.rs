@@ -0,0 +1,146 @@
+//! A mod that shares the colliders and meshes of identical map shapes.
+//!
+//! Maps tend to repeat the same few shapes: walls of the same size, identical crates, and copies
+//! of a prefab. Without a cache, every one of them builds its own collider and mesh, which is
+//! slow for heightfields and wastes memory. The [`ShapeCache`] builds each distinct [`MapShape`]
+//! once and hands out clones that share it. Rapier colliders are reference counted, so the clones
+//! share the same geometry, and meshes are shared through their asset handle.
+//!
+//! The cache holds strong handles to its meshes, so call [`ShapeCache::clear`] after unloading a
+//! map to free the ones that are no longer used.
+
+use super::*;
+
+use bevy::utils::HashMap;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+/// A shape that has already been built.
+#[derive(Clone)]
+struct CachedShape {
+    shape: MapShape,
+    collider: Collider,
+    mesh: Option<Handle<Mesh>>,
+}
+
+/// A cache of the colliders and meshes built for [`MapShape`]s.
+#[derive(Resource, Default)]
+pub struct ShapeCache {
+    shapes: HashMap<u64, Vec<CachedShape>>,
+    hits: usize,
+    misses: usize,
+}
+
+impl ShapeCache {
+    /// Creates a new, empty [`ShapeCache`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the collider of a shape, building it if it is not cached yet.
+    ///
+    /// Panics if the shape is invalid, see [`MapShape::validate`].
+    pub fn collider(&mut self, shape: &MapShape) -> Collider {
+        self.entry(shape).collider.clone()
+    }
+
+    /// Returns the collider and mesh of a shape, building them if they are not cached yet.
+    ///
+    /// Panics if the shape is invalid, see [`MapShape::validate`].
+    pub fn shape_bundle(
+        &mut self,
+        shape: &MapShape,
+        meshes: &mut ResMut<Assets<Mesh>>,
+    ) -> RapierShapeBundle {
+        let cached = self.entry(shape);
+        let mesh = match &cached.mesh {
+            Some(mesh) => mesh.clone(),
+            None => {
+                let bundle = shape.to_shape_bundle(meshes);
+                cached.mesh = Some(bundle.mesh.clone());
+                bundle.mesh
+            }
+        };
+        RapierShapeBundle {
+            collider: cached.collider.clone(),
+            mesh,
+        }
+    }
+
+    /// The number of distinct shapes in the cache.
+    pub fn len(&self) -> usize {
+        self.shapes.values().map(Vec::len).sum()
+    }
+
+    /// Whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    /// How many times a shape was found in the cache and how many times it had to be built.
+    pub fn stats(&self) -> (usize, usize) {
+        (self.hits, self.misses)
+    }
+
+    /// Forgets every cached shape and releases the meshes held by the cache.
+    pub fn clear(&mut self) {
+        self.shapes.clear();
+    }
+
+    fn entry(&mut self, shape: &MapShape) -> &mut CachedShape {
+        let bucket = self.shapes.entry(shape_hash(shape)).or_default();
+        // Shapes with the same hash are told apart by comparing them.
+        match bucket.iter().position(|cached| cached.shape == *shape) {
+            Some(index) => {
+                self.hits += 1;
+                &mut bucket[index]
+            }
+            None => {
+                self.misses += 1;
+                bucket.push(CachedShape {
+                    shape: shape.clone(),
+                    collider: shape.to_collider(),
+                    mesh: None,
+                });
+                bucket.last_mut().unwrap()
+            }
+        }
+    }
+}
+
+/// Hashes the exact bits of a shape, since floats do not implement [`Hash`].
+fn shape_hash(shape: &MapShape) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let floats = |values: &[f32], hasher: &mut DefaultHasher| {
+        for value in values {
+            value.to_bits().hash(hasher);
+        }
+    };
+
+    std::mem::discriminant(shape).hash(&mut hasher);
+    match shape {
+        MapShape::Plane { half_size } => floats(&half_size.to_array(), &mut hasher),
+        MapShape::Cuboid { half_size } => floats(&half_size.to_array(), &mut hasher),
+        MapShape::Sphere { radius } => floats(&[*radius], &mut hasher),
+        MapShape::Capsule {
+            half_length,
+            radius,
+        } => floats(&[*half_length, *radius], &mut hasher),
+        MapShape::Heightfield {
+            heights,
+            num_rows,
+            num_cols,
+            size,
+            holes,
+            skirt_depth,
+        } => {
+            floats(heights, &mut hasher);
+            floats(&size.to_array(), &mut hasher);
+            floats(&[*skirt_depth], &mut hasher);
+            (num_rows, num_cols, holes).hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
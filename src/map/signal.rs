@@ -0,0 +1,242 @@
+//! A mod for the signals that map logic sends along named channels.
+//!
+//! The logic of a map is wired together by name. Producers send a [`MapSignal`] on a channel
+//! named after themselves, and consumers listen on the channel named by their trigger. Neither
+//! side knows about the other, so new kinds of producers and consumers, such as scripts, only have
+//! to send or read [`MapSignal`]s.
+//!
+//! The producers of the crate send:
+//! - a [`SignalPayload::Pulse`] on the name of an [`EventSpace`] when a player enters it;
+//! - a [`SignalPayload::Bool`] on the [`Name`] of a [`PressurePlate`] when it is pressed or
+//!   released;
+//! - a [`SignalPayload::Pulse`] on the [`Name`] of an [`Interactable`] when a player uses it.
+//!
+//! Doors and reset volumes listen on their triggers. The [`SignalGraph`] resource lists what
+//! sends and listens on every channel, along with the last payload sent on it, so that the editor
+//! can show how the logic of a map is wired.
+
+use super::{animated::*, event_space::*, pressure_plate::*, reset_volume::*, *};
+
+use std::collections::BTreeMap;
+
+/// The value carried by a [`MapSignal`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignalPayload {
+    /// Something happened once, such as a button being pressed.
+    Pulse,
+    /// Something was turned on or off, such as a pressure plate.
+    Bool(bool),
+    /// A number, such as the position of a lever.
+    Number(f32),
+    /// A piece of text.
+    Text(String),
+    /// An entity, such as the one that set the signal off.
+    Entity(Entity),
+}
+
+impl SignalPayload {
+    /// Whether the payload turns something on or off, or `None` if it is not that kind of value.
+    ///
+    /// A pulse turns things on, and so does any number other than zero.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Pulse => Some(true),
+            Self::Bool(value) => Some(*value),
+            Self::Number(value) => Some(*value != 0.0),
+            Self::Text(_) | Self::Entity(_) => None,
+        }
+    }
+}
+
+/// An event sent on a named channel of the map logic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapSignal {
+    /// The name of the channel.
+    pub channel: String,
+    /// The entity that sent the signal, if it was sent by one.
+    pub source: Option<Entity>,
+    /// The value carried by the signal.
+    pub payload: SignalPayload,
+}
+
+impl MapSignal {
+    /// Creates a new [`MapSignal`] that carries a payload on a channel.
+    pub fn new(channel: impl Into<String>, payload: SignalPayload) -> Self {
+        Self {
+            channel: channel.into(),
+            source: None,
+            payload,
+        }
+    }
+
+    /// Creates a new [`MapSignal`] that pulses a channel.
+    pub fn pulse(channel: impl Into<String>) -> Self {
+        Self::new(channel, SignalPayload::Pulse)
+    }
+
+    /// Records the entity that sent the signal.
+    pub fn with_source(self, source: Entity) -> Self {
+        Self {
+            source: Some(source),
+            ..self
+        }
+    }
+}
+
+/// What sends and listens on a channel.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SignalChannel {
+    /// The entities that send signals on the channel.
+    pub producers: Vec<Entity>,
+    /// The entities that listen on the channel.
+    pub consumers: Vec<Entity>,
+    /// The last payload sent on the channel, and the entity that sent it, if any.
+    pub last: Option<(SignalPayload, Option<Entity>)>,
+}
+
+/// Every channel of the map logic, by name.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct SignalGraph {
+    /// The channels that something sends or listens on, or that a signal was sent on.
+    pub channels: BTreeMap<String, SignalChannel>,
+}
+
+impl SignalGraph {
+    /// The channels that something listens on but nothing sends on, which usually means a
+    /// trigger has a typo.
+    pub fn unconnected(&self) -> impl Iterator<Item = &str> {
+        self.channels
+            .iter()
+            .filter(|(_, channel)| {
+                channel.producers.is_empty()
+                    && channel.last.is_none()
+                    && !channel.consumers.is_empty()
+            })
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// A plugin that turns map events into [`MapSignal`]s and keeps the [`SignalGraph`] up to date.
+///
+/// Plugins that react to [`MapSignal`]s, such as [`AnimatedDoorPlugin`] and [`ResetVolumePlugin`],
+/// add it when it has not been added yet, so it only needs to be added before them if at all.
+///
+/// [`AnimatedDoorPlugin`]: super::animated::AnimatedDoorPlugin
+/// [`ResetVolumePlugin`]: super::reset_volume::ResetVolumePlugin
+#[derive(Default)]
+pub struct MapSignalPlugin;
+
+impl MapSignalPlugin {
+    /// Creates a new [`MapSignalPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for MapSignalPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SignalGraph>()
+            .add_event::<MapSignal>()
+            .add_event::<EventSpaceEvent>()
+            .add_event::<PressurePlateEvent>()
+            .add_event::<InteractEvent>()
+            .add_system(send_map_signals)
+            .add_system(update_signal_graph.after(send_map_signals));
+    }
+}
+
+/// Sends a [`MapSignal`] for every event space entered by a player, every pressure plate pressed
+/// or released, and every named object used.
+pub fn send_map_signals(
+    mut event_space_events: EventReader<EventSpaceEvent>,
+    mut pressure_plate_events: EventReader<PressurePlateEvent>,
+    mut interact_events: EventReader<InteractEvent>,
+    mut signals: EventWriter<MapSignal>,
+    event_spaces: Query<&EventSpace>,
+    names: Query<&Name>,
+    players: Query<(), With<Player>>,
+) {
+    for event in event_space_events.iter() {
+        let EventSpaceEvent::Entered { space, entity } = *event else {
+            continue;
+        };
+        if let (Ok(event_space), true) = (event_spaces.get(space), players.contains(entity)) {
+            signals.send(MapSignal::pulse(&event_space.name).with_source(space));
+        }
+    }
+
+    for event in pressure_plate_events.iter() {
+        let (plate, pressed) = match *event {
+            PressurePlateEvent::Pressed { plate } => (plate, true),
+            PressurePlateEvent::Released { plate } => (plate, false),
+        };
+        if let Ok(name) = names.get(plate) {
+            signals.send(
+                MapSignal::new(name.as_str(), SignalPayload::Bool(pressed)).with_source(plate),
+            );
+        }
+    }
+
+    for event in interact_events.iter() {
+        if let Ok(name) = names.get(event.target) {
+            signals.send(MapSignal::pulse(name.as_str()).with_source(event.target));
+        }
+    }
+}
+
+/// Lists what sends and listens on every channel, and records the signals sent this frame.
+#[allow(clippy::type_complexity)]
+pub fn update_signal_graph(
+    mut graph: ResMut<SignalGraph>,
+    mut signals: EventReader<MapSignal>,
+    event_spaces: Query<(Entity, &EventSpace)>,
+    named_producers: Query<(Entity, &Name), Or<(With<PressurePlate>, With<Interactable>)>>,
+    doors: Query<
+        (Entity, Option<&SlidingDoor>, Option<&RotatingDoor>),
+        Or<(With<SlidingDoor>, With<RotatingDoor>)>,
+    >,
+    reset_volumes: Query<(Entity, &ResetVolume)>,
+) {
+    let graph = &mut *graph;
+    for channel in graph.channels.values_mut() {
+        channel.producers.clear();
+        channel.consumers.clear();
+    }
+
+    let producers = event_spaces
+        .iter()
+        .map(|(entity, event_space)| (entity, event_space.name.as_str()))
+        .chain(
+            named_producers
+                .iter()
+                .map(|(entity, name)| (entity, name.as_str())),
+        );
+    for (entity, channel) in producers {
+        let channel = graph.channels.entry(channel.to_string()).or_default();
+        channel.producers.push(entity);
+    }
+
+    let door_triggers = doors.iter().filter_map(|(entity, sliding, rotating)| {
+        sliding
+            .and_then(|door| door.trigger.as_ref())
+            .or_else(|| rotating.and_then(|door| door.trigger.as_ref()))
+            .map(|trigger| (entity, trigger))
+    });
+    let reset_triggers = reset_volumes
+        .iter()
+        .filter_map(|(entity, volume)| volume.trigger.as_ref().map(|trigger| (entity, trigger)));
+    for (entity, channel) in door_triggers.chain(reset_triggers) {
+        let channel = graph.channels.entry(channel.clone()).or_default();
+        channel.consumers.push(entity);
+    }
+
+    for signal in signals.iter() {
+        let channel = graph.channels.entry(signal.channel.clone()).or_default();
+        channel.last = Some((signal.payload.clone(), signal.source));
+    }
+
+    // Forget channels that nothing uses anymore.
+    graph.channels.retain(|_, channel| {
+        !channel.producers.is_empty() || !channel.consumers.is_empty() || channel.last.is_some()
+    });
+}
@@ -0,0 +1,263 @@
+//! A mod for the places where players enter a map.
+//!
+//! Maps declare [`SpawnPoint`]s instead of the application hard-coding where players start.
+//! [`spawn_player_at`] then builds the whole player hierarchy: a capsule body driven by the
+//! [`FpsCameraPlugin`](crate::controller::fps_controller::FpsCameraPlugin) with a first-person
+//! camera as its child. When several spawn points share a tag, players are spread over them in
+//! turn.
+
+use super::*;
+use crate::controller::{capsule::*, fps_controller::*, tuning::*, *};
+
+use bevy::ecs::system::{Command, SystemState};
+
+/// A place where players can enter the map, facing the forward direction of its transform.
+#[derive(Component, Debug, Clone, Default, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+pub struct SpawnPoint {
+    /// The tag used to find the spawn point, such as `"player"` or `"red_base"`.
+    pub tag: String,
+    /// The team allowed to spawn here, if it is limited to one.
+    #[serde(default)]
+    pub team: Option<String>,
+    /// How many players have been spawned here, used to spread players between spawn points.
+    #[serde(skip)]
+    #[reflect(ignore)]
+    uses: u32,
+}
+
+impl SpawnPoint {
+    /// Creates a new [`SpawnPoint`] with a tag.
+    pub fn new(tag: impl Into<String>) -> Self {
+        Self {
+            tag: tag.into(),
+            ..default()
+        }
+    }
+
+    /// Creates a new [`SpawnPoint`] with a tag that is limited to one team.
+    pub fn for_team(tag: impl Into<String>, team: impl Into<String>) -> Self {
+        Self {
+            team: Some(team.into()),
+            ..Self::new(tag)
+        }
+    }
+
+    /// Whether the spawn point matches a tag, either by its own tag or by its team.
+    pub fn matches(&self, tag: &str) -> bool {
+        self.tag == tag || self.team.as_deref() == Some(tag)
+    }
+}
+
+/// A struct that contains the components of a [`SpawnPoint`].
+#[derive(Bundle, Default)]
+pub struct SpawnPointBundle {
+    /// The spawn point.
+    pub spawn_point: SpawnPoint,
+    /// The position and orientation of the spawn point.
+    pub transform: TransformBundle,
+}
+
+impl SpawnPointBundle {
+    /// Creates a new [`SpawnPointBundle`].
+    pub fn new(spawn_point: SpawnPoint, transform: Transform) -> Self {
+        Self {
+            spawn_point,
+            transform: TransformBundle::from_transform(transform),
+        }
+    }
+}
+
+/// A [`SpawnPoint`] placed in a [`Map`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapSpawnPoint {
+    /// The spawn point.
+    pub spawn_point: SpawnPoint,
+    /// Where the spawn point is placed in the world.
+    #[serde(default)]
+    pub transform: MapTransform,
+}
+
+impl MapSpawnPoint {
+    /// Spawns the spawn point relative to the floating origin.
+    pub fn spawn(&self, commands: &mut Commands, origin: &FloatingOrigin) -> Entity {
+        commands
+            .spawn(SpawnPointBundle::new(
+                self.spawn_point.clone(),
+                self.transform.to_transform(origin),
+            ))
+            .id()
+    }
+}
+
+/// Settings for the players created by [`spawn_player_at`].
+///
+/// Insert this as a resource to change the defaults.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct PlayerSpawnSettings {
+    /// Half the length of the straight part of the player's capsule.
+    pub half_length: f32,
+    /// The radius of the player's capsule.
+    pub radius: f32,
+    /// The color of the player's capsule.
+    pub color: Color,
+    /// The height of the camera above the center of the capsule.
+    pub eye_height: f32,
+    /// The tuning of the player's controller, converted with the [`WorldScale`] resource.
+    pub preset: ControllerPreset,
+}
+
+impl Default for PlayerSpawnSettings {
+    fn default() -> Self {
+        Self {
+            half_length: 0.5,
+            radius: 0.5,
+            color: Color::rgb(0.3, 0.3, 0.7),
+            eye_height: 0.0,
+            preset: ControllerPreset::default(),
+        }
+    }
+}
+
+/// A plugin for [`SpawnPoint`]s and the [`Player`]s spawned at them.
+#[derive(Default)]
+pub struct SpawnPointPlugin;
+
+impl SpawnPointPlugin {
+    /// Creates a new [`SpawnPointPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for SpawnPointPlugin {
+    fn build(&self, app: &mut App) {
+        register_map_types(app);
+
+        app.register_type::<SpawnPoint>().register_type::<Player>();
+    }
+}
+
+/// A marker for the bodies of players, as opposed to other characters such as steering agents.
+///
+/// Bodies spawned with [`spawn_player_at`] get one. Insert it by hand into player bodies that are
+/// built some other way.
+#[derive(Component, Debug, Clone, Copy, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct Player;
+
+/// The entities created by [`spawn_player_at`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpawnedPlayer {
+    /// The capsule body that is moved by the controller.
+    pub body: Entity,
+    /// The first-person camera, a child of the body.
+    pub camera: Entity,
+}
+
+/// Spawns a player at a [`SpawnPoint`] matching the tag.
+///
+/// The player is created when the commands are applied, so spawn points spawned earlier by the
+/// same commands are found. Components inserted into the returned entities afterwards, such as a
+/// different [`Camera`], replace the defaults. If no spawn point matches, the player is spawned
+/// at the origin and a warning is logged.
+///
+/// The player becomes the [`FloatingOriginAnchor`] unless another entity already is one, so with
+/// several players the origin follows the first one spawned.
+pub fn spawn_player_at(commands: &mut Commands, tag: impl Into<String>) -> SpawnedPlayer {
+    let player = SpawnedPlayer {
+        body: commands.spawn_empty().id(),
+        camera: commands.spawn_empty().id(),
+    };
+    commands.add(SpawnPlayer {
+        tag: tag.into(),
+        player,
+    });
+    player
+}
+
+struct SpawnPlayer {
+    tag: String,
+    player: SpawnedPlayer,
+}
+
+impl Command for SpawnPlayer {
+    fn write(self, world: &mut World) {
+        let settings = world
+            .get_resource::<PlayerSpawnSettings>()
+            .copied()
+            .unwrap_or_default();
+        let scale = world
+            .get_resource::<WorldScale>()
+            .copied()
+            .unwrap_or_default();
+
+        // Use the least used matching spawn point.
+        let mut spawn_points = world.query::<(&mut SpawnPoint, &Transform)>();
+        let transform = match spawn_points
+            .iter_mut(world)
+            .filter(|(spawn_point, _)| spawn_point.matches(&self.tag))
+            .min_by_key(|(spawn_point, _)| spawn_point.uses)
+        {
+            Some((mut spawn_point, transform)) => {
+                spawn_point.uses += 1;
+                *transform
+            }
+            None => {
+                warn!(
+                    "No spawn point matches {:?}, spawning at the origin",
+                    self.tag
+                );
+                Transform::IDENTITY
+            }
+        };
+
+        // The body stays upright and the camera looks along the spawn point's forward direction.
+        let forward = transform.forward();
+        let yaw = forward.x.atan2(forward.z);
+        let body_transform = Transform::from_translation(transform.translation);
+
+        let mut meshes: SystemState<ResMut<Assets<Mesh>>> = SystemState::new(world);
+        let shape = RapierShapeBundle::capsule(
+            settings.half_length,
+            settings.radius,
+            &mut meshes.get_mut(world),
+        );
+        let material = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(settings.color.into());
+
+        world.entity_mut(self.player.body).insert((
+            Name::new("Player"),
+            Player,
+            RapierColliderPbrBundle {
+                shape,
+                material,
+                transform: body_transform,
+                ..default()
+            },
+            FpsControllerBodyBundle::with_preset(settings.preset, scale),
+            CharacterCapsule::new(settings.half_length, settings.radius, settings.eye_height),
+        ));
+        // There must only be one anchor, even when several players are spawned.
+        let mut anchors = world.query_filtered::<(), With<FloatingOriginAnchor>>();
+        if anchors.iter(world).next().is_none() {
+            world
+                .entity_mut(self.player.body)
+                .insert(FloatingOriginAnchor);
+        }
+        world
+            .entity_mut(self.player.camera)
+            .insert(LookTransformCameraBundle {
+                look_transform: LookTransform::from_pitch_yaw_offset(
+                    0.0,
+                    yaw,
+                    settings.eye_height * Vec3::Y,
+                ),
+                ..default()
+            });
+        world
+            .entity_mut(self.player.body)
+            .push_children(&[self.player.camera]);
+    }
+}
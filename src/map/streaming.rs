@@ -0,0 +1,265 @@
+//! A mod that streams a large map in and out around the player.
+//!
+//! A [`StreamedMap`] partitions the objects of a [`Map`] into square chunks on the XZ plane. The
+//! [`ChunkStreamingPlugin`] spawns every chunk within the load radius of a [`StreamingAnchor`] and
+//! despawns chunks that are further than the unload radius from every anchor, along with their
+//! colliders. Keeping the unload radius larger than the load radius stops chunks on the border
+//! from being spawned and despawned every frame.
+//!
+//! Objects are always respawned at their authored position, so dynamic objects that were moved
+//! are reset when their chunk is reloaded.
+
+use super::*;
+
+use bevy::utils::HashMap;
+
+/// The integer coordinates of a chunk on the XZ plane.
+pub type ChunkCoord = IVec2;
+
+/// A map that has been partitioned into chunks for streaming.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct StreamedMap {
+    /// The width and depth of every chunk.
+    pub chunk_size: f64,
+    /// The objects of every chunk.
+    pub chunks: HashMap<ChunkCoord, Vec<MapObject>>,
+    /// The entities spawned for every loaded chunk.
+    pub loaded: HashMap<ChunkCoord, Vec<Entity>>,
+}
+
+impl StreamedMap {
+    /// Partitions the objects of a map into chunks of the given size.
+    pub fn new(map: &Map, chunk_size: f64) -> Self {
+        let mut chunks: HashMap<ChunkCoord, Vec<MapObject>> = HashMap::default();
+        for object in &map.objects {
+            let coord = Self::chunk_coord(chunk_size, object.transform.translation);
+            chunks.entry(coord).or_default().push(object.clone());
+        }
+
+        Self {
+            chunk_size,
+            chunks,
+            loaded: HashMap::default(),
+        }
+    }
+
+    /// Returns the coordinates of the chunk that contains a world position.
+    pub fn chunk_coord(chunk_size: f64, position: DVec3) -> ChunkCoord {
+        IVec2::new(
+            (position.x / chunk_size).floor() as i32,
+            (position.z / chunk_size).floor() as i32,
+        )
+    }
+
+    /// Returns the world position of the center of a chunk.
+    pub fn chunk_center(&self, coord: ChunkCoord) -> DVec3 {
+        DVec3::new(
+            (coord.x as f64 + 0.5) * self.chunk_size,
+            0.0,
+            (coord.y as f64 + 0.5) * self.chunk_size,
+        )
+    }
+
+    /// Whether a chunk is currently spawned.
+    pub fn is_loaded(&self, coord: ChunkCoord) -> bool {
+        self.loaded.contains_key(&coord)
+    }
+
+    fn horizontal_distance(&self, coord: ChunkCoord, position: DVec3) -> f64 {
+        let center = self.chunk_center(coord);
+        (center.x - position.x).hypot(center.z - position.z)
+    }
+}
+
+/// A marker for entities around which chunks are loaded, usually the player or the camera.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct StreamingAnchor;
+
+/// Events sent when chunks are spawned or despawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkStreamingEvent {
+    /// A chunk has been spawned.
+    Loaded(ChunkCoord),
+    /// A chunk has been despawned.
+    Unloaded(ChunkCoord),
+}
+
+/// Settings for the [`ChunkStreamingPlugin`].
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct ChunkStreamingSettings {
+    /// Chunks whose center is closer than this to an anchor are spawned.
+    pub load_radius: f64,
+    /// Chunks whose center is further than this from every anchor are despawned.
+    pub unload_radius: f64,
+}
+
+impl Default for ChunkStreamingSettings {
+    fn default() -> Self {
+        Self {
+            load_radius: 200.0,
+            unload_radius: 250.0,
+        }
+    }
+}
+
+/// A plugin that spawns and despawns the chunks of the [`StreamedMap`] resource.
+#[derive(Default)]
+pub struct ChunkStreamingPlugin {
+    /// The settings used by the plugin.
+    pub settings: ChunkStreamingSettings,
+}
+
+impl ChunkStreamingPlugin {
+    /// Creates a new [`ChunkStreamingPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`ChunkStreamingPlugin`] with the given load and unload radii.
+    pub fn with_radii(load_radius: f64, unload_radius: f64) -> Self {
+        Self {
+            settings: ChunkStreamingSettings {
+                load_radius,
+                unload_radius: unload_radius.max(load_radius),
+            },
+        }
+    }
+}
+
+impl Plugin for ChunkStreamingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings)
+            .init_resource::<StreamedMap>()
+            .init_resource::<ShapeCache>()
+            .add_event::<ChunkStreamingEvent>()
+            .add_system(stream_chunks);
+    }
+}
+
+/// Spawns chunks close to a [`StreamingAnchor`] and despawns chunks far from all of them.
+#[allow(clippy::too_many_arguments)]
+pub fn stream_chunks(
+    mut commands: Commands,
+    settings: Res<ChunkStreamingSettings>,
+    origin: Option<Res<FloatingOrigin>>,
+    mut streamed_map: ResMut<StreamedMap>,
+    mut shapes: ResMut<ShapeCache>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut events: EventWriter<ChunkStreamingEvent>,
+    anchors: Query<&GlobalTransform, With<StreamingAnchor>>,
+) {
+    if streamed_map.chunk_size <= 0.0 {
+        return;
+    }
+
+    let origin = origin.map(|origin| *origin).unwrap_or_default();
+    let anchor_positions: Vec<DVec3> = anchors
+        .iter()
+        .map(|transform| origin.local_to_world(transform.translation()))
+        .collect();
+    // Without an anchor, such as while the player respawns, every chunk would be too far away.
+    if anchor_positions.is_empty() {
+        return;
+    }
+
+    // Despawn the chunks that are too far from every anchor.
+    let to_unload: Vec<ChunkCoord> = streamed_map
+        .loaded
+        .keys()
+        .copied()
+        .filter(|coord| {
+            anchor_positions.iter().all(|position| {
+                streamed_map.horizontal_distance(*coord, *position) > settings.unload_radius
+            })
+        })
+        .collect();
+    for coord in to_unload {
+        for entity in streamed_map.loaded.remove(&coord).unwrap_or_default() {
+            if let Some(entity_commands) = commands.get_entity(entity) {
+                entity_commands.despawn_recursive();
+            }
+        }
+        events.send(ChunkStreamingEvent::Unloaded(coord));
+    }
+
+    // Spawn the chunks that are close enough to any anchor.
+    let reach = (settings.load_radius / streamed_map.chunk_size).ceil() as i32 + 1;
+    for position in anchor_positions {
+        let center = StreamedMap::chunk_coord(streamed_map.chunk_size, position);
+        for x in -reach..=reach {
+            for z in -reach..=reach {
+                let coord = center + IVec2::new(x, z);
+                if streamed_map.is_loaded(coord)
+                    || streamed_map.horizontal_distance(coord, position) > settings.load_radius
+                {
+                    continue;
+                }
+
+                let Some(objects) = streamed_map.chunks.get(&coord) else {
+                    continue;
+                };
+                let entities = objects
+                    .iter()
+                    .filter_map(|object| {
+                        object.spawn(
+                            &mut commands,
+                            &mut shapes,
+                            &mut meshes,
+                            &mut materials,
+                            &origin,
+                        )
+                    })
+                    .collect();
+                streamed_map.loaded.insert(coord, entities);
+                events.send(ChunkStreamingEvent::Loaded(coord));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_with_loaded_chunk() -> App {
+        let mut app = App::new();
+        app.add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>()
+            .insert_resource(ChunkStreamingSettings::default())
+            .init_resource::<ShapeCache>()
+            .add_event::<ChunkStreamingEvent>()
+            .add_system(stream_chunks);
+
+        let entity = app.world.spawn_empty().id();
+        let mut streamed_map = StreamedMap::new(&Map::new(), 10.0);
+        streamed_map.loaded.insert(ChunkCoord::ZERO, vec![entity]);
+        app.insert_resource(streamed_map);
+        app
+    }
+
+    #[test]
+    fn chunks_stay_loaded_without_anchors() {
+        let mut app = app_with_loaded_chunk();
+
+        app.update();
+
+        let streamed_map = app.world.resource::<StreamedMap>();
+        assert!(streamed_map.is_loaded(ChunkCoord::ZERO));
+    }
+
+    #[test]
+    fn chunks_far_from_every_anchor_are_unloaded() {
+        let mut app = app_with_loaded_chunk();
+        app.world.spawn((
+            StreamingAnchor,
+            GlobalTransform::from_translation(Vec3::new(1000.0, 0.0, 0.0)),
+        ));
+
+        app.update();
+
+        let streamed_map = app.world.resource::<StreamedMap>();
+        assert!(!streamed_map.is_loaded(ChunkCoord::ZERO));
+    }
+}
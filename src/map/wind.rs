@@ -0,0 +1,138 @@
+//! A mod for wind volumes that push characters and props.
+//!
+//! A [`WindVolume`] is placed on an [`EventSpace`] and accelerates everything inside it along
+//! its wind, which is given in the space of the volume. Updrafts are wind volumes that blow
+//! upward harder than gravity pulls, and they carry characters and props up to the speed of the
+//! wind. Together with a [`Glide`] on the player, they make it possible to build aerial
+//! traversal sections where players ride from one updraft to the next.
+//!
+//! [`Glide`]: crate::controller::abilities::Glide
+
+use super::{event_space::*, *};
+use crate::{controller::*, state::*};
+
+/// A volume that blows characters and props along its wind.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+pub struct WindVolume {
+    /// The acceleration given to bodies inside the volume, in the space of the volume.
+    ///
+    /// Updrafts need to be stronger than gravity to lift anything.
+    pub acceleration: Vec3,
+    /// The speed along the wind that bodies are not pushed beyond, in units per second.
+    #[serde(default = "WindVolume::default_max_speed")]
+    pub max_speed: f32,
+}
+
+impl Default for WindVolume {
+    fn default() -> Self {
+        Self::new(Vec3::ZERO)
+    }
+}
+
+impl WindVolume {
+    /// Creates a new [`WindVolume`] with an acceleration in the space of the volume.
+    pub fn new(acceleration: Vec3) -> Self {
+        Self {
+            acceleration,
+            max_speed: Self::default_max_speed(),
+        }
+    }
+
+    /// Creates a new [`WindVolume`] that blows straight up.
+    pub fn updraft(acceleration: f32, max_speed: f32) -> Self {
+        Self {
+            acceleration: acceleration * Vec3::Y,
+            max_speed,
+        }
+    }
+
+    fn default_max_speed() -> f32 {
+        8.0
+    }
+
+    /// The acceleration of the wind in world space.
+    pub fn world_acceleration(&self, transform: &GlobalTransform) -> Vec3 {
+        let (_, rotation, _) = transform.to_scale_rotation_translation();
+        rotation * self.acceleration
+    }
+
+    /// Pushes a velocity along the wind for a time step, without going past the maximum speed.
+    pub fn push(&self, velocity: Vec3, acceleration: Vec3, dt: f32) -> Vec3 {
+        let direction = acceleration.normalize_or_zero();
+        let speed = velocity.dot(direction);
+        if speed >= self.max_speed {
+            return velocity;
+        }
+        let gain = (acceleration.length() * dt).min(self.max_speed - speed);
+        velocity + gain * direction
+    }
+}
+
+/// A plugin that blows bodies along [`WindVolume`]s.
+#[derive(Default)]
+pub struct WindPlugin;
+
+impl WindPlugin {
+    /// Creates a new [`WindPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for WindPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<WindVolume>()
+            .add_system(apply_wind.with_run_criteria(is_playing));
+    }
+}
+
+/// Accelerates the characters and dynamic bodies inside every [`WindVolume`].
+#[allow(clippy::type_complexity)]
+pub fn apply_wind(
+    mut commands: Commands,
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    volumes: Query<(Entity, &WindVolume, &GlobalTransform), With<EventSpace>>,
+    mut bodies: Query<(
+        &RigidBody,
+        Option<&mut Velocity>,
+        Option<&mut CustomVelocity>,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    for (volume, wind, transform) in &volumes {
+        let acceleration = wind.world_acceleration(transform);
+        for (collider1, collider2, intersecting) in rapier_context.intersections_with(volume) {
+            if !intersecting {
+                continue;
+            }
+            let other = if collider1 == volume {
+                collider2
+            } else {
+                collider1
+            };
+            let body = rapier_context.collider_parent(other).unwrap_or(other);
+            let Ok((rigid_body, velocity, custom_velocity)) = bodies.get_mut(body) else {
+                continue;
+            };
+
+            match (rigid_body, custom_velocity, velocity) {
+                (_, Some(mut custom_velocity), _) => {
+                    custom_velocity.0 = wind.push(custom_velocity.0, acceleration, dt);
+                }
+                (RigidBody::Dynamic, None, Some(mut velocity)) => {
+                    velocity.linvel = wind.push(velocity.linvel, acceleration, dt);
+                }
+                (RigidBody::Dynamic, None, None) => {
+                    commands.entity(body).insert(Velocity::linear(wind.push(
+                        Vec3::ZERO,
+                        acceleration,
+                        dt,
+                    )));
+                }
+                _ => {}
+            }
+        }
+    }
+}
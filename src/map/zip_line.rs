@@ -0,0 +1,259 @@
+//! A mod for zip lines that carry players between two points.
+//!
+//! A [`ZipLine`] is placed on the object that serves as its start handle and stretches a rope to
+//! its end point. Players use the handle like any other [`Interactable`], which hangs their body
+//! below the rope and carries it to the end. The body eases onto the rope instead of snapping to
+//! it, and the first-person camera rides along with it.
+//!
+//! Riders let go at the end of the rope, when they jump if the zip line allows it, or when they
+//! are blocked by something in the way. They are then back under normal control.
+
+use super::*;
+use crate::{
+    controller::{fps_controller::*, split_screen::*, tuning::*, *},
+    state::*,
+};
+
+/// A rope that carries players from the object it is placed on to an end point.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+pub struct ZipLine {
+    /// Where the rope ends, in the space of the start handle.
+    pub end: Vec3,
+    /// How fast riders move along the rope, in units per second.
+    #[serde(default = "ZipLine::default_speed")]
+    pub speed: f32,
+    /// How far below the rope the center of a rider's body hangs.
+    #[serde(default = "ZipLine::default_hang")]
+    pub hang: f32,
+    /// Whether riders can let go by jumping.
+    #[serde(default = "ZipLine::default_detach_on_jump")]
+    pub detach_on_jump: bool,
+    /// How long a rider may be blocked by an obstacle before letting go, in seconds.
+    #[serde(default = "ZipLine::default_blocked_time")]
+    pub blocked_time: f32,
+}
+
+impl Default for ZipLine {
+    fn default() -> Self {
+        Self::new(Vec3::ZERO)
+    }
+}
+
+impl ZipLine {
+    /// Creates a new [`ZipLine`] that ends at a point in the space of its start handle.
+    pub fn new(end: Vec3) -> Self {
+        Self {
+            end,
+            speed: Self::default_speed(),
+            hang: Self::default_hang(),
+            detach_on_jump: Self::default_detach_on_jump(),
+            blocked_time: Self::default_blocked_time(),
+        }
+    }
+
+    fn default_speed() -> f32 {
+        6.0
+    }
+
+    fn default_hang() -> f32 {
+        1.5
+    }
+
+    fn default_detach_on_jump() -> bool {
+        true
+    }
+
+    fn default_blocked_time() -> f32 {
+        0.25
+    }
+}
+
+/// A marker for the rope mesh spawned under a [`ZipLine`].
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct ZipLineRope;
+
+/// A body that is riding a [`ZipLine`].
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct ZipLineRider {
+    /// The zip line being ridden.
+    pub line: Entity,
+    /// The start of the rope, relative to the floating origin.
+    pub start: Vec3,
+    /// The end of the rope, relative to the floating origin.
+    pub end: Vec3,
+    /// How far along the rope the rider is.
+    pub distance: f32,
+    /// How long the rider has been blocked, in seconds.
+    pub blocked: f32,
+    /// The translation asked of the character controller in the last frame.
+    pub last_translation: Vec3,
+}
+
+/// A plugin that lets players ride [`ZipLine`]s.
+#[derive(Default)]
+pub struct ZipLinePlugin;
+
+impl ZipLinePlugin {
+    /// Creates a new [`ZipLinePlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for ZipLinePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ZipLine>()
+            .add_event::<InteractEvent>()
+            .add_event::<FloatingOriginShifted>()
+            .add_event::<FpsControlEvent>()
+            .add_event::<PlayerFpsControlEvent>()
+            .add_system(init_zip_lines)
+            .add_system(attach_to_zip_lines.with_run_criteria(is_playing))
+            .add_system(
+                ride_zip_lines
+                    .with_run_criteria(is_playing)
+                    .after(attach_to_zip_lines)
+                    .after(fps_control_system),
+            );
+    }
+}
+
+/// Makes new zip lines usable and gives them a rope.
+pub fn init_zip_lines(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    lines: Query<(Entity, &ZipLine, Option<&Interactable>), Added<ZipLine>>,
+) {
+    for (entity, line, interactable) in &lines {
+        if interactable.is_none() {
+            commands
+                .entity(entity)
+                .insert(Interactable::new("Ride zip line"));
+        }
+
+        let direction = line.end.normalize_or_zero();
+        let rope = commands
+            .spawn((
+                PbrBundle {
+                    mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+                    material: materials.add(Color::DARK_GRAY.into()),
+                    transform: Transform {
+                        translation: 0.5 * line.end,
+                        rotation: Quat::from_rotation_arc(Vec3::Y, direction),
+                        scale: Vec3::new(0.03, line.end.length(), 0.03),
+                    },
+                    ..default()
+                },
+                ZipLineRope,
+            ))
+            .id();
+        commands.entity(entity).add_child(rope);
+    }
+}
+
+/// Hangs the bodies of players that use a [`ZipLine`] from its rope.
+pub fn attach_to_zip_lines(
+    mut commands: Commands,
+    mut events: EventReader<InteractEvent>,
+    lines: Query<(&ZipLine, &GlobalTransform)>,
+    bodies: Query<(), (With<KinematicCharacterController>, Without<ZipLineRider>)>,
+) {
+    for event in events.iter() {
+        let Ok((line, transform)) = lines.get(event.target) else {
+            continue;
+        };
+        if !bodies.contains(event.user) {
+            continue;
+        }
+        commands.entity(event.user).insert(ZipLineRider {
+            line: event.target,
+            start: transform.translation(),
+            end: transform.transform_point(line.end),
+            distance: 0.0,
+            blocked: 0.0,
+            last_translation: Vec3::ZERO,
+        });
+    }
+}
+
+/// Carries riders along their rope and lets go of them when they are done.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn ride_zip_lines(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut shifts: EventReader<FloatingOriginShifted>,
+    mut keyboard_events: EventReader<FpsControlEvent>,
+    mut player_events: EventReader<PlayerFpsControlEvent>,
+    lines: Query<&ZipLine>,
+    cameras: Query<(&Parent, Option<&PlayerCamera>, Option<&PlayerInput>), With<LookTransform>>,
+    mut riders: Query<(
+        Entity,
+        &mut ZipLineRider,
+        &GlobalTransform,
+        &mut KinematicCharacterController,
+        Option<&mut CustomVelocity>,
+        Option<&KinematicCharacterControllerOutput>,
+        Option<&FpsControllerSettings>,
+    )>,
+) {
+    let shift: Vec3 = shifts.iter().map(|shifted| shifted.shift).sum();
+    let dt = time.delta_seconds();
+
+    let jumping = jumping_bodies(keyboard_events.iter(), player_events.iter(), &cameras);
+
+    for (entity, mut rider, transform, mut controller, velocity, output, settings) in &mut riders {
+        let Ok(line) = lines.get(rider.line) else {
+            commands.entity(entity).remove::<ZipLineRider>();
+            continue;
+        };
+        rider.start -= shift;
+        rider.end -= shift;
+
+        // A rider that cannot move as far as it was asked to is stuck on something.
+        let asked = rider.last_translation.length();
+        let moved = output.map_or(asked, |output| output.effective_translation.length());
+        if asked > f32::EPSILON && moved < 0.25 * asked {
+            rider.blocked += dt;
+        } else {
+            rider.blocked = 0.0;
+        }
+
+        let rope = rider.end - rider.start;
+        let length = rope.length();
+        let target = rider.start + rope.normalize_or_zero() * rider.distance.min(length)
+            - line.hang * Vec3::Y;
+        let offset = target - transform.translation();
+
+        // Ease onto the rope first, then move along it. Some slack keeps uneven frame times from
+        // stopping the rider.
+        let max_step = line.speed * dt;
+        let on_rope = offset.length() <= 2.0 * max_step;
+        if on_rope {
+            rider.distance += max_step;
+        }
+
+        let jumped = line.detach_on_jump && jumping.contains(&entity);
+        let finished = on_rope && rider.distance >= length;
+        if jumped || finished || rider.blocked >= line.blocked_time {
+            if let Some(mut velocity) = velocity {
+                velocity.0 = if jumped {
+                    settings.copied().unwrap_or_default().jump_speed * Vec3::Y
+                } else {
+                    Vec3::ZERO
+                };
+            }
+            commands.entity(entity).remove::<ZipLineRider>();
+            continue;
+        }
+
+        // Hold the rider up against gravity and ignore walking while on the rope.
+        if let Some(mut velocity) = velocity {
+            velocity.0 = Vec3::ZERO;
+        }
+        let translation = offset.clamp_length_max(2.0 * max_step);
+        controller.translation = Some(translation);
+        rider.last_translation = translation;
+    }
+}
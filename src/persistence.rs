@@ -0,0 +1,313 @@
+//! A mod that saves and restores the dynamic state of a running map.
+//!
+//! A [`WorldSnapshot`] holds what changes while a map is played: where the rigid bodies are and
+//! how fast they move, the [`CustomVelocity`] of kinematic characters, which event spaces have
+//! been triggered, the respawn point, and the time of day. It is meant for save games and for
+//! reproducing bugs, and is restored into a map that has been freshly loaded from the static map
+//! format.
+//!
+//! Entities are matched by their [`Name`], so only named root bodies and named event spaces are
+//! saved. Positions are saved in world space, so snapshots stay valid when the
+//! [`FloatingOrigin`] moves. Snapshots are written as RON and carry a version number so old
+//! files are rejected instead of being misread.
+
+use crate::{
+    controller::*,
+    environment::*,
+    error::*,
+    floating_origin::*,
+    map::{checkpoint::*, event_space::*, *},
+};
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
+
+/// The version written to new snapshots. Snapshots with another version cannot be restored.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// The saved state of a rigid body.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BodySnapshot {
+    /// The world transform of the body.
+    pub transform: MapTransform,
+    /// The Rapier velocity of the body, if it has one.
+    #[serde(default)]
+    pub velocity: Option<(Vec3, Vec3)>,
+    /// The velocity of a kinematic character, if the body is one.
+    #[serde(default)]
+    pub custom_velocity: Option<Vec3>,
+}
+
+/// The saved respawn point of the checkpoint system.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RespawnSnapshot {
+    /// The world transform to respawn at.
+    pub transform: MapTransform,
+    /// The velocity to respawn with.
+    pub velocity: Vec3,
+}
+
+/// The dynamic state of a running map.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    /// The version of the snapshot format.
+    pub version: u32,
+    /// The state of every named root body, by name.
+    pub bodies: BTreeMap<String, BodySnapshot>,
+    /// Whether every named event space has been triggered, by name.
+    pub event_spaces: BTreeMap<String, bool>,
+    /// The respawn point, if one has been recorded.
+    #[serde(default)]
+    pub respawn: Option<RespawnSnapshot>,
+    /// The time of day, if the day/night cycle is running.
+    #[serde(default)]
+    pub time_of_day: Option<TimeOfDay>,
+}
+
+impl WorldSnapshot {
+    /// Captures the dynamic state of the world.
+    pub fn capture(world: &mut World) -> Self {
+        let origin = world
+            .get_resource::<FloatingOrigin>()
+            .copied()
+            .unwrap_or_default();
+
+        let mut bodies = BTreeMap::new();
+        let mut query = world.query_filtered::<(
+            &Name,
+            &Transform,
+            Option<&Velocity>,
+            Option<&CustomVelocity>,
+        ), (With<RigidBody>, Without<Parent>)>();
+        for (name, transform, velocity, custom_velocity) in query.iter(world) {
+            let snapshot = BodySnapshot {
+                transform: MapTransform::from_transform(transform, &origin),
+                velocity: velocity.map(|velocity| (velocity.linvel, velocity.angvel)),
+                custom_velocity: custom_velocity.map(|velocity| velocity.0),
+            };
+            if bodies.insert(name.to_string(), snapshot).is_some() {
+                warn!("Several bodies are named {:?}, only one is saved", name);
+            }
+        }
+
+        let mut query = world.query::<&EventSpace>();
+        let event_spaces = query
+            .iter(world)
+            .map(|event_space| (event_space.name.clone(), event_space.triggered))
+            .collect();
+
+        let respawn = world.get_resource::<RespawnState>().and_then(|state| {
+            state.transform.map(|transform| RespawnSnapshot {
+                transform,
+                velocity: state.velocity,
+            })
+        });
+
+        Self {
+            version: SNAPSHOT_VERSION,
+            bodies,
+            event_spaces,
+            respawn,
+            time_of_day: world.get_resource::<TimeOfDay>().copied(),
+        }
+    }
+
+    /// Restores the snapshot into the world.
+    ///
+    /// Entities that are not in the snapshot are left alone, and saved entities that no longer
+    /// exist are skipped.
+    pub fn restore(&self, world: &mut World) {
+        let origin = world
+            .get_resource::<FloatingOrigin>()
+            .copied()
+            .unwrap_or_default();
+
+        let mut query = world.query_filtered::<(
+            &Name,
+            &mut Transform,
+            Option<&mut Velocity>,
+            Option<&mut CustomVelocity>,
+        ), (With<RigidBody>, Without<Parent>)>();
+        for (name, mut transform, velocity, custom_velocity) in query.iter_mut(world) {
+            let Some(snapshot) = self.bodies.get(name.as_str()) else {
+                continue;
+            };
+            *transform = snapshot.transform.to_transform(&origin);
+            if let (Some(mut velocity), Some((linvel, angvel))) = (velocity, snapshot.velocity) {
+                *velocity = Velocity { linvel, angvel };
+            }
+            if let (Some(mut custom_velocity), Some(saved)) =
+                (custom_velocity, snapshot.custom_velocity)
+            {
+                custom_velocity.0 = saved;
+            }
+        }
+
+        let mut query = world.query::<&mut EventSpace>();
+        for mut event_space in query.iter_mut(world) {
+            if let Some(triggered) = self.event_spaces.get(&event_space.name) {
+                event_space.triggered = *triggered;
+            }
+        }
+
+        if let (Some(mut state), Some(respawn)) =
+            (world.get_resource_mut::<RespawnState>(), self.respawn)
+        {
+            state.transform = Some(respawn.transform);
+            state.velocity = respawn.velocity;
+        }
+
+        if let Some(time_of_day) = self.time_of_day {
+            world.insert_resource(time_of_day);
+        }
+    }
+
+    /// Reads a snapshot from RON, rejecting other versions of the format.
+    pub fn from_ron(ron: &str) -> Result<Self> {
+        let snapshot: Self = ron::from_str(ron)?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(MapBuilderError::UnsupportedVersion {
+                kind: "snapshot",
+                found: snapshot.version,
+                expected: SNAPSHOT_VERSION,
+            });
+        }
+        Ok(snapshot)
+    }
+
+    /// Writes the snapshot to RON.
+    pub fn to_ron(&self) -> Result<String> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    /// Reads a snapshot file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_ron(&std::fs::read_to_string(path)?)
+    }
+
+    /// Writes the snapshot to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(std::fs::write(path, self.to_ron()?)?)
+    }
+}
+
+/// A request to save or load a snapshot file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotRequest {
+    /// Saves the world to the file.
+    Save(std::path::PathBuf),
+    /// Restores the world from the file.
+    Load(std::path::PathBuf),
+}
+
+impl std::fmt::Display for SnapshotRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotRequest::Save(path) => write!(f, "saving snapshot {}", path.display()),
+            SnapshotRequest::Load(path) => write!(f, "loading snapshot {}", path.display()),
+        }
+    }
+}
+
+/// A plugin that saves and loads snapshots when [`SnapshotRequest`]s are sent.
+///
+/// Requests that fail send a [`MapBuilderErrorEvent`].
+#[derive(Default)]
+pub struct PersistencePlugin;
+
+impl PersistencePlugin {
+    /// Creates a new [`PersistencePlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for PersistencePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SnapshotRequest>()
+            .add_event::<MapBuilderErrorEvent>()
+            .add_system_to_stage(CoreStage::Last, handle_snapshot_requests);
+    }
+}
+
+/// Saves and loads snapshots for every [`SnapshotRequest`].
+pub fn handle_snapshot_requests(world: &mut World) {
+    let requests: Vec<SnapshotRequest> = world
+        .resource_mut::<Events<SnapshotRequest>>()
+        .drain()
+        .collect();
+
+    for request in requests {
+        let result = match &request {
+            SnapshotRequest::Save(path) => WorldSnapshot::capture(world).save(path),
+            SnapshotRequest::Load(path) => {
+                WorldSnapshot::load(path).map(|snapshot| snapshot.restore(world))
+            }
+        };
+        if let Err(error) = result {
+            report_error(world, request.to_string(), error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bevy::math::DVec3;
+
+    fn snapshot() -> WorldSnapshot {
+        let transform = MapTransform {
+            translation: DVec3::new(1.5, 2.0, -3.25),
+            ..default()
+        };
+        WorldSnapshot {
+            version: SNAPSHOT_VERSION,
+            bodies: BTreeMap::from([(
+                "crate".to_string(),
+                BodySnapshot {
+                    transform,
+                    velocity: Some((Vec3::X, Vec3::ZERO)),
+                    custom_velocity: None,
+                },
+            )]),
+            event_spaces: BTreeMap::from([("door".to_string(), true)]),
+            respawn: Some(RespawnSnapshot {
+                transform,
+                velocity: Vec3::new(0.0, -2.0, 0.0),
+            }),
+            time_of_day: Some(TimeOfDay {
+                hour: 18.5,
+                paused: true,
+            }),
+        }
+    }
+
+    #[test]
+    fn snapshot_round_trips_through_ron() {
+        let snapshot = snapshot();
+
+        let ron = snapshot.to_ron().unwrap();
+        assert_eq!(WorldSnapshot::from_ron(&ron).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn snapshot_rejects_other_versions() {
+        let snapshot = WorldSnapshot {
+            version: SNAPSHOT_VERSION + 1,
+            ..snapshot()
+        };
+
+        let ron = snapshot.to_ron().unwrap();
+        assert!(matches!(
+            WorldSnapshot::from_ron(&ron),
+            Err(MapBuilderError::UnsupportedVersion { found, expected, .. })
+                if found == SNAPSHOT_VERSION + 1 && expected == SNAPSHOT_VERSION
+        ));
+    }
+}
@@ -1,5 +1,19 @@
-use bevy::prelude::*;
-use bevy_rapier3d::prelude::*;
+use crate::{error::*, surface::*};
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        render_resource::PrimitiveTopology,
+    },
+};
+use bevy_rapier3d::{
+    parry::{
+        math::{Isometry, Real},
+        shape::HeightFieldCellStatus,
+    },
+    prelude::*,
+};
 
 /// A struct that contains a rapier collider and as well as a mesh handle.
 ///
@@ -55,12 +69,20 @@ impl RapierShapeBundle {
 
     /// Creates a collider and a mesh for a sphere.
     pub fn sphere(radius: f32, meshes: &mut ResMut<Assets<Mesh>>) -> Self {
+        Self::sphere_with_quality(radius, MeshQuality::High, meshes)
+    }
+
+    /// Creates a collider and a mesh for a sphere with the given mesh quality.
+    ///
+    /// The collider is always a perfect sphere no matter the quality.
+    pub fn sphere_with_quality(
+        radius: f32,
+        quality: MeshQuality,
+        meshes: &mut ResMut<Assets<Mesh>>,
+    ) -> Self {
         RapierShapeBundle {
             collider: Collider::ball(radius),
-            mesh: meshes.add(Mesh::from(shape::UVSphere {
-                radius,
-                ..default()
-            })),
+            mesh: meshes.add(quality.sphere_mesh(radius)),
         }
     }
 
@@ -68,21 +90,578 @@ impl RapierShapeBundle {
     ///
     /// Note: half_length describes half the length between the two hemispheres of the capsule.
     pub fn capsule(half_length: f32, radius: f32, meshes: &mut ResMut<Assets<Mesh>>) -> Self {
+        Self::capsule_with_quality(half_length, radius, MeshQuality::High, meshes)
+    }
+
+    /// Creates a collider and a mesh for a capsule with the given mesh quality.
+    ///
+    /// The collider is always a perfect capsule no matter the quality.
+    pub fn capsule_with_quality(
+        half_length: f32,
+        radius: f32,
+        quality: MeshQuality,
+        meshes: &mut ResMut<Assets<Mesh>>,
+    ) -> Self {
         RapierShapeBundle {
             collider: Collider::capsule(
                 Vec3::new(0., -half_length, 0.),
                 Vec3::new(0., half_length, 0.),
                 radius,
             ),
-            mesh: meshes.add(Mesh::from(shape::Capsule {
-                radius,
-                depth: half_length * 2.,
-                ..default()
-            })),
+            mesh: meshes.add(quality.capsule_mesh(half_length, radius)),
+        }
+    }
+
+    /// Creates a collider and a mesh for a heightfield terrain in the XZ plane.
+    ///
+    /// The heights are given in column-major order where rows run along the Z axis and columns
+    /// run along the X axis. The terrain is centered on the origin and `size` scales the X, Y, and
+    /// Z axes respectively.
+    pub fn heightfield(
+        heights: Vec<f32>,
+        num_rows: usize,
+        num_cols: usize,
+        size: Vec3,
+        meshes: &mut ResMut<Assets<Mesh>>,
+    ) -> Self {
+        Self::heightfield_with_holes(heights, num_rows, num_cols, size, &[], 0.0, meshes)
+    }
+
+    /// Creates a collider and a mesh for a heightfield terrain with holes cut into it.
+    ///
+    /// Each hole is the `(row, column)` index of a cell, where a cell is the quad between four
+    /// neighbouring heights. Holes are removed from both the mesh and the collider so that
+    /// basements and tunnel entrances can pierce the terrain. The rim of every hole is stitched
+    /// with vertical walls that extend `skirt_depth` below the terrain surface so the hole does
+    /// not look paper-thin from the inside. Holes outside the grid of cells are skipped with a
+    /// warning.
+    pub fn heightfield_with_holes(
+        heights: Vec<f32>,
+        num_rows: usize,
+        num_cols: usize,
+        size: Vec3,
+        holes: &[(usize, usize)],
+        skirt_depth: f32,
+        meshes: &mut ResMut<Assets<Mesh>>,
+    ) -> Self {
+        let holes = holes_in_grid(holes, num_rows, num_cols);
+        let mesh = heightfield_mesh(&heights, num_rows, num_cols, size, &holes, skirt_depth);
+
+        RapierShapeBundle {
+            collider: heightfield_collider(heights, num_rows, num_cols, size, &holes),
+            mesh: meshes.add(mesh),
+        }
+    }
+}
+
+/// Creates a heightfield collider with holes cut into it.
+///
+/// This is the collider used by [`RapierShapeBundle::heightfield_with_holes`] for when no mesh
+/// is needed. Holes outside the grid of cells are skipped with a warning.
+pub fn heightfield_collider(
+    heights: Vec<f32>,
+    num_rows: usize,
+    num_cols: usize,
+    size: Vec3,
+    holes: &[(usize, usize)],
+) -> Collider {
+    let mut collider = Collider::heightfield(heights, num_rows, num_cols, size);
+    if let Some(mut heightfield) = collider.as_heightfield_mut() {
+        for (row, col) in holes_in_grid(holes, num_rows, num_cols) {
+            heightfield.set_cell_status(
+                row,
+                col,
+                HeightFieldCellStatus::LEFT_TRIANGLE_REMOVED
+                    | HeightFieldCellStatus::RIGHT_TRIANGLE_REMOVED,
+            );
+        }
+    }
+    collider
+}
+
+/// Keeps the holes that are cells of a heightfield with `num_rows` rows and `num_cols` columns of
+/// heights, warning about the others.
+fn holes_in_grid(
+    holes: &[(usize, usize)],
+    num_rows: usize,
+    num_cols: usize,
+) -> Vec<(usize, usize)> {
+    holes
+        .iter()
+        .copied()
+        .filter(|&(row, col)| {
+            let in_grid = row + 1 < num_rows && col + 1 < num_cols;
+            if !in_grid {
+                warn!(
+                    "Skipping the heightfield hole ({row}, {col}) outside the {}x{} grid of cells.",
+                    num_rows.saturating_sub(1),
+                    num_cols.saturating_sub(1)
+                );
+            }
+            in_grid
+        })
+        .collect()
+}
+
+/// Builds the render mesh of a heightfield so that it matches the triangulation used by Rapier.
+fn heightfield_mesh(
+    heights: &[f32],
+    num_rows: usize,
+    num_cols: usize,
+    size: Vec3,
+    holes: &[(usize, usize)],
+    skirt_depth: f32,
+) -> Mesh {
+    assert_eq!(
+        heights.len(),
+        num_rows * num_cols,
+        "Invalid number of heights provided."
+    );
+    assert!(
+        num_rows > 1 && num_cols > 1,
+        "A heightfield must have at least 2 rows and columns."
+    );
+
+    let is_hole = |row: usize, col: usize| holes.contains(&(row, col));
+    let vertex_index = |row: usize, col: usize| (col * num_rows + row) as u32;
+    let vertex_position = |row: usize, col: usize| {
+        Vec3::new(
+            (-0.5 + col as f32 / (num_cols - 1) as f32) * size.x,
+            heights[col * num_rows + row] * size.y,
+            (-0.5 + row as f32 / (num_rows - 1) as f32) * size.z,
+        )
+    };
+
+    let mut positions = Vec::with_capacity(heights.len());
+    let mut uvs = Vec::with_capacity(heights.len());
+    for col in 0..num_cols {
+        for row in 0..num_rows {
+            positions.push(vertex_position(row, col));
+            uvs.push([
+                col as f32 / (num_cols - 1) as f32,
+                row as f32 / (num_rows - 1) as f32,
+            ]);
+        }
+    }
+
+    // Triangulate every cell that is not a hole, accumulating the face normals so the surface
+    // is smoothly shaded.
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    let mut indices = Vec::new();
+    for col in 0..num_cols - 1 {
+        for row in 0..num_rows - 1 {
+            if is_hole(row, col) {
+                continue;
+            }
+
+            let p00 = vertex_index(row, col);
+            let p10 = vertex_index(row + 1, col);
+            let p01 = vertex_index(row, col + 1);
+            let p11 = vertex_index(row + 1, col + 1);
+            for triangle in [[p00, p10, p01], [p10, p11, p01]] {
+                let [a, b, c] = triangle.map(|i| positions[i as usize]);
+                let normal = (b - a).cross(c - a);
+                for i in triangle {
+                    normals[i as usize] += normal;
+                }
+                indices.extend(triangle);
+            }
+        }
+    }
+    let mut normals: Vec<Vec3> = normals
+        .into_iter()
+        .map(|normal| normal.try_normalize().unwrap_or(Vec3::Y))
+        .collect();
+
+    // Stitch the rim of each hole with a wall facing into the hole.
+    if skirt_depth > 0.0 {
+        for &(row, col) in holes {
+            let neighbours = [
+                (
+                    row.checked_sub(1).map(|r| (r, col)),
+                    (row, col),
+                    (row, col + 1),
+                    Vec3::Z,
+                ),
+                (
+                    Some((row + 1, col)),
+                    (row + 1, col),
+                    (row + 1, col + 1),
+                    -Vec3::Z,
+                ),
+                (
+                    col.checked_sub(1).map(|c| (row, c)),
+                    (row, col),
+                    (row + 1, col),
+                    Vec3::X,
+                ),
+                (
+                    Some((row, col + 1)),
+                    (row, col + 1),
+                    (row + 1, col + 1),
+                    -Vec3::X,
+                ),
+            ];
+
+            for (neighbour, (row0, col0), (row1, col1), inward) in neighbours {
+                let Some((neighbour_row, neighbour_col)) = neighbour else {
+                    continue;
+                };
+                if neighbour_row >= num_rows - 1
+                    || neighbour_col >= num_cols - 1
+                    || is_hole(neighbour_row, neighbour_col)
+                {
+                    continue;
+                }
+
+                let top0 = vertex_position(row0, col0);
+                let top1 = vertex_position(row1, col1);
+                let bottom0 = top0 - skirt_depth * Vec3::Y;
+                let bottom1 = top1 - skirt_depth * Vec3::Y;
+
+                let first = positions.len() as u32;
+                positions.extend([top0, top1, bottom1, bottom0]);
+                normals.extend([inward; 4]);
+                uvs.extend([[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+
+                if (top1 - top0).cross(bottom1 - top0).dot(inward) >= 0.0 {
+                    indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+                } else {
+                    indices.extend([first, first + 2, first + 1, first, first + 3, first + 2]);
+                }
+            }
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        positions
+            .into_iter()
+            .map(<[f32; 3]>::from)
+            .collect::<Vec<_>>(),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        normals
+            .into_iter()
+            .map(<[f32; 3]>::from)
+            .collect::<Vec<_>>(),
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+/// A single part of a [`CompoundShapeBuilder`].
+#[derive(Clone)]
+struct CompoundPart {
+    transform: Transform,
+    collider: Option<Collider>,
+    mesh: Option<Mesh>,
+}
+
+impl CompoundPart {
+    /// The size below which a part counts as flat along an axis.
+    const FLAT: f32 = 1e-6;
+
+    /// Whether the collider of the part cannot be touched, because its transform is not finite or
+    /// it is flat along at least two axes.
+    fn is_degenerate(&self) -> bool {
+        let Some(collider) = &self.collider else {
+            return false;
+        };
+        let transform = &self.transform;
+        if !(transform.translation.is_finite()
+            && transform.rotation.is_finite()
+            && transform.scale.is_finite())
+        {
+            return true;
         }
+        let aabb = collider.raw.compute_local_aabb();
+        let size = Vec3::from(aabb.maxs - aabb.mins) * transform.scale.abs();
+        let flat_axes = size
+            .to_array()
+            .iter()
+            .filter(|size| **size <= Self::FLAT)
+            .count();
+        flat_axes >= 2
+    }
+}
+
+/// The collider of a [`CompoundPart`] placed in the compound.
+struct PlacedPart {
+    transform: Transform,
+    isometry: Isometry<Real>,
+    collider: Collider,
+}
+
+impl PlacedPart {
+    /// Whether another part lies entirely inside this one, which is only known for convex parts.
+    fn contains(&self, other: &PlacedPart) -> bool {
+        // A convex shape that holds every corner of a box holds the whole box.
+        self.collider.raw.is_convex()
+            && other
+                .collider
+                .raw
+                .compute_aabb(&other.isometry)
+                .vertices()
+                .iter()
+                .all(|corner| self.collider.raw.contains_point(&self.isometry, corner))
     }
 }
 
+/// A builder for compound colliders made of several shapes, each with its own local transform.
+///
+/// The builder produces the compound [`Collider`] and a single render mesh that merges the
+/// meshes of all the parts, so a compound object only needs one entity:
+///
+/// ```ignore
+/// let table = CompoundShapeBuilder::new()
+///     .add_cuboid(Vec3::new(1.0, 0.05, 0.5), Transform::from_xyz(0.0, 0.75, 0.0))
+///     .add_cuboid(Vec3::new(0.05, 0.35, 0.05), Transform::from_xyz(0.9, 0.35, 0.4))
+///     .add_ball(0.1, Vec3::new(0.0, 0.9, 0.0))
+///     .build(&mut meshes)?;
+/// ```
+///
+/// The scale of a part's transform is applied to its collider, which turns balls and capsules
+/// into convex approximations when the scale is not uniform.
+///
+/// Degenerate parts, whose transform is not finite or that are squashed to a point or a line,
+/// are left out of the collider, since they cannot be touched and only slow down queries. So are
+/// parts that lie entirely inside another convex part. Their meshes are kept. Rapier computes the
+/// bounding boxes of the remaining parts once, when the compound is built.
+#[derive(Clone, Default)]
+pub struct CompoundShapeBuilder {
+    parts: Vec<CompoundPart>,
+    quality: MeshQuality,
+}
+
+impl CompoundShapeBuilder {
+    /// Creates a new, empty [`CompoundShapeBuilder`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the quality of the meshes of curved parts added after this call.
+    pub fn with_quality(mut self, quality: MeshQuality) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Adds a sphere centered at a point.
+    pub fn add_ball(self, radius: f32, translation: Vec3) -> Self {
+        let mesh = self.quality.sphere_mesh(radius);
+        self.add_part(
+            Collider::ball(radius),
+            Some(mesh),
+            Transform::from_translation(translation),
+        )
+    }
+
+    /// Adds a box.
+    pub fn add_cuboid(self, half_extents: Vec3, transform: Transform) -> Self {
+        let mesh = Mesh::from(shape::Box::new(
+            2. * half_extents.x,
+            2. * half_extents.y,
+            2. * half_extents.z,
+        ));
+        self.add_part(
+            Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+            Some(mesh),
+            transform,
+        )
+    }
+
+    /// Adds a capsule that stands tall in the Y direction of its transform.
+    pub fn add_capsule(self, half_length: f32, radius: f32, transform: Transform) -> Self {
+        let mesh = self.quality.capsule_mesh(half_length, radius);
+        self.add_part(
+            Collider::capsule_y(half_length, radius),
+            Some(mesh),
+            transform,
+        )
+    }
+
+    /// Adds any collider along with an optional mesh for it.
+    ///
+    /// Compound colliders are flattened into their parts, since Rapier does not allow compounds
+    /// to be nested. Other shapes made of many parts, such as heightfields, triangle meshes, and
+    /// polylines, cannot be part of a compound and are rejected.
+    pub fn add_collider(
+        mut self,
+        collider: Collider,
+        mesh: Option<Mesh>,
+        transform: Transform,
+    ) -> Result<Self> {
+        if let Some(compound) = collider.raw.as_compound() {
+            for (isometry, shape) in compound.shapes() {
+                let local = Transform {
+                    translation: Vec3::from(isometry.translation.vector),
+                    rotation: Quat::from(isometry.rotation),
+                    scale: Vec3::ONE,
+                };
+                self.parts.push(CompoundPart {
+                    transform: transform * local,
+                    collider: Some(Collider::from(shape.clone())),
+                    mesh: None,
+                });
+            }
+            // The mesh covers the whole compound, so it keeps the compound's transform.
+            if mesh.is_some() {
+                self.parts.push(CompoundPart {
+                    transform,
+                    collider: None,
+                    mesh,
+                });
+            }
+            return Ok(self);
+        }
+
+        if collider.raw.as_composite_shape().is_some() {
+            return Err(MapBuilderError::InvalidShape {
+                shape: "compound",
+                message: format!(
+                    "a {:?} cannot be part of a compound",
+                    collider.raw.shape_type()
+                ),
+            });
+        }
+        Ok(self.add_part(collider, mesh, transform))
+    }
+
+    /// Adds a part whose collider is known not to be made of many parts.
+    fn add_part(mut self, collider: Collider, mesh: Option<Mesh>, transform: Transform) -> Self {
+        self.parts.push(CompoundPart {
+            transform,
+            collider: Some(collider),
+            mesh,
+        });
+        self
+    }
+
+    /// Builds the compound collider.
+    ///
+    /// Returns an error if no parts with a collider are left once degenerate parts are left out.
+    pub fn build_collider(&self) -> Result<Collider> {
+        let mut kept: Vec<PlacedPart> = Vec::new();
+        for part in self.parts.iter().filter(|part| !part.is_degenerate()) {
+            let Some(mut collider) = part.collider.clone() else {
+                continue;
+            };
+            if part.transform.scale != Vec3::ONE {
+                collider.set_scale(part.transform.scale, 8);
+            }
+            let part = PlacedPart {
+                isometry: (part.transform.translation, part.transform.rotation).into(),
+                transform: part.transform,
+                collider,
+            };
+
+            // Parts inside other parts cannot be touched without touching the outer part first.
+            if kept.iter().any(|outer| outer.contains(&part)) {
+                continue;
+            }
+            kept.retain(|inner| !part.contains(inner));
+            kept.push(part);
+        }
+
+        if kept.is_empty() {
+            return Err(MapBuilderError::InvalidShape {
+                shape: "compound",
+                message: "no parts with a collider".to_string(),
+            });
+        }
+        Ok(Collider::compound(
+            kept.into_iter()
+                .map(|part| {
+                    (
+                        part.transform.translation,
+                        part.transform.rotation,
+                        part.collider,
+                    )
+                })
+                .collect(),
+        ))
+    }
+
+    /// Builds a single mesh from the meshes of all the parts, if any of them have one.
+    pub fn build_mesh(&self) -> Option<Mesh> {
+        let parts: Vec<(&Transform, &Mesh)> = self
+            .parts
+            .iter()
+            .filter_map(|part| part.mesh.as_ref().map(|mesh| (&part.transform, mesh)))
+            .collect();
+        (!parts.is_empty()).then(|| merge_meshes(&parts))
+    }
+
+    /// Builds the compound collider along with the merged mesh.
+    ///
+    /// Returns an error if no parts with a collider are left, see
+    /// [`CompoundShapeBuilder::build_collider`].
+    pub fn build(&self, meshes: &mut ResMut<Assets<Mesh>>) -> Result<RapierShapeBundle> {
+        Ok(RapierShapeBundle {
+            collider: self.build_collider()?,
+            mesh: self
+                .build_mesh()
+                .map(|mesh| meshes.add(mesh))
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Merges triangle meshes into one, moving each by its transform.
+fn merge_meshes(parts: &[(&Transform, &Mesh)]) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+
+    for (transform, mesh) in parts {
+        let Some(VertexAttributeValues::Float32x3(part_positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            continue;
+        };
+        let first = positions.len() as u32;
+        let count = part_positions.len();
+
+        positions.extend(
+            part_positions
+                .iter()
+                .map(|position| transform.transform_point(Vec3::from(*position)).to_array()),
+        );
+        match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+            // Normals are scaled by the inverse of the scale to stay perpendicular to the surface.
+            Some(VertexAttributeValues::Float32x3(part_normals)) => {
+                normals.extend(part_normals.iter().map(|normal| {
+                    (transform.rotation * (Vec3::from(*normal) / transform.scale))
+                        .normalize_or_zero()
+                        .to_array()
+                }))
+            }
+            _ => normals.extend(std::iter::repeat_n([0.0, 1.0, 0.0], count)),
+        }
+        match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+            Some(VertexAttributeValues::Float32x2(part_uvs)) => uvs.extend(part_uvs),
+            _ => uvs.extend(std::iter::repeat_n([0.0, 0.0], count)),
+        }
+        match mesh.indices() {
+            Some(part_indices) => {
+                indices.extend(part_indices.iter().map(|index| first + index as u32))
+            }
+            None => indices.extend(first..first + count as u32),
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
 /// A component bundle for rapier entities with a [`Collider`], [`Mesh`] and a [`StandardMaterial`].
 pub type RapierColliderPbrBundle = RapierColliderMaterialMeshBundle<StandardMaterial>;
 
@@ -93,6 +672,8 @@ pub struct RapierColliderMaterialMeshBundle<M: Material> {
     pub shape: RapierShapeBundle,
     /// The material assigned to the mesh.
     pub material: Handle<M>,
+    /// The physical material of the collider's surface.
+    pub surface: SurfaceMaterial,
     /// The transform applied to both the collider and the mesh.
     pub transform: Transform,
     /// The global transform (ncessary to make the transform work).
@@ -108,6 +689,7 @@ impl<M: Material> Default for RapierColliderMaterialMeshBundle<M> {
         Self {
             shape: RapierShapeBundle::default(),
             material: Default::default(),
+            surface: Default::default(),
             transform: Default::default(),
             global_transform: Default::default(),
             visibility: Default::default(),
@@ -115,3 +697,205 @@ impl<M: Material> Default for RapierColliderMaterialMeshBundle<M> {
         }
     }
 }
+
+/// The number of triangles used for curved meshes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeshQuality {
+    /// A quarter of the resolution of [`MeshQuality::High`], for objects far away.
+    Low,
+    /// Half the resolution of [`MeshQuality::High`].
+    Medium,
+    /// Bevy's default resolution.
+    #[default]
+    High,
+}
+
+impl MeshQuality {
+    /// Every quality from the highest to the lowest.
+    pub const ALL: [MeshQuality; 3] = [MeshQuality::High, MeshQuality::Medium, MeshQuality::Low];
+
+    fn divisor(&self) -> usize {
+        match self {
+            MeshQuality::Low => 4,
+            MeshQuality::Medium => 2,
+            MeshQuality::High => 1,
+        }
+    }
+
+    /// Creates a sphere mesh at this quality.
+    pub fn sphere_mesh(&self, radius: f32) -> Mesh {
+        let default = shape::UVSphere::default();
+        Mesh::from(shape::UVSphere {
+            radius,
+            sectors: (default.sectors / self.divisor()).max(6),
+            stacks: (default.stacks / self.divisor()).max(4),
+        })
+    }
+
+    /// Creates a capsule mesh at this quality.
+    pub fn capsule_mesh(&self, half_length: f32, radius: f32) -> Mesh {
+        let default = shape::Capsule::default();
+        Mesh::from(shape::Capsule {
+            radius,
+            depth: half_length * 2.,
+            latitudes: (default.latitudes / self.divisor()).max(4),
+            longitudes: (default.longitudes / self.divisor()).max(6),
+            ..default
+        })
+    }
+}
+
+/// A component that swaps the rendered mesh of an entity depending on its distance to the
+/// closest active camera.
+///
+/// Only the mesh is swapped, the collider of the entity is never changed.
+#[derive(Component, Debug, Clone, Default)]
+pub struct MeshLod {
+    /// The meshes and the distance up to which each one is used, sorted by distance.
+    ///
+    /// The last mesh is used beyond the last distance.
+    pub levels: Vec<(f32, Handle<Mesh>)>,
+    current: Option<usize>,
+}
+
+impl MeshLod {
+    /// Creates a new [`MeshLod`] from meshes and the distances up to which they are used.
+    pub fn new(mut levels: Vec<(f32, Handle<Mesh>)>) -> Self {
+        levels.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        Self {
+            levels,
+            current: None,
+        }
+    }
+
+    /// Creates the levels of detail of a sphere.
+    ///
+    /// `distances` are the distances up to which the high and medium quality meshes are used.
+    pub fn sphere(radius: f32, distances: [f32; 2], meshes: &mut ResMut<Assets<Mesh>>) -> Self {
+        Self::from_qualities(distances, meshes, |quality| quality.sphere_mesh(radius))
+    }
+
+    /// Creates the levels of detail of a capsule.
+    ///
+    /// `distances` are the distances up to which the high and medium quality meshes are used.
+    pub fn capsule(
+        half_length: f32,
+        radius: f32,
+        distances: [f32; 2],
+        meshes: &mut ResMut<Assets<Mesh>>,
+    ) -> Self {
+        Self::from_qualities(distances, meshes, |quality| {
+            quality.capsule_mesh(half_length, radius)
+        })
+    }
+
+    fn from_qualities(
+        distances: [f32; 2],
+        meshes: &mut ResMut<Assets<Mesh>>,
+        mesh: impl Fn(MeshQuality) -> Mesh,
+    ) -> Self {
+        let [high, medium] = distances;
+        Self::new(
+            MeshQuality::ALL
+                .into_iter()
+                .zip([high, medium, f32::INFINITY])
+                .map(|(quality, distance)| (distance, meshes.add(mesh(quality))))
+                .collect(),
+        )
+    }
+
+    /// Returns the index of the level to use at the given distance.
+    pub fn level_at(&self, distance: f32) -> Option<usize> {
+        self.levels
+            .iter()
+            .position(|(max_distance, _)| distance <= *max_distance)
+            .or_else(|| self.levels.len().checked_sub(1))
+    }
+}
+
+/// A plugin that swaps the meshes of entities with a [`MeshLod`].
+#[derive(Default)]
+pub struct MeshLodPlugin;
+
+impl MeshLodPlugin {
+    /// Creates a new [`MeshLodPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for MeshLodPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(CoreStage::PostUpdate, update_mesh_lods);
+    }
+}
+
+/// Picks the level of detail of every [`MeshLod`] from the distance to the closest active camera.
+pub fn update_mesh_lods(
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut lods: Query<(&mut MeshLod, &mut Handle<Mesh>, &GlobalTransform)>,
+) {
+    let camera_positions: Vec<Vec3> = cameras
+        .iter()
+        .filter(|(camera, _)| camera.is_active)
+        .map(|(_, transform)| transform.translation())
+        .collect();
+    if camera_positions.is_empty() {
+        return;
+    }
+
+    for (mut lod, mut mesh, transform) in &mut lods {
+        let position = transform.translation();
+        let distance = camera_positions
+            .iter()
+            .map(|camera| camera.distance(position))
+            .fold(f32::INFINITY, f32::min);
+
+        let level = lod.level_at(distance);
+        if level != lod.current {
+            lod.current = level;
+            if let Some((_, handle)) = level.and_then(|level| lod.levels.get(level)) {
+                *mesh = handle.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compound_without_parts_is_rejected() {
+        assert!(matches!(
+            CompoundShapeBuilder::new().build_collider(),
+            Err(MapBuilderError::InvalidShape { .. })
+        ));
+        assert!(CompoundShapeBuilder::new()
+            .add_cuboid(Vec3::new(1.0, 0.0, 0.0), Transform::IDENTITY)
+            .build_collider()
+            .is_err());
+    }
+
+    #[test]
+    fn compound_rejects_composite_parts() {
+        let trimesh = Collider::trimesh(vec![Vec3::ZERO, Vec3::X, Vec3::Z], vec![[0, 1, 2]]);
+
+        assert!(CompoundShapeBuilder::new()
+            .add_collider(trimesh, None, Transform::IDENTITY)
+            .is_err());
+    }
+
+    #[test]
+    fn compound_leaves_out_parts_inside_other_parts() {
+        let collider = CompoundShapeBuilder::new()
+            .add_cuboid(Vec3::ONE, Transform::IDENTITY)
+            .add_ball(0.5, Vec3::new(0.2, 0.0, 0.0))
+            .add_cuboid(Vec3::ONE, Transform::IDENTITY)
+            .add_ball(0.5, Vec3::new(2.0, 0.0, 0.0))
+            .build_collider()
+            .unwrap();
+
+        assert_eq!(collider.as_compound().unwrap().raw.shapes().len(), 2);
+    }
+}
@@ -0,0 +1,337 @@
+//! A mod that records controller input and plays it back.
+//!
+//! A [`Replay`] stores the [`FpsControlEvent`]s and the [`PlayerFpsControlEvent`]s of gamepad and
+//! split-screen players of every frame along with the frame's delta time.
+//! Playing it back feeds the same events through the same systems with the same delta times, so
+//! the character moves exactly as it did when it was recorded. This is used to write regression
+//! tests for the controller and the physics, and to share reproductions of collision bugs.
+//!
+//! Live input is discarded while a replay plays. Recordings start with a [`WorldSnapshot`] that is
+//! restored before playback, so the world starts in the same state too.
+
+use crate::{
+    controller::{fps_controller::*, split_screen::*},
+    error::*,
+    persistence::*,
+    state::*,
+    time_scale::*,
+};
+
+use bevy::{ecs::event::ManualEventReader, prelude::*, time::TimeSystem};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// The version written to new replays. Replays with another version cannot be played.
+pub const REPLAY_VERSION: u32 = 1;
+
+/// The input of a single frame.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    /// The delta time of the frame in seconds.
+    pub delta: f32,
+    /// The control events of the frame.
+    pub events: Vec<FpsControlEvent>,
+    /// The control events of the frame for players in a single slot.
+    #[serde(default)]
+    pub player_events: Vec<PlayerFpsControlEvent>,
+}
+
+/// A recording of controller input.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    /// The version of the replay format.
+    pub version: u32,
+    /// The state of the world when the recording started.
+    #[serde(default)]
+    pub snapshot: Option<WorldSnapshot>,
+    /// The input of every recorded frame.
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    /// Creates an empty replay that starts from a snapshot.
+    pub fn new(snapshot: Option<WorldSnapshot>) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            snapshot,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Reads a replay from RON, rejecting other versions of the format.
+    pub fn from_ron(ron: &str) -> Result<Self> {
+        let replay: Self = ron::from_str(ron)?;
+        if replay.version != REPLAY_VERSION {
+            return Err(MapBuilderError::UnsupportedVersion {
+                kind: "replay",
+                found: replay.version,
+                expected: REPLAY_VERSION,
+            });
+        }
+        Ok(replay)
+    }
+
+    /// Writes the replay to RON.
+    pub fn to_ron(&self) -> Result<String> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    /// Reads a replay file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_ron(&std::fs::read_to_string(path)?)
+    }
+
+    /// Writes the replay to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(std::fs::write(path, self.to_ron()?)?)
+    }
+}
+
+/// What the replay system is currently doing.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub enum ReplayState {
+    /// Neither recording nor playing.
+    #[default]
+    Idle,
+    /// Recording input into the replay.
+    Recording(Replay),
+    /// Playing the replay back.
+    Playing {
+        /// The replay being played.
+        replay: Replay,
+        /// The index of the next frame to play.
+        frame: usize,
+    },
+}
+
+impl ReplayState {
+    fn current_frame(&self) -> Option<&ReplayFrame> {
+        match self {
+            ReplayState::Playing { replay, frame } => replay.frames.get(*frame),
+            _ => None,
+        }
+    }
+}
+
+/// A request to start or stop recording or playing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayRequest {
+    /// Starts recording from a snapshot of the current world.
+    StartRecording,
+    /// Stops recording and saves the replay to the file.
+    StopRecording(PathBuf),
+    /// Loads a replay file and plays it.
+    PlayFile(PathBuf),
+    /// Plays a replay.
+    Play(Replay),
+    /// Stops recording or playing, discarding any recording.
+    Stop,
+}
+
+impl std::fmt::Display for ReplayRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayRequest::StartRecording => write!(f, "starting a recording"),
+            ReplayRequest::StopRecording(path) => {
+                write!(f, "saving replay {}", path.display())
+            }
+            ReplayRequest::PlayFile(path) => write!(f, "playing replay {}", path.display()),
+            ReplayRequest::Play(_) => write!(f, "playing a replay"),
+            ReplayRequest::Stop => write!(f, "stopping the replay"),
+        }
+    }
+}
+
+/// An event sent when a replay has played its last frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayFinished;
+
+/// A plugin that records and plays back controller input.
+///
+/// Requests that fail send a [`MapBuilderErrorEvent`].
+#[derive(Default)]
+pub struct ReplayPlugin;
+
+impl ReplayPlugin {
+    /// Creates a new [`ReplayPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayState>()
+            .add_event::<ReplayRequest>()
+            .add_event::<ReplayFinished>()
+            .add_event::<MapBuilderErrorEvent>()
+            .add_event::<FpsControlEvent>()
+            .add_event::<PlayerFpsControlEvent>()
+            .add_system_to_stage(
+                CoreStage::First,
+                apply_replay_delta.after(TimeSystem).after(advance_frame),
+            )
+            .add_system(
+                record_and_play_input
+                    .with_run_criteria(is_playing)
+                    .after(custom_input_map)
+                    .after(gamepad_input_map)
+                    .before(fps_control_system),
+            )
+            .add_system_to_stage(CoreStage::Last, handle_replay_requests);
+    }
+}
+
+/// Replaces the delta time of the frame with the recorded one while a replay plays.
+pub fn apply_replay_delta(replay_state: Res<ReplayState>, mut time: ResMut<Time>) {
+    if let Some(frame) = replay_state.current_frame() {
+        // The recorded delta already includes the time scale it was recorded with.
+        set_delta(&mut time, Duration::from_secs_f32(frame.delta.max(0.0)));
+    }
+}
+
+/// Records the control events of the frame, or replaces them with the recorded ones.
+pub fn record_and_play_input(
+    time: Res<Time>,
+    mut replay_state: ResMut<ReplayState>,
+    mut control_events: ResMut<Events<FpsControlEvent>>,
+    mut player_events: ResMut<Events<PlayerFpsControlEvent>>,
+    mut readers: Local<(
+        ManualEventReader<FpsControlEvent>,
+        ManualEventReader<PlayerFpsControlEvent>,
+    )>,
+    mut finished: EventWriter<ReplayFinished>,
+) {
+    let (reader, player_reader) = &mut *readers;
+
+    // Always read, so that the events of the frame before a recording starts are not recorded.
+    let live_events: Vec<FpsControlEvent> = reader.iter(&control_events).copied().collect();
+    let live_player_events: Vec<PlayerFpsControlEvent> =
+        player_reader.iter(&player_events).copied().collect();
+
+    match &mut *replay_state {
+        ReplayState::Idle => {}
+        ReplayState::Recording(replay) => replay.frames.push(ReplayFrame {
+            delta: time.delta_seconds(),
+            events: live_events,
+            player_events: live_player_events,
+        }),
+        ReplayState::Playing { replay, frame } => {
+            control_events.clear();
+            player_events.clear();
+            if let Some(recorded) = replay.frames.get(*frame) {
+                control_events.extend(recorded.events.iter().copied());
+                player_events.extend(recorded.player_events.iter().copied());
+            }
+            // Skip the played events so they are not read again next frame.
+            reader.iter(&control_events).for_each(drop);
+            player_reader.iter(&player_events).for_each(drop);
+
+            *frame += 1;
+            if *frame >= replay.frames.len() {
+                *replay_state = ReplayState::Idle;
+                finished.send(ReplayFinished);
+            }
+        }
+    }
+}
+
+/// Starts and stops recordings and playback for every [`ReplayRequest`].
+pub fn handle_replay_requests(world: &mut World) {
+    let requests: Vec<ReplayRequest> = world
+        .resource_mut::<Events<ReplayRequest>>()
+        .drain()
+        .collect();
+
+    for request in requests {
+        let result = match request.clone() {
+            ReplayRequest::StartRecording => {
+                let snapshot = WorldSnapshot::capture(world);
+                *world.resource_mut::<ReplayState>() =
+                    ReplayState::Recording(Replay::new(Some(snapshot)));
+                Ok(())
+            }
+            ReplayRequest::StopRecording(path) => {
+                match std::mem::take(&mut *world.resource_mut::<ReplayState>()) {
+                    ReplayState::Recording(replay) => replay.save(path),
+                    _ => Err(MapBuilderError::NotRecording),
+                }
+            }
+            ReplayRequest::PlayFile(path) => Replay::load(path).map(|replay| play(world, replay)),
+            ReplayRequest::Play(replay) => {
+                play(world, replay);
+                Ok(())
+            }
+            ReplayRequest::Stop => {
+                *world.resource_mut::<ReplayState>() = ReplayState::Idle;
+                Ok(())
+            }
+        };
+        if let Err(error) = result {
+            report_error(world, request.to_string(), error);
+        }
+    }
+}
+
+fn play(world: &mut World, replay: Replay) {
+    if let Some(snapshot) = &replay.snapshot {
+        snapshot.restore(world);
+    }
+    *world.resource_mut::<ReplayState>() = if replay.frames.is_empty() {
+        ReplayState::Idle
+    } else {
+        ReplayState::Playing { replay, frame: 0 }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_round_trips_through_ron() {
+        let mut replay = Replay::new(Some(WorldSnapshot {
+            version: SNAPSHOT_VERSION,
+            ..default()
+        }));
+        replay.frames.push(ReplayFrame {
+            delta: 0.016,
+            events: vec![
+                FpsControlEvent::Translate(Vec3::new(0.5, 0.0, -1.0)),
+                FpsControlEvent::Jump(Vec3::Y),
+            ],
+            player_events: vec![PlayerFpsControlEvent {
+                player: 1,
+                event: FpsControlEvent::RotateCamera(Vec2::new(0.25, -0.5)),
+            }],
+        });
+        replay.frames.push(ReplayFrame {
+            delta: 0.02,
+            ..default()
+        });
+
+        let ron = replay.to_ron().unwrap();
+        assert_eq!(Replay::from_ron(&ron).unwrap(), replay);
+    }
+
+    #[test]
+    fn replay_rejects_other_versions() {
+        let replay = Replay {
+            version: REPLAY_VERSION + 1,
+            ..Replay::new(None)
+        };
+
+        let ron = replay.to_ron().unwrap();
+        assert!(matches!(
+            Replay::from_ron(&ron),
+            Err(MapBuilderError::UnsupportedVersion { found, expected, .. })
+                if found == REPLAY_VERSION + 1 && expected == REPLAY_VERSION
+        ));
+    }
+}
@@ -0,0 +1,154 @@
+//! A mod that integrates the crate with a stack of game states.
+//!
+//! [`MapBuilderState`] is a Bevy state. Gameplay systems in this crate, such as the controllers,
+//! use the [`is_playing`] run criteria and only run in [`MapBuilderState::Playing`]. The Rapier
+//! physics pipeline is paused whenever the current state is anything else.
+//!
+//! Apps that do not add the [`MapBuilderStatePlugin`] have no state at all, in which case the
+//! gameplay systems always run.
+//!
+//! Host games change the state by sending [`MapBuilderStateRequest`]s, or by using the
+//! [`State<MapBuilderState>`] resource directly. Pausing pushes [`MapBuilderState::Paused`] on
+//! top of the current state so that resuming returns to whatever was running before.
+
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+use bevy_rapier3d::prelude::*;
+
+/// The states of an app that uses the map builder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MapBuilderState {
+    /// The map is being loaded.
+    Loading,
+    /// The map is being edited.
+    Editing,
+    /// The map is being played.
+    Playing,
+    /// The game is paused on top of another state.
+    Paused,
+}
+
+/// Run criteria that only runs gameplay systems while playing, or when there is no state.
+pub fn is_playing(state: Option<Res<State<MapBuilderState>>>) -> ShouldRun {
+    match state {
+        Some(state) if *state.current() != MapBuilderState::Playing => ShouldRun::No,
+        _ => ShouldRun::Yes,
+    }
+}
+
+/// Run criteria that only runs editing systems while editing, or when there is no state.
+pub fn is_editing(state: Option<Res<State<MapBuilderState>>>) -> ShouldRun {
+    match state {
+        Some(state) if *state.current() != MapBuilderState::Editing => ShouldRun::No,
+        _ => ShouldRun::Yes,
+    }
+}
+
+/// A request to change the [`MapBuilderState`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapBuilderStateRequest {
+    /// Replaces the whole stack with a state.
+    Set(MapBuilderState),
+    /// Pushes a state on top of the current one.
+    Push(MapBuilderState),
+    /// Returns to the previous state.
+    Pop,
+    /// Pushes [`MapBuilderState::Paused`], or pops it if the game is already paused.
+    TogglePause,
+}
+
+/// A plugin that adds the [`MapBuilderState`] and pauses physics outside of play.
+pub struct MapBuilderStatePlugin {
+    /// The state when the app starts.
+    pub initial: MapBuilderState,
+    /// A key that toggles the pause, if any.
+    pub pause_key: Option<KeyCode>,
+}
+
+impl Default for MapBuilderStatePlugin {
+    fn default() -> Self {
+        Self {
+            initial: MapBuilderState::Playing,
+            pause_key: Some(KeyCode::Escape),
+        }
+    }
+}
+
+impl MapBuilderStatePlugin {
+    /// Creates a new [`MapBuilderStatePlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`MapBuilderStatePlugin`] that starts in the given state.
+    pub fn with_initial_state(initial: MapBuilderState) -> Self {
+        Self {
+            initial,
+            ..default()
+        }
+    }
+}
+
+/// The key that toggles the pause, used by [`pause_on_key`].
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct PauseKey(pub KeyCode);
+
+impl Plugin for MapBuilderStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state(self.initial)
+            .add_event::<MapBuilderStateRequest>()
+            .add_system(apply_state_requests)
+            .add_system(pause_physics_outside_play);
+
+        if let Some(pause_key) = self.pause_key {
+            app.insert_resource(PauseKey(pause_key))
+                .add_system(pause_on_key.before(apply_state_requests));
+        }
+    }
+}
+
+/// Sends a [`MapBuilderStateRequest::TogglePause`] when the [`PauseKey`] is pressed.
+pub fn pause_on_key(
+    pause_key: Res<PauseKey>,
+    keyboard: Res<Input<KeyCode>>,
+    mut requests: EventWriter<MapBuilderStateRequest>,
+) {
+    if keyboard.just_pressed(pause_key.0) {
+        requests.send(MapBuilderStateRequest::TogglePause);
+    }
+}
+
+/// Applies the [`MapBuilderStateRequest`]s to the state stack.
+pub fn apply_state_requests(
+    mut state: ResMut<State<MapBuilderState>>,
+    mut requests: EventReader<MapBuilderStateRequest>,
+) {
+    for request in requests.iter() {
+        let result = match *request {
+            MapBuilderStateRequest::Set(next) => state.replace(next),
+            MapBuilderStateRequest::Push(next) => state.push(next),
+            MapBuilderStateRequest::Pop => state.pop(),
+            MapBuilderStateRequest::TogglePause => match state.current() {
+                MapBuilderState::Paused => state.pop(),
+                MapBuilderState::Playing => state.push(MapBuilderState::Paused),
+                // Only gameplay can be paused.
+                _ => Ok(()),
+            },
+        };
+
+        // Only one change can be queued per frame, so the remaining requests are dropped.
+        if let Err(error) = result {
+            warn!("Could not apply {:?}: {}", request, error);
+        }
+    }
+}
+
+/// Pauses the Rapier physics pipeline whenever the state is not [`MapBuilderState::Playing`].
+pub fn pause_physics_outside_play(
+    state: Res<State<MapBuilderState>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    let active = *state.current() == MapBuilderState::Playing;
+    if rapier_config.physics_pipeline_active != active {
+        rapier_config.physics_pipeline_active = active;
+    }
+}
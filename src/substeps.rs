@@ -0,0 +1,182 @@
+//! A mod that splits the physics step into substeps when bodies move fast.
+//!
+//! A body that moves further in one step than it is thick can pass through thin walls between
+//! two steps. Every frame, the [`SubstepPlugin`] finds the fastest dynamic body and sets the
+//! substeps of Rapier's [`TimestepMode`] so that no body moves further than
+//! [`SubstepSettings::max_step_distance`] in one substep. Because the limit is a distance rather
+//! than a speed, low frame rates get more substeps and high frame rates fewer, and bodies are
+//! kept from tunneling at any frame rate.
+//!
+//! The substeps are capped at [`SubstepSettings::max_substeps`]. Bodies too fast even for that
+//! have continuous collision detection turned on through [`Ccd`] until they slow down again.
+//!
+//! Character controllers sweep their whole move against the map every frame, so they cannot
+//! tunnel and are not counted.
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+/// Settings for the [`SubstepPlugin`].
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct SubstepSettings {
+    /// The furthest a body may move in one substep, in units.
+    pub max_step_distance: f32,
+    /// The most substeps a step is split into.
+    pub max_substeps: usize,
+    /// Whether bodies too fast for the most substeps get continuous collision detection.
+    pub ccd_beyond_max: bool,
+}
+
+impl Default for SubstepSettings {
+    fn default() -> Self {
+        Self {
+            max_step_distance: 0.25,
+            max_substeps: 8,
+            ccd_beyond_max: true,
+        }
+    }
+}
+
+impl SubstepSettings {
+    /// The substeps needed for a body to move no further than the limit in any of them.
+    pub fn substeps_for(&self, distance: f32) -> usize {
+        if self.max_step_distance <= 0.0 || !distance.is_finite() {
+            return self.max_substeps.max(1);
+        }
+        ((distance / self.max_step_distance).ceil() as usize).clamp(1, self.max_substeps.max(1))
+    }
+}
+
+/// A marker for bodies whose [`Ccd`] was turned on by the [`SubstepPlugin`].
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct SubstepCcd;
+
+/// A plugin that splits the physics step into substeps so that fast bodies do not tunnel.
+#[derive(Default)]
+pub struct SubstepPlugin {
+    /// The settings used by the plugin.
+    pub settings: SubstepSettings,
+}
+
+impl SubstepPlugin {
+    /// Creates a new [`SubstepPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`SubstepPlugin`] that keeps bodies from moving further than a distance in
+    /// one substep.
+    pub fn with_max_step_distance(max_step_distance: f32) -> Self {
+        Self {
+            settings: SubstepSettings {
+                max_step_distance,
+                ..default()
+            },
+        }
+    }
+}
+
+impl Plugin for SubstepPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings)
+            .add_system_to_stage(CoreStage::PostUpdate, update_substeps);
+    }
+}
+
+/// The length of one physics step and its number of substeps.
+fn step_length(mode: &TimestepMode, delta: f32) -> (f32, usize) {
+    match *mode {
+        TimestepMode::Fixed { dt, substeps } => (dt, substeps),
+        TimestepMode::Variable {
+            max_dt,
+            time_scale,
+            substeps,
+        } => ((delta * time_scale).min(max_dt), substeps),
+        TimestepMode::Interpolated {
+            dt,
+            time_scale,
+            substeps,
+        } => (dt * time_scale, substeps),
+    }
+}
+
+/// Sets the substeps for the next step from the fastest dynamic body, and turns continuous
+/// collision detection on and off for bodies that are too fast for them.
+#[allow(clippy::type_complexity)]
+pub fn update_substeps(
+    mut commands: Commands,
+    settings: Res<SubstepSettings>,
+    time: Res<Time>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    bodies: Query<(
+        Entity,
+        &RigidBody,
+        &Velocity,
+        Option<&Ccd>,
+        Option<&SubstepCcd>,
+    )>,
+) {
+    let (dt, current) = step_length(&rapier_config.timestep_mode, time.delta_seconds());
+    if dt <= 0.0 {
+        return;
+    }
+
+    let mut fastest: f32 = 0.0;
+    for (entity, body, velocity, ccd, auto_ccd) in &bodies {
+        if *body != RigidBody::Dynamic {
+            continue;
+        }
+        let distance = velocity.linvel.length() * dt;
+        if distance.is_finite() {
+            fastest = fastest.max(distance);
+        }
+
+        // Only bodies that still move too far with every substep need continuous detection.
+        let too_fast = settings.ccd_beyond_max
+            && distance > settings.max_step_distance * settings.max_substeps.max(1) as f32;
+        if too_fast && ccd.is_none() {
+            commands.entity(entity).insert((Ccd::enabled(), SubstepCcd));
+        } else if !too_fast && auto_ccd.is_some() {
+            commands.entity(entity).remove::<(Ccd, SubstepCcd)>();
+        }
+    }
+
+    let substeps = settings.substeps_for(fastest);
+    if substeps != current {
+        match &mut rapier_config.timestep_mode {
+            TimestepMode::Fixed { substeps: s, .. }
+            | TimestepMode::Variable { substeps: s, .. }
+            | TimestepMode::Interpolated { substeps: s, .. } => *s = substeps,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substeps_keep_each_step_under_the_limit() {
+        let settings = SubstepSettings::default();
+
+        assert_eq!(settings.substeps_for(0.0), 1);
+        assert_eq!(settings.substeps_for(0.25), 1);
+        assert_eq!(settings.substeps_for(0.26), 2);
+        assert_eq!(settings.substeps_for(1.0), 4);
+        assert_eq!(settings.substeps_for(100.0), settings.max_substeps);
+    }
+
+    #[test]
+    fn substeps_use_the_most_for_bad_input() {
+        let settings = SubstepSettings {
+            max_step_distance: 0.0,
+            ..default()
+        };
+
+        assert_eq!(settings.substeps_for(1.0), settings.max_substeps);
+        assert_eq!(
+            SubstepSettings::default().substeps_for(f32::NAN),
+            settings.max_substeps
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! A mod with physics materials for the surfaces of a map.
+//!
+//! A [`SurfaceMaterial`] describes how slippery and bouncy a collider is, along with a
+//! [`SurfaceTag`] saying what it is made of. The [`SurfaceMaterialPlugin`] keeps the Rapier
+//! [`Friction`] and [`Restitution`] of every collider in sync with its material, so maps only
+//! need to pick a material from the library, such as [`SurfaceMaterial::STONE`] or
+//! [`SurfaceMaterial::ICE`].
+//!
+//! The tag is what games use to pick footstep and impact sounds. [`SurfaceQuery`] finds the
+//! surface below a character, and the plugin uses it to send a [`FootstepEvent`] every stride
+//! that a character controller walks.
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// What a surface is made of.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, Reflect, FromReflect,
+)]
+pub enum SurfaceTag {
+    /// A surface without any particular material.
+    #[default]
+    Generic,
+    /// Rock, concrete, and tiles.
+    Stone,
+    /// Planks, crates, and furniture.
+    Wood,
+    /// Sheet metal, grates, and pipes.
+    Metal,
+    /// Grass, dirt, and other soft ground.
+    Grass,
+    /// Ice and other slippery surfaces.
+    Ice,
+    /// Rubber and other bouncy surfaces.
+    Rubber,
+}
+
+impl SurfaceTag {
+    /// Every tag in the library.
+    pub const ALL: [SurfaceTag; 7] = [
+        SurfaceTag::Generic,
+        SurfaceTag::Stone,
+        SurfaceTag::Wood,
+        SurfaceTag::Metal,
+        SurfaceTag::Grass,
+        SurfaceTag::Ice,
+        SurfaceTag::Rubber,
+    ];
+}
+
+/// The physical properties of a collider's surface.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Component, Default)]
+#[serde(default)]
+pub struct SurfaceMaterial {
+    /// What the surface is made of.
+    pub tag: SurfaceTag,
+    /// The friction coefficient, where `0.0` is frictionless.
+    pub friction: f32,
+    /// The restitution coefficient, where `0.0` does not bounce and `1.0` bounces forever.
+    pub restitution: f32,
+}
+
+impl Default for SurfaceMaterial {
+    fn default() -> Self {
+        Self::GENERIC
+    }
+}
+
+impl From<SurfaceTag> for SurfaceMaterial {
+    fn from(tag: SurfaceTag) -> Self {
+        match tag {
+            SurfaceTag::Generic => Self::GENERIC,
+            SurfaceTag::Stone => Self::STONE,
+            SurfaceTag::Wood => Self::WOOD,
+            SurfaceTag::Metal => Self::METAL,
+            SurfaceTag::Grass => Self::GRASS,
+            SurfaceTag::Ice => Self::ICE,
+            SurfaceTag::Rubber => Self::RUBBER,
+        }
+    }
+}
+
+impl SurfaceMaterial {
+    /// Rapier's default friction and restitution.
+    pub const GENERIC: Self = Self::new(SurfaceTag::Generic, 0.5, 0.0);
+    /// A rough surface that barely bounces.
+    pub const STONE: Self = Self::new(SurfaceTag::Stone, 0.8, 0.05);
+    /// A surface with moderate grip.
+    pub const WOOD: Self = Self::new(SurfaceTag::Wood, 0.6, 0.1);
+    /// A smooth surface with a slight bounce.
+    pub const METAL: Self = Self::new(SurfaceTag::Metal, 0.4, 0.15);
+    /// A soft surface that absorbs impacts.
+    pub const GRASS: Self = Self::new(SurfaceTag::Grass, 0.7, 0.0);
+    /// A surface with almost no grip.
+    pub const ICE: Self = Self::new(SurfaceTag::Ice, 0.03, 0.05);
+    /// A grippy surface that bounces strongly.
+    pub const RUBBER: Self = Self::new(SurfaceTag::Rubber, 1.0, 0.8);
+
+    /// Creates a new [`SurfaceMaterial`].
+    pub const fn new(tag: SurfaceTag, friction: f32, restitution: f32) -> Self {
+        Self {
+            tag,
+            friction,
+            restitution,
+        }
+    }
+}
+
+/// An event sent when a character controller takes a step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FootstepEvent {
+    /// The character that took the step.
+    pub entity: Entity,
+    /// What the character stepped on.
+    pub surface: SurfaceTag,
+}
+
+/// Settings for the [`SurfaceMaterialPlugin`].
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct SurfaceSettings {
+    /// How far a character walks between footsteps, in world units.
+    pub stride_length: f32,
+    /// How far below the bottom of a character the ground is searched for.
+    pub ground_distance: f32,
+}
+
+impl Default for SurfaceSettings {
+    fn default() -> Self {
+        Self {
+            stride_length: 0.75,
+            ground_distance: 0.2,
+        }
+    }
+}
+
+/// A plugin that applies [`SurfaceMaterial`]s and sends [`FootstepEvent`]s.
+#[derive(Default)]
+pub struct SurfaceMaterialPlugin {
+    /// The settings used by the plugin.
+    pub settings: SurfaceSettings,
+}
+
+impl SurfaceMaterialPlugin {
+    /// Creates a new [`SurfaceMaterialPlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Plugin for SurfaceMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SurfaceMaterial>()
+            .register_type::<SurfaceTag>()
+            .insert_resource(self.settings)
+            .add_event::<FootstepEvent>()
+            .add_system(apply_surface_materials)
+            .add_system(emit_footsteps);
+    }
+}
+
+/// Copies changed [`SurfaceMaterial`]s into the Rapier friction and restitution of colliders.
+///
+/// Colliders that already have a [`Friction`] or [`Restitution`] keep their combine rule.
+#[allow(clippy::type_complexity)]
+pub fn apply_surface_materials(
+    mut commands: Commands,
+    mut surfaces: Query<
+        (
+            Entity,
+            &SurfaceMaterial,
+            Option<&mut Friction>,
+            Option<&mut Restitution>,
+        ),
+        Changed<SurfaceMaterial>,
+    >,
+) {
+    for (entity, surface, friction, restitution) in &mut surfaces {
+        match friction {
+            Some(mut friction) => friction.coefficient = surface.friction,
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(Friction::coefficient(surface.friction));
+            }
+        }
+        match restitution {
+            Some(mut restitution) => restitution.coefficient = surface.restitution,
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(Restitution::coefficient(surface.restitution));
+            }
+        }
+    }
+}
+
+/// Finds the surfaces that characters stand on.
+#[derive(SystemParam)]
+pub struct SurfaceQuery<'w, 's> {
+    rapier_context: Res<'w, RapierContext>,
+    settings: Res<'w, SurfaceSettings>,
+    bodies: Query<'w, 's, (&'static GlobalTransform, Option<&'static Collider>)>,
+    surfaces: Query<'w, 's, &'static SurfaceMaterial>,
+    parents: Query<'w, 's, &'static Parent>,
+}
+
+impl<'w, 's> SurfaceQuery<'w, 's> {
+    /// Finds the material of the ground below a body.
+    ///
+    /// Colliders without a [`SurfaceMaterial`] use the material of their closest ancestor that
+    /// has one, or [`SurfaceMaterial::GENERIC`]. Returns [`None`] if there is no ground within
+    /// reach of the bottom of the body.
+    pub fn below(&self, body: Entity) -> Option<SurfaceMaterial> {
+        let (transform, collider) = self.bodies.get(body).ok()?;
+        let origin = transform.translation();
+        let half_height = collider.map_or(0.0, |collider| {
+            collider.raw.compute_local_aabb().half_extents().y
+        });
+        let filter = QueryFilter::default()
+            .exclude_sensors()
+            .exclude_rigid_body(body)
+            .exclude_collider(body);
+
+        let (hit, _) = self.rapier_context.cast_ray(
+            origin,
+            -Vec3::Y,
+            half_height + self.settings.ground_distance,
+            true,
+            filter,
+        )?;
+        Some(self.material_of(hit))
+    }
+
+    /// Finds the material of a collider, inheriting it from its ancestors if it has none.
+    pub fn material_of(&self, collider: Entity) -> SurfaceMaterial {
+        std::iter::successors(Some(collider), |entity| {
+            self.parents.get(*entity).ok().map(|parent| parent.get())
+        })
+        .find_map(|entity| self.surfaces.get(entity).ok().copied())
+        .unwrap_or_default()
+    }
+}
+
+/// Sends a [`FootstepEvent`] whenever a grounded character controller has walked a stride.
+pub fn emit_footsteps(
+    settings: Res<SurfaceSettings>,
+    surface_query: SurfaceQuery,
+    mut footsteps: EventWriter<FootstepEvent>,
+    mut walked: Local<HashMap<Entity, f32>>,
+    controllers: Query<(Entity, &KinematicCharacterControllerOutput)>,
+) {
+    walked.retain(|entity, _| controllers.contains(*entity));
+
+    for (entity, output) in &controllers {
+        let distance = walked.entry(entity).or_default();
+        let translation = output.effective_translation;
+        *distance += Vec2::new(translation.x, translation.z).length();
+
+        // Steps taken in the air are counted and heard on landing.
+        if !output.grounded || *distance < settings.stride_length {
+            continue;
+        }
+        *distance %= settings.stride_length;
+
+        let surface = surface_query.below(entity).unwrap_or_default();
+        footsteps.send(FootstepEvent {
+            entity,
+            surface: surface.tag,
+        });
+    }
+}
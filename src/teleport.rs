@@ -0,0 +1,158 @@
+//! A mod for moving bodies somewhere else without leaving them stuck or still moving.
+//!
+//! Writing a new [`Transform`] moves a body, but it can land inside a wall and it keeps the speed
+//! it had before. [`TeleportExt::teleport_entity`] checks the destination against the fixed
+//! geometry first and moves the body to the nearest free spot within the search radius of the
+//! [`DepenetrationSettings`]. It then stops the body by resetting its [`CustomVelocity`] and
+//! [`Velocity`] and dropping the movement queued on its [`KinematicCharacterController`].
+//!
+//! Every teleport sends a [`TeleportEvent`], so that systems which follow bodies between frames
+//! can tell a teleport from a body moving very fast.
+
+use crate::{controller::*, depenetration::*};
+
+use bevy::{ecs::system::Command, prelude::*};
+use bevy_rapier3d::prelude::*;
+
+/// An event sent when a body is teleported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TeleportEvent {
+    /// The body that was teleported.
+    pub entity: Entity,
+    /// Where the body was before the teleport.
+    pub from: Vec3,
+    /// Where the body is now, which can differ from the requested destination.
+    pub to: Vec3,
+}
+
+/// A plugin for the [`TeleportEvent`]s sent by teleports.
+#[derive(Default)]
+pub struct TeleportPlugin;
+
+impl TeleportPlugin {
+    /// Creates a new [`TeleportPlugin`].
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Plugin for TeleportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TeleportEvent>();
+    }
+}
+
+/// A command that teleports a body to a transform.
+///
+/// The body should not have a parent, since the transform is written as it is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TeleportEntity {
+    /// The body to teleport.
+    pub entity: Entity,
+    /// Where to teleport the body to.
+    pub target: Transform,
+    /// The velocity the body has after the teleport.
+    pub velocity: Vec3,
+}
+
+impl TeleportEntity {
+    /// Creates a new [`TeleportEntity`] that leaves the body standing still.
+    pub fn new(entity: Entity, target: Transform) -> Self {
+        Self {
+            entity,
+            target,
+            velocity: Vec3::ZERO,
+        }
+    }
+
+    /// Leaves the body moving with a velocity after the teleport.
+    pub fn with_velocity(self, velocity: Vec3) -> Self {
+        Self { velocity, ..self }
+    }
+
+    /// The nearest free position to the target, or the target itself when the body has no
+    /// collider, the physics is not running, or no free spot is found.
+    fn free_translation(&self, world: &World) -> Vec3 {
+        let target = self.target.translation;
+        let (Some(collider), Some(rapier_context)) = (
+            world.get::<Collider>(self.entity),
+            world.get_resource::<RapierContext>(),
+        ) else {
+            return target;
+        };
+        let settings = world
+            .get_resource::<DepenetrationSettings>()
+            .copied()
+            .unwrap_or_default();
+
+        let filter = QueryFilter::only_fixed()
+            .exclude_sensors()
+            .exclude_collider(self.entity)
+            .exclude_rigid_body(self.entity);
+        resolve_overlap(
+            rapier_context,
+            &settings,
+            collider,
+            target,
+            self.target.rotation,
+            filter,
+        )
+        .unwrap_or_else(|| {
+            warn!(
+                "no free spot within {} units of {target} to teleport {:?} to",
+                settings.search_radius, self.entity
+            );
+            target
+        })
+    }
+}
+
+impl Command for TeleportEntity {
+    fn write(self, world: &mut World) {
+        let translation = self.free_translation(world);
+        let Some(mut entity) = world.get_entity_mut(self.entity) else {
+            return;
+        };
+
+        let Some(mut transform) = entity.get_mut::<Transform>() else {
+            return;
+        };
+        let from = transform.translation;
+        *transform = Transform {
+            translation,
+            ..self.target
+        };
+
+        if let Some(mut velocity) = entity.get_mut::<CustomVelocity>() {
+            velocity.0 = self.velocity;
+        }
+        if let Some(mut velocity) = entity.get_mut::<Velocity>() {
+            *velocity = Velocity::linear(self.velocity);
+        }
+        // Writing the transform is enough for bevy_rapier to move a kinematic body, but the
+        // movement queued before the teleport must be dropped.
+        if let Some(mut controller) = entity.get_mut::<KinematicCharacterController>() {
+            controller.translation = None;
+        }
+
+        if let Some(mut events) = world.get_resource_mut::<Events<TeleportEvent>>() {
+            events.send(TeleportEvent {
+                entity: self.entity,
+                from,
+                to: translation,
+            });
+        }
+    }
+}
+
+/// An extension to [`Commands`] for teleporting bodies.
+pub trait TeleportExt {
+    /// Teleports a body to the nearest free spot around a transform and stops it.
+    fn teleport_entity(&mut self, entity: Entity, target: Transform);
+}
+
+impl<'w, 's> TeleportExt for Commands<'w, 's> {
+    fn teleport_entity(&mut self, entity: Entity, target: Transform) {
+        self.add(TeleportEntity::new(entity, target));
+    }
+}
@@ -0,0 +1,209 @@
+//! A mod that slows down, speeds up and steps the simulation clock.
+//!
+//! The [`TimeScale`] resource sets the relative speed of Bevy's [`Time`], so every system that
+//! moves things by [`Time::delta_seconds`] follows it: the controllers and their collision
+//! handling, kinematic animators, and the day/night cycle. It is also written into the
+//! [`TimestepMode`] of Rapier. Fixed and interpolated steps are shortened by the scale, while
+//! variable steps already follow the scaled [`Time`] and keep their own `time_scale`. The
+//! timestep before scaling is kept in [`UnscaledTimestep`], and modes written into the
+//! [`RapierConfiguration`] by other systems replace it.
+//!
+//! A scale of zero freezes the simulation. While it is frozen, a [`FrameAdvance`] event (sent by
+//! the frame advance key) runs the next frame with a fixed delta time so the simulation can be
+//! stepped one frame at a time.
+
+use bevy::{prelude::*, time::TimeSystem};
+use bevy_rapier3d::prelude::*;
+use std::time::Duration;
+
+/// How fast the simulation runs compared to real time, where `0.0` freezes it.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct TimeScale(pub f32);
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        TimeScale(1.0)
+    }
+}
+
+impl TimeScale {
+    /// Whether the simulation is frozen.
+    pub fn is_frozen(&self) -> bool {
+        self.0 <= 0.0
+    }
+}
+
+/// The Rapier [`TimestepMode`] that the [`TimeScale`] is applied to.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct UnscaledTimestep(pub TimestepMode);
+
+impl Default for UnscaledTimestep {
+    fn default() -> Self {
+        Self(RapierConfiguration::default().timestep_mode)
+    }
+}
+
+impl UnscaledTimestep {
+    /// The timestep with fixed and interpolated steps shortened by `scale`.
+    pub fn scaled(&self, scale: f32) -> TimestepMode {
+        match self.0 {
+            TimestepMode::Fixed { dt, substeps } => TimestepMode::Fixed {
+                dt: dt * scale,
+                substeps,
+            },
+            TimestepMode::Interpolated {
+                dt,
+                time_scale,
+                substeps,
+            } => TimestepMode::Interpolated {
+                dt: dt * scale,
+                time_scale,
+                substeps,
+            },
+            mode @ TimestepMode::Variable { .. } => mode,
+        }
+    }
+}
+
+/// An event that advances a frozen simulation by one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameAdvance;
+
+/// Settings for stepping a frozen simulation.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct FrameAdvanceSettings {
+    /// A key that sends a [`FrameAdvance`], if any.
+    pub key: Option<KeyCode>,
+    /// The delta time of an advanced frame in seconds.
+    pub delta: f32,
+}
+
+impl Default for FrameAdvanceSettings {
+    fn default() -> Self {
+        Self {
+            key: Some(KeyCode::F10),
+            delta: 1.0 / 60.0,
+        }
+    }
+}
+
+/// A plugin that applies the [`TimeScale`] and steps the simulation while it is frozen.
+#[derive(Default)]
+pub struct TimeScalePlugin {
+    /// The time scale when the app starts.
+    pub scale: TimeScale,
+    /// The settings used to step the simulation.
+    pub frame_advance: FrameAdvanceSettings,
+}
+
+impl TimeScalePlugin {
+    /// Creates a new [`TimeScalePlugin`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`TimeScalePlugin`] that starts with the given time scale.
+    pub fn with_scale(scale: f32) -> Self {
+        Self {
+            scale: TimeScale(scale),
+            ..default()
+        }
+    }
+}
+
+impl Plugin for TimeScalePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.scale)
+            .insert_resource(self.frame_advance)
+            .init_resource::<UnscaledTimestep>()
+            .add_event::<FrameAdvance>()
+            .add_system_to_stage(CoreStage::First, apply_time_scale.before(TimeSystem))
+            .add_system_to_stage(CoreStage::First, advance_frame.after(TimeSystem))
+            .add_system_to_stage(
+                CoreStage::First,
+                apply_time_scale_to_rapier.after(advance_frame),
+            )
+            .add_system(frame_advance_on_key);
+    }
+}
+
+/// Sets the relative speed of [`Time`] to the [`TimeScale`].
+pub fn apply_time_scale(time_scale: Res<TimeScale>, mut time: ResMut<Time>) {
+    if !time_scale.0.is_finite() {
+        return;
+    }
+    let speed = time_scale.0.max(0.0);
+    if time.relative_speed() != speed {
+        time.set_relative_speed(speed);
+    }
+}
+
+/// Writes the [`UnscaledTimestep`], scaled by the [`TimeScale`], into the Rapier configuration.
+///
+/// A frozen simulation takes full steps on the frames that a [`FrameAdvance`] gave a delta time.
+pub fn apply_time_scale_to_rapier(
+    time: Res<Time>,
+    time_scale: Res<TimeScale>,
+    mut unscaled: ResMut<UnscaledTimestep>,
+    mut written: Local<Option<TimestepMode>>,
+    rapier_config: Option<ResMut<RapierConfiguration>>,
+) {
+    let Some(mut rapier_config) = rapier_config else {
+        return;
+    };
+    if *written != Some(rapier_config.timestep_mode) {
+        unscaled.0 = rapier_config.timestep_mode;
+    }
+    if !time_scale.0.is_finite() {
+        return;
+    }
+
+    let scale = match (time_scale.is_frozen(), time.delta_seconds() > 0.0) {
+        (true, true) => 1.0,
+        (true, false) => 0.0,
+        (false, _) => time_scale.0,
+    };
+    let mode = unscaled.scaled(scale);
+    if rapier_config.timestep_mode != mode {
+        rapier_config.timestep_mode = mode;
+    }
+    *written = Some(mode);
+}
+
+/// Sends a [`FrameAdvance`] when the frame advance key is pressed.
+pub fn frame_advance_on_key(
+    settings: Res<FrameAdvanceSettings>,
+    keyboard: Res<Input<KeyCode>>,
+    mut frame_advance: EventWriter<FrameAdvance>,
+) {
+    if settings.key.is_some_and(|key| keyboard.just_pressed(key)) {
+        frame_advance.send(FrameAdvance);
+    }
+}
+
+/// Gives the frame a fixed delta time when a [`FrameAdvance`] is sent while the simulation is
+/// frozen.
+pub fn advance_frame(
+    time_scale: Res<TimeScale>,
+    settings: Res<FrameAdvanceSettings>,
+    mut time: ResMut<Time>,
+    mut frame_advance: EventReader<FrameAdvance>,
+) {
+    if frame_advance.iter().count() == 0 || !time_scale.is_frozen() {
+        return;
+    }
+    set_delta(&mut time, Duration::from_secs_f32(settings.delta.max(0.0)));
+}
+
+/// Replaces the delta time of the current frame, ignoring the relative speed of the clock.
+///
+/// This must run after the [`TimeSystem`] has updated the clock for the frame.
+pub fn set_delta(time: &mut Time, delta: Duration) {
+    let Some(last_update) = time.last_update() else {
+        return;
+    };
+    let speed = time.relative_speed_f64();
+    time.set_relative_speed_f64(1.0);
+    time.update_with_instant(last_update + delta);
+    time.set_relative_speed_f64(speed);
+}